@@ -395,6 +395,7 @@ impl<
                     self.invalidated_artifacts.inc();
                     warn!(self.log, "Invalid artifact {} {:?}", s, artifact);
                 }
+                ConsensusAction::AddEquivocationEvidence(_) => {}
             }
         }
         debug!(
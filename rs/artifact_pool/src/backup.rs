@@ -13,6 +13,9 @@
 //! and since we backup all artifacts instantly after the pool update, there is
 //! no possibility to inject purging (or any other deletion) of artifacts
 //! between the pool update and the backup.
+//!
+//! The backed up artifacts can be read back with [`read_artifacts_at_height`],
+//! e.g. to replay the finalized chain on top of a checkpoint.
 
 use ic_config::artifact_pool::BACKUP_GROUP_SIZE;
 use ic_interfaces::{
@@ -34,6 +37,8 @@ use ic_types::{
 use prometheus::IntCounter;
 use prost::Message;
 use std::{
+    collections::BTreeSet,
+    convert::{TryFrom, TryInto},
     fs,
     io::{self, Write},
     path::{Path, PathBuf},
@@ -481,6 +486,49 @@ fn get_all_persisted_artifacts(pool: &dyn ConsensusPool) -> Vec<ConsensusMessage
         .collect()
 }
 
+/// Reads all artifacts backed up at the given height.
+///
+/// `subnet_path` is the backup directory of a subnet, i.e.
+/// `<spool_path>/<subnet_id>`. Artifacts backed up by all replica versions are
+/// returned, without duplicates. If nothing was backed up at the given height,
+/// an empty vector is returned.
+pub fn read_artifacts_at_height(
+    subnet_path: &Path,
+    height: Height,
+) -> Result<Vec<ConsensusMessage>, io::Error> {
+    let mut file_names = BTreeSet::new();
+    let mut artifacts = Vec::new();
+    for entry in fs::read_dir(subnet_path)? {
+        let height_path = height_path(&entry?.path(), height);
+        if !height_path.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&height_path)? {
+            let path = entry?.path();
+            let file_name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(file_name) => file_name.to_string(),
+                None => continue,
+            };
+            // Artifacts backed up by several replica versions (e.g. the CUP of
+            // an upgrade) have the same file name.
+            if file_names.insert(file_name.clone()) {
+                let artifact = BackupArtifact::read_from_disk(&path, &file_name)?;
+                artifacts.push(artifact.into());
+            }
+        }
+    }
+    Ok(artifacts)
+}
+
+// Returns the directory containing the artifacts of the given height.
+fn height_path(path: &Path, height: Height) -> PathBuf {
+    // We group heights by directories to avoid running into any kind of unexpected
+    // FS inode limitations. Each group directory will contain at most
+    // `BACKUP_GROUP_SIZE` heights.
+    let group_key = (height.get() / BACKUP_GROUP_SIZE) * BACKUP_GROUP_SIZE;
+    path.join(group_key.to_string()).join(height.to_string())
+}
+
 impl Drop for Backup {
     fn drop(&mut self) {
         let _ = self.backup_queue.send(BackupRequest::Shutdown);
@@ -571,12 +619,69 @@ impl BackupArtifact {
             RandomBeacon(artifact) => (artifact.height(), "random_beacon.bin".to_string()),
             CatchUpPackage(artifact) => (artifact.height(), "catch_up_package.bin".to_string()),
         };
-        // We group heights by directories to avoid running into any kind of unexpected
-        // FS inode limitations. Each group directory will contain at most
-        // `BACKUP_GROUP_SIZE` heights.
-        let group_key = (height.get() / BACKUP_GROUP_SIZE) * BACKUP_GROUP_SIZE;
-        let path_with_height = path.join(group_key.to_string()).join(height.to_string());
-        (path_with_height, file_name)
+        (height_path(path, height), file_name)
+    }
+
+    // Reads the artifact stored in the given file, determining its type from
+    // the file name.
+    fn read_from_disk(path: &Path, file_name: &str) -> Result<Self, io::Error> {
+        let bytes = fs::read(path)?;
+        Self::deserialize(file_name, &bytes).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to deserialize {}: {}", path.display(), err),
+            )
+        })
+    }
+
+    // Deserializes the artifact from protobuf.
+    fn deserialize(file_name: &str, bytes: &[u8]) -> Result<Self, String> {
+        use BackupArtifact::*;
+        fn decode<M: Message + Default>(bytes: &[u8]) -> Result<M, String> {
+            M::decode(bytes).map_err(|err| err.to_string())
+        }
+        if file_name.starts_with("finalization_") {
+            Ok(Finalization(Box::new(
+                decode::<pb::Finalization>(bytes)?.try_into()?,
+            )))
+        } else if file_name.starts_with("notarization_") {
+            Ok(Notarization(Box::new(
+                decode::<pb::Notarization>(bytes)?.try_into()?,
+            )))
+        } else if file_name.starts_with("block_proposal_") {
+            Ok(BlockProposal(Box::new(
+                decode::<pb::BlockProposal>(bytes)?.try_into()?,
+            )))
+        } else if file_name == "random_tape.bin" {
+            Ok(RandomTape(Box::new(
+                decode::<pb::RandomTape>(bytes)?.try_into()?,
+            )))
+        } else if file_name == "random_beacon.bin" {
+            Ok(RandomBeacon(Box::new(
+                decode::<pb::RandomBeacon>(bytes)?.try_into()?,
+            )))
+        } else if file_name == "catch_up_package.bin" {
+            Ok(CatchUpPackage(Box::new(
+                ic_types::consensus::CatchUpPackage::try_from(&decode::<pb::CatchUpPackage>(
+                    bytes,
+                )?)?,
+            )))
+        } else {
+            Err(format!("Unknown backup artifact: {}", file_name))
+        }
+    }
+}
+
+impl From<BackupArtifact> for ConsensusMessage {
+    fn from(artifact: BackupArtifact) -> Self {
+        match artifact {
+            BackupArtifact::Finalization(artifact) => ConsensusMessage::Finalization(*artifact),
+            BackupArtifact::Notarization(artifact) => ConsensusMessage::Notarization(*artifact),
+            BackupArtifact::BlockProposal(artifact) => ConsensusMessage::BlockProposal(*artifact),
+            BackupArtifact::RandomTape(artifact) => ConsensusMessage::RandomTape(*artifact),
+            BackupArtifact::RandomBeacon(artifact) => ConsensusMessage::RandomBeacon(*artifact),
+            BackupArtifact::CatchUpPackage(artifact) => ConsensusMessage::CatchUpPackage(*artifact),
+        }
     }
 }
 
@@ -595,6 +700,7 @@ pub(super) fn bytes_to_hex_str<T>(hash: &CryptoHashOf<T>) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ic_consensus_message::ConsensusMessageHashable;
     use ic_test_utilities::{consensus::fake::*, mock_time, types::ids::node_test_id};
     use ic_types::{
         batch::*,
@@ -669,4 +775,54 @@ mod tests {
             BlockProposal::try_from(pb::BlockProposal::decode(buf.as_slice()).unwrap()).unwrap()
         );
     }
+
+    #[test]
+    fn test_read_artifacts_at_height() {
+        let tmp_dir = tempfile::Builder::new().tempdir().unwrap();
+        let height = Height::from(BACKUP_GROUP_SIZE + 22);
+        let random_tape = RandomTape::fake(RandomTapeContent::new(height));
+        let finalization = Finalization::fake(FinalizationContent::new(
+            height,
+            CryptoHashOf::from(CryptoHash(vec![1, 2, 3])),
+        ));
+        let notarization = Notarization::fake(NotarizationContent::new(
+            height,
+            CryptoHashOf::from(CryptoHash(vec![1, 2, 3])),
+        ));
+        let other_random_tape = RandomTape::fake(RandomTapeContent::new(height.increment()));
+
+        // Back up the artifacts, with the random tape backed up by two replica
+        // versions.
+        store_artifacts(
+            vec![
+                random_tape.clone().into_message(),
+                finalization.clone().into_message(),
+                other_random_tape.into_message(),
+            ],
+            &tmp_dir.path().join("version_1"),
+        )
+        .unwrap();
+        store_artifacts(
+            vec![
+                random_tape.clone().into_message(),
+                notarization.clone().into_message(),
+            ],
+            &tmp_dir.path().join("version_2"),
+        )
+        .unwrap();
+
+        let mut artifacts = read_artifacts_at_height(tmp_dir.path(), height).unwrap();
+        artifacts.sort_by_key(|artifact| format!("{:?}", artifact));
+        let mut expected = vec![
+            random_tape.into_message(),
+            finalization.into_message(),
+            notarization.into_message(),
+        ];
+        expected.sort_by_key(|artifact| format!("{:?}", artifact));
+        assert_eq!(artifacts, expected);
+
+        assert!(read_artifacts_at_height(tmp_dir.path(), height.decrement())
+            .unwrap()
+            .is_empty());
+    }
 }
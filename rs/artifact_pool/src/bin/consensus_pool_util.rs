@@ -2,15 +2,19 @@ use clap::{App, Arg, SubCommand};
 use ic_artifact_pool::{
     certification_pool::CertificationPoolImpl,
     consensus_pool::{PoolSectionOps, UncachedConsensusPoolImpl},
+    pool_migration::migrate_persistent_pool,
 };
-use ic_config::artifact_pool::ArtifactPoolConfig;
+use ic_config::artifact_pool::{ArtifactPoolConfig, ArtifactPoolTomlConfig};
 use ic_consensus_message::ConsensusMessageHashable;
 use ic_interfaces::consensus_pool::*;
 use ic_logger::{LoggerImpl, ReplicaLogger};
 use ic_metrics::MetricsRegistry;
 use ic_types::{
-    consensus::{certification::CertificationMessage, CatchUpPackage},
+    consensus::{
+        certification::CertificationMessage, Block, BlockProposal, CatchUpPackage, HasHeight, Rank,
+    },
     time::current_time,
+    CountBytes, Height,
 };
 use prost::Message;
 use serde::{Deserialize, Serialize};
@@ -52,6 +56,63 @@ fn main() {
                         .takes_value(true),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("export-equivocation-evidence")
+                .about("Export the evidence of equivocations to stdout"),
+        )
+        .subcommand(
+            SubCommand::with_name("inspect")
+                .about("Print the finalized chain between two heights")
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .value_name("HEIGHT")
+                        .help("First height (default: height of the highest CatchUpPackage)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("HEIGHT")
+                        .help("Last height (default: highest finalized height)")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(SubCommand::with_name("verify").about(
+            "Verify the hash chain of the finalized blocks and the presence of their \
+             notarizations and finalizations",
+        ))
+        .subcommand(
+            SubCommand::with_name("prune")
+                .about("Purge the validated pool below the height of a CatchUpPackage")
+                .arg(
+                    Arg::with_name("height")
+                        .long("height")
+                        .value_name("HEIGHT")
+                        .help("Height of the CatchUpPackage (default: height of the highest one)")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Migrate the consensus and certification pools to another backend")
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("BACKEND")
+                        .help("Backend to migrate the pools to")
+                        .possible_values(&["lmdb", "rocksdb"])
+                        .required(true),
+                ),
+        )
+        .arg(
+            Arg::with_name("backend")
+                .long("backend")
+                .value_name("BACKEND")
+                .help("Backend of the consensus pool")
+                .possible_values(&["lmdb", "rocksdb"])
+                .default_value("lmdb"),
+        )
         .args_from_usage("<PATH>       'PATH to the consensus pool directory'");
     let mut help = Vec::new();
     app.write_help(&mut help)
@@ -60,12 +121,23 @@ fn main() {
     let path = matches
         .value_of("PATH")
         .expect("Missing PATH to consensus pool directory");
+    let backend = matches.value_of("backend").unwrap();
     if let Some(matches) = matches.subcommand_matches("export") {
-        export(path, matches)
+        export(path, backend, matches)
     } else if let Some(_matches) = matches.subcommand_matches("import") {
-        import(path)
+        import(path, backend)
     } else if let Some(matches) = matches.subcommand_matches("export-cup-proto") {
-        export_cup_proto(path, matches)
+        export_cup_proto(path, backend, matches)
+    } else if let Some(_matches) = matches.subcommand_matches("export-equivocation-evidence") {
+        export_equivocation_evidence(path, backend)
+    } else if let Some(matches) = matches.subcommand_matches("inspect") {
+        inspect(path, backend, matches)
+    } else if let Some(_matches) = matches.subcommand_matches("verify") {
+        verify(path, backend)
+    } else if let Some(matches) = matches.subcommand_matches("prune") {
+        prune(path, backend, matches)
+    } else if let Some(matches) = matches.subcommand_matches("migrate") {
+        migrate(path, matches)
     } else {
         eprintln!(
             "{}",
@@ -106,12 +178,13 @@ fn parse_artifact_names<'a, 'b>(names: &'a [&'b str]) -> Vec<&'static str> {
         .collect::<Vec<_>>()
 }
 
-fn open_consensus_pool(path: &str, read_only: bool) -> UncachedConsensusPoolImpl {
+fn open_consensus_pool(path: &str, backend: &str, read_only: bool) -> UncachedConsensusPoolImpl {
     let logger = LoggerImpl::new(&Default::default(), "dump_consensus_pool".to_string());
     let log = ReplicaLogger::new(logger.root.clone().into());
 
-    let path = PathBuf::from(path);
-    let mut config = ArtifactPoolConfig::new(path);
+    let mut toml_config = ArtifactPoolTomlConfig::new(PathBuf::from(path), None);
+    toml_config.consensus_pool_backend = Some(backend.to_string());
+    let mut config = ArtifactPoolConfig::from(toml_config);
     config.persistent_pool_read_only = read_only;
     UncachedConsensusPoolImpl::new(config, log)
 }
@@ -140,13 +213,13 @@ fn to_string<T: Serialize>(msg: &T) -> String {
     String::from_utf8(out).expect("UTF8 conversion error")
 }
 
-fn export(path: &str, matches: &clap::ArgMatches) {
+fn export(path: &str, backend: &str, matches: &clap::ArgMatches) {
     let artifacts = match matches.values_of("artifact") {
         Some(names) => parse_artifact_names(&names.collect::<Vec<&str>>()),
         None => ALL_ARTIFACT_NAMES.to_vec(),
     };
 
-    let consensus_pool = open_consensus_pool(path, true);
+    let consensus_pool = open_consensus_pool(path, backend, true);
     let certification_pool = open_certification_pool(path, true);
 
     for artifact in artifacts {
@@ -236,8 +309,8 @@ fn export(path: &str, matches: &clap::ArgMatches) {
     }
 }
 
-fn import(path: &str) {
-    let mut consensus_pool = open_consensus_pool(path, false);
+fn import(path: &str, backend: &str) {
+    let mut consensus_pool = open_consensus_pool(path, backend, false);
     let certification_pool = open_certification_pool(path, false);
     let stdin = std::io::stdin();
     for line in stdin.lock().lines() {
@@ -257,13 +330,13 @@ fn import(path: &str) {
     }
 }
 
-fn export_cup_proto(path: &str, matches: &clap::ArgMatches) {
+fn export_cup_proto(path: &str, backend: &str, matches: &clap::ArgMatches) {
     let filename = matches
         .value_of("output")
         .expect("Expect an output filename");
     let mut file = std::fs::File::create(filename)
         .unwrap_or_else(|err| panic!("Cannot open file {} for write: {:?}", filename, err));
-    let consensus_pool = open_consensus_pool(path, true);
+    let consensus_pool = open_consensus_pool(path, backend, true);
     let mut buf = Vec::<u8>::new();
     let cup_proto = consensus_pool.validated().highest_catch_up_package_proto();
     let cup = CatchUpPackage::try_from(&cup_proto).unwrap_or_else(|err| panic!("{}", err));
@@ -274,3 +347,220 @@ fn export_cup_proto(path: &str, matches: &clap::ArgMatches) {
     file.write_all(&buf)
         .unwrap_or_else(|err| panic!("Cannot write to file {}: {:?}", filename, err));
 }
+
+fn export_equivocation_evidence(path: &str, backend: &str) {
+    let consensus_pool = open_consensus_pool(path, backend, true);
+    for evidence in consensus_pool.equivocation_evidence().get_all() {
+        println!("{}", to_string(&evidence));
+    }
+}
+
+fn parse_height(matches: &clap::ArgMatches, name: &str) -> Option<Height> {
+    matches.value_of(name).map(|value| {
+        Height::from(
+            value
+                .parse::<u64>()
+                .unwrap_or_else(|err| panic!("Invalid {} '{}': {}", name, value, err)),
+        )
+    })
+}
+
+fn highest_catch_up_package(consensus_pool: &UncachedConsensusPoolImpl) -> CatchUpPackage {
+    consensus_pool
+        .validated()
+        .catch_up_package()
+        .get_highest()
+        .unwrap_or_else(|err| panic!("No CatchUpPackage found: {:?}", err))
+}
+
+/// Returns the block proposals at the given height that are finalized by a
+/// finalization in the validated pool. More than one block proposal means
+/// that conflicting blocks were finalized.
+fn finalized_block_proposals(
+    consensus_pool: &UncachedConsensusPoolImpl,
+    height: Height,
+) -> Vec<BlockProposal> {
+    let finalized_hashes: Vec<_> = consensus_pool
+        .validated()
+        .finalization()
+        .get_by_height(height)
+        .map(|finalization| finalization.content.block)
+        .collect();
+    consensus_pool
+        .validated()
+        .block_proposal()
+        .get_by_height(height)
+        .filter(|proposal| finalized_hashes.contains(proposal.content.get_hash()))
+        .collect()
+}
+
+fn inspect(path: &str, backend: &str, matches: &clap::ArgMatches) {
+    let consensus_pool = open_consensus_pool(path, backend, true);
+    let cup = highest_catch_up_package(&consensus_pool);
+    let from = parse_height(matches, "from").unwrap_or_else(|| cup.height());
+    let to = parse_height(matches, "to").unwrap_or_else(|| {
+        consensus_pool
+            .validated()
+            .finalization()
+            .max_height()
+            .unwrap_or_else(|| cup.height())
+    });
+
+    let mut height = from;
+    while height <= to {
+        // The block of the CUP is the only finalized block at its height that
+        // does not have to be in the pool as a proposal.
+        let blocks: Vec<(String, Block)> = if height == cup.height() {
+            vec![(
+                "CatchUpPackage".to_string(),
+                cup.content.block.as_ref().clone(),
+            )]
+        } else {
+            finalized_block_proposals(&consensus_pool, height)
+                .into_iter()
+                .map(|proposal| {
+                    (
+                        proposal.signature.signer.to_string(),
+                        proposal.content.as_ref().clone(),
+                    )
+                })
+                .collect()
+        };
+        if blocks.is_empty() {
+            println!("{}: no finalized block", height);
+        }
+        for (maker, block) in blocks {
+            let Rank(rank) = block.rank;
+            let payload = block.payload.as_ref();
+            if payload.is_summary() {
+                println!("{}: maker {} rank {} summary payload", height, maker, rank);
+            } else {
+                let batch = &payload.as_data().batch;
+                println!(
+                    "{}: maker {} rank {} payload {} bytes ingress {} messages",
+                    height,
+                    maker,
+                    rank,
+                    batch.ingress.count_bytes()
+                        + batch.xnet.count_bytes()
+                        + batch.self_validating.count_bytes(),
+                    batch.ingress.message_count()
+                );
+            }
+        }
+        height = height.increment();
+    }
+}
+
+fn verify(path: &str, backend: &str) {
+    let consensus_pool = open_consensus_pool(path, backend, true);
+    let cup = highest_catch_up_package(&consensus_pool);
+    let finalized_height = consensus_pool
+        .validated()
+        .finalization()
+        .max_height()
+        .unwrap_or_else(|| cup.height());
+
+    let mut errors = 0;
+    let mut report = |height: Height, error: String| {
+        println!("{}: {}", height, error);
+        errors += 1;
+    };
+    // The hash of the finalized block at the previous height, if known.
+    let mut parent = Some(cup.content.block.get_hash().clone());
+    let mut height = cup.height().increment();
+    while height <= finalized_height {
+        let finalizations: Vec<_> = consensus_pool
+            .validated()
+            .finalization()
+            .get_by_height(height)
+            .collect();
+        let proposals = finalized_block_proposals(&consensus_pool, height);
+        let proposal = match (finalizations.len(), proposals.as_slice()) {
+            (0, _) => {
+                report(height, "missing finalization".to_string());
+                None
+            }
+            (_, []) => {
+                report(height, "missing finalized block proposal".to_string());
+                None
+            }
+            (_, [proposal]) => Some(proposal),
+            (_, _) => {
+                report(height, "conflicting finalized blocks".to_string());
+                None
+            }
+        };
+
+        parent = proposal.and_then(|proposal| {
+            let hash = proposal.content.get_hash();
+            if &ic_crypto::crypto_hash(proposal.content.as_ref()) != hash {
+                report(height, "block hash mismatch".to_string());
+            }
+            if let Some(parent) = &parent {
+                if &proposal.content.as_ref().parent != parent {
+                    report(height, "block does not extend the parent block".to_string());
+                }
+            }
+            if !consensus_pool
+                .validated()
+                .notarization()
+                .get_by_height(height)
+                .any(|notarization| &notarization.content.block == hash)
+            {
+                report(height, "missing notarization".to_string());
+            }
+            Some(hash.clone())
+        });
+        height = height.increment();
+    }
+
+    println!(
+        "Verified heights {} to {}: {} errors",
+        cup.height(),
+        finalized_height,
+        errors
+    );
+    if errors > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn prune(path: &str, backend: &str, matches: &clap::ArgMatches) {
+    let mut consensus_pool = open_consensus_pool(path, backend, false);
+    let height = parse_height(matches, "height")
+        .unwrap_or_else(|| highest_catch_up_package(&consensus_pool).height());
+    // Only purge below a CUP, so that the replica can still start from the
+    // remaining artifacts.
+    if consensus_pool
+        .validated()
+        .catch_up_package()
+        .get_by_height(height)
+        .next()
+        .is_none()
+    {
+        panic!("No CatchUpPackage at height {}", height);
+    }
+
+    let mut ops = PoolSectionOps::new();
+    ops.purge_below(height);
+    consensus_pool.validated.mutate(ops);
+    println!("Purged the validated pool below height {}", height);
+}
+
+fn migrate(path: &str, matches: &clap::ArgMatches) {
+    let logger = LoggerImpl::new(&Default::default(), "dump_consensus_pool".to_string());
+    let log = ReplicaLogger::new(logger.root.clone().into());
+
+    let mut toml_config = ArtifactPoolTomlConfig::new(PathBuf::from(path), None);
+    toml_config.consensus_pool_backend = matches.value_of("to").map(String::from);
+    let config = ArtifactPoolConfig::from(toml_config);
+    match migrate_persistent_pool(&config, &log) {
+        Ok(true) => println!("Migrated the pools to {}", matches.value_of("to").unwrap()),
+        Ok(false) => println!("Nothing to migrate"),
+        Err(err) => {
+            eprintln!("Failed to migrate the pools: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
         get_highest_catch_up_package, get_highest_finalized_block, update_summary_block,
         ConsensusCacheImpl,
     },
+    equivocation_pool::EquivocationEvidenceSection,
     inmemory_pool::InMemoryPoolSection,
     metrics::{LABEL_POOL_TYPE, POOL_TYPE_UNVALIDATED, POOL_TYPE_VALIDATED},
 };
@@ -20,8 +21,8 @@ use ic_interfaces::{
 };
 use ic_logger::ReplicaLogger;
 use ic_types::{
-    artifact::ConsensusMessageId, consensus::catchup::CUPWithOriginalProtobuf, consensus::*,
-    Height, SubnetId, Time,
+    artifact::ConsensusMessageId, consensus::catchup::CUPWithOriginalProtobuf,
+    consensus::equivocation::EquivocationEvidence, consensus::*, Height, SubnetId, Time,
 };
 use prometheus::{labels, opts, IntGauge};
 use std::marker::PhantomData;
@@ -190,8 +191,10 @@ impl PoolMetrics {
 pub struct ConsensusPoolImpl {
     validated: Box<dyn InitializablePoolSection + Send + Sync>,
     unvalidated: Box<dyn MutablePoolSection<UnvalidatedConsensusArtifact> + Send + Sync>,
+    equivocation_evidence: EquivocationEvidenceSection,
     validated_metrics: PoolMetrics,
     unvalidated_metrics: PoolMetrics,
+    equivocation_evidence_size: IntGauge,
     cache: Arc<ConsensusCacheImpl>,
     backup: Option<Backup>,
 }
@@ -200,10 +203,14 @@ pub struct ConsensusPoolImpl {
 pub struct UncachedConsensusPoolImpl {
     pub validated: Box<dyn InitializablePoolSection + Send + Sync>,
     unvalidated: Box<dyn MutablePoolSection<UnvalidatedConsensusArtifact> + Send + Sync>,
+    equivocation_evidence: EquivocationEvidenceSection,
 }
 
 impl UncachedConsensusPoolImpl {
     pub fn new(config: ArtifactPoolConfig, log: ReplicaLogger) -> UncachedConsensusPoolImpl {
+        let equivocation_evidence_path = config
+            .persistent_pool_db_path()
+            .join("equivocation_evidence");
         let validated = match config.persistent_pool_backend {
             PersistentPoolBackend::Lmdb(lmdb_config) => Box::new(
                 crate::lmdb_pool::PersistentHeightIndexedPool::new_consensus_pool(
@@ -220,9 +227,12 @@ impl UncachedConsensusPoolImpl {
             ) as Box<_>,
         };
 
+        let equivocation_evidence =
+            EquivocationEvidenceSection::new(Some(equivocation_evidence_path), log.clone());
         UncachedConsensusPoolImpl {
             validated,
             unvalidated: Box::new(InMemoryPoolSection::new(log)),
+            equivocation_evidence,
         }
     }
 }
@@ -269,6 +279,10 @@ impl ConsensusPool for UncachedConsensusPoolImpl {
     fn as_cache(&self) -> &dyn ConsensusPoolCache {
         self
     }
+
+    fn equivocation_evidence(&self) -> &dyn HeightIndexedPool<EquivocationEvidence> {
+        &self.equivocation_evidence
+    }
 }
 
 impl ConsensusPoolImpl {
@@ -340,11 +354,18 @@ impl ConsensusPoolImpl {
         registry: ic_metrics::MetricsRegistry,
     ) -> ConsensusPoolImpl {
         let cache = Arc::new(ConsensusCacheImpl::new(&uncached));
+        let equivocation_evidence_size = registry.int_gauge(
+            "consensus_pool_equivocation_evidence",
+            "The number of pieces of equivocation evidence in the consensus pool",
+        );
+        equivocation_evidence_size.set(uncached.equivocation_evidence.len() as i64);
         ConsensusPoolImpl {
             validated: uncached.validated,
             unvalidated: uncached.unvalidated,
+            equivocation_evidence: uncached.equivocation_evidence,
             validated_metrics: PoolMetrics::new(registry.clone(), POOL_TYPE_VALIDATED),
             unvalidated_metrics: PoolMetrics::new(registry, POOL_TYPE_UNVALIDATED),
+            equivocation_evidence_size,
             cache,
             backup: None,
         }
@@ -399,6 +420,10 @@ impl ConsensusPool for ConsensusPoolImpl {
     fn as_cache(&self) -> &dyn ConsensusPoolCache {
         self.cache.as_ref()
     }
+
+    fn equivocation_evidence(&self) -> &dyn HeightIndexedPool<EquivocationEvidence> {
+        &self.equivocation_evidence
+    }
 }
 
 impl MutableConsensusPool for ConsensusPoolImpl {
@@ -450,6 +475,12 @@ impl MutableConsensusPool for ConsensusPoolImpl {
                 ChangeAction::HandleInvalid(to_remove, _) => {
                     unvalidated_ops.remove(to_remove.get_id());
                 }
+                ChangeAction::AddEquivocationEvidence(evidence) => {
+                    if self.equivocation_evidence.insert(evidence) {
+                        self.equivocation_evidence_size
+                            .set(self.equivocation_evidence.len() as i64);
+                    }
+                }
             }
         }
 
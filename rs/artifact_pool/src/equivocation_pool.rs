@@ -0,0 +1,359 @@
+//! Section of the consensus pool holding the evidence of equivocations found
+//! by the validator.
+use crate::height_index::HeightIndex;
+use ic_interfaces::consensus_pool::{HeightIndexedPool, HeightRange, OnlyError};
+use ic_logger::{warn, ReplicaLogger};
+use ic_types::{
+    consensus::{equivocation::EquivocationEvidence, HasHeight},
+    Height,
+};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The maximum number of pieces of evidence that are kept. When the section is
+/// full, the evidence at the lowest heights is dropped first.
+pub(crate) const MAX_EQUIVOCATION_EVIDENCE: usize = 100;
+
+/// The extension of the files holding the persisted evidence.
+const EVIDENCE_FILE_EXTENSION: &str = "bin";
+
+/// Holds at most one piece of evidence per node, height and artifact type, as
+/// one is enough to prove misbehaviour. Unlike the other artifacts, the
+/// evidence is not purged together with the validated pool, so that it can
+/// still be exported after the subnet has moved on.
+///
+/// Every piece of evidence is persisted as a separate file in the directory of
+/// the section, if any, and loaded again when the section is created.
+pub(crate) struct EquivocationEvidenceSection {
+    index: HeightIndex<EquivocationEvidence>,
+    len: usize,
+    path: Option<PathBuf>,
+    log: ReplicaLogger,
+}
+
+impl EquivocationEvidenceSection {
+    /// Creates a section persisting its evidence in the directory at `path`, if
+    /// given, and loads the evidence persisted there. Files that cannot be
+    /// read are skipped.
+    pub(crate) fn new(path: Option<PathBuf>, log: ReplicaLogger) -> Self {
+        let mut section = Self {
+            index: HeightIndex::default(),
+            len: 0,
+            path,
+            log,
+        };
+        for evidence in section.load() {
+            if !section.contains(&evidence) && section.index.insert(evidence.height(), &evidence) {
+                section.len += 1;
+            }
+        }
+        for evidence in section.evict() {
+            section.remove_file(&evidence);
+        }
+        section
+    }
+
+    /// Inserts `evidence`, unless there already is evidence of the same type
+    /// against the same node at the same height. Returns `true` if `evidence`
+    /// was inserted.
+    pub(crate) fn insert(&mut self, evidence: EquivocationEvidence) -> bool {
+        if self.contains(&evidence) || !self.index.insert(evidence.height(), &evidence) {
+            return false;
+        }
+        self.len += 1;
+        self.write_file(&evidence);
+        for evidence in self.evict() {
+            self.remove_file(&evidence);
+        }
+        true
+    }
+
+    /// Returns the number of pieces of evidence in the section.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the section holds evidence of the same type against
+    /// the same node at the same height as `evidence`.
+    fn contains(&self, evidence: &EquivocationEvidence) -> bool {
+        self.index.lookup(evidence.height()).any(|existing| {
+            existing.signer() == evidence.signer()
+                && existing.artifact_type() == evidence.artifact_type()
+        })
+    }
+
+    /// Drops the evidence at the lowest heights until the section is no longer
+    /// over capacity, and returns the dropped evidence.
+    fn evict(&mut self) -> Vec<EquivocationEvidence> {
+        let mut evicted = Vec::new();
+        while self.len > MAX_EQUIVOCATION_EVIDENCE {
+            let lowest = match self.index.heights().next() {
+                Some(height) => *height,
+                None => break,
+            };
+            let bucket = self.index.remove_all(lowest);
+            self.len -= bucket.len();
+            // Keep as much of the lowest bucket as still fits.
+            let keep = MAX_EQUIVOCATION_EVIDENCE.saturating_sub(self.len);
+            for (i, evidence) in bucket.into_iter().enumerate() {
+                if i < keep {
+                    self.index.insert(lowest, &evidence);
+                    self.len += 1;
+                } else {
+                    evicted.push(evidence);
+                }
+            }
+        }
+        evicted
+    }
+
+    /// Returns the path of the file holding `evidence`, if the section is
+    /// persisted.
+    fn file_path(&self, evidence: &EquivocationEvidence) -> Option<PathBuf> {
+        self.path.as_ref().map(|path| {
+            path.join(format!(
+                "{}_{}_{}.{}",
+                evidence.height(),
+                evidence.signer(),
+                evidence.artifact_type(),
+                EVIDENCE_FILE_EXTENSION
+            ))
+        })
+    }
+
+    /// Reads all evidence persisted in the directory of the section.
+    fn load(&self) -> Vec<EquivocationEvidence> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Vec::new(),
+        };
+        let entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries,
+            // Nothing was persisted yet.
+            Err(_) => return Vec::new(),
+        };
+        entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| {
+                file.extension()
+                    .map_or(false, |ext| ext == EVIDENCE_FILE_EXTENSION)
+            })
+            .filter_map(|file| match read_evidence(&file) {
+                Ok(evidence) => Some(evidence),
+                Err(err) => {
+                    warn!(
+                        self.log,
+                        "Skipping unreadable equivocation evidence {}: {}",
+                        file.display(),
+                        err
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn write_file(&self, evidence: &EquivocationEvidence) {
+        let (path, file) = match (&self.path, self.file_path(evidence)) {
+            (Some(path), Some(file)) => (path, file),
+            _ => return,
+        };
+        let bytes = bincode::serialize(evidence).expect("Failed to serialize evidence");
+        if let Err(err) = std::fs::create_dir_all(path).and_then(|()| {
+            ic_utils::fs::write_using_tmp_file(&file, |writer| writer.write_all(&bytes))
+        }) {
+            warn!(
+                self.log,
+                "Failed to persist equivocation evidence {}: {}",
+                file.display(),
+                err
+            );
+        }
+    }
+
+    fn remove_file(&self, evidence: &EquivocationEvidence) {
+        if let Some(file) = self.file_path(evidence) {
+            if let Err(err) = std::fs::remove_file(&file) {
+                warn!(
+                    self.log,
+                    "Failed to remove equivocation evidence {}: {}",
+                    file.display(),
+                    err
+                );
+            }
+        }
+    }
+}
+
+fn read_evidence(file: &Path) -> Result<EquivocationEvidence, String> {
+    let bytes = std::fs::read(file).map_err(|err| err.to_string())?;
+    bincode::deserialize(&bytes).map_err(|err| err.to_string())
+}
+
+impl HeightIndexedPool<EquivocationEvidence> for EquivocationEvidenceSection {
+    fn height_range(&self) -> Option<HeightRange> {
+        let mut heights = self.index.heights();
+        let min = *heights.next()?;
+        let max = heights.last().copied().unwrap_or(min);
+        Some(HeightRange::new(min, max))
+    }
+
+    fn max_height(&self) -> Option<Height> {
+        self.height_range().map(|range| range.max)
+    }
+
+    fn get_all(&self) -> Box<dyn Iterator<Item = EquivocationEvidence>> {
+        Box::new(
+            self.index
+                .get_all()
+                .cloned()
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )
+    }
+
+    fn get_by_height(&self, h: Height) -> Box<dyn Iterator<Item = EquivocationEvidence>> {
+        Box::new(
+            self.index
+                .lookup(h)
+                .cloned()
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )
+    }
+
+    fn get_by_height_range(
+        &self,
+        range: HeightRange,
+    ) -> Box<dyn Iterator<Item = EquivocationEvidence>> {
+        if range.min > range.max {
+            return Box::new(std::iter::empty());
+        }
+        Box::new(
+            self.index
+                .range(range.min..=range.max)
+                .flat_map(|(_, bucket)| bucket.iter().cloned())
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )
+    }
+
+    fn get_only_by_height(&self, h: Height) -> Result<EquivocationEvidence, OnlyError> {
+        let mut evidence = self.index.lookup(h);
+        match (evidence.next(), evidence.next()) {
+            (None, _) => Err(OnlyError::NoneAvailable),
+            (Some(evidence), None) => Ok(evidence.clone()),
+            _ => Err(OnlyError::MultipleValues),
+        }
+    }
+
+    fn get_highest(&self) -> Result<EquivocationEvidence, OnlyError> {
+        match self.max_height() {
+            Some(h) => self.get_only_by_height(h),
+            None => Err(OnlyError::NoneAvailable),
+        }
+    }
+
+    fn get_highest_iter(&self) -> Box<dyn Iterator<Item = EquivocationEvidence>> {
+        match self.max_height() {
+            Some(h) => self.get_by_height(h),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_logger::replica_logger::no_op_logger;
+    use ic_test_utilities::{consensus::fake::*, types::ids::node_test_id};
+    use ic_types::consensus::{dkg, Block, BlockProposal};
+
+    fn make_genesis_block(height: Height) -> Block {
+        let mut summary = dkg::Summary::fake();
+        summary.height = height;
+        ic_consensus_message::make_genesis(summary)
+            .content
+            .block
+            .into_inner()
+    }
+
+    // Returns a pair of conflicting proposals of `node` at `height`.
+    fn make_evidence(node: u64, height: Height) -> EquivocationEvidence {
+        let parent = make_genesis_block(height.decrement());
+        let first = Block::from_parent(&parent);
+        let mut second = first.clone();
+        second.context.time += std::time::Duration::from_millis(1);
+        EquivocationEvidence::from_block_proposals(
+            BlockProposal::fake(first, node_test_id(node)),
+            BlockProposal::fake(second, node_test_id(node)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_evidence_is_deduplicated_per_node_and_height() {
+        let mut section = EquivocationEvidenceSection::new(None, no_op_logger());
+        let evidence = make_evidence(1, Height::from(5));
+        assert_eq!(evidence.height(), Height::from(5));
+        assert!(section.insert(evidence.clone()));
+        assert!(!section.insert(evidence));
+        assert!(section.insert(make_evidence(2, Height::from(5))));
+        assert!(section.insert(make_evidence(1, Height::from(6))));
+
+        assert_eq!(section.len(), 3);
+        assert_eq!(section.get_by_height(Height::from(5)).count(), 2);
+        let range = section.height_range().unwrap();
+        assert_eq!((range.min, range.max), (Height::from(5), Height::from(6)));
+        assert_eq!(section.get_highest().unwrap().signer(), node_test_id(1));
+        assert_eq!(
+            section
+                .get_by_height_range(HeightRange::new(Height::from(6), Height::from(9)))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_lowest_evidence_is_dropped_when_full() {
+        let mut section = EquivocationEvidenceSection::new(None, no_op_logger());
+        for i in 0..=MAX_EQUIVOCATION_EVIDENCE as u64 {
+            assert!(section.insert(make_evidence(1, Height::from(i + 1))));
+        }
+        assert_eq!(section.len(), MAX_EQUIVOCATION_EVIDENCE);
+        let range = section.height_range().unwrap();
+        assert_eq!(range.min, Height::from(2));
+        assert_eq!(
+            range.max,
+            Height::from(MAX_EQUIVOCATION_EVIDENCE as u64 + 1)
+        );
+    }
+
+    #[test]
+    fn test_evidence_is_persisted() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("evidence")
+            .tempdir()
+            .unwrap();
+        let path = tmp_dir.path().join("equivocation_evidence");
+        let mut section = EquivocationEvidenceSection::new(Some(path.clone()), no_op_logger());
+        for i in 0..=MAX_EQUIVOCATION_EVIDENCE as u64 {
+            assert!(section.insert(make_evidence(1, Height::from(i + 1))));
+        }
+
+        // The evicted evidence is removed from disk as well.
+        assert_eq!(
+            std::fs::read_dir(&path).unwrap().count(),
+            MAX_EQUIVOCATION_EVIDENCE
+        );
+        // Unreadable files are skipped.
+        std::fs::write(path.join("corrupt.bin"), b"corrupt").unwrap();
+
+        let reloaded = EquivocationEvidenceSection::new(Some(path), no_op_logger());
+        assert_eq!(reloaded.len(), MAX_EQUIVOCATION_EVIDENCE);
+        assert_eq!(
+            reloaded.get_all().collect::<Vec<_>>(),
+            section.get_all().collect::<Vec<_>>()
+        );
+    }
+}
@@ -5,9 +5,9 @@ use crate::{
     metrics::{PoolMetrics, POOL_TYPE_UNVALIDATED, POOL_TYPE_VALIDATED},
     peer_index::PeerIndex,
 };
-use ic_config::artifact_pool::ArtifactPoolConfig;
+use ic_config::artifact_pool::{ArtifactPoolConfig, PersistentPoolBackend};
 use ic_interfaces::{
-    artifact_pool::{ArtifactPoolError, HasTimestamp, UnvalidatedArtifact},
+    artifact_pool::{ArtifactPoolError, HasTimestamp, UnvalidatedArtifact, ValidatedArtifact},
     gossip_pool::{GossipPool, IngressGossipPool},
     ingress_pool::{
         ChangeAction, ChangeSet, IngressPool, IngressPoolObject, IngressPoolSelect,
@@ -15,15 +15,102 @@ use ic_interfaces::{
         UnvalidatedIngressArtifact, ValidatedIngressArtifact,
     },
 };
-use ic_logger::{debug, trace, ReplicaLogger};
+use ic_logger::{debug, info, trace, ReplicaLogger};
 use ic_metrics::MetricsRegistry;
 use ic_types::{
     artifact::IngressMessageId,
     messages::{MessageId, SignedIngress, EXPECTED_MESSAGE_ID_LENGTH},
+    time::current_time,
     CountBytes, NodeId, Time,
 };
 use prometheus::IntCounter;
 use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub enum PersistentIngressOp {
+    Insert(ValidatedIngressArtifact),
+    Remove(IngressMessageId),
+    PurgeBelow(Time), // Non-inclusive
+}
+
+#[derive(Clone, Default)]
+pub struct PersistentIngressOps {
+    pub ops: Vec<PersistentIngressOp>,
+}
+
+impl PersistentIngressOps {
+    pub fn new() -> PersistentIngressOps {
+        PersistentIngressOps { ops: Vec::new() }
+    }
+    pub fn insert(&mut self, artifact: ValidatedIngressArtifact) {
+        self.ops.push(PersistentIngressOp::Insert(artifact));
+    }
+    pub fn remove(&mut self, message_id: IngressMessageId) {
+        self.ops.push(PersistentIngressOp::Remove(message_id));
+    }
+    pub fn purge_below(&mut self, expiry: Time) {
+        self.ops.push(PersistentIngressOp::PurgeBelow(expiry));
+    }
+}
+
+/// Operations on the persistent copy of the validated section of the ingress
+/// pool, from which the section is restored when the replica restarts.
+pub trait PersistentIngressSection: Send + Sync {
+    /// Apply the given operations, in order and as one write.
+    fn mutate(&self, ops: PersistentIngressOps);
+    /// Return all messages, ordered by their ids.
+    fn load(&self) -> Vec<ValidatedIngressArtifact>;
+}
+
+/// Return the key of a message in the persistent section: the expiry in big
+/// endian followed by the message id, so that keys are ordered like
+/// `IngressMessageId`s and can be purged by expiry.
+pub(crate) fn persistent_key(message_id: &IngressMessageId) -> Vec<u8> {
+    let mut key = Vec::with_capacity(8 + EXPECTED_MESSAGE_ID_LENGTH);
+    let expiry = message_id.expiry().as_nanos_since_unix_epoch();
+    key.extend(&expiry.to_be_bytes());
+    key.extend(message_id.message_id.as_bytes());
+    key
+}
+
+/// Return the smallest key of the messages that expire at `expiry` or later.
+pub(crate) fn persistent_key_lower_bound(expiry: Time) -> Vec<u8> {
+    let zero_bytes = [0; EXPECTED_MESSAGE_ID_LENGTH];
+    persistent_key(&IngressMessageId::new(expiry, MessageId::from(zero_bytes)))
+}
+
+/// Serialize a validated message for the persistent section. Only the signed
+/// message and the timestamp are stored, the rest is derived when loading.
+pub(crate) fn serialize_persistent(
+    artifact: &ValidatedIngressArtifact,
+) -> bincode::Result<Vec<u8>> {
+    bincode::serialize(&ValidatedArtifact {
+        msg: &artifact.msg.signed_ingress,
+        timestamp: artifact.timestamp,
+    })
+}
+
+/// Deserialize a validated message of the persistent section.
+pub(crate) fn deserialize_persistent(bytes: &[u8]) -> bincode::Result<ValidatedIngressArtifact> {
+    bincode::deserialize::<ValidatedArtifact<SignedIngress>>(bytes)
+        .map(|artifact| artifact.map(IngressPoolObject::from))
+}
+
+/// Open the persistent copy of the validated section with the given backend.
+pub(crate) fn open_persistent_section(
+    backend: PersistentPoolBackend,
+    log: ReplicaLogger,
+) -> Arc<dyn PersistentIngressSection> {
+    match backend {
+        PersistentPoolBackend::Lmdb(config) => Arc::new(
+            crate::lmdb_pool::PersistentIngressPool::new_ingress_pool(config, log),
+        ),
+        PersistentPoolBackend::RocksDB(config) => {
+            Arc::new(crate::rocksdb_pool::PersistentIngressPool::new_ingress_pool(config, log))
+        }
+    }
+}
 
 #[derive(Clone)]
 struct IngressPoolSection<T: AsRef<IngressPoolObject>> {
@@ -143,6 +230,8 @@ pub struct IngressPoolImpl {
     peer_index: PeerIndex,
     ingress_pool_size_threshold: Option<usize>,
     ingress_messages_throttled: IntCounter,
+    // Persistent copy of the validated section, if the pool is writable.
+    persistent_validated: Option<Arc<dyn PersistentIngressSection>>,
     log: ReplicaLogger,
 }
 
@@ -154,7 +243,15 @@ impl IngressPoolImpl {
         metrics_registry: MetricsRegistry,
         log: ReplicaLogger,
     ) -> IngressPoolImpl {
-        IngressPoolImpl {
+        let persistent_validated = if config.persistent_pool_read_only {
+            None
+        } else {
+            Some(open_persistent_section(
+                config.persistent_pool_backend,
+                log.clone(),
+            ))
+        };
+        let mut pool = IngressPoolImpl {
             ingress_pool_size_threshold: config.ingress_pool_size_threshold,
             ingress_messages_throttled: metrics_registry.int_counter(
                 "ingress_messages_throttled",
@@ -166,13 +263,52 @@ impl IngressPoolImpl {
                 POOL_TYPE_VALIDATED,
             )),
             unvalidated: IngressPoolSection::new(PoolMetrics::new(
-                metrics_registry,
+                metrics_registry.clone(),
                 POOL_INGRESS,
                 POOL_TYPE_UNVALIDATED,
             )),
             peer_index: PeerIndex::new(config.ingress_pool_unvalidated_capacity_per_peer),
+            persistent_validated,
             log,
+        };
+        pool.recover_validated(&metrics_registry, current_time());
+        pool
+    }
+
+    /// Restore the validated section from its persistent copy, dropping the
+    /// messages that expired before `now`, and report the number of recovered
+    /// and dropped messages.
+    fn recover_validated(&mut self, metrics_registry: &MetricsRegistry, now: Time) {
+        let persistent_validated = match &self.persistent_validated {
+            Some(persistent_validated) => Arc::clone(persistent_validated),
+            None => return,
+        };
+        let recovered = metrics_registry.int_counter(
+            "ingress_pool_recovered_messages",
+            "Number of validated ingress messages restored from disk at startup",
+        );
+        let expired = metrics_registry.int_counter(
+            "ingress_pool_recovered_expired_messages",
+            "Number of persisted ingress messages dropped at startup because they expired",
+        );
+        for artifact in persistent_validated.load() {
+            let message_id = IngressMessageId::from(&artifact.msg);
+            if message_id.expiry() < now {
+                expired.inc();
+            } else {
+                recovered.inc();
+                self.validated.insert(message_id, artifact);
+            }
         }
+        let mut ops = PersistentIngressOps::new();
+        ops.purge_below(now);
+        persistent_validated.mutate(ops);
+        info!(
+            self.log,
+            "Ingress pool: recovered {} validated messages, dropped {} expired ones",
+            recovered.get(),
+            expired.get()
+        );
     }
 
     /// Remove an artifact from unvalidated pool and remove it from peer_index
@@ -239,6 +375,9 @@ impl MutableIngressPool for IngressPoolImpl {
 
     /// Apply changeset to the Ingress Pool
     fn apply_changeset(&mut self, change_set: ChangeSet) {
+        // Changes to the persistent copy of the validated section are written
+        // together once the whole change set is applied.
+        let mut persistent_ops = PersistentIngressOps::new();
         for change_action in change_set {
             match change_action {
                 ChangeAction::MoveToValidated((message_id, _, _, _, _)) => {
@@ -246,13 +385,14 @@ impl MutableIngressPool for IngressPoolImpl {
                     // to the validated pool
                     match self.remove_unvalidated(&message_id) {
                         Some((unvalidated_artifact, size)) => {
-                            self.validated.insert(
-                                message_id,
-                                ValidatedIngressArtifact {
-                                    msg: unvalidated_artifact.message,
-                                    timestamp: unvalidated_artifact.timestamp,
-                                },
-                            );
+                            let artifact = ValidatedIngressArtifact {
+                                msg: unvalidated_artifact.message,
+                                timestamp: unvalidated_artifact.timestamp,
+                            };
+                            if self.persistent_validated.is_some() {
+                                persistent_ops.insert(artifact.clone());
+                            }
+                            self.validated.insert(message_id, artifact);
                             debug!(
                                 self.log,
                                 "Ingress pool: move {} bytes from unvalidated to validated", size
@@ -284,6 +424,9 @@ impl MutableIngressPool for IngressPoolImpl {
                     }
                 }
                 ChangeAction::RemoveFromValidated(message_id) => {
+                    if self.persistent_validated.is_some() {
+                        persistent_ops.remove(message_id.clone());
+                    }
                     match self.validated.remove(&message_id) {
                         Some(artifact) => {
                             let size = artifact.msg.signed_ingress.count_bytes();
@@ -302,6 +445,9 @@ impl MutableIngressPool for IngressPoolImpl {
                     }
                 }
                 ChangeAction::PurgeBelowExpiry(expiry) => {
+                    if self.persistent_validated.is_some() {
+                        persistent_ops.purge_below(expiry);
+                    }
                     let _unused = self.validated.purge_below(expiry);
                     for artifact in self.unvalidated.purge_below(expiry) {
                         let size = artifact.message.signed_ingress.count_bytes();
@@ -310,6 +456,11 @@ impl MutableIngressPool for IngressPoolImpl {
                 }
             }
         }
+        if let Some(persistent_validated) = &self.persistent_validated {
+            if !persistent_ops.ops.is_empty() {
+                persistent_validated.mutate(persistent_ops);
+            }
+        }
     }
 }
 
@@ -393,11 +544,14 @@ impl IngressPoolThrottler for IngressPoolImpl {
 mod tests {
     use super::*;
     use ic_interfaces::time_source::TimeSource;
+    use ic_test_utilities::metrics::fetch_int_counter;
     use ic_test_utilities::{
         mock_time, types::ids::node_test_id, types::messages::SignedIngressBuilder,
         with_test_replica_logger, FastForwardTimeSource,
     };
-    use ic_types::{artifact::IngressMessageAttribute, ingress::MAX_INGRESS_TTL};
+    use ic_types::{
+        artifact::IngressMessageAttribute, crypto::CryptoHash, ingress::MAX_INGRESS_TTL,
+    };
     use rand::Rng;
     use std::time::Duration;
 
@@ -721,4 +875,106 @@ mod tests {
             })
         })
     }
+
+    // Adds `ingress_msg` to the validated section of `ingress_pool`.
+    fn insert_validated(ingress_pool: &mut IngressPoolImpl, ingress_msg: SignedIngress) {
+        let message_id = IngressMessageId::from(&ingress_msg);
+        let attribute = IngressMessageAttribute::new(&ingress_msg);
+        let size = ingress_msg.count_bytes();
+        ingress_pool.insert(UnvalidatedArtifact {
+            message: ingress_msg,
+            peer_id: node_test_id(0),
+            timestamp: mock_time(),
+        });
+        ingress_pool.apply_changeset(vec![ChangeAction::MoveToValidated((
+            message_id,
+            node_test_id(0),
+            size,
+            attribute,
+            CryptoHash(vec![]),
+        ))]);
+    }
+
+    fn test_validated_section_is_recovered(pool_config: ArtifactPoolConfig) {
+        with_test_replica_logger(|log| {
+            let expiry = current_time() + MAX_INGRESS_TTL;
+            let recovered_msg = SignedIngressBuilder::new()
+                .nonce(1)
+                .expiry_time(expiry)
+                .build();
+            let removed_msg = SignedIngressBuilder::new()
+                .nonce(2)
+                .expiry_time(expiry)
+                .build();
+            let expired_msg = SignedIngressBuilder::new()
+                .nonce(3)
+                .expiry_time(mock_time())
+                .build();
+            {
+                let mut ingress_pool =
+                    IngressPoolImpl::new(pool_config.clone(), MetricsRegistry::new(), log.clone());
+                insert_validated(&mut ingress_pool, recovered_msg.clone());
+                insert_validated(&mut ingress_pool, removed_msg.clone());
+                insert_validated(&mut ingress_pool, expired_msg.clone());
+                ingress_pool.apply_changeset(vec![ChangeAction::RemoveFromValidated(
+                    IngressMessageId::from(&removed_msg),
+                )]);
+                // Unvalidated messages are not persisted.
+                ingress_pool.insert(UnvalidatedArtifact {
+                    message: SignedIngressBuilder::new()
+                        .nonce(4)
+                        .expiry_time(expiry)
+                        .build(),
+                    peer_id: node_test_id(0),
+                    timestamp: mock_time(),
+                });
+                assert_eq!(ingress_pool.validated().size(), 2);
+            }
+
+            let metrics_registry = MetricsRegistry::new();
+            let ingress_pool = IngressPoolImpl::new(pool_config, metrics_registry.clone(), log);
+            assert_eq!(ingress_pool.validated().size(), 1);
+            assert_eq!(ingress_pool.unvalidated().size(), 0);
+            assert_eq!(
+                ingress_pool.get_validated_by_identifier(&IngressMessageId::from(&recovered_msg)),
+                Some(recovered_msg)
+            );
+            assert_eq!(
+                fetch_int_counter(&metrics_registry, "ingress_pool_recovered_messages"),
+                Some(1)
+            );
+            assert_eq!(
+                fetch_int_counter(&metrics_registry, "ingress_pool_recovered_expired_messages"),
+                Some(1)
+            );
+            // The expired message was also dropped from disk.
+            assert_eq!(
+                ingress_pool
+                    .persistent_validated
+                    .as_ref()
+                    .unwrap()
+                    .load()
+                    .len(),
+                1
+            );
+        })
+    }
+
+    #[test]
+    fn test_validated_section_is_recovered_lmdb() {
+        ic_test_utilities::artifact_pool_config::with_test_pool_config(
+            test_validated_section_is_recovered,
+        )
+    }
+
+    #[test]
+    fn test_validated_section_is_recovered_rocksdb() {
+        ic_test_utilities::artifact_pool_config::with_test_rocksdb_pool_config(|config| {
+            let mut pool_config = ArtifactPoolConfig::new(
+                config.persistent_pool_validated_persistent_db_path.clone(),
+            );
+            pool_config.persistent_pool_backend = PersistentPoolBackend::RocksDB(config);
+            test_validated_section_is_recovered(pool_config)
+        })
+    }
 }
@@ -1,16 +1,18 @@
+pub mod backup;
 pub mod certification_pool;
 pub mod consensus_pool;
 mod consensus_pool_cache;
 pub mod dkg_pool;
 pub mod ecdsa_objects;
 pub mod ecdsa_pool;
+mod equivocation_pool;
 mod height_index;
 pub mod ingress_pool;
 mod inmemory_pool;
 mod metrics;
 mod peer_index;
+pub mod pool_migration;
 
-mod backup;
 mod lmdb_iterator;
 mod lmdb_pool;
 mod rocksdb_iterator;
@@ -1,4 +1,8 @@
 use crate::consensus_pool::{InitializablePoolSection, PoolSectionOp, PoolSectionOps};
+use crate::ingress_pool::{
+    deserialize_persistent, persistent_key, persistent_key_lower_bound, serialize_persistent,
+    PersistentIngressOp, PersistentIngressOps, PersistentIngressSection,
+};
 use crate::lmdb_iterator::LMDBIterator;
 use ic_config::artifact_pool::LMDBConfig;
 use ic_consensus_message::ConsensusMessageHashable;
@@ -8,11 +12,12 @@ use ic_interfaces::{
         HeightIndexedPool, HeightRange, OnlyError, PoolSection, ValidatedConsensusArtifact,
     },
     crypto::CryptoHashable,
+    ingress_pool::ValidatedIngressArtifact,
 };
 use ic_logger::{error, ReplicaLogger};
 use ic_protobuf::types::v1 as pb;
 use ic_types::{
-    artifact::{CertificationMessageId, ConsensusMessageId},
+    artifact::{CertificationMessageId, ConsensusMessageId, IngressMessageId},
     batch::BatchPayload,
     consensus::{
         catchup::CUPWithOriginalProtobuf,
@@ -283,6 +288,17 @@ const MAX_PERSISTENT_POOL_SIZE: usize = 0x0010_0000_0000; // 64GB
 /// Max number of DB readers.
 const MAX_READERS: c_uint = 2048;
 
+/// Mark fds created by lmdb as FD_CLOEXEC to prevent them from leaking into
+/// canister sandbox process. Details in NODE-166
+fn set_cloexec(db_env: &Environment) {
+    unsafe {
+        let mut fd: lmdb_sys::mdb_filehandle_t = lmdb_sys::mdb_filehandle_t::default();
+        lmdb_sys::mdb_env_get_fd(db_env.env(), &mut fd);
+        nix::fcntl::fcntl(fd, nix::fcntl::F_SETFD(nix::fcntl::FdFlag::FD_CLOEXEC))
+            .expect("Unable to mark FD_CLOEXEC");
+    };
+}
+
 ///////////////////////////// Generic Pool /////////////////////////////
 
 /// Collection of generic pool functions, indexed by Artifact type.
@@ -314,14 +330,7 @@ impl<Artifact: PoolArtifact> PersistentHeightIndexedPool<Artifact> {
                 panic!("Error opening LMDB environment at {:?}: {:?}", path, err)
             });
 
-        unsafe {
-            // Mark fds created by lmdb as FD_CLOEXEC to prevent them from leaking into
-            // canister sandbox process. Details in NODE-166
-            let mut fd: lmdb_sys::mdb_filehandle_t = lmdb_sys::mdb_filehandle_t::default();
-            lmdb_sys::mdb_env_get_fd(db_env.env(), &mut fd);
-            nix::fcntl::fcntl(fd, nix::fcntl::F_SETFD(nix::fcntl::FdFlag::FD_CLOEXEC))
-                .expect("Unable to mark FD_CLOEXEC");
-        };
+        set_cloexec(&db_env);
 
         // Create all databases.
         let meta = if read_only {
@@ -1258,6 +1267,127 @@ impl crate::certification_pool::MutablePoolSection
     }
 }
 
+///////////////////////////// Ingress Pool /////////////////////////////
+
+/// Persistent copy of the validated section of the ingress pool.
+///
+/// Ingress messages are not indexed by height, so unlike the pools above this
+/// is a single "INGRESS" database that maps the key of an `IngressMessageId`
+/// (see `persistent_key`) to the bincode encoded message and its timestamp.
+pub struct PersistentIngressPool {
+    db_env: Environment,
+    artifacts: Database,
+    log: ReplicaLogger,
+}
+
+impl PersistentIngressPool {
+    /// Return the persistent ingress pool in the "ingress" folder under the
+    /// configured path. Create the pool if it does not already exist.
+    /// Panic if initialization fails.
+    pub fn new_ingress_pool(config: LMDBConfig, log: ReplicaLogger) -> PersistentIngressPool {
+        let mut path = config.persistent_pool_validated_persistent_db_path;
+        path.push("ingress");
+        std::fs::create_dir_all(path.as_path()).ok();
+        let mut builder = Environment::new();
+        builder.set_flags(EnvironmentFlags::NO_TLS);
+        builder.set_max_readers(MAX_READERS);
+        builder.set_max_dbs(1);
+        builder.set_map_size(MAX_PERSISTENT_POOL_SIZE);
+        let db_env = builder
+            .open_with_permissions(path.as_path(), 0o644)
+            .unwrap_or_else(|err| {
+                panic!("Error opening LMDB environment at {:?}: {:?}", path, err)
+            });
+        set_cloexec(&db_env);
+        let artifacts = db_env
+            .create_db(Some("INGRESS"), DatabaseFlags::empty())
+            .unwrap_or_else(|err| panic!("Error creating db for ingress messages: {:?}", err));
+        PersistentIngressPool {
+            db_env,
+            artifacts,
+            log,
+        }
+    }
+
+    fn tx_mutate(&self, ops: PersistentIngressOps) -> lmdb::Result<()> {
+        let mut tx = self.db_env.begin_rw_txn()?;
+        for op in ops.ops {
+            match op {
+                PersistentIngressOp::Insert(artifact) => {
+                    let key = persistent_key(&IngressMessageId::from(&artifact.msg));
+                    // Skip messages that cannot be serialized, the others are
+                    // still written.
+                    if let Some(bytes) = log_err!(
+                        serialize_persistent(&artifact),
+                        self.log,
+                        "PersistentIngressPool::mutate serialize"
+                    ) {
+                        tx.put(self.artifacts, &key, &bytes, WriteFlags::empty())?
+                    }
+                }
+                PersistentIngressOp::Remove(message_id) => {
+                    // Ignore NotFound
+                    match tx.del(self.artifacts, &persistent_key(&message_id), None) {
+                        Err(lmdb::Error::NotFound) => Ok(()),
+                        result => result,
+                    }?
+                }
+                PersistentIngressOp::PurgeBelow(expiry) => {
+                    self.tx_purge_below(&mut tx, &persistent_key_lower_bound(expiry))?
+                }
+            }
+        }
+        tx.commit()
+    }
+
+    fn tx_purge_below<'a>(&self, tx: &mut RwTransaction<'a>, min_key: &[u8]) -> lmdb::Result<()> {
+        let mut cursor = tx.open_rw_cursor(self.artifacts)?;
+        loop {
+            match cursor.iter().next().transpose()? {
+                None => break,
+                Some((key, _)) => {
+                    if key >= min_key {
+                        break;
+                    }
+                    cursor.del(WriteFlags::empty())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn tx_load(&self) -> lmdb::Result<Vec<ValidatedIngressArtifact>> {
+        let tx = self.db_env.begin_ro_txn()?;
+        let mut cursor = tx.open_ro_cursor(self.artifacts)?;
+        let mut artifacts = Vec::new();
+        for entry in cursor.iter_start() {
+            let (_, bytes) = entry?;
+            if let Some(artifact) = log_err!(
+                deserialize_persistent(bytes),
+                self.log,
+                "PersistentIngressPool::load deserialize"
+            ) {
+                artifacts.push(artifact);
+            }
+        }
+        Ok(artifacts)
+    }
+}
+
+impl PersistentIngressSection for PersistentIngressPool {
+    fn mutate(&self, ops: PersistentIngressOps) {
+        log_err!(
+            self.tx_mutate(ops),
+            self.log,
+            "PersistentIngressPool::mutate"
+        );
+    }
+
+    fn load(&self) -> Vec<ValidatedIngressArtifact> {
+        log_err!(self.tx_load(), self.log, "PersistentIngressPool::load").unwrap_or_default()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,687 @@
+//! Migration of the persistent pool between the LMDB and RocksDB backends.
+//!
+//! Both backends keep the validated consensus pool, the validated
+//! certification pool and the validated ingress messages in the `consensus`,
+//! `certification` and `ingress` sections of the persistent pool directory.
+//! When the backend on disk differs from the configured one, all validated
+//! artifacts are copied into a fresh pool of the configured backend in the
+//! `migration` subdirectory and compared against the original pool. Only if
+//! both pools hold the same artifacts are the sections swapped; a migration
+//! interrupted at any point is rolled back and redone on the next start.
+
+use crate::{
+    certification_pool::CertificationPoolImpl,
+    consensus_pool::{
+        InitializablePoolSection, MutablePoolSection, PoolSectionOps, UncachedConsensusPoolImpl,
+    },
+    ingress_pool::{open_persistent_section, PersistentIngressOps},
+};
+use ic_config::artifact_pool::{ArtifactPoolConfig, ArtifactPoolTomlConfig, PersistentPoolBackend};
+use ic_consensus_message::ConsensusMessageHashable;
+use ic_interfaces::{
+    consensus_pool::{HeightIndexedPool, PoolSection, ValidatedConsensusArtifact},
+    ingress_pool::ValidatedIngressArtifact,
+};
+use ic_logger::{info, warn, ReplicaLogger};
+use ic_metrics::MetricsRegistry;
+use ic_types::{
+    artifact::{ConsensusMessageId, IngressMessageId},
+    consensus::{catchup::CUPWithOriginalProtobuf, ConsensusMessage},
+    time::current_time,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The sections of the persistent pool, as subdirectories of its path.
+const SECTIONS: [&str; 3] = ["consensus", "certification", "ingress"];
+
+/// The subdirectory the migrated pool is written to.
+const MIGRATION_DIR: &str = "migration";
+
+/// The suffix of a section that is being replaced by its migrated copy.
+const OLD_SECTION_SUFFIX: &str = "old";
+
+/// A backend of the persistent pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Lmdb,
+    RocksDB,
+}
+
+impl Backend {
+    /// Returns the name of the backend, as used by `consensus_pool_backend`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Backend::Lmdb => "lmdb",
+            Backend::RocksDB => "rocksdb",
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl From<&PersistentPoolBackend> for Backend {
+    fn from(backend: &PersistentPoolBackend) -> Self {
+        match backend {
+            PersistentPoolBackend::Lmdb(_) => Backend::Lmdb,
+            PersistentPoolBackend::RocksDB(_) => Backend::RocksDB,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PoolMigrationError {
+    /// The sections of the pool on disk are stored with different backends.
+    MixedBackends,
+    /// The migrated pool does not hold the same artifacts as the original one.
+    Inconsistent(String),
+    /// Reading or writing the pool directory failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for PoolMigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolMigrationError::MixedBackends => write!(
+                f,
+                "the sections of the persistent pool are stored with different backends"
+            ),
+            PoolMigrationError::Inconsistent(msg) => {
+                write!(f, "the migrated persistent pool is inconsistent: {}", msg)
+            }
+            PoolMigrationError::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl From<io::Error> for PoolMigrationError {
+    fn from(err: io::Error) -> Self {
+        PoolMigrationError::Io(err)
+    }
+}
+
+/// Returns the backend of the pool section stored at `section_path`, or
+/// `None` if there is no such section.
+fn detect_section_backend(section_path: &Path) -> Option<Backend> {
+    if section_path.join("data.mdb").exists() {
+        Some(Backend::Lmdb)
+    } else if section_path.join("CURRENT").exists() {
+        Some(Backend::RocksDB)
+    } else {
+        None
+    }
+}
+
+/// Returns the backend of the persistent pool stored at `pool_path`, or `None`
+/// if there is no pool.
+pub fn detect_backend(pool_path: &Path) -> Result<Option<Backend>, PoolMigrationError> {
+    let mut backends = SECTIONS
+        .iter()
+        .filter_map(|section| detect_section_backend(&pool_path.join(section)));
+    let backend = backends.next();
+    if backends.any(|other| Some(other) != backend) {
+        return Err(PoolMigrationError::MixedBackends);
+    }
+    Ok(backend)
+}
+
+/// Returns the config of a persistent pool at `pool_path` stored with
+/// `backend`, otherwise equal to `config`.
+fn config_for_backend(
+    config: &ArtifactPoolConfig,
+    backend: Backend,
+    pool_path: PathBuf,
+) -> ArtifactPoolConfig {
+    let mut toml_config = ArtifactPoolTomlConfig::new(pool_path, None);
+    toml_config.consensus_pool_backend = Some(backend.to_string());
+    let persistent_pool_backend = match (
+        ArtifactPoolConfig::from(toml_config).persistent_pool_backend,
+        &config.persistent_pool_backend,
+    ) {
+        // Keep the RocksDB parameters of the configured pool.
+        (PersistentPoolBackend::RocksDB(mut rocksdb_config), PersistentPoolBackend::RocksDB(c)) => {
+            rocksdb_config.persistent_pool_validated_skip_fsync_for_tests =
+                c.persistent_pool_validated_skip_fsync_for_tests;
+            rocksdb_config.persistent_pool_validated_purge_interval =
+                c.persistent_pool_validated_purge_interval;
+            PersistentPoolBackend::RocksDB(rocksdb_config)
+        }
+        (backend, _) => backend,
+    };
+    ArtifactPoolConfig {
+        persistent_pool_backend,
+        persistent_pool_read_only: false,
+        backup_config: None,
+        ..config.clone()
+    }
+}
+
+/// Migrates the persistent pool to the backend configured in `config`, if it
+/// is stored with a different backend on disk. Returns whether the pool was
+/// migrated.
+///
+/// Must be called before the pools are opened.
+pub fn migrate_persistent_pool(
+    config: &ArtifactPoolConfig,
+    log: &ReplicaLogger,
+) -> Result<bool, PoolMigrationError> {
+    let pool_path = config.persistent_pool_db_path();
+    recover_interrupted_migration(&pool_path, log)?;
+
+    let target = Backend::from(&config.persistent_pool_backend);
+    let source = match detect_backend(&pool_path)? {
+        Some(source) if source != target => source,
+        _ => return Ok(false),
+    };
+    info!(
+        log,
+        "Migrating the persistent pool at {:?} from {} to {}", pool_path, source, target
+    );
+
+    let migration_path = pool_path.join(MIGRATION_DIR);
+    if migration_path.exists() {
+        fs::remove_dir_all(&migration_path)?;
+    }
+    let source_config = config_for_backend(config, source, pool_path.clone());
+    let target_config = config_for_backend(config, target, migration_path.clone());
+    let result = copy_consensus_pool(&source_config, &target_config, log)
+        .and_then(|()| copy_certification_pool(&source_config, &target_config, log))
+        .and_then(|()| copy_ingress_pool(&source_config, &target_config, log));
+    if let Err(err) = result {
+        fs::remove_dir_all(&migration_path)?;
+        return Err(err);
+    }
+
+    // Swap the sections, such that an interruption leaves either the complete
+    // original sections next to the migration directory, or no migration
+    // directory at all.
+    for section in SECTIONS.iter() {
+        let section_path = pool_path.join(section);
+        if section_path.exists() {
+            fs::rename(&section_path, old_section_path(&pool_path, section))?;
+        }
+    }
+    for section in SECTIONS.iter() {
+        let migrated_path = migration_path.join(section);
+        if migrated_path.exists() {
+            fs::rename(&migrated_path, pool_path.join(section))?;
+        }
+    }
+    fs::remove_dir_all(&migration_path)?;
+    remove_old_sections(&pool_path)?;
+
+    info!(
+        log,
+        "Migrated the persistent pool at {:?} from {} to {}", pool_path, source, target
+    );
+    Ok(true)
+}
+
+fn old_section_path(pool_path: &Path, section: &str) -> PathBuf {
+    pool_path.join(format!("{}.{}", section, OLD_SECTION_SUFFIX))
+}
+
+fn remove_old_sections(pool_path: &Path) -> io::Result<()> {
+    for section in SECTIONS.iter() {
+        let old_path = old_section_path(pool_path, section);
+        if old_path.exists() {
+            fs::remove_dir_all(old_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Cleans up after a migration that was interrupted. If the migrated sections
+/// were not completely swapped in yet, the original sections are restored.
+fn recover_interrupted_migration(pool_path: &Path, log: &ReplicaLogger) -> io::Result<()> {
+    let migration_path = pool_path.join(MIGRATION_DIR);
+    if migration_path.exists() {
+        warn!(
+            log,
+            "Rolling back an interrupted migration of the persistent pool at {:?}", pool_path
+        );
+        for section in SECTIONS.iter() {
+            let old_path = old_section_path(pool_path, section);
+            if old_path.exists() {
+                let section_path = pool_path.join(section);
+                if section_path.exists() {
+                    fs::remove_dir_all(&section_path)?;
+                }
+                fs::rename(old_path, section_path)?;
+            }
+        }
+        fs::remove_dir_all(migration_path)?;
+    }
+    remove_old_sections(pool_path)
+}
+
+/// Returns all validated consensus artifacts except catch-up packages, which
+/// are copied along with their protobufs.
+fn consensus_artifacts(
+    pool: &dyn PoolSection<ValidatedConsensusArtifact>,
+) -> Vec<ConsensusMessage> {
+    fn all<T: ConsensusMessageHashable>(
+        pool: &dyn HeightIndexedPool<T>,
+    ) -> impl Iterator<Item = ConsensusMessage> {
+        pool.get_all().map(|artifact| artifact.into_message())
+    }
+    all(pool.random_beacon())
+        .chain(all(pool.random_beacon_share()))
+        .chain(all(pool.block_proposal()))
+        .chain(all(pool.notarization()))
+        .chain(all(pool.notarization_share()))
+        .chain(all(pool.finalization()))
+        .chain(all(pool.finalization_share()))
+        .chain(all(pool.random_tape()))
+        .chain(all(pool.random_tape_share()))
+        .chain(all(pool.catch_up_package_share()))
+        .collect()
+}
+
+fn copy_consensus_pool(
+    source_config: &ArtifactPoolConfig,
+    target_config: &ArtifactPoolConfig,
+    log: &ReplicaLogger,
+) -> Result<(), PoolMigrationError> {
+    let source = UncachedConsensusPoolImpl::new(source_config.clone(), log.clone());
+    let mut target = UncachedConsensusPoolImpl::new(target_config.clone(), log.clone());
+    let source_section = source.validated.pool_section();
+
+    let artifacts = consensus_artifacts(source_section);
+    let mut ops = PoolSectionOps::new();
+    for msg in artifacts.iter() {
+        let timestamp = source_section
+            .get_timestamp(&msg.get_id())
+            .unwrap_or_else(current_time);
+        ops.insert(ValidatedConsensusArtifact {
+            msg: msg.clone(),
+            timestamp,
+        });
+    }
+    target.validated.mutate(ops);
+
+    // The highest catch-up package keeps the protobuf it was received as, the
+    // protobufs of the others are not retained by the pool.
+    let highest_cup = source_section.catch_up_package().get_highest().ok();
+    let highest_cup_id = highest_cup.as_ref().map(|cup| cup.get_id());
+    for cup in source_section.catch_up_package().get_all() {
+        if Some(cup.get_id()) != highest_cup_id {
+            target
+                .validated
+                .insert_cup_with_proto(CUPWithOriginalProtobuf::from_cup(cup));
+        }
+    }
+    if let Some(cup) = highest_cup {
+        target
+            .validated
+            .insert_cup_with_proto(CUPWithOriginalProtobuf {
+                cup,
+                protobuf: source_section.highest_catch_up_package_proto(),
+            });
+    }
+
+    check_consensus_pool(source_section, target.validated.pool_section())?;
+    info!(
+        log,
+        "Migrated {} consensus artifacts and {} catch-up packages",
+        artifacts.len(),
+        source_section.catch_up_package().get_all().count()
+    );
+    Ok(())
+}
+
+/// Checks that `target` holds the same validated consensus artifacts as
+/// `source`.
+fn check_consensus_pool(
+    source: &dyn PoolSection<ValidatedConsensusArtifact>,
+    target: &dyn PoolSection<ValidatedConsensusArtifact>,
+) -> Result<(), PoolMigrationError> {
+    fn ids(pool: &dyn PoolSection<ValidatedConsensusArtifact>) -> HashSet<ConsensusMessageId> {
+        consensus_artifacts(pool)
+            .into_iter()
+            .chain(
+                pool.catch_up_package()
+                    .get_all()
+                    .map(|cup| cup.into_message()),
+            )
+            .map(|msg| msg.get_id())
+            .collect()
+    }
+    let source_ids = ids(source);
+    let target_ids = ids(target);
+    if source_ids != target_ids {
+        return Err(PoolMigrationError::Inconsistent(format!(
+            "{} consensus artifacts are missing, {} are unexpected",
+            source_ids.difference(&target_ids).count(),
+            target_ids.difference(&source_ids).count()
+        )));
+    }
+
+    // Block payloads are stored separately from the proposals and compared
+    // by hash only, hence they are compared explicitly.
+    for proposal in source.block_proposal().get_all() {
+        let id = proposal.get_id();
+        let migrated = match target.get(&id) {
+            Some(ConsensusMessage::BlockProposal(migrated)) => migrated,
+            _ => {
+                return Err(PoolMigrationError::Inconsistent(format!(
+                    "block proposal {:?} is missing",
+                    id
+                )))
+            }
+        };
+        if proposal.content.as_ref().payload.as_ref() != migrated.content.as_ref().payload.as_ref()
+        {
+            return Err(PoolMigrationError::Inconsistent(format!(
+                "payload of block proposal {:?} differs",
+                id
+            )));
+        }
+    }
+
+    if source.catch_up_package().get_highest().is_ok()
+        && source.highest_catch_up_package_proto() != target.highest_catch_up_package_proto()
+    {
+        return Err(PoolMigrationError::Inconsistent(
+            "protobuf of the highest catch-up package differs".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn copy_certification_pool(
+    source_config: &ArtifactPoolConfig,
+    target_config: &ArtifactPoolConfig,
+    log: &ReplicaLogger,
+) -> Result<(), PoolMigrationError> {
+    let source =
+        CertificationPoolImpl::new(source_config.clone(), log.clone(), MetricsRegistry::new());
+    let target =
+        CertificationPoolImpl::new(target_config.clone(), log.clone(), MetricsRegistry::new());
+
+    let certifications = source.persistent_pool.certifications().get_all();
+    let shares = source.persistent_pool.certification_shares().get_all();
+    for certification in certifications {
+        target.persistent_pool.insert(certification.into());
+    }
+    for share in shares {
+        target.persistent_pool.insert(share.into());
+    }
+
+    let source_certifications = source
+        .persistent_pool
+        .certifications()
+        .get_all()
+        .collect::<HashSet<_>>();
+    let source_shares = source
+        .persistent_pool
+        .certification_shares()
+        .get_all()
+        .collect::<HashSet<_>>();
+    if source_certifications
+        != target
+            .persistent_pool
+            .certifications()
+            .get_all()
+            .collect::<HashSet<_>>()
+        || source_shares
+            != target
+                .persistent_pool
+                .certification_shares()
+                .get_all()
+                .collect::<HashSet<_>>()
+    {
+        return Err(PoolMigrationError::Inconsistent(
+            "certification artifacts differ".to_string(),
+        ));
+    }
+    info!(
+        log,
+        "Migrated {} certifications and {} certification shares",
+        source_certifications.len(),
+        source_shares.len()
+    );
+    Ok(())
+}
+
+fn copy_ingress_pool(
+    source_config: &ArtifactPoolConfig,
+    target_config: &ArtifactPoolConfig,
+    log: &ReplicaLogger,
+) -> Result<(), PoolMigrationError> {
+    let source =
+        open_persistent_section(source_config.persistent_pool_backend.clone(), log.clone());
+    let target =
+        open_persistent_section(target_config.persistent_pool_backend.clone(), log.clone());
+
+    let artifacts = source.load();
+    let mut ops = PersistentIngressOps::new();
+    for artifact in artifacts.iter() {
+        ops.insert(artifact.clone());
+    }
+    target.mutate(ops);
+
+    fn ids(artifacts: &[ValidatedIngressArtifact]) -> Vec<IngressMessageId> {
+        artifacts
+            .iter()
+            .map(|artifact| IngressMessageId::from(&artifact.msg))
+            .collect()
+    }
+    if ids(&artifacts) != ids(&target.load()) {
+        return Err(PoolMigrationError::Inconsistent(
+            "ingress messages differ".to_string(),
+        ));
+    }
+    info!(log, "Migrated {} ingress messages", artifacts.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_consensus_message::make_genesis;
+    use ic_interfaces::artifact_pool::ValidatedArtifact;
+    use ic_interfaces::consensus_pool::ConsensusPool;
+    use ic_logger::replica_logger::no_op_logger;
+    use ic_test_utilities::{
+        consensus::fake::*, mock_time, types::ids::node_test_id,
+        types::messages::SignedIngressBuilder, with_test_replica_logger,
+    };
+    use ic_types::{
+        consensus::{
+            certification::{Certification, CertificationContent, CertificationShare},
+            dkg::Summary,
+            Block, BlockProposal, RandomBeacon, ThresholdSignature, ThresholdSignatureShare,
+        },
+        crypto::{CryptoHash, Signed},
+        CryptoHashOfPartialState, Height,
+    };
+
+    fn pool_config(path: &Path, backend: Backend) -> ArtifactPoolConfig {
+        let mut toml_config = ArtifactPoolTomlConfig::new(path.to_path_buf(), None);
+        toml_config.consensus_pool_backend = Some(backend.to_string());
+        ArtifactPoolConfig::from(toml_config)
+    }
+
+    fn make_summary(genesis_height: Height) -> Summary {
+        let mut summary = Summary::fake();
+        summary.height = genesis_height;
+        summary
+    }
+
+    fn populate_pool(config: &ArtifactPoolConfig) {
+        let mut pool = UncachedConsensusPoolImpl::new(config.clone(), no_op_logger());
+        let cup = make_genesis(make_summary(Height::from(0)));
+        let mut ops = PoolSectionOps::new();
+        for i in 1..10 {
+            let parent = make_genesis(make_summary(Height::from(i - 1)));
+            let proposal = BlockProposal::fake(
+                Block::from_parent(parent.content.block.as_ref()),
+                node_test_id(0),
+            );
+            let beacon = RandomBeacon::from_parent(parent.content.random_beacon.as_ref());
+            ops.insert(ValidatedConsensusArtifact {
+                msg: proposal.into_message(),
+                timestamp: mock_time(),
+            });
+            ops.insert(ValidatedConsensusArtifact {
+                msg: beacon.into_message(),
+                timestamp: mock_time(),
+            });
+        }
+        pool.validated.mutate(ops);
+        pool.validated
+            .insert_cup_with_proto(CUPWithOriginalProtobuf::from_cup(cup));
+
+        let certification_pool =
+            CertificationPoolImpl::new(config.clone(), no_op_logger(), MetricsRegistry::new());
+        for height in 1..5 {
+            let content =
+                CertificationContent::new(CryptoHashOfPartialState::from(CryptoHash(vec![])));
+            certification_pool.persistent_pool.insert(
+                Certification {
+                    height: Height::from(height),
+                    signed: Signed {
+                        content: content.clone(),
+                        signature: ThresholdSignature::fake(),
+                    },
+                }
+                .into(),
+            );
+            certification_pool.persistent_pool.insert(
+                CertificationShare {
+                    height: Height::from(height),
+                    signed: Signed {
+                        content,
+                        signature: ThresholdSignatureShare::fake(node_test_id(height)),
+                    },
+                }
+                .into(),
+            );
+        }
+
+        let ingress_pool =
+            open_persistent_section(config.persistent_pool_backend.clone(), no_op_logger());
+        let mut ops = PersistentIngressOps::new();
+        for nonce in 0..3 {
+            ops.insert(ValidatedArtifact {
+                msg: SignedIngressBuilder::new().nonce(nonce).build().into(),
+                timestamp: mock_time(),
+            });
+        }
+        ingress_pool.mutate(ops);
+    }
+
+    fn check_pool(config: &ArtifactPoolConfig) {
+        let pool = UncachedConsensusPoolImpl::new(config.clone(), no_op_logger());
+        assert_eq!(pool.validated().block_proposal().get_all().count(), 9);
+        assert_eq!(pool.validated().random_beacon().get_all().count(), 9);
+        assert_eq!(pool.validated().catch_up_package().get_all().count(), 1);
+        for proposal in pool.validated().block_proposal().get_all() {
+            assert_eq!(
+                pool.validated().get_timestamp(&proposal.get_id()),
+                Some(mock_time())
+            );
+        }
+
+        let certification_pool =
+            CertificationPoolImpl::new(config.clone(), no_op_logger(), MetricsRegistry::new());
+        assert_eq!(
+            certification_pool
+                .persistent_pool
+                .certifications()
+                .get_all()
+                .count(),
+            4
+        );
+        assert_eq!(
+            certification_pool
+                .persistent_pool
+                .certification_shares()
+                .get_all()
+                .count(),
+            4
+        );
+
+        let ingress_pool =
+            open_persistent_section(config.persistent_pool_backend.clone(), no_op_logger());
+        assert_eq!(ingress_pool.load().len(), 3);
+    }
+
+    fn test_migration(source: Backend, target: Backend) {
+        with_test_replica_logger(|log| {
+            let tempdir = tempfile::Builder::new()
+                .prefix("persistent-pool")
+                .tempdir()
+                .unwrap();
+            populate_pool(&pool_config(tempdir.path(), source));
+            assert_eq!(detect_backend(tempdir.path()).unwrap(), Some(source));
+
+            let config = pool_config(tempdir.path(), target);
+            assert!(migrate_persistent_pool(&config, &log).unwrap());
+            assert_eq!(detect_backend(tempdir.path()).unwrap(), Some(target));
+            assert!(!tempdir.path().join(MIGRATION_DIR).exists());
+            check_pool(&config);
+
+            // The pool is only migrated once.
+            assert!(!migrate_persistent_pool(&config, &log).unwrap());
+            check_pool(&config);
+        })
+    }
+
+    #[test]
+    fn test_migrate_lmdb_to_rocksdb() {
+        test_migration(Backend::Lmdb, Backend::RocksDB);
+    }
+
+    #[test]
+    fn test_migrate_rocksdb_to_lmdb() {
+        test_migration(Backend::RocksDB, Backend::Lmdb);
+    }
+
+    #[test]
+    fn test_migrate_empty_pool() {
+        with_test_replica_logger(|log| {
+            let tempdir = tempfile::Builder::new()
+                .prefix("persistent-pool")
+                .tempdir()
+                .unwrap();
+            let config = pool_config(tempdir.path(), Backend::RocksDB);
+            assert_eq!(detect_backend(tempdir.path()).unwrap(), None);
+            assert!(!migrate_persistent_pool(&config, &log).unwrap());
+        })
+    }
+
+    #[test]
+    fn test_interrupted_migration_is_rolled_back() {
+        with_test_replica_logger(|log| {
+            let tempdir = tempfile::Builder::new()
+                .prefix("persistent-pool")
+                .tempdir()
+                .unwrap();
+            let pool_path = tempdir.path();
+            populate_pool(&pool_config(pool_path, Backend::Lmdb));
+
+            // Simulate an interruption after the first section was moved
+            // aside and before the migrated sections were swapped in.
+            fs::create_dir_all(pool_path.join(MIGRATION_DIR).join("consensus")).unwrap();
+            fs::rename(
+                pool_path.join("consensus"),
+                old_section_path(pool_path, "consensus"),
+            )
+            .unwrap();
+
+            let config = pool_config(pool_path, Backend::RocksDB);
+            assert!(migrate_persistent_pool(&config, &log).unwrap());
+            assert!(!old_section_path(pool_path, "consensus").exists());
+            check_pool(&config);
+        })
+    }
+}
@@ -2,6 +2,10 @@
 use crate::consensus_pool::{
     InitializablePoolSection, MutablePoolSection, PoolSectionOp, PoolSectionOps,
 };
+use crate::ingress_pool::{
+    deserialize_persistent, persistent_key, persistent_key_lower_bound, serialize_persistent,
+    PersistentIngressOp, PersistentIngressOps, PersistentIngressSection,
+};
 use crate::rocksdb_iterator::{StandaloneIterator, StandaloneSnapshot};
 use bincode::{deserialize, serialize};
 use byteorder::{BigEndian, ReadBytesExt};
@@ -13,10 +17,11 @@ use ic_interfaces::consensus_pool::{
     HeightIndexedPool, HeightRange, OnlyError, PoolSection, ValidatedConsensusArtifact,
 };
 use ic_interfaces::crypto::CryptoHashable;
+use ic_interfaces::ingress_pool::ValidatedIngressArtifact;
 use ic_logger::{info, warn, ReplicaLogger};
 use ic_protobuf::types::v1 as pb;
 use ic_types::{
-    artifact::ConsensusMessageId,
+    artifact::{ConsensusMessageId, IngressMessageId},
     batch::BatchPayload,
     consensus::{
         catchup::CUPWithOriginalProtobuf,
@@ -30,7 +35,7 @@ use ic_types::{
 };
 use rocksdb::{
     compaction_filter::{CompactionFilterFn, Decision},
-    ColumnFamilyDescriptor, DBCompressionType, Options, WriteBatch, DB,
+    ColumnFamilyDescriptor, DBCompressionType, IteratorMode, Options, WriteBatch, DB,
 };
 use std::convert::TryFrom;
 use std::marker::PhantomData;
@@ -1139,11 +1144,100 @@ impl<Message: CertificationType + PerTypeCFInfo + 'static> HeightIndexedPool<Mes
     }
 }
 
+/////////////////////////////  Ingress Pool  /////////////////////////////
+
+/// Persistent copy of the validated section of the ingress pool.
+///
+/// Ingress messages are not indexed by height, so this is a plain DB without
+/// column families that maps the key of an `IngressMessageId` (see
+/// `persistent_key`) to the bincode encoded message and its timestamp.
+pub struct PersistentIngressPool {
+    db: DB,
+    log: ReplicaLogger,
+}
+
+impl PersistentIngressPool {
+    /// Return the persistent ingress pool in the "ingress" folder under the
+    /// configured path. Create the pool if it does not already exist.
+    pub fn new_ingress_pool(config: RocksDBConfig, log: ReplicaLogger) -> PersistentIngressPool {
+        let mut db_path = config.persistent_pool_validated_persistent_db_path;
+        db_path.push("ingress");
+        let mut db_options = Options::default();
+        set_common_db_options(&mut db_options);
+        if config.persistent_pool_validated_skip_fsync_for_tests {
+            db_options.set_use_fsync(false);
+        }
+        match DB::open(&db_options, &db_path) {
+            Ok(db) => PersistentIngressPool { db, log },
+            Err(err) => panic!(
+                "Error creating persistent ingress pool at: {:?}. Error: {}",
+                db_path, err
+            ),
+        }
+    }
+
+    /// Add the deletion of all messages that expire before `expiry` to the
+    /// given 'WriteBatch', including those inserted by the batch itself, whose
+    /// keys are given by 'batch_keys'.
+    fn purge_below(&self, batch: &mut WriteBatch, batch_keys: &[Vec<u8>], expiry: Time) {
+        let min_key = persistent_key_lower_bound(expiry);
+        for (key, _) in self.db.iterator(IteratorMode::Start) {
+            if *key >= *min_key {
+                break;
+            }
+            batch.delete(key);
+        }
+        for key in batch_keys.iter().filter(|key| **key < min_key) {
+            batch.delete(key);
+        }
+    }
+}
+
+impl PersistentIngressSection for PersistentIngressPool {
+    fn mutate(&self, ops: PersistentIngressOps) {
+        let mut batch = WriteBatch::default();
+        let mut batch_keys = Vec::new();
+        for op in ops.ops {
+            match op {
+                PersistentIngressOp::Insert(artifact) => {
+                    let key = persistent_key(&IngressMessageId::from(&artifact.msg));
+                    batch.put(&key, check_ok_uw!(serialize_persistent(&artifact)));
+                    batch_keys.push(key);
+                }
+                PersistentIngressOp::Remove(message_id) => {
+                    batch.delete(persistent_key(&message_id));
+                }
+                PersistentIngressOp::PurgeBelow(expiry) => {
+                    self.purge_below(&mut batch, &batch_keys, expiry)
+                }
+            }
+        }
+        check_ok!(self.db.write(batch));
+    }
+
+    fn load(&self) -> Vec<ValidatedIngressArtifact> {
+        self.db
+            .iterator(IteratorMode::Start)
+            .filter_map(|(key, bytes)| match deserialize_persistent(&bytes) {
+                Ok(artifact) => Some(artifact),
+                Err(err) => {
+                    warn!(
+                        self.log,
+                        "Skipping corrupt persistent ingress message {:?}: {:?}", key, err
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use ic_consensus_message::make_genesis;
     use ic_test_utilities::types::ids::node_test_id;
+    use ic_test_utilities::types::messages::SignedIngressBuilder;
     use ic_test_utilities::{consensus::fake::*, mock_time};
     use ic_types::{
         consensus::{
@@ -1913,4 +2007,47 @@ mod tests {
             }
         });
     }
+
+    // Test that the ingress pool applies its operations in order, including a
+    // purge of messages inserted by the same batch, and skips corrupt entries
+    // when loading.
+    #[test]
+    fn test_ingress_pool_skips_corrupt_entries() {
+        run_persistent_pool_test("test_ingress_pool_skips_corrupt_entries", |config, log| {
+            let expiry = mock_time() + Duration::from_secs(100);
+            let artifacts: Vec<ValidatedIngressArtifact> = (0..3)
+                .map(|nonce| ValidatedArtifact {
+                    msg: SignedIngressBuilder::new()
+                        .nonce(nonce)
+                        .expiry_time(expiry + Duration::from_secs(nonce))
+                        .build()
+                        .into(),
+                    timestamp: mock_time(),
+                })
+                .collect();
+            let message_ids: Vec<_> = artifacts
+                .iter()
+                .map(|artifact| IngressMessageId::from(&artifact.msg))
+                .collect();
+
+            let pool = PersistentIngressPool::new_ingress_pool(config.clone(), log.clone());
+            let mut ops = PersistentIngressOps::new();
+            for artifact in artifacts {
+                ops.insert(artifact);
+            }
+            ops.remove(message_ids[1].clone());
+            ops.purge_below(expiry + Duration::from_secs(1));
+            pool.mutate(ops);
+            check_ok!(pool.db.put(persistent_key(&message_ids[1]), b"corrupt"));
+            drop(pool);
+
+            let pool = PersistentIngressPool::new_ingress_pool(config, log);
+            let loaded: Vec<_> = pool
+                .load()
+                .iter()
+                .map(|artifact| IngressMessageId::from(&artifact.msg))
+                .collect();
+            assert_eq!(loaded, vec![message_ids[2].clone()]);
+        });
+    }
 }
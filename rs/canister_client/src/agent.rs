@@ -223,7 +223,7 @@ pub struct Agent {
     pub ingress_timeout: Duration,
 
     // How long to wait for queries.
-    query_timeout: Duration,
+    pub(crate) query_timeout: Duration,
 
     // Per reqwest document, cloning a client does not clone the actual connection pool inside.
     // Therefore directly owning a client as opposed to a reference is the standard way to go.
@@ -568,7 +568,7 @@ pub fn sign_read(
     })
 }
 
-fn bytes_to_cbor(bytes: Vec<u8>) -> Result<CBOR, String> {
+pub(crate) fn bytes_to_cbor(bytes: Vec<u8>) -> Result<CBOR, String> {
     let cbor = serde_cbor::from_slice(&bytes).map_err(|e| {
         format!(
             "Agent::bytes_to_cbor: Failed to parse result from IC, got: {:?} - error {:?}",
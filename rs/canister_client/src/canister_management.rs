@@ -1,7 +1,17 @@
 //! Functions for clients to talk to the Management Canister, a.k.a ic:00.
-use crate::agent::Agent;
-
-use ic_types::ic00::{InstallCodeArgs, Method, Payload, IC_00};
+use crate::{
+    agent::Agent,
+    typed_call::{CallError, UpdateBuilder},
+};
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
+use ic_types::{
+    ic00::{
+        CanisterIdRecord, CanisterSettingsArgs, CanisterStatusResultV2, InstallCodeArgs, Method,
+        Payload, ProvisionalCreateCanisterWithCyclesArgs, ProvisionalTopUpCanisterArgs,
+        SetControllerArgs, UpdateSettingsArgs, IC_00,
+    },
+    CanisterId, CanisterInstallMode, PrincipalId,
+};
 
 impl Agent {
     // Ships a binary wasm module to a canister.
@@ -11,3 +21,371 @@ impl Agent {
             .map(|_| ())
     }
 }
+
+/// A typed client for the methods of the management canister that can be
+/// called with ingress messages.
+///
+/// `create_canister`, `deposit_cycles` and `raw_rand` are missing on purpose:
+/// the management canister rejects ingress messages for them, they can only
+/// be called by canisters (e.g. through a wallet canister).
+///
+/// Calls that act on an existing canister are routed to the subnet hosting
+/// that canister, i.e. the canister is used as the effective canister id.
+/// Calls that do not name a canister (provisionally creating canisters) are
+/// routed using the effective canister id the client was created with.
+pub struct ManagementCanister<'a> {
+    agent: &'a Agent,
+    effective_canister_id: CanisterId,
+}
+
+impl<'a> ManagementCanister<'a> {
+    /// Creates a client that routes calls not naming a canister to `IC_00`.
+    pub fn new(agent: &'a Agent) -> Self {
+        Self {
+            agent,
+            effective_canister_id: IC_00,
+        }
+    }
+
+    /// Sets the effective canister id used for calls that do not name a
+    /// canister. Any canister id on the target subnet can be used to make
+    /// sure that e.g. new canisters are created on that subnet.
+    pub fn with_effective_canister_id(mut self, effective_canister_id: CanisterId) -> Self {
+        self.effective_canister_id = effective_canister_id;
+        self
+    }
+
+    /// Creates a new canister holding `amount` cycles (or the default amount,
+    /// if `None`) and returns its id. Only available on subnets that allow
+    /// provisional calls.
+    pub async fn provisional_create_canister_with_cycles(
+        &self,
+        amount: Option<u64>,
+        settings: Option<CanisterSettingsArgs>,
+    ) -> Result<CanisterId, CallError> {
+        let (record,) = self
+            .provisional_create_canister_with_cycles_call(amount, settings)
+            .call()
+            .await?;
+        Ok(record.get_canister_id())
+    }
+
+    /// Adds `amount` cycles to the balance of the given canister. Only
+    /// available on subnets that allow provisional calls.
+    pub async fn provisional_top_up_canister(
+        &self,
+        canister_id: CanisterId,
+        amount: u64,
+    ) -> Result<(), CallError> {
+        self.provisional_top_up_canister_call(canister_id, amount)
+            .call()
+            .await
+    }
+
+    /// Updates the settings of the given canister. Settings that are `None`
+    /// are left unchanged.
+    pub async fn update_settings(
+        &self,
+        canister_id: CanisterId,
+        settings: CanisterSettingsArgs,
+    ) -> Result<(), CallError> {
+        self.update_settings_call(canister_id, settings)
+            .call()
+            .await
+    }
+
+    /// Sets the (only) controller of the given canister.
+    pub async fn set_controller(
+        &self,
+        canister_id: CanisterId,
+        controller: PrincipalId,
+    ) -> Result<(), CallError> {
+        self.set_controller_call(canister_id, controller)
+            .call()
+            .await
+    }
+
+    /// Installs code as described by `install_args`.
+    pub async fn install_code(&self, install_args: InstallCodeArgs) -> Result<(), CallError> {
+        self.install_code_call(install_args).call().await
+    }
+
+    /// Installs `wasm_module` on an empty canister, passing `arg` to its init
+    /// method.
+    pub async fn install(
+        &self,
+        canister_id: CanisterId,
+        wasm_module: Vec<u8>,
+        arg: Vec<u8>,
+    ) -> Result<(), CallError> {
+        self.install_with_mode(CanisterInstallMode::Install, canister_id, wasm_module, arg)
+            .await
+    }
+
+    /// Replaces the code of the given canister, discarding its state.
+    pub async fn reinstall(
+        &self,
+        canister_id: CanisterId,
+        wasm_module: Vec<u8>,
+        arg: Vec<u8>,
+    ) -> Result<(), CallError> {
+        self.install_with_mode(
+            CanisterInstallMode::Reinstall,
+            canister_id,
+            wasm_module,
+            arg,
+        )
+        .await
+    }
+
+    /// Upgrades the code of the given canister, preserving its stable memory.
+    pub async fn upgrade(
+        &self,
+        canister_id: CanisterId,
+        wasm_module: Vec<u8>,
+        arg: Vec<u8>,
+    ) -> Result<(), CallError> {
+        self.install_with_mode(CanisterInstallMode::Upgrade, canister_id, wasm_module, arg)
+            .await
+    }
+
+    /// Removes the code and state of the given canister.
+    pub async fn uninstall_code(&self, canister_id: CanisterId) -> Result<(), CallError> {
+        self.canister_id_call(Method::UninstallCode, canister_id)
+            .call()
+            .await
+    }
+
+    /// Starts the given canister.
+    pub async fn start_canister(&self, canister_id: CanisterId) -> Result<(), CallError> {
+        self.canister_id_call(Method::StartCanister, canister_id)
+            .call()
+            .await
+    }
+
+    /// Stops the given canister. Returns once the canister is stopped.
+    pub async fn stop_canister(&self, canister_id: CanisterId) -> Result<(), CallError> {
+        self.canister_id_call(Method::StopCanister, canister_id)
+            .call()
+            .await
+    }
+
+    /// Deletes the given canister. The canister must be stopped.
+    pub async fn delete_canister(&self, canister_id: CanisterId) -> Result<(), CallError> {
+        self.canister_id_call(Method::DeleteCanister, canister_id)
+            .call()
+            .await
+    }
+
+    /// Returns the status of the given canister.
+    pub async fn canister_status(
+        &self,
+        canister_id: CanisterId,
+    ) -> Result<CanisterStatusResultV2, CallError> {
+        let (status,) = self
+            .canister_id_call(Method::CanisterStatus, canister_id)
+            .call()
+            .await?;
+        Ok(status)
+    }
+
+    async fn install_with_mode(
+        &self,
+        mode: CanisterInstallMode,
+        canister_id: CanisterId,
+        wasm_module: Vec<u8>,
+        arg: Vec<u8>,
+    ) -> Result<(), CallError> {
+        self.install_code(InstallCodeArgs::new(
+            mode,
+            canister_id,
+            wasm_module,
+            arg,
+            None,
+            None,
+            None,
+        ))
+        .await
+    }
+
+    fn provisional_create_canister_with_cycles_call(
+        &self,
+        amount: Option<u64>,
+        settings: Option<CanisterSettingsArgs>,
+    ) -> UpdateBuilder<'a, (ProvisionalCreateCanisterWithCyclesArgs,), (CanisterIdRecord,)> {
+        let args = ProvisionalCreateCanisterWithCyclesArgs {
+            settings,
+            ..ProvisionalCreateCanisterWithCyclesArgs::new(amount)
+        };
+        self.call(
+            Method::ProvisionalCreateCanisterWithCycles,
+            (args,),
+            self.effective_canister_id,
+        )
+    }
+
+    fn provisional_top_up_canister_call(
+        &self,
+        canister_id: CanisterId,
+        amount: u64,
+    ) -> UpdateBuilder<'a, (ProvisionalTopUpCanisterArgs,), ()> {
+        self.call(
+            Method::ProvisionalTopUpCanister,
+            (ProvisionalTopUpCanisterArgs::new(canister_id, amount),),
+            canister_id,
+        )
+    }
+
+    fn update_settings_call(
+        &self,
+        canister_id: CanisterId,
+        settings: CanisterSettingsArgs,
+    ) -> UpdateBuilder<'a, (UpdateSettingsArgs,), ()> {
+        let args = UpdateSettingsArgs {
+            canister_id: canister_id.get(),
+            settings,
+        };
+        self.call(Method::UpdateSettings, (args,), canister_id)
+    }
+
+    fn set_controller_call(
+        &self,
+        canister_id: CanisterId,
+        controller: PrincipalId,
+    ) -> UpdateBuilder<'a, (SetControllerArgs,), ()> {
+        self.call(
+            Method::SetController,
+            (SetControllerArgs::new(canister_id, controller),),
+            canister_id,
+        )
+    }
+
+    fn install_code_call(
+        &self,
+        install_args: InstallCodeArgs,
+    ) -> UpdateBuilder<'a, (InstallCodeArgs,), ()> {
+        let canister_id = install_args.get_canister_id();
+        self.call(Method::InstallCode, (install_args,), canister_id)
+    }
+
+    /// Builds a call to a method taking a `CanisterIdRecord`, routed to that
+    /// canister.
+    fn canister_id_call<Ret>(
+        &self,
+        method: Method,
+        canister_id: CanisterId,
+    ) -> UpdateBuilder<'a, (CanisterIdRecord,), Ret>
+    where
+        Ret: for<'de> ArgumentDecoder<'de>,
+    {
+        self.call(method, (CanisterIdRecord::from(canister_id),), canister_id)
+    }
+
+    fn call<Args, Ret>(
+        &self,
+        method: Method,
+        args: Args,
+        effective_canister_id: CanisterId,
+    ) -> UpdateBuilder<'a, Args, Ret>
+    where
+        Args: ArgumentEncoder,
+        Ret: for<'de> ArgumentDecoder<'de>,
+    {
+        self.agent
+            .update(&IC_00, method, args)
+            .with_effective_canister_id(effective_canister_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::Sender;
+    use ic_test_utilities::types::ids::{canister_test_id, user_test_id};
+    use url::Url;
+
+    fn agent() -> Agent {
+        Agent::new(
+            Url::parse("http://localhost:8080").unwrap(),
+            Sender::Anonymous,
+        )
+    }
+
+    #[test]
+    fn encodes_calls_naming_a_canister() {
+        let agent = agent();
+        let management_canister = ManagementCanister::new(&agent);
+        let canister_id = canister_test_id(7);
+
+        let request = management_canister
+            .canister_id_call::<()>(Method::StopCanister, canister_id)
+            .encode()
+            .unwrap();
+        assert_eq!(request.canister_id, IC_00);
+        assert_eq!(request.method_name, "stop_canister");
+        assert_eq!(request.effective_canister_id, canister_id);
+        assert_eq!(
+            CanisterIdRecord::decode(&request.arg)
+                .unwrap()
+                .get_canister_id(),
+            canister_id
+        );
+
+        let request = management_canister
+            .set_controller_call(canister_id, user_test_id(1).get())
+            .encode()
+            .unwrap();
+        assert_eq!(request.method_name, "set_controller");
+        assert_eq!(request.effective_canister_id, canister_id);
+        let decoded = SetControllerArgs::decode(&request.arg).unwrap();
+        assert_eq!(decoded.get_canister_id(), canister_id);
+        assert_eq!(decoded.get_new_controller(), user_test_id(1).get());
+    }
+
+    #[test]
+    fn encodes_install_code() {
+        let agent = agent();
+        let canister_id = canister_test_id(7);
+        let install_args = InstallCodeArgs::new(
+            CanisterInstallMode::Upgrade,
+            canister_id,
+            vec![0, 97, 115, 109],
+            vec![1, 2, 3],
+            None,
+            None,
+            None,
+        );
+
+        let request = ManagementCanister::new(&agent)
+            .install_code_call(install_args.clone())
+            .encode()
+            .unwrap();
+        assert_eq!(request.method_name, "install_code");
+        assert_eq!(request.effective_canister_id, canister_id);
+        let decoded = InstallCodeArgs::decode(&request.arg).unwrap();
+        assert_eq!(decoded.mode, CanisterInstallMode::Upgrade);
+        assert_eq!(decoded.get_canister_id(), canister_id);
+        assert_eq!(decoded.wasm_module, install_args.wasm_module);
+        assert_eq!(decoded.arg, install_args.arg);
+    }
+
+    #[test]
+    fn routes_provisional_creation_by_effective_canister_id() {
+        let agent = agent();
+        let effective_canister_id = canister_test_id(3);
+
+        let request = ManagementCanister::new(&agent)
+            .with_effective_canister_id(effective_canister_id)
+            .provisional_create_canister_with_cycles_call(Some(1_000), None)
+            .encode()
+            .unwrap();
+        assert_eq!(
+            request.method_name,
+            "provisional_create_canister_with_cycles"
+        );
+        assert_eq!(request.effective_canister_id, effective_canister_id);
+        let decoded = ProvisionalCreateCanisterWithCyclesArgs::decode(&request.arg).unwrap();
+        assert!(decoded.settings.is_none());
+        assert_eq!(decoded.amount, Some(candid::Nat::from(1_000)));
+    }
+}
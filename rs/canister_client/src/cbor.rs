@@ -13,11 +13,12 @@ use ic_types::{
     time::current_time_and_expiry_time,
     CanisterId,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use serde_cbor::value::Value as CBOR;
 use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::error::Error;
+use tree_deserializer::types::Leb128EncodedU64;
 
 // An auxiliary structure that mirrors the request statuses
 // encoded in a certificate, starting from the root of the tree.
@@ -30,6 +31,8 @@ struct RequestStatuses {
 pub struct RequestStatus {
     pub status: String,
     pub reply: Option<Vec<u8>>,
+    #[serde(default, deserialize_with = "deserialize_reject_code")]
+    pub reject_code: Option<u64>,
     pub reject_message: Option<String>,
 }
 
@@ -38,11 +41,20 @@ impl RequestStatus {
         RequestStatus {
             status: "unknown".to_string(),
             reply: None,
+            reject_code: None,
             reject_message: None,
         }
     }
 }
 
+// In the certified state tree reject codes are LEB128-encoded leaves.
+fn deserialize_reject_code<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<Leb128EncodedU64>::deserialize(deserializer)?.map(|code| code.0))
+}
+
 #[derive(Debug)]
 struct CanisterCallResponse {
     status: String,
@@ -126,7 +138,11 @@ pub(crate) fn parse_canister_query_response(message: &CBOR) -> Result<RequestSta
         }
     }?;
 
-    // Attempt to extract reject message from reply
+    // Attempt to extract reject code and message from reply
+    let mut reject_code = None;
+    if let Some(CBOR::Integer(code)) = &content.get(&CBOR::Text("reject_code".to_string())) {
+        reject_code = u64::try_from(*code).ok();
+    }
     let mut reject_message = None;
     if let Some(CBOR::Text(b)) = &content.get(&CBOR::Text("reject_message".to_string())) {
         reject_message = Some(b.to_string());
@@ -135,6 +151,7 @@ pub(crate) fn parse_canister_query_response(message: &CBOR) -> Result<RequestSta
     Ok(RequestStatus {
         status,
         reply,
+        reject_code,
         reject_message,
     })
 }
@@ -182,25 +199,43 @@ impl Agent {
         Ok((http_body.into(), request_id))
     }
 
-    /// Prepares and serializes a CBOR query request.
-    pub fn prepare_query(
+    /// Prepares a query request.
+    pub fn prepare_query_raw(
         &self,
         canister_id: &CanisterId,
         method: &str,
         arguments: Vec<u8>,
-    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        nonce: Option<Vec<u8>>,
+        ingress_expiry: Time,
+    ) -> Result<HttpRequestEnvelope<HttpReadContent>, Box<dyn Error>> {
         let content = HttpReadContent::Query {
             query: HttpUserQuery {
                 canister_id: to_blob(canister_id),
                 method_name: method.to_string(),
                 arg: Blob(arguments),
                 sender: self.sender_field.clone(),
-                nonce: None,
-                ingress_expiry: current_time_and_expiry_time().1.as_nanos_since_unix_epoch(),
+                nonce: nonce.map(Blob),
+                ingress_expiry: ingress_expiry.as_nanos_since_unix_epoch(),
             },
         };
 
-        let request = sign_read(content, &self.sender)?;
+        sign_read(content, &self.sender)
+    }
+
+    /// Prepares and serializes a CBOR query request.
+    pub fn prepare_query(
+        &self,
+        canister_id: &CanisterId,
+        method: &str,
+        arguments: Vec<u8>,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let request = self.prepare_query_raw(
+            canister_id,
+            method,
+            arguments,
+            None,
+            current_time_and_expiry_time().1,
+        )?;
         Ok(SignedRequestBytes::try_from(request)?.into())
     }
 
@@ -318,6 +353,7 @@ mod tests {
             Ok(RequestStatus {
                 status: "replied".to_string(),
                 reply: Some(vec![68, 73, 68, 76, 0, 0]),
+                reject_code: None,
                 reject_message: None
             }),
         );
@@ -329,4 +365,55 @@ mod tests {
             Ok(RequestStatus::unknown())
         );
     }
+
+    #[test]
+    fn test_parse_read_state_response_rejected() {
+        let request_id: MessageId = MessageId::from([1; 32]);
+        let tree = MixedHashTree::Fork(Box::new((
+            MixedHashTree::Labeled(
+                "request_status".into(),
+                Box::new(MixedHashTree::Labeled(
+                    request_id.as_bytes().to_vec().into(),
+                    Box::new(MixedHashTree::Fork(Box::new((
+                        MixedHashTree::Fork(Box::new((
+                            MixedHashTree::Labeled(
+                                "reject_code".into(),
+                                Box::new(MixedHashTree::Leaf(vec![4])),
+                            ),
+                            MixedHashTree::Labeled(
+                                "reject_message".into(),
+                                Box::new(MixedHashTree::Leaf(b"no way".to_vec())),
+                            ),
+                        ))),
+                        MixedHashTree::Labeled(
+                            "status".into(),
+                            Box::new(MixedHashTree::Leaf(b"rejected".to_vec())),
+                        ),
+                    )))),
+                )),
+            ),
+            MixedHashTree::Labeled("time".into(), Box::new(MixedHashTree::Leaf(vec![1]))),
+        )));
+
+        let certificate = Certificate {
+            tree,
+            signature: Blob(vec![]),
+            delegation: None,
+        };
+        let response = HttpReadStateResponse {
+            certificate: Blob(to_self_describing_cbor(&certificate).unwrap()),
+        };
+        let response: CBOR =
+            serde_cbor::from_slice(&to_self_describing_cbor(&response).unwrap()).unwrap();
+
+        assert_eq!(
+            parse_read_state_response(&request_id, response),
+            Ok(RequestStatus {
+                status: "rejected".to_string(),
+                reply: None,
+                reject_code: Some(4),
+                reject_message: Some("no way".to_string()),
+            }),
+        );
+    }
 }
@@ -4,11 +4,14 @@ mod canister_management;
 /// Asynchronous method to interact with canisters.
 mod cbor;
 mod http_client;
+mod typed_call;
 
 pub use agent::{
     ed25519_public_key_to_der, get_backoff_policy, query_path, read_state_path, sign_submit,
     update_path, Agent, Sender,
 };
+pub use canister_management::ManagementCanister;
 pub use cbor::parse_read_state_response;
 pub use http_client::HttpClient;
 pub use hyper::StatusCode as HttpStatusCode;
+pub use typed_call::{CallError, PollingStrategy, QueryBuilder, UpdateBuilder};
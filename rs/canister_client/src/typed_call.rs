@@ -0,0 +1,476 @@
+//! A Candid-typed layer over [`Agent`] for query and update calls.
+//!
+//! Instead of encoding arguments and decoding replies by hand, callers name
+//! the argument and return tuples and let the builders do the rest:
+//!
+//! ```ignore
+//! let (balance,): (u64,) = agent
+//!     .query::<_, (u64,)>(&ledger_id, "account_balance", (account,))
+//!     .call()
+//!     .await?;
+//! ```
+use crate::{
+    agent::{bytes_to_cbor, get_backoff_policy, query_path, update_path, Agent},
+    cbor::{parse_canister_query_response, RequestStatus},
+};
+use backoff::{backoff::Backoff, ExponentialBackoff};
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
+use ic_error_types::RejectCode;
+use ic_types::{
+    messages::{MessageId, SignedRequestBytes},
+    time::current_time_and_expiry_time,
+    CanisterId, Time,
+};
+use std::{
+    convert::TryFrom,
+    error::Error,
+    fmt,
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep_until;
+
+/// The time to wait before polling for the status of an update call for the
+/// first time. A successful call takes at least the time between two blocks.
+const FIRST_POLL_DELAY: Duration = Duration::from_secs(2);
+
+/// Errors returned by typed query and update calls.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CallError {
+    /// The arguments could not be Candid-encoded.
+    Encode(String),
+    /// The request could not be signed or sent, or the response of the
+    /// replica could not be parsed.
+    Transport(String),
+    /// The call was rejected by the system or by the canister.
+    Reject { code: RejectCode, message: String },
+    /// The update call did not reach a final state before the polling
+    /// strategy gave up.
+    Timeout(MessageId),
+    /// The reply could not be Candid-decoded into the expected type.
+    Decode(String),
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Encode(err) => write!(f, "Failed to encode arguments: {}", err),
+            Self::Transport(err) => write!(f, "Transport error: {}", err),
+            Self::Reject { code, message } => write!(
+                f,
+                "Call rejected with code {}: {}",
+                code.to_string(),
+                message
+            ),
+            Self::Timeout(request_id) => write!(
+                f,
+                "Request {} did not complete before the deadline",
+                request_id
+            ),
+            Self::Decode(err) => write!(f, "Failed to decode reply: {}", err),
+        }
+    }
+}
+
+impl Error for CallError {}
+
+/// Determines when and for how long the status of an update call is polled.
+pub struct PollingStrategy {
+    /// The delay before the first status request.
+    pub initial_delay: Duration,
+    /// The intervals between subsequent status requests. Polling stops once
+    /// the backoff returns `None`.
+    pub backoff: ExponentialBackoff,
+    /// The maximum time to wait for the call to reach a final state.
+    pub timeout: Duration,
+}
+
+impl PollingStrategy {
+    /// Creates a polling strategy with the agent's default backoff that gives
+    /// up after `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            initial_delay: FIRST_POLL_DELAY,
+            backoff: get_backoff_policy(),
+            timeout,
+        }
+    }
+}
+
+/// Parameters shared by typed query and update calls.
+struct CallParams<'a, Args> {
+    agent: &'a Agent,
+    canister_id: CanisterId,
+    method_name: String,
+    args: Args,
+    effective_canister_id: Option<CanisterId>,
+    ingress_expiry: Option<Time>,
+    nonce: Option<Vec<u8>>,
+}
+
+impl<'a, Args: ArgumentEncoder> CallParams<'a, Args> {
+    fn new(agent: &'a Agent, canister_id: CanisterId, method_name: String, args: Args) -> Self {
+        Self {
+            agent,
+            canister_id,
+            method_name,
+            args,
+            effective_canister_id: None,
+            ingress_expiry: None,
+            nonce: None,
+        }
+    }
+
+    /// The canister id used to route the request. Defaults to the target
+    /// canister.
+    fn effective_canister_id(&self) -> CanisterId {
+        self.effective_canister_id.unwrap_or(self.canister_id)
+    }
+
+    fn ingress_expiry(&self) -> Time {
+        self.ingress_expiry
+            .unwrap_or_else(|| current_time_and_expiry_time().1)
+    }
+}
+
+/// Builder for a Candid-typed query call. Created by [`Agent::query`].
+pub struct QueryBuilder<'a, Args, Ret> {
+    params: CallParams<'a, Args>,
+    _ret: PhantomData<fn() -> Ret>,
+}
+
+impl<'a, Args, Ret> QueryBuilder<'a, Args, Ret>
+where
+    Args: ArgumentEncoder,
+    Ret: for<'de> ArgumentDecoder<'de>,
+{
+    /// Sets the canister id used to route the request, if it differs from the
+    /// target canister (e.g. for calls to the management canister).
+    pub fn with_effective_canister_id(mut self, effective_canister_id: CanisterId) -> Self {
+        self.params.effective_canister_id = Some(effective_canister_id);
+        self
+    }
+
+    /// Sets the expiry time of the request.
+    pub fn with_ingress_expiry(mut self, ingress_expiry: Time) -> Self {
+        self.params.ingress_expiry = Some(ingress_expiry);
+        self
+    }
+
+    /// Sets the nonce of the request.
+    pub fn with_nonce(mut self, nonce: Vec<u8>) -> Self {
+        self.params.nonce = Some(nonce);
+        self
+    }
+
+    /// Sends the query and decodes the reply.
+    pub async fn call(self) -> Result<Ret, CallError> {
+        let effective_canister_id = self.params.effective_canister_id();
+        let ingress_expiry = self.params.ingress_expiry();
+        let CallParams {
+            agent,
+            canister_id,
+            method_name,
+            args,
+            nonce,
+            ..
+        } = self.params;
+
+        let arg = candid::encode_args(args).map_err(|e| CallError::Encode(e.to_string()))?;
+        let envelope = agent
+            .prepare_query_raw(&canister_id, &method_name, arg, nonce, ingress_expiry)
+            .map_err(|e| CallError::Transport(format!("Failed to prepare query: {}", e)))?;
+        let http_body = SignedRequestBytes::try_from(envelope)
+            .map_err(|e| CallError::Transport(format!("Failed to serialize query: {}", e)))?;
+        let bytes = agent
+            .http_client()
+            .post_with_response(
+                &agent.url,
+                &query_path(effective_canister_id),
+                http_body.into(),
+                tokio::time::Instant::now() + agent.query_timeout,
+            )
+            .await
+            .map_err(CallError::Transport)?;
+        let cbor = bytes_to_cbor(bytes).map_err(CallError::Transport)?;
+        let status = parse_canister_query_response(&cbor).map_err(CallError::Transport)?;
+        decode_reply(status)
+    }
+}
+
+/// Builder for a Candid-typed update call. Created by [`Agent::update`].
+pub struct UpdateBuilder<'a, Args, Ret> {
+    params: CallParams<'a, Args>,
+    polling_strategy: Option<PollingStrategy>,
+    _ret: PhantomData<fn() -> Ret>,
+}
+
+impl<'a, Args, Ret> UpdateBuilder<'a, Args, Ret>
+where
+    Args: ArgumentEncoder,
+    Ret: for<'de> ArgumentDecoder<'de>,
+{
+    /// Sets the canister id used to route the request, if it differs from the
+    /// target canister (e.g. for calls to the management canister).
+    pub fn with_effective_canister_id(mut self, effective_canister_id: CanisterId) -> Self {
+        self.params.effective_canister_id = Some(effective_canister_id);
+        self
+    }
+
+    /// Sets the expiry time of the request.
+    pub fn with_ingress_expiry(mut self, ingress_expiry: Time) -> Self {
+        self.params.ingress_expiry = Some(ingress_expiry);
+        self
+    }
+
+    /// Sets the nonce of the request.
+    pub fn with_nonce(mut self, nonce: Vec<u8>) -> Self {
+        self.params.nonce = Some(nonce);
+        self
+    }
+
+    /// Sets the strategy used to poll for the result of the call. Defaults to
+    /// the agent's backoff policy and ingress timeout.
+    pub fn with_polling_strategy(mut self, polling_strategy: PollingStrategy) -> Self {
+        self.polling_strategy = Some(polling_strategy);
+        self
+    }
+
+    /// Submits the update, polls for its result and decodes the reply.
+    pub async fn call(self) -> Result<Ret, CallError> {
+        let effective_canister_id = self.params.effective_canister_id();
+        let ingress_expiry = self.params.ingress_expiry();
+        let CallParams {
+            agent,
+            canister_id,
+            method_name,
+            args,
+            nonce,
+            ..
+        } = self.params;
+        let mut polling_strategy = self
+            .polling_strategy
+            .unwrap_or_else(|| PollingStrategy::new(agent.ingress_timeout));
+
+        let arg = candid::encode_args(args).map_err(|e| CallError::Encode(e.to_string()))?;
+        let deadline = Instant::now() + polling_strategy.timeout;
+        let (envelope, request_id) = agent
+            .prepare_update_raw(
+                &canister_id,
+                method_name,
+                arg,
+                nonce.unwrap_or_default(),
+                ingress_expiry,
+            )
+            .map_err(|e| CallError::Transport(format!("Failed to prepare update: {}", e)))?;
+        let http_body = SignedRequestBytes::try_from(envelope)
+            .map_err(|e| CallError::Transport(format!("Failed to serialize update: {}", e)))?;
+        agent
+            .http_client()
+            .post_with_response(
+                &agent.url,
+                &update_path(effective_canister_id),
+                http_body.into(),
+                tokio::time::Instant::from_std(deadline),
+            )
+            .await
+            .map_err(CallError::Transport)?;
+
+        let mut next_poll_time = Instant::now() + polling_strategy.initial_delay;
+        while next_poll_time < deadline {
+            sleep_until(tokio::time::Instant::from_std(next_poll_time)).await;
+            // Poll before consulting the backoff, such that a reply arriving
+            // during the last interval is not reported as a timeout.
+            let status = agent
+                .wait_ingress(request_id.clone(), deadline, &effective_canister_id)
+                .await
+                .map_err(CallError::Transport)?;
+            match status.status.as_ref() {
+                "unknown" | "received" | "processing" => {}
+                _ => return decode_reply(status),
+            }
+            next_poll_time = match polling_strategy.backoff.next_backoff() {
+                Some(interval) => Instant::now() + interval,
+                None => break,
+            };
+        }
+        Err(CallError::Timeout(request_id))
+    }
+}
+
+impl Agent {
+    /// Prepares a Candid-typed call to the query method `method_name` of the
+    /// given canister.
+    pub fn query<Args, Ret>(
+        &self,
+        canister_id: &CanisterId,
+        method_name: impl ToString,
+        args: Args,
+    ) -> QueryBuilder<'_, Args, Ret>
+    where
+        Args: ArgumentEncoder,
+        Ret: for<'de> ArgumentDecoder<'de>,
+    {
+        QueryBuilder {
+            params: CallParams::new(self, *canister_id, method_name.to_string(), args),
+            _ret: PhantomData,
+        }
+    }
+
+    /// Prepares a Candid-typed call to the update method `method_name` of the
+    /// given canister.
+    pub fn update<Args, Ret>(
+        &self,
+        canister_id: &CanisterId,
+        method_name: impl ToString,
+        args: Args,
+    ) -> UpdateBuilder<'_, Args, Ret>
+    where
+        Args: ArgumentEncoder,
+        Ret: for<'de> ArgumentDecoder<'de>,
+    {
+        UpdateBuilder {
+            params: CallParams::new(self, *canister_id, method_name.to_string(), args),
+            polling_strategy: None,
+            _ret: PhantomData,
+        }
+    }
+}
+
+/// The parts of a typed call as they end up in the request, for tests.
+#[cfg(test)]
+pub(crate) struct EncodedCall {
+    pub canister_id: CanisterId,
+    pub method_name: String,
+    pub effective_canister_id: CanisterId,
+    pub arg: Vec<u8>,
+}
+
+#[cfg(test)]
+impl<'a, Args, Ret> UpdateBuilder<'a, Args, Ret>
+where
+    Args: ArgumentEncoder,
+    Ret: for<'de> ArgumentDecoder<'de>,
+{
+    /// Encodes the arguments as `call` would, without sending anything.
+    pub(crate) fn encode(self) -> Result<EncodedCall, CallError> {
+        let effective_canister_id = self.params.effective_canister_id();
+        let CallParams {
+            canister_id,
+            method_name,
+            args,
+            ..
+        } = self.params;
+        let arg = candid::encode_args(args).map_err(|e| CallError::Encode(e.to_string()))?;
+        Ok(EncodedCall {
+            canister_id,
+            method_name,
+            effective_canister_id,
+            arg,
+        })
+    }
+}
+
+/// Turns the final status of a call into either the decoded reply or the
+/// appropriate error.
+fn decode_reply<Ret>(status: RequestStatus) -> Result<Ret, CallError>
+where
+    Ret: for<'de> ArgumentDecoder<'de>,
+{
+    match status.status.as_ref() {
+        "replied" => {
+            let reply = status
+                .reply
+                .ok_or_else(|| CallError::Transport("Reply is missing".to_string()))?;
+            candid::decode_args(&reply).map_err(|e| CallError::Decode(e.to_string()))
+        }
+        "rejected" => {
+            let message = status.reject_message.unwrap_or_default();
+            match status.reject_code.map(RejectCode::try_from) {
+                Some(Ok(code)) => Err(CallError::Reject { code, message }),
+                _ => Err(CallError::Transport(format!(
+                    "Call rejected with invalid reject code {:?}: {}",
+                    status.reject_code, message
+                ))),
+            }
+        }
+        other => Err(CallError::Transport(format!(
+            "Unexpected request status '{}'",
+            other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(status: &str) -> RequestStatus {
+        RequestStatus {
+            status: status.to_string(),
+            reply: None,
+            reject_code: None,
+            reject_message: None,
+        }
+    }
+
+    #[test]
+    fn decodes_replies() {
+        let reply = RequestStatus {
+            reply: Some(candid::encode_args((42_u64, "foo")).unwrap()),
+            ..status("replied")
+        };
+        assert_eq!(
+            decode_reply::<(u64, String)>(reply),
+            Ok((42, "foo".to_string()))
+        );
+    }
+
+    #[test]
+    fn reports_decoding_failures() {
+        let reply = RequestStatus {
+            reply: Some(candid::encode_args(("foo",)).unwrap()),
+            ..status("replied")
+        };
+        assert!(matches!(
+            decode_reply::<(u64,)>(reply),
+            Err(CallError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn reports_rejects_with_reject_code() {
+        let reject = RequestStatus {
+            reject_code: Some(RejectCode::CanisterReject as u64),
+            reject_message: Some("go away".to_string()),
+            ..status("rejected")
+        };
+        assert_eq!(
+            decode_reply::<()>(reject),
+            Err(CallError::Reject {
+                code: RejectCode::CanisterReject,
+                message: "go away".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn reports_invalid_reject_codes_as_transport_errors() {
+        let reject = RequestStatus {
+            reject_code: Some(42),
+            ..status("rejected")
+        };
+        assert!(matches!(
+            decode_reply::<()>(reject),
+            Err(CallError::Transport(_))
+        ));
+    }
+
+    #[test]
+    fn reports_unexpected_statuses_as_transport_errors() {
+        assert!(matches!(
+            decode_reply::<()>(status("done")),
+            Err(CallError::Transport(_))
+        ));
+    }
+}
@@ -9,9 +9,23 @@
 //! names are replaced by indices (similar to protocol buffers), for more
 //! concise encoding. This is safe to do given the use of canonical types
 //! covered by compatibility tests.
+//!
+//! Encoders take the certification version of the state being certified, so
+//! that changes to the encoding can be rolled out gradually (see
+//! `MAX_SUPPORTED_CERTIFICATION_VERSION`). Decoders take the certification
+//! version of the encoding replica and reject versions greater than
+//! `MAX_SUPPORTED_CERTIFICATION_VERSION`, as well as values that a replica
+//! certifying states with the given version could not have produced. The
+//! `compatibility` tests pin down the encoding produced by every supported
+//! version and the `golden` tests check the hashes of these encodings across
+//! versions.
 
+use crate::MAX_SUPPORTED_CERTIFICATION_VERSION;
 use ic_protobuf::proxy::ProxyDecodeError;
-use ic_replicated_state::metadata_state::SystemMetadata;
+use ic_replicated_state::{
+    metadata_state::{NodeMetrics, SystemMetadata},
+    ReplicatedState,
+};
 use ic_types::{messages::RequestOrResponse, xnet::StreamHeader, PrincipalId};
 use serde::Serialize;
 use std::collections::BTreeSet;
@@ -24,6 +38,7 @@ mod tests {
     mod compatibility;
     mod conversion;
     mod encoding;
+    mod golden;
     mod test_fixtures;
 }
 
@@ -64,14 +79,52 @@ where
     }
 }
 
+/// Decodes a `T` from canonical CBOR representation via the canonical type `M`,
+/// as encoded by a replica certifying states with `certification_version`.
+fn versioned_decode<'de, T, M>(
+    bytes: &'de [u8],
+    certification_version: u32,
+) -> Result<T, ProxyDecodeError>
+where
+    M: serde::Deserialize<'de> + TryInto<T> + types::Versioned,
+    M::Error: Into<ProxyDecodeError>,
+{
+    if certification_version > MAX_SUPPORTED_CERTIFICATION_VERSION {
+        return Err(ProxyDecodeError::ValueOutOfRange {
+            typ: "certification_version",
+            err: format!(
+                "certification version {} is not supported, the maximum supported version is {}",
+                certification_version, MAX_SUPPORTED_CERTIFICATION_VERSION
+            ),
+        });
+    }
+
+    let m: M = serde_cbor::from_slice(bytes)
+        .map_err(|err| ProxyDecodeError::CborDecodeError(Box::new(err)))?;
+    let min_certification_version = m.min_certification_version();
+    if min_certification_version > certification_version {
+        return Err(ProxyDecodeError::Other(format!(
+            "{}: encoding requires certification version {}, got {}",
+            std::any::type_name::<M>(),
+            min_certification_version,
+            certification_version
+        )));
+    }
+    m.try_into().map_err(|e| e.into())
+}
+
 /// Encodes a `RequestOrResponse` into canonical CBOR representation.
 pub fn encode_message(msg: &RequestOrResponse, certification_version: u32) -> Vec<u8> {
     types::RequestOrResponse::proxy_encode((msg, certification_version)).unwrap()
 }
 
-/// Decodes a `RequestOrResponse` from canonical CBOR representation.
-pub fn decode_message(bytes: &[u8]) -> Result<RequestOrResponse, ProxyDecodeError> {
-    types::RequestOrResponse::proxy_decode(bytes)
+/// Decodes a `RequestOrResponse` from canonical CBOR representation, as
+/// encoded with the given certification version.
+pub fn decode_message(
+    bytes: &[u8],
+    certification_version: u32,
+) -> Result<RequestOrResponse, ProxyDecodeError> {
+    versioned_decode::<_, types::RequestOrResponse>(bytes, certification_version)
 }
 
 /// Encodes a `StreamHeader` into canonical CBOR representation.
@@ -79,9 +132,13 @@ pub fn encode_stream_header(header: &StreamHeader, certification_version: u32) -
     types::StreamHeader::proxy_encode((header, certification_version)).unwrap()
 }
 
-/// Decodes a `StreamHeader` from canonical CBOR representation.
-pub fn decode_stream_header(bytes: &[u8]) -> Result<StreamHeader, ProxyDecodeError> {
-    types::StreamHeader::proxy_decode(bytes)
+/// Decodes a `StreamHeader` from canonical CBOR representation, as encoded
+/// with the given certification version.
+pub fn decode_stream_header(
+    bytes: &[u8],
+    certification_version: u32,
+) -> Result<StreamHeader, ProxyDecodeError> {
+    versioned_decode::<_, types::StreamHeader>(bytes, certification_version)
 }
 
 /// Encodes a `SystemMetadata` into canonical CBOR representation.
@@ -89,6 +146,18 @@ pub fn encode_metadata(msg: &SystemMetadata) -> Vec<u8> {
     types::SystemMetadata::proxy_encode(msg).unwrap()
 }
 
+/// Encodes the metrics of the subnet hosting `state` into canonical CBOR
+/// representation.
+pub fn encode_subnet_metrics(state: &ReplicatedState) -> Vec<u8> {
+    types::SubnetMetrics::proxy_encode(state).unwrap()
+}
+
+/// Encodes the block maker statistics of a node into canonical CBOR
+/// representation.
+pub fn encode_node_metrics(metrics: &NodeMetrics) -> Vec<u8> {
+    types::NodeMetrics::proxy_encode(metrics).unwrap()
+}
+
 /// Encodes the list of canister ID ranges assigned to a subnet according to
 /// the interface specification.
 ///
@@ -8,10 +8,13 @@
 //! (and ideally forwards) compatibility with one or more preceeding
 //! protocol versions.
 
-use crate::{encoding::*, CURRENT_CERTIFICATION_VERSION};
+use crate::{encoding::*, MAX_SUPPORTED_CERTIFICATION_VERSION};
 use assert_matches::assert_matches;
 use ic_registry_subnet_type::SubnetType;
-use ic_replicated_state::metadata_state::SystemMetadata;
+use ic_replicated_state::{
+    metadata_state::{NodeMetrics, SystemMetadata},
+    ReplicatedState,
+};
 use ic_test_utilities::types::{
     ids::{canister_test_id, subnet_test_id},
     messages::{RequestBuilder, ResponseBuilder},
@@ -60,7 +63,7 @@ const CERTIFICATION_VERSION_4: u32 = 4;
 /// ```
 #[test]
 fn canonical_encoding_stream_header() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let header = StreamHeader {
             begin: 23.into(),
             end: 25.into(),
@@ -130,10 +133,12 @@ fn canonical_encoding_request() {
             .build(),
     );
 
-    assert_eq!(
-        "A1 00 A6 00 4A 00 00 00 00 00 00 00 01 01 01 01 4A 00 00 00 00 00 00 00 02 01 01 02 03 03 A1 00 A1 00 04 04 64 74 65 73 74 05 41 06",
-        as_hex(&encode_message(&request, CURRENT_CERTIFICATION_VERSION))
-    );
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
+        assert_eq!(
+            "A1 00 A6 00 4A 00 00 00 00 00 00 00 01 01 01 01 4A 00 00 00 00 00 00 00 02 01 01 02 03 03 A1 00 A1 00 04 04 64 74 65 73 74 05 41 06",
+            as_hex(&encode_message(&request, certification_version))
+        );
+    }
 }
 
 /// Canonical CBOR encoding of:
@@ -194,10 +199,12 @@ fn canonical_encoding_request_u128() {
             .build(),
     );
 
-    assert_eq!(
-        "A1 00 A6 00 4A 00 00 00 00 00 00 00 01 01 01 01 4A 00 00 00 00 00 00 00 02 01 01 02 03 03 A1 00 A2 00 1B C3 73 E0 EE 4E 3F 0A D2 01 1B 00 00 00 01 8E E9 0F F6 04 64 74 65 73 74 05 41 06",
-        as_hex(&encode_message(&request, CURRENT_CERTIFICATION_VERSION))
-    );
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
+        assert_eq!(
+            "A1 00 A6 00 4A 00 00 00 00 00 00 00 01 01 01 01 4A 00 00 00 00 00 00 00 02 01 01 02 03 03 A1 00 A2 00 1B C3 73 E0 EE 4E 3F 0A D2 01 1B 00 00 00 01 8E E9 0F F6 04 64 74 65 73 74 05 41 06",
+            as_hex(&encode_message(&request, certification_version))
+        );
+    }
 }
 
 /// Canonical CBOR encoding of:
@@ -253,10 +260,12 @@ fn canonical_encoding_response() {
             .build(),
     );
 
-    assert_eq!(
-        "A1 01 A5 00 4A 00 00 00 00 00 00 00 05 01 01 01 4A 00 00 00 00 00 00 00 04 01 01 02 03 03 A1 00 A1 00 02 04 A1 00 41 01",
-        as_hex(&encode_message(&response, CURRENT_CERTIFICATION_VERSION))
-    );
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
+        assert_eq!(
+            "A1 01 A5 00 4A 00 00 00 00 00 00 00 05 01 01 01 4A 00 00 00 00 00 00 00 04 01 01 02 03 03 A1 00 A1 00 02 04 A1 00 41 01",
+            as_hex(&encode_message(&response, certification_version))
+        );
+    }
 }
 
 ///
@@ -315,10 +324,12 @@ fn canonical_encoding_response_u128() {
             .build(),
     );
 
-    assert_eq!(
-        "A1 01 A5 00 4A 00 00 00 00 00 00 00 05 01 01 01 4A 00 00 00 00 00 00 00 04 01 01 02 03 03 A1 00 A2 00 1B C3 73 E0 EE 4E 3F 0A D2 01 1B 00 00 00 01 8E E9 0F F6 04 A1 00 41 01",
-        as_hex(&encode_message(&response, CURRENT_CERTIFICATION_VERSION))
-    );
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
+        assert_eq!(
+            "A1 01 A5 00 4A 00 00 00 00 00 00 00 05 01 01 01 4A 00 00 00 00 00 00 00 04 01 01 02 03 03 A1 00 A2 00 1B C3 73 E0 EE 4E 3F 0A D2 01 1B 00 00 00 01 8E E9 0F F6 04 A1 00 41 01",
+            as_hex(&encode_message(&response, certification_version))
+        );
+    }
 }
 
 /// Canonical CBOR encoding of:
@@ -383,10 +394,12 @@ fn canonical_encoding_reject_response() {
             .build(),
     );
 
-    assert_eq!(
-        "A1 01 A5 00 4A 00 00 00 00 00 00 00 06 01 01 01 4A 00 00 00 00 00 00 00 05 01 01 02 04 03 A1 00 A1 00 03 04 A1 01 A2 00 01 01 64 4F 6F 70 73",
-        as_hex(&encode_message(&reject_response, CURRENT_CERTIFICATION_VERSION))
-    );
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
+        assert_eq!(
+            "A1 01 A5 00 4A 00 00 00 00 00 00 00 06 01 01 01 4A 00 00 00 00 00 00 00 05 01 01 02 04 03 A1 00 A1 00 03 04 A1 01 A2 00 01 01 64 4F 6F 70 73",
+            as_hex(&encode_message(&reject_response, certification_version))
+        );
+    }
 }
 
 /// Canonical CBOR encoding of:
@@ -419,6 +432,61 @@ fn canonical_encoding_system_metadata() {
     assert_eq!("A2 00 0E 01 81 0F", as_hex(&encode_metadata(&metadata)));
 }
 
+/// Canonical CBOR encoding of the metrics of an empty subnet.
+///
+/// Expected:
+///
+/// ```text
+/// A3       # map(3)
+///    00    # field_index(SubnetMetrics::num_canisters)
+///    00    # unsigned(0)
+///    01    # field_index(SubnetMetrics::canister_state_bytes)
+///    00    # unsigned(0)
+///    02    # field_index(SubnetMetrics::consumed_cycles_total)
+///    A1    # map(1)
+///       00 # field_index(Cycles::low)
+///       00 # unsigned(0)
+/// ```
+#[test]
+fn canonical_encoding_subnet_metrics() {
+    let state = ReplicatedState::new_rooted_at(
+        subnet_test_id(13),
+        SubnetType::Application,
+        "NOT_USED".into(),
+    );
+
+    assert_eq!(
+        "A3 00 00 01 00 02 A1 00 00",
+        as_hex(&encode_subnet_metrics(&state))
+    );
+}
+
+/// Canonical CBOR encoding of the block maker statistics of a node.
+///
+/// Expected:
+///
+/// ```text
+/// A2       # map(2)
+///    00    # field_index(NodeMetrics::blocks_proposed_total)
+///    05    # unsigned(5)
+///    01    # field_index(NodeMetrics::failed_proposals_by_rank)
+///    82    # array(2)
+///       01 # unsigned(1)
+///       02 # unsigned(2)
+/// ```
+#[test]
+fn canonical_encoding_node_metrics() {
+    let metrics = NodeMetrics {
+        blocks_proposed_total: 5,
+        failed_proposals_by_rank: vec![1, 2],
+    };
+
+    assert_eq!(
+        "A2 00 05 01 82 01 02",
+        as_hex(&encode_node_metrics(&metrics))
+    );
+}
+
 //
 // `RequestOrResponse` decoding
 //
@@ -426,7 +494,7 @@ fn canonical_encoding_system_metadata() {
 #[test]
 #[should_panic(expected = "expected field index 0 <= i < 2")]
 fn invalid_message_extra_field() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes = types::RequestOrResponse::encode_with_extra_field((
             &request_message(),
             certification_version,
@@ -442,7 +510,7 @@ fn invalid_message_extra_field() {
     expected = "RequestOrResponse: expected exactly one of `request` or `response` to be `Some(_)`, got `RequestOrResponse { request: None, response: None }`"
 )]
 fn invalid_message_empty() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes = types::RequestOrResponse::encode_without_field(
             (&request_message(), certification_version),
             0,
@@ -518,7 +586,7 @@ impl From<(&ic_types::messages::RequestOrResponse, u32)> for RequestOrResponseV3
 
 #[test]
 fn valid_request() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let request = request();
         let bytes = types::Request::proxy_encode((&request, certification_version)).unwrap();
 
@@ -548,9 +616,58 @@ fn encoding_request_for_certification_3_and_4_is_the_same() {
     assert_eq!(v3_encoded_request, v4_encoded_request);
 }
 
+#[test]
+fn decoding_request_with_cycles_payment_requires_certification_4() {
+    let mut request = types::RequestOrResponse::from((&request_message(), CERTIFICATION_VERSION_4));
+    request.request.as_mut().unwrap().cycles_payment =
+        Some((&cycles(), CERTIFICATION_VERSION_4).into());
+    let bytes = serde_cbor::ser::to_vec_packed(&request).unwrap();
+
+    for certification_version in 0..=CERTIFICATION_VERSION_3 {
+        assert_matches!(
+            decode_message(&bytes, certification_version),
+            Err(ProxyDecodeError::Other(err)) if err.contains("requires certification version 4")
+        );
+    }
+    for certification_version in CERTIFICATION_VERSION_4..=MAX_SUPPORTED_CERTIFICATION_VERSION {
+        assert_eq!(
+            request_message(),
+            decode_message(&bytes, certification_version).unwrap()
+        );
+    }
+}
+
+#[test]
+fn decoding_unsupported_certification_version_fails() {
+    let version = MAX_SUPPORTED_CERTIFICATION_VERSION + 1;
+
+    let header = StreamHeader {
+        begin: 23.into(),
+        end: 25.into(),
+        signals_end: 256.into(),
+    };
+    let bytes = encode_stream_header(&header, MAX_SUPPORTED_CERTIFICATION_VERSION);
+    assert_matches!(
+        decode_stream_header(&bytes, version),
+        Err(ProxyDecodeError::ValueOutOfRange {
+            typ: "certification_version",
+            ..
+        })
+    );
+
+    let bytes = encode_message(&request_message(), MAX_SUPPORTED_CERTIFICATION_VERSION);
+    assert_matches!(
+        decode_message(&bytes, version),
+        Err(ProxyDecodeError::ValueOutOfRange {
+            typ: "certification_version",
+            ..
+        })
+    );
+}
+
 #[test]
 fn invalid_request_extra_field() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         if certification_version <= CERTIFICATION_VERSION_3 {
             let bytes =
                 RequestV3::encode_with_extra_field((&request(), certification_version)).unwrap();
@@ -579,7 +696,7 @@ fn invalid_request_extra_field() {
 #[test]
 #[should_panic(expected = "missing field `receiver`")]
 fn invalid_request_missing_receiver() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Request::encode_without_field((&request(), certification_version), 0).unwrap();
 
@@ -590,7 +707,7 @@ fn invalid_request_missing_receiver() {
 #[test]
 #[should_panic(expected = "missing field `sender`")]
 fn invalid_request_missing_sender() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Request::encode_without_field((&request(), certification_version), 1).unwrap();
 
@@ -601,7 +718,7 @@ fn invalid_request_missing_sender() {
 #[test]
 #[should_panic(expected = "missing field `sender_reply_callback`")]
 fn invalid_request_missing_sender_reply_callback() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Request::encode_without_field((&request(), certification_version), 2).unwrap();
 
@@ -612,7 +729,7 @@ fn invalid_request_missing_sender_reply_callback() {
 #[test]
 #[should_panic(expected = "missing field `payment`")]
 fn invalid_request_missing_payment() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Request::encode_without_field((&request(), certification_version), 3).unwrap();
 
@@ -623,7 +740,7 @@ fn invalid_request_missing_payment() {
 #[test]
 #[should_panic(expected = "missing field `method_name`")]
 fn invalid_request_missing_method_name() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Request::encode_without_field((&request(), certification_version), 4).unwrap();
 
@@ -634,7 +751,7 @@ fn invalid_request_missing_method_name() {
 #[test]
 #[should_panic(expected = "missing field `method_payload`")]
 fn invalid_request_missing_method_payload() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Request::encode_without_field((&request(), certification_version), 5).unwrap();
 
@@ -677,7 +794,7 @@ impl From<(&ic_types::messages::Response, u32)> for ResponseV3 {
 
 #[test]
 fn valid_response() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let response = response();
         let bytes = types::Response::proxy_encode((&response, certification_version)).unwrap();
 
@@ -706,9 +823,31 @@ fn encoding_response_for_certification_3_and_4_is_the_same() {
     assert_eq!(v3_encoded_response, v4_encoded_response);
 }
 
+#[test]
+fn decoding_response_with_cycles_refund_requires_certification_4() {
+    let message = RequestOrResponse::Response(response());
+    let mut response = types::RequestOrResponse::from((&message, CERTIFICATION_VERSION_4));
+    response.response.as_mut().unwrap().cycles_refund =
+        Some((&cycles(), CERTIFICATION_VERSION_4).into());
+    let bytes = serde_cbor::ser::to_vec_packed(&response).unwrap();
+
+    for certification_version in 0..=CERTIFICATION_VERSION_3 {
+        assert_matches!(
+            decode_message(&bytes, certification_version),
+            Err(ProxyDecodeError::Other(err)) if err.contains("requires certification version 4")
+        );
+    }
+    for certification_version in CERTIFICATION_VERSION_4..=MAX_SUPPORTED_CERTIFICATION_VERSION {
+        assert_eq!(
+            message,
+            decode_message(&bytes, certification_version).unwrap()
+        );
+    }
+}
+
 #[test]
 fn invalid_response_extra_field() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         if certification_version <= CERTIFICATION_VERSION_3 {
             let bytes =
                 ResponseV3::encode_with_extra_field((&response(), certification_version)).unwrap();
@@ -737,7 +876,7 @@ fn invalid_response_extra_field() {
 #[test]
 #[should_panic(expected = "missing field `originator`")]
 fn invalid_response_missing_originator() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Response::encode_without_field((&response(), certification_version), 0).unwrap();
 
@@ -748,7 +887,7 @@ fn invalid_response_missing_originator() {
 #[test]
 #[should_panic(expected = "missing field `respondent`")]
 fn invalid_response_missing_respondent() {
-    for certification_version in 0..CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Response::encode_without_field((&response(), certification_version), 1).unwrap();
 
@@ -759,7 +898,7 @@ fn invalid_response_missing_respondent() {
 #[test]
 #[should_panic(expected = "missing field `originator_reply_callback`")]
 fn invalid_response_missing_originator_reply_callback() {
-    for certification_version in 0..CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Response::encode_without_field((&response(), certification_version), 2).unwrap();
 
@@ -770,7 +909,7 @@ fn invalid_response_missing_originator_reply_callback() {
 #[test]
 #[should_panic(expected = "missing field `refund`")]
 fn invalid_response_missing_refund() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Response::encode_without_field((&response(), certification_version), 3).unwrap();
 
@@ -781,7 +920,7 @@ fn invalid_response_missing_refund() {
 #[test]
 #[should_panic(expected = "missing field `response_payload`")]
 fn invalid_response_missing_response_payload() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Response::encode_without_field((&response(), certification_version), 4).unwrap();
 
@@ -795,7 +934,7 @@ fn invalid_response_missing_response_payload() {
 
 #[test]
 fn valid_funds() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let funds = funds();
         let bytes = types::Funds::proxy_encode((&funds, certification_version)).unwrap();
 
@@ -806,7 +945,7 @@ fn valid_funds() {
 #[test]
 #[should_panic(expected = "expected field index 0 <= i < 2")]
 fn invalid_funds_extra_field() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Funds::encode_with_extra_field((&funds(), certification_version)).unwrap();
 
@@ -817,7 +956,7 @@ fn invalid_funds_extra_field() {
 #[test]
 #[should_panic(expected = "missing field `cycles`")]
 fn invalid_funds_missing_cycles() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Funds::encode_without_field((&funds(), certification_version), 0).unwrap();
 
@@ -831,7 +970,7 @@ fn invalid_funds_missing_cycles() {
 
 #[test]
 fn valid_data_payload() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let payload = data_payload();
         let bytes = types::Payload::proxy_encode((&payload, certification_version)).unwrap();
 
@@ -841,7 +980,7 @@ fn valid_data_payload() {
 
 #[test]
 fn valid_reject_payload() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let payload = reject_payload();
         let bytes = types::Payload::proxy_encode((&payload, certification_version)).unwrap();
 
@@ -852,7 +991,7 @@ fn valid_reject_payload() {
 #[test]
 #[should_panic(expected = "expected field index 0 <= i < 2")]
 fn invalid_payload_extra_field() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Payload::encode_with_extra_field((&data_payload(), certification_version))
                 .unwrap();
@@ -866,7 +1005,7 @@ fn invalid_payload_extra_field() {
     expected = "Payload: expected exactly one of `data` or `reject` to be `Some(_)`, got `Payload { data: None, reject: None }`"
 )]
 fn invalid_payload_empty() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes =
             types::Payload::encode_without_field((&data_payload(), certification_version), 0)
                 .unwrap();
@@ -881,7 +1020,7 @@ fn invalid_payload_empty() {
 
 #[test]
 fn valid_reject_context() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let context = reject_context();
         let bytes = types::RejectContext::proxy_encode((&context, certification_version)).unwrap();
 
@@ -892,7 +1031,7 @@ fn valid_reject_context() {
 #[test]
 #[should_panic(expected = "expected field index 0 <= i < 2")]
 fn invalid_reject_context_extra_field() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes = types::RejectContext::encode_with_extra_field((
             &reject_context(),
             certification_version,
@@ -906,7 +1045,7 @@ fn invalid_reject_context_extra_field() {
 #[test]
 #[should_panic(expected = "missing field `code`")]
 fn invalid_reject_context_missing_code() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes = types::RejectContext::encode_without_field(
             (&reject_context(), certification_version),
             0,
@@ -920,7 +1059,7 @@ fn invalid_reject_context_missing_code() {
 #[test]
 #[should_panic(expected = "missing field `message`")]
 fn invalid_reject_context_missing_message() {
-    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+    for certification_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
         let bytes = types::RejectContext::encode_without_field(
             (&reject_context(), certification_version),
             1,
@@ -0,0 +1,148 @@
+//! Golden tests for the versioned canonical encoding of XNet messages and
+//! stream headers.
+//!
+//! Every fixture is encoded with every supported certification version and the
+//! SHA-256 hash of the encoding is compared against the golden table entry for
+//! that version. A change to the encoding must be introduced together with a
+//! new certification version and recorded as a new golden entry starting at
+//! that version; existing entries must never be modified, as replicas may still
+//! be producing (and verifying) those encodings.
+
+use super::test_fixtures::*;
+use crate::{encoding::*, MAX_SUPPORTED_CERTIFICATION_VERSION};
+use ic_crypto_sha::Sha256;
+use ic_protobuf::proxy::ProxyDecodeError;
+use std::fmt::Debug;
+
+/// Golden encodings of a fixture, as `(first_certification_version, sha256)`
+/// pairs sorted by certification version. Each entry applies from its
+/// certification version up to (but excluding) that of the next entry.
+type Golden = &'static [(u32, &'static str)];
+
+const STREAM_HEADER: Golden = &[(
+    0,
+    "5769bdb42825f9246cf1bea0389612aa1a22f1684feadb1e96316692a35e6101",
+)];
+
+const REQUEST: Golden = &[(
+    0,
+    "5bb2558fedd3d2295eb25bbe0a90d26da380f8c9a6a52129c81ed4368df317e3",
+)];
+
+const RESPONSE: Golden = &[(
+    0,
+    "615a789fe8449f5af4d6552b791a983560b099981d1a383e98d84ab0b4ba8f9f",
+)];
+
+const REJECT_RESPONSE: Golden = &[(
+    0,
+    "95c241c8cb3e01a63368a019b775f7ad6d24624d99fc67427eb5e4c388f4c70d",
+)];
+
+/// Returns the golden hash for the given certification version.
+fn golden_hash(golden: Golden, certification_version: u32) -> &'static str {
+    golden
+        .iter()
+        .rev()
+        .find(|(version, _)| *version <= certification_version)
+        .map(|(_, hash)| *hash)
+        .unwrap_or_else(|| panic!("no golden entry for version {}", certification_version))
+}
+
+/// Checks the encoding of `value` with every supported certification version
+/// against `golden`; and that every encoding decodes back to `value` under all
+/// supported certification versions that produce the same encoding or are
+/// more recent.
+fn check_golden<T: PartialEq + Debug>(
+    name: &str,
+    value: &T,
+    golden: Golden,
+    encode: impl Fn(&T, u32) -> Vec<u8>,
+    decode: impl Fn(&[u8], u32) -> Result<T, ProxyDecodeError>,
+) {
+    assert_eq!(
+        0, golden[0].0,
+        "{}: golden table must start at version 0",
+        name
+    );
+    assert!(
+        golden.windows(2).all(|w| w[0].0 < w[1].0),
+        "{}: golden table must be sorted by version",
+        name
+    );
+    assert!(
+        golden.last().unwrap().0 <= MAX_SUPPORTED_CERTIFICATION_VERSION,
+        "{}: golden entry for an unsupported version",
+        name
+    );
+
+    for version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
+        let bytes = encode(value, version);
+        assert_eq!(
+            golden_hash(golden, version),
+            hex::encode(Sha256::hash(&bytes)),
+            "{}: encoding with certification version {} changed",
+            name,
+            version
+        );
+
+        for decoding_version in 0..=MAX_SUPPORTED_CERTIFICATION_VERSION {
+            if decoding_version < version
+                && golden_hash(golden, decoding_version) != golden_hash(golden, version)
+            {
+                continue;
+            }
+            assert_eq!(
+                value,
+                &decode(&bytes, decoding_version).unwrap_or_else(|err| panic!(
+                    "{}: failed to decode version {} encoding as version {}: {}",
+                    name, version, decoding_version, err
+                )),
+            );
+        }
+    }
+}
+
+#[test]
+fn golden_encoding_stream_header() {
+    check_golden(
+        "StreamHeader",
+        &stream_header(),
+        STREAM_HEADER,
+        encode_stream_header,
+        decode_stream_header,
+    );
+}
+
+#[test]
+fn golden_encoding_request() {
+    check_golden(
+        "Request",
+        &request(),
+        REQUEST,
+        encode_message,
+        decode_message,
+    );
+}
+
+#[test]
+fn golden_encoding_response() {
+    check_golden(
+        "Response",
+        &response(),
+        RESPONSE,
+        encode_message,
+        decode_message,
+    );
+}
+
+#[test]
+fn golden_encoding_reject_response() {
+    check_golden(
+        "RejectResponse",
+        &reject_response(),
+        REJECT_RESPONSE,
+        encode_message,
+        decode_message,
+    );
+}
@@ -117,6 +117,81 @@ pub struct SystemMetadata {
     pub prev_state_hash: Option<Vec<u8>>,
 }
 
+/// Canonical representation of the subnet metrics leaf.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubnetMetrics {
+    /// The number of canisters hosted by the subnet.
+    pub num_canisters: u64,
+    /// The total memory taken by the canisters hosted by the subnet.
+    pub canister_state_bytes: u64,
+    /// The cycles consumed by the canisters currently hosted by the subnet.
+    pub consumed_cycles_total: Cycles,
+}
+
+/// Canonical representation of the metrics leaf of a node.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NodeMetrics {
+    /// The number of blocks proposed by the node that were finalized, which is
+    /// also its number of finalized blocks (see
+    /// `ic_replicated_state::metadata_state::NodeMetrics`).
+    pub blocks_proposed_total: u64,
+    /// The number of times the node failed to propose a block that got
+    /// finalized, indexed by its rank as a block maker.
+    pub failed_proposals_by_rank: Vec<u64>,
+}
+
+/// Implemented by canonical types whose encoding depends on the certification
+/// version, in order to reject values that a replica certifying states with a
+/// given version could not have produced.
+pub trait Versioned {
+    /// The lowest certification version that may produce this value.
+    fn min_certification_version(&self) -> u32;
+}
+
+impl Versioned for StreamHeader {
+    fn min_certification_version(&self) -> u32 {
+        0
+    }
+}
+
+impl Versioned for RequestOrResponse {
+    fn min_certification_version(&self) -> u32 {
+        let request = self
+            .request
+            .as_ref()
+            .map_or(0, Versioned::min_certification_version);
+        let response = self
+            .response
+            .as_ref()
+            .map_or(0, Versioned::min_certification_version);
+        request.max(response)
+    }
+}
+
+impl Versioned for Request {
+    fn min_certification_version(&self) -> u32 {
+        // `cycles_payment` was added in certification version 4.
+        if self.cycles_payment.is_some() {
+            4
+        } else {
+            0
+        }
+    }
+}
+
+impl Versioned for Response {
+    fn min_certification_version(&self) -> u32 {
+        // `cycles_refund` was added in certification version 4.
+        if self.cycles_refund.is_some() {
+            4
+        } else {
+            0
+        }
+    }
+}
+
 impl From<(&ic_types::xnet::StreamHeader, u32)> for StreamHeader {
     fn from((header, _certification_version): (&ic_types::xnet::StreamHeader, u32)) -> Self {
         Self {
@@ -362,3 +437,36 @@ impl From<&ic_replicated_state::metadata_state::SystemMetadata> for SystemMetada
         }
     }
 }
+
+impl From<&ic_replicated_state::ReplicatedState> for SubnetMetrics {
+    fn from(state: &ic_replicated_state::ReplicatedState) -> Self {
+        let consumed_cycles_total = state
+            .canisters_iter()
+            .map(|canister| canister.system_state.consumed_cycles_since_replica_started)
+            .fold(
+                ic_types::nominal_cycles::NominalCycles::default(),
+                |acc, c| acc + c,
+            );
+        let (high, low) = consumed_cycles_total.into_parts();
+        Self {
+            num_canisters: state.num_canisters() as u64,
+            canister_state_bytes: state.total_memory_taken().get(),
+            consumed_cycles_total: Cycles {
+                low,
+                high: match high {
+                    0 => None,
+                    _ => Some(high),
+                },
+            },
+        }
+    }
+}
+
+impl From<&ic_replicated_state::metadata_state::NodeMetrics> for NodeMetrics {
+    fn from(metrics: &ic_replicated_state::metadata_state::NodeMetrics) -> Self {
+        Self {
+            blocks_proposed_total: metrics.blocks_proposed_total,
+            failed_proposals_by_rank: metrics.failed_proposals_by_rank.clone(),
+        }
+    }
+}
@@ -1,15 +1,17 @@
 //! Conversion from `ReplicatedState` to `LazyTree`.
 
-use super::{blob, fork, num, string, Lazy, LazyFork, LazyTree};
+use super::{blob, fork, num, num_u128, string, Lazy, LazyFork, LazyTree};
 use crate::encoding::{
-    encode_controllers, encode_message, encode_metadata, encode_stream_header,
-    encode_subnet_canister_ranges,
+    encode_controllers, encode_message, encode_metadata, encode_node_metrics, encode_stream_header,
+    encode_subnet_canister_ranges, encode_subnet_metrics,
 };
 use ic_crypto_tree_hash::Label;
 use ic_registry_routing_table::RoutingTable;
 use ic_replicated_state::{
     canister_state::CanisterState,
-    metadata_state::{IngressHistoryState, StreamMap, SubnetTopology, SystemMetadata},
+    metadata_state::{
+        IngressHistoryState, NodeMetrics, NodeTopology, StreamMap, SubnetTopology, SystemMetadata,
+    },
     replicated_state::ReplicatedStateMessageRouting,
     ReplicatedState,
 };
@@ -18,7 +20,7 @@ use ic_types::{
     messages::{MessageId, EXPECTED_MESSAGE_ID_LENGTH},
     user_error::RejectCode,
     xnet::{StreamHeader, StreamIndex, StreamIndexedQueue},
-    CanisterId, PrincipalId, SubnetId,
+    CanisterId, CanisterStatusType, NodeId, PrincipalId, SubnetId,
 };
 use std::collections::BTreeMap;
 use std::convert::{AsRef, TryInto};
@@ -40,6 +42,20 @@ impl<'a> FiniteMap<'a> {
         self
     }
 
+    /// If condition is true, adds a new lazily computed subtree to this map.
+    /// Otherwise does nothing.
+    pub fn with_if<B, T>(self, condition: bool, blob: B, func: T) -> Self
+    where
+        B: AsRef<[u8]>,
+        T: Fn() -> LazyTree<'a> + 'a,
+    {
+        if condition {
+            self.with(blob, func)
+        } else {
+            self
+        }
+    }
+
     /// Adds a subtree with the specified label to this map.
     pub fn with_tree<B: AsRef<[u8]>>(mut self, label: B, tree: LazyTree<'a>) -> Self {
         self.0.insert(Label::from(label), Lazy::Value(tree));
@@ -211,6 +227,9 @@ fn state_as_tree(state: &ReplicatedState) -> LazyTree<'_> {
             .with("canister", move || {
                 canisters_as_tree(&state.canister_states, certification_version)
             })
+            .with_if(certification_version > 6, "canister_status", move || {
+                canister_statuses_as_tree(&state.canister_states, certification_version)
+            })
             .with_tree(
                 "request_status",
                 fork(IngressHistoryFork(&state.metadata.ingress_history)),
@@ -222,6 +241,7 @@ fn state_as_tree(state: &ReplicatedState) -> LazyTree<'_> {
                 subnets_as_tree(
                     &state.metadata.network_topology.subnets,
                     inverted_routing_table,
+                    state,
                     certification_version,
                 )
             })
@@ -351,11 +371,46 @@ fn canisters_as_tree(
     })
 }
 
-fn subnets_as_tree(
-    subnets: &BTreeMap<SubnetId, SubnetTopology>,
-    inverted_routing_table: Arc<BTreeMap<SubnetId, Vec<(PrincipalId, PrincipalId)>>>,
+/// The cycles balance, memory size and status of every canister, which only
+/// controllers of the canister may read.
+///
+/// They are kept out of the public `/canister/<canister_id>` subtrees, so that
+/// witnesses for public paths only ever include the hash of all canister
+/// statuses taken together, rather than the hashes of individual (and easily
+/// guessed) values.
+fn canister_statuses_as_tree(
+    canisters: &BTreeMap<CanisterId, CanisterState>,
     certification_version: u32,
 ) -> LazyTree<'_> {
+    fork(MapTransformFork {
+        map: canisters,
+        certification_version,
+        mk_tree: |_canister_id, canister, _certification_version| {
+            let status = match canister.status() {
+                CanisterStatusType::Running => "running",
+                CanisterStatusType::Stopping => "stopping",
+                CanisterStatusType::Stopped => "stopped",
+            };
+            fork(
+                FiniteMap::default()
+                    .with_tree(
+                        "cycles_balance",
+                        num_u128(canister.system_state.cycles_balance.get()),
+                    )
+                    .with_tree("memory_size", num(canister.memory_usage().get()))
+                    .with_tree("status", string(status)),
+            )
+        },
+    })
+}
+
+fn subnets_as_tree<'a>(
+    subnets: &'a BTreeMap<SubnetId, SubnetTopology>,
+    inverted_routing_table: Arc<BTreeMap<SubnetId, Vec<(PrincipalId, PrincipalId)>>>,
+    own_subnet_state: &'a ReplicatedState,
+    certification_version: u32,
+) -> LazyTree<'a> {
+    let own_subnet_id = own_subnet_state.metadata.own_subnet_id;
     fork(MapTransformFork {
         map: subnets,
         certification_version,
@@ -374,6 +429,58 @@ fn subnets_as_tree(
                                 )
                             }
                         }),
+                    )
+                    .with_tree_if(
+                        certification_version > 4,
+                        "node",
+                        nodes_as_tree(
+                            &subnet_topology.nodes,
+                            // Node metrics are only known for the subnet the
+                            // state belongs to.
+                            if subnet_id == own_subnet_id {
+                                Some(&own_subnet_state.metadata.node_metrics)
+                            } else {
+                                None
+                            },
+                            certification_version,
+                        ),
+                    )
+                    // Metrics are only known for the subnet the state belongs to.
+                    .with_tree_if(
+                        certification_version > 5 && subnet_id == own_subnet_id,
+                        "metrics",
+                        blob(move || encode_subnet_metrics(own_subnet_state)),
+                    ),
+            )
+        },
+    })
+}
+
+fn nodes_as_tree<'a>(
+    nodes: &'a BTreeMap<NodeId, NodeTopology>,
+    node_metrics: Option<&'a BTreeMap<NodeId, NodeMetrics>>,
+    certification_version: u32,
+) -> LazyTree<'a> {
+    fork(MapTransformFork {
+        map: nodes,
+        certification_version,
+        mk_tree: move |node_id, node_topology, certification_version| {
+            fork(
+                FiniteMap::default()
+                    .with_tree_if(
+                        node_topology.public_key.is_some(),
+                        "public_key",
+                        Blob(node_topology.public_key.as_deref().unwrap_or_default()),
+                    )
+                    // Staged: certification version 8 is not produced yet (see
+                    // `CURRENT_CERTIFICATION_VERSION`).
+                    .with_tree_if(
+                        certification_version > 7 && node_metrics.is_some(),
+                        "metrics",
+                        blob(move || {
+                            let metrics = node_metrics.and_then(|metrics| metrics.get(&node_id));
+                            encode_node_metrics(metrics.unwrap_or(&NodeMetrics::default()))
+                        }),
                     ),
             )
         },
@@ -19,7 +19,9 @@ pub use traversal::traverse;
 pub use visitor::{Control, Visitor};
 
 /// The Canonical State certification version that should be used for newly
-/// computed states.
+/// computed states. The version a state was certified with is recorded in its
+/// `SystemMetadata::certification_version`, so states are migrated to a new
+/// version as soon as the next state is computed.
 ///
 /// Version history:
 ///
@@ -29,4 +31,25 @@ pub use visitor::{Control, Visitor};
 ///   3. Added subnet to canister ID ranges routing tables.
 ///   4. Added optional `Request::cycles_payment` and `Response::cycles_refund`
 ///      fields that are not yet populated.
-pub const CURRENT_CERTIFICATION_VERSION: u32 = 4;
+///   5. Added node public keys under `/subnet/<subnet_id>/node/<node_id>`.
+///   6. Added subnet metrics under `/subnet/<own_subnet_id>/metrics`.
+///   7. Added canister `cycles_balance`, `memory_size` and `status` under
+///      `/canister_status/<canister_id>`, readable by controllers only.
+///      Staged: supported, but not produced yet.
+///   8. Added node block maker statistics under
+///      `/subnet/<own_subnet_id>/node/<node_id>/metrics`. Staged: supported,
+///      but not produced yet. Until then, the statistics are kept in
+///      `SystemMetadata::node_metrics` without being certified.
+///
+/// Versions above `CURRENT_CERTIFICATION_VERSION` are supported (see
+/// `MAX_SUPPORTED_CERTIFICATION_VERSION`) but not yet produced.
+pub const CURRENT_CERTIFICATION_VERSION: u32 = 6;
+
+/// The highest certification version this replica is able to decode and
+/// verify. Always at least `CURRENT_CERTIFICATION_VERSION`.
+///
+/// Changes to the encoding are rolled out in two stages: one replica release
+/// bumps `MAX_SUPPORTED_CERTIFICATION_VERSION`, so that all replicas are able
+/// to decode the new encoding; and only a later release bumps
+/// `CURRENT_CERTIFICATION_VERSION`, so that replicas start producing it.
+pub const MAX_SUPPORTED_CERTIFICATION_VERSION: u32 = 8;
@@ -43,7 +43,10 @@ pub fn traverse<V: Visitor>(state: &ReplicatedState, mut v: V) -> V::Output {
 mod tests {
     use super::*;
     use crate::{
-        encoding::{encode_stream_header, types::SystemMetadata, CborProxyEncoder},
+        encoding::{
+            encode_node_metrics, encode_stream_header, encode_subnet_metrics,
+            types::SystemMetadata, CborProxyEncoder,
+        },
         subtree_visitor::{Pattern, SubtreeVisitor},
         test_visitors::{NoopVisitor, TraceEntry as E, TracingVisitor},
     };
@@ -55,7 +58,7 @@ mod tests {
         canister_state::{
             execution_state::WasmBinary, ExecutionState, ExportedFunctions, Global, NumWasmPages,
         },
-        metadata_state::SubnetTopology,
+        metadata_state::{NodeMetrics, NodeTopology, SubnetTopology},
         page_map::PageMap,
         testing::ReplicatedStateTesting,
         Memory,
@@ -63,7 +66,7 @@ mod tests {
     use ic_test_utilities::{
         mock_time,
         state::new_canister_state,
-        types::ids::{canister_test_id, subnet_test_id, user_test_id},
+        types::ids::{canister_test_id, node_test_id, subnet_test_id, user_test_id},
     };
     use ic_types::{CanisterId, Cycles, ExecutionRound};
     use ic_wasm_types::BinaryEncodedWasm;
@@ -683,4 +686,320 @@ mod tests {
             traverse(&state, visitor).0
         );
     }
+
+    #[test]
+    fn test_traverse_subnet_nodes() {
+        let tmpdir = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+        let mut state = ReplicatedState::new_rooted_at(
+            subnet_test_id(1),
+            SubnetType::Application,
+            tmpdir.path().into(),
+        );
+
+        state.metadata.network_topology.subnets = btreemap! {
+            subnet_test_id(1) => SubnetTopology {
+                public_key: vec![1, 2, 3, 4],
+                nodes: btreemap!{
+                    node_test_id(2) => NodeTopology {
+                        ip_address: "2a00:fb01:400:42:5000:22ff:fe5e:e3c4".to_string(),
+                        http_port: 8080,
+                        public_key: Some(vec![5, 6, 7, 8]),
+                    },
+                    node_test_id(3) => NodeTopology {
+                        ip_address: "2a00:fb01:400:42:5000:22ff:fe5e:e3c5".to_string(),
+                        http_port: 8080,
+                        public_key: None,
+                    },
+                },
+                subnet_type: SubnetType::Application,
+            },
+        };
+
+        let pattern = Pattern::match_only("subnet", Pattern::all());
+
+        // Node public keys are not certified before version 5.
+        let visitor = SubtreeVisitor::new(&pattern, TracingVisitor::new(NoopVisitor));
+        state.metadata.certification_version = 4;
+        assert_eq!(
+            vec![
+                E::StartSubtree,
+                edge("subnet"),
+                E::StartSubtree,
+                E::EnterEdge(subnet_test_id(1).get().into_vec()),
+                E::StartSubtree,
+                edge("canister_ranges"),
+                // D9 D9F7  # tag(55799)
+                //    80    # array(0)
+                E::VisitBlob(hex::decode("d9d9f780").unwrap()),
+                edge("public_key"),
+                E::VisitBlob(vec![1, 2, 3, 4]),
+                E::EndSubtree, // subnet
+                E::EndSubtree, // subnets
+                E::EndSubtree, // global
+            ],
+            traverse(&state, visitor).0
+        );
+
+        let visitor = SubtreeVisitor::new(&pattern, TracingVisitor::new(NoopVisitor));
+        state.metadata.certification_version = 5;
+        assert_eq!(
+            vec![
+                E::StartSubtree,
+                edge("subnet"),
+                E::StartSubtree,
+                E::EnterEdge(subnet_test_id(1).get().into_vec()),
+                E::StartSubtree,
+                edge("canister_ranges"),
+                E::VisitBlob(hex::decode("d9d9f780").unwrap()),
+                edge("node"),
+                E::StartSubtree,
+                E::EnterEdge(node_test_id(2).get().into_vec()),
+                E::StartSubtree,
+                edge("public_key"),
+                E::VisitBlob(vec![5, 6, 7, 8]),
+                E::EndSubtree, // node
+                // Nodes without a signing key have no public key.
+                E::EnterEdge(node_test_id(3).get().into_vec()),
+                E::StartSubtree,
+                E::EndSubtree, // node
+                E::EndSubtree, // nodes
+                edge("public_key"),
+                E::VisitBlob(vec![1, 2, 3, 4]),
+                E::EndSubtree, // subnet
+                E::EndSubtree, // subnets
+                E::EndSubtree, // global
+            ],
+            traverse(&state, visitor).0
+        );
+    }
+
+    #[test]
+    fn test_traverse_subnet_metrics() {
+        let canister_id = canister_test_id(2);
+        let controller = user_test_id(24);
+        let tmpdir = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+        let mut state = ReplicatedState::new_rooted_at(
+            subnet_test_id(1),
+            SubnetType::Application,
+            tmpdir.path().into(),
+        );
+        state.put_canister_state(new_canister_state(
+            canister_id,
+            controller.get(),
+            INITIAL_CYCLES,
+            NumSeconds::from(100_000),
+        ));
+
+        state.metadata.network_topology.subnets = btreemap! {
+            subnet_test_id(0) => SubnetTopology {
+                public_key: vec![1, 2, 3, 4],
+                nodes: btreemap!{},
+                subnet_type: SubnetType::Application,
+            },
+            subnet_test_id(1) => SubnetTopology {
+                public_key: vec![5, 6, 7, 8],
+                nodes: btreemap!{},
+                subnet_type: SubnetType::Application,
+            },
+        };
+        state.metadata.certification_version = 6;
+
+        // Metrics are only certified for the subnet the state belongs to.
+        let pattern = Pattern::match_only("subnet", Pattern::all());
+        let visitor = SubtreeVisitor::new(&pattern, TracingVisitor::new(NoopVisitor));
+        assert_eq!(
+            vec![
+                E::StartSubtree,
+                edge("subnet"),
+                E::StartSubtree,
+                E::EnterEdge(subnet_test_id(0).get().into_vec()),
+                E::StartSubtree,
+                edge("canister_ranges"),
+                E::VisitBlob(hex::decode("d9d9f780").unwrap()),
+                edge("node"),
+                E::StartSubtree,
+                E::EndSubtree, // nodes
+                edge("public_key"),
+                E::VisitBlob(vec![1, 2, 3, 4]),
+                E::EndSubtree, // subnet
+                E::EnterEdge(subnet_test_id(1).get().into_vec()),
+                E::StartSubtree,
+                edge("canister_ranges"),
+                E::VisitBlob(hex::decode("d9d9f780").unwrap()),
+                edge("metrics"),
+                E::VisitBlob(encode_subnet_metrics(&state)),
+                edge("node"),
+                E::StartSubtree,
+                E::EndSubtree, // nodes
+                edge("public_key"),
+                E::VisitBlob(vec![5, 6, 7, 8]),
+                E::EndSubtree, // subnet
+                E::EndSubtree, // subnets
+                E::EndSubtree, // global
+            ],
+            traverse(&state, visitor).0
+        );
+    }
+
+    #[test]
+    fn test_traverse_node_metrics() {
+        let tmpdir = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+        let mut state = ReplicatedState::new_rooted_at(
+            subnet_test_id(1),
+            SubnetType::Application,
+            tmpdir.path().into(),
+        );
+
+        state.metadata.network_topology.subnets = btreemap! {
+            subnet_test_id(0) => SubnetTopology {
+                public_key: vec![1, 2, 3, 4],
+                nodes: btreemap!{ node_test_id(2) => NodeTopology::default() },
+                subnet_type: SubnetType::Application,
+            },
+            subnet_test_id(1) => SubnetTopology {
+                public_key: vec![5, 6, 7, 8],
+                nodes: btreemap!{
+                    node_test_id(3) => NodeTopology::default(),
+                    node_test_id(4) => NodeTopology::default(),
+                },
+                subnet_type: SubnetType::Application,
+            },
+        };
+        let node_metrics = NodeMetrics {
+            blocks_proposed_total: 5,
+            failed_proposals_by_rank: vec![1, 2],
+        };
+        state.metadata.node_metrics = btreemap! { node_test_id(3) => node_metrics.clone() };
+        state.metadata.certification_version = 8;
+
+        // Metrics are only certified for the nodes of the subnet the state
+        // belongs to, including those without any metrics yet.
+        let pattern = Pattern::match_only("subnet", Pattern::all());
+        let visitor = SubtreeVisitor::new(&pattern, TracingVisitor::new(NoopVisitor));
+        assert_eq!(
+            vec![
+                E::StartSubtree,
+                edge("subnet"),
+                E::StartSubtree,
+                E::EnterEdge(subnet_test_id(0).get().into_vec()),
+                E::StartSubtree,
+                edge("canister_ranges"),
+                E::VisitBlob(hex::decode("d9d9f780").unwrap()),
+                edge("node"),
+                E::StartSubtree,
+                E::EnterEdge(node_test_id(2).get().into_vec()),
+                E::StartSubtree,
+                E::EndSubtree, // node
+                E::EndSubtree, // nodes
+                edge("public_key"),
+                E::VisitBlob(vec![1, 2, 3, 4]),
+                E::EndSubtree, // subnet
+                E::EnterEdge(subnet_test_id(1).get().into_vec()),
+                E::StartSubtree,
+                edge("canister_ranges"),
+                E::VisitBlob(hex::decode("d9d9f780").unwrap()),
+                edge("metrics"),
+                E::VisitBlob(encode_subnet_metrics(&state)),
+                edge("node"),
+                E::StartSubtree,
+                E::EnterEdge(node_test_id(3).get().into_vec()),
+                E::StartSubtree,
+                edge("metrics"),
+                E::VisitBlob(encode_node_metrics(&node_metrics)),
+                E::EndSubtree, // node
+                E::EnterEdge(node_test_id(4).get().into_vec()),
+                E::StartSubtree,
+                edge("metrics"),
+                E::VisitBlob(encode_node_metrics(&NodeMetrics::default())),
+                E::EndSubtree, // node
+                E::EndSubtree, // nodes
+                edge("public_key"),
+                E::VisitBlob(vec![5, 6, 7, 8]),
+                E::EndSubtree, // subnet
+                E::EndSubtree, // subnets
+                E::EndSubtree, // global
+            ],
+            traverse(&state, visitor).0
+        );
+    }
+
+    #[test]
+    fn test_traverse_canister_status() {
+        let canister_id = canister_test_id(2);
+        let controller = user_test_id(24);
+        let tmpdir = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+        let mut state = ReplicatedState::new_rooted_at(
+            subnet_test_id(1),
+            SubnetType::Application,
+            tmpdir.path().into(),
+        );
+        let canister_state = new_canister_state(
+            canister_id,
+            controller.get(),
+            INITIAL_CYCLES,
+            NumSeconds::from(100_000),
+        );
+        let memory_size = canister_state.memory_usage().get();
+        state.put_canister_state(canister_state);
+        let controllers_cbor = {
+            let mut cbor = vec![217, 217, 247, 129, 74];
+            cbor.extend(controller.get().to_vec());
+            cbor
+        };
+
+        let pattern = Pattern::match_any(
+            vec![
+                ("canister", Pattern::all()),
+                ("canister_status", Pattern::all()),
+            ]
+            .into_iter(),
+        );
+        let trace = |state: &ReplicatedState| {
+            traverse(
+                state,
+                SubtreeVisitor::new(&pattern, TracingVisitor::new(NoopVisitor)),
+            )
+            .0
+        };
+
+        state.metadata.certification_version = 6;
+        let canisters_trace = vec![
+            edge("canister"),
+            E::StartSubtree,
+            E::EnterEdge(canister_id.get().into_vec()),
+            E::StartSubtree,
+            edge("controller"),
+            E::VisitBlob(controller.get().to_vec()),
+            edge("controllers"),
+            E::VisitBlob(controllers_cbor),
+            E::EndSubtree, // canister
+            E::EndSubtree, // canisters
+        ];
+        let mut v6_trace = vec![E::StartSubtree]; // global
+        v6_trace.extend(canisters_trace.clone());
+        v6_trace.push(E::EndSubtree); // global
+        assert_eq!(v6_trace, trace(&state));
+
+        // The status is certified in a separate subtree, so that witnesses for
+        // the public canister paths do not reveal its hash.
+        state.metadata.certification_version = 7;
+        let mut v7_trace = vec![E::StartSubtree]; // global
+        v7_trace.extend(canisters_trace);
+        v7_trace.extend(vec![
+            edge("canister_status"),
+            E::StartSubtree,
+            E::EnterEdge(canister_id.get().into_vec()),
+            E::StartSubtree,
+            edge("cycles_balance"),
+            leb_num(INITIAL_CYCLES.get() as u64),
+            edge("memory_size"),
+            leb_num(memory_size),
+            edge("status"),
+            E::VisitBlob(b"running".to_vec()),
+            E::EndSubtree, // canister
+            E::EndSubtree, // canister_status
+            E::EndSubtree, // global
+        ]);
+        assert_eq!(v7_trace, trace(&state));
+    }
 }
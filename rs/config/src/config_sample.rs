@@ -80,6 +80,12 @@ pub const SAMPLE_CONFIG: &str = r#"
 
         // mapping of flow ids to TCP port number, also depth of send queue
         p2p_flows: [{flow_tag: 1, server_port: 3000, queue_size: 1024}],
+
+        // Protocol the flows with peers are run over. Alternatives:
+        //   * "tls_over_tcp": a TLS-over-TCP connection per flow and peer
+        //   * "quic": a stream per flow over a single QUIC connection per
+        //     peer, on the server port of the flow with the lowest flow tag
+        backend: "tls_over_tcp",
     },
     // ============================================
     // Configuration of registry client
@@ -106,7 +112,18 @@ pub const SAMPLE_CONFIG: &str = r#"
     // ============================================
     state_manager: {
         // The directory that should be used to persist node state.
-        state_root: "/tmp/ic_state"
+        state_root: "/tmp/ic_state",
+        // What to keep on disk beyond the data the node needs to operate.
+        retention_policy: {
+            // The number of latest checkpoints kept for state sync.
+            checkpoints_to_keep: 3,
+            // How many diverged checkpoints and backups to keep, and for how long.
+            diverged_states_to_keep: 2,
+            diverged_state_retention_days: 30,
+            // Below this share of free disk space, the state sync cache, backups
+            // and diverged checkpoints are deleted. Disabled if 0.
+            min_free_disk_space_percent: 0
+        }
     },
     // ============================================
     // Configuration of the node artifact pool persistence.
@@ -169,7 +186,15 @@ pub const SAMPLE_CONFIG: &str = r#"
     // ====================================
     http_handler: {
         // The address to listen on.
-        listen_addr: "127.0.0.1:8080"
+        listen_addr: "127.0.0.1:8080",
+
+        // Token buckets limiting the rate at which ingress messages are
+        // accepted from a single sender and to a single canister. Rate
+        // limited requests are rejected with `429 Too Many Requests`.
+        // No limits are applied if not set.
+        //
+        // EXAMPLE: ingress_rate_limit_per_sender: { max_requests_per_second: 10, burst_size: 50 },
+        // EXAMPLE: ingress_rate_limit_per_canister: { max_requests_per_second: 100, burst_size: 500 },
     },
     // ==================================================
     // Configuration of the metrics collection subsystem.
@@ -20,6 +20,26 @@ pub enum PortConfig {
     WritePortTo(PathBuf),
 }
 
+/// A token bucket limiting the rate at which ingress messages are accepted.
+///
+/// The bucket holds up to `burst_size` tokens and is refilled at
+/// `max_requests_per_second`. Every accepted ingress message takes one token.
+///
+/// ```json5
+/// {
+///   max_requests_per_second: 10,
+///   burst_size: 50
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// The rate at which the bucket is refilled.
+    pub max_requests_per_second: u32,
+    /// The capacity of the bucket, i.e. the number of requests that may be
+    /// accepted at once after a period of inactivity.
+    pub burst_size: u32,
+}
+
 /// The external configuration that can be loaded from a configuration file.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(default)]
@@ -85,6 +105,25 @@ pub struct ExternalConfig {
     //       major security risk for the IC, but developers should not be
     //       tempted to get the IC's root key from this insecure location.
     pub show_root_key_in_status: bool,
+
+    /// Limits the rate at which ingress messages from a single sender are
+    /// accepted. Disabled if not set.
+    ///
+    /// ```json5
+    /// {
+    ///   http_handler: {
+    ///     ingress_rate_limit_per_sender: {
+    ///       max_requests_per_second: 10,
+    ///       burst_size: 50
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub ingress_rate_limit_per_sender: Option<RateLimitConfig>,
+
+    /// Limits the rate at which ingress messages to a single canister are
+    /// accepted. Disabled if not set.
+    pub ingress_rate_limit_per_canister: Option<RateLimitConfig>,
 }
 
 impl Default for ExternalConfig {
@@ -94,6 +133,8 @@ impl Default for ExternalConfig {
             allow_ipv6_my_users_have_no_privacy: None,
             port: None,
             show_root_key_in_status: true,
+            ingress_rate_limit_per_sender: None,
+            ingress_rate_limit_per_canister: None,
         }
     }
 }
@@ -110,6 +151,10 @@ pub struct Config {
     pub port_file_path: Option<PathBuf>,
     /// True if the replica public key is returned from the `/status` endpoint
     pub show_root_key_in_status: bool,
+    /// Token bucket applied to the ingress messages of every sender
+    pub ingress_rate_limit_per_sender: Option<RateLimitConfig>,
+    /// Token bucket applied to the ingress messages to every canister
+    pub ingress_rate_limit_per_canister: Option<RateLimitConfig>,
 }
 
 impl Default for Config {
@@ -121,6 +166,8 @@ impl Default for Config {
             ),
             port_file_path: None,
             show_root_key_in_status: true,
+            ingress_rate_limit_per_sender: None,
+            ingress_rate_limit_per_canister: None,
         }
     }
 }
@@ -155,6 +202,18 @@ impl TryFrom<ExternalConfig> for Config {
         }?;
 
         config.show_root_key_in_status = ec.show_root_key_in_status;
+
+        for limit in ec
+            .ingress_rate_limit_per_sender
+            .iter()
+            .chain(ec.ingress_rate_limit_per_canister.iter())
+        {
+            if limit.max_requests_per_second == 0 || limit.burst_size == 0 {
+                return Err("ingress rate limits must be positive");
+            }
+        }
+        config.ingress_rate_limit_per_sender = ec.ingress_rate_limit_per_sender;
+        config.ingress_rate_limit_per_canister = ec.ingress_rate_limit_per_canister;
         Ok(config)
     }
 }
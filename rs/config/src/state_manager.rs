@@ -4,14 +4,75 @@ use std::path::PathBuf;
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Config {
     state_root: PathBuf,
+    #[serde(default)]
+    retention_policy: RetentionPolicy,
 }
 
 impl Config {
     pub fn new(state_root: PathBuf) -> Self {
-        Self { state_root }
+        Self {
+            state_root,
+            retention_policy: RetentionPolicy::default(),
+        }
+    }
+
+    pub fn with_retention_policy(self, retention_policy: RetentionPolicy) -> Self {
+        Self {
+            retention_policy,
+            ..self
+        }
     }
 
     pub fn state_root(&self) -> PathBuf {
         self.state_root.clone()
     }
+
+    pub fn retention_policy(&self) -> &RetentionPolicy {
+        &self.retention_policy
+    }
+}
+
+/// Determines how much data the state manager keeps on disk beyond what it
+/// needs to operate.
+///
+/// ```json5
+/// {
+///   state_manager: {
+///     state_root: "/var/lib/ic/data/ic_state",
+///     retention_policy: {
+///       checkpoints_to_keep: 3,
+///       diverged_states_to_keep: 2,
+///       diverged_state_retention_days: 30,
+///       min_free_disk_space_percent: 0
+///     }
+///   }
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// The number of latest checkpoints to keep for state sync. Checkpoints
+    /// needed to recompute states that consensus might still request are kept
+    /// regardless.
+    pub checkpoints_to_keep: usize,
+    /// The maximum number of diverged checkpoints, and separately of backups,
+    /// to keep.
+    pub diverged_states_to_keep: usize,
+    /// Diverged checkpoints and backups older than this are deleted.
+    pub diverged_state_retention_days: u64,
+    /// If less than this share of the disk holding the state root is free,
+    /// optional data (the state sync cache, backups and diverged checkpoints)
+    /// is deleted until enough space is available again. Disabled if 0.
+    pub min_free_disk_space_percent: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            checkpoints_to_keep: 3,
+            diverged_states_to_keep: 2,
+            diverged_state_retention_days: 30,
+            min_free_disk_space_percent: 0,
+        }
+    }
 }
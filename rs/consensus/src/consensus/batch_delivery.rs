@@ -3,6 +3,7 @@
 //! subnets.
 
 use crate::consensus::{
+    membership::Membership,
     pool_reader::PoolReader,
     prelude::*,
     utils::{crypto_hashable_to_seed, get_block_hash_string, lookup_replica_version},
@@ -35,6 +36,7 @@ use std::time::Duration;
 #[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn deliver_batches(
     message_routing: &dyn MessageRouting,
+    membership: &Membership,
     pool: &PoolReader<'_>,
     state_manager: &dyn StateManager<State = ReplicatedState>,
     registry_client: &dyn RegistryClient,
@@ -120,6 +122,20 @@ pub fn deliver_batches(
                     }
                 }
 
+                // The block makers end up in the replicated state, so the batch
+                // must not be delivered without them.
+                let blockmaker_metrics = match get_blockmaker_metrics(membership, pool, &block) {
+                    Ok(blockmaker_metrics) => blockmaker_metrics,
+                    Err(err) => {
+                        warn!(
+                            log,
+                            "Skipping batch delivery because the block makers are unknown: {:?}",
+                            err
+                        );
+                        return Ok(last_delivered_batch_height);
+                    }
+                };
+
                 let block_hash = get_block_hash_string(&block);
                 let block_height = block.height().get();
 
@@ -137,6 +153,7 @@ pub fn deliver_batches(
                     registry_version: block.context.registry_version,
                     time: block.context.time,
                     consensus_responses,
+                    blockmaker_metrics: Some(blockmaker_metrics),
                 };
                 let batch_height = batch.batch_number.get();
                 let ingress_count = batch.payload.ingress.message_count();
@@ -187,6 +204,35 @@ pub fn deliver_batches(
     Ok(last_delivered_batch_height)
 }
 
+/// Return the maker of the given finalized block, and the block makers of a
+/// lower rank, who failed to get their block finalized.
+fn get_blockmaker_metrics(
+    membership: &Membership,
+    pool: &PoolReader<'_>,
+    block: &Block,
+) -> Result<BlockmakerMetrics, String> {
+    let height = block.height();
+    let previous_beacon = pool
+        .get_random_beacon(height.decrement())
+        .ok_or_else(|| format!("No random beacon found at height {}", height.decrement()))?;
+    let mut block_makers = membership
+        .get_block_makers(height, &previous_beacon)
+        .map_err(|err| format!("{:?}", err))?;
+    let rank = block.rank.0 as usize;
+    if rank >= block_makers.len() {
+        return Err(format!(
+            "No block maker of rank {} at height {}",
+            block.rank.0, height
+        ));
+    }
+    let blockmaker = block_makers[rank];
+    block_makers.truncate(rank);
+    Ok(BlockmakerMetrics {
+        blockmaker,
+        failed_blockmakers: block_makers,
+    })
+}
+
 /// This function creates responses to the system calls that are redirected to
 /// consensus.
 pub fn generate_responses_to_subnet_calls(
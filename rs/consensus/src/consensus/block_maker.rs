@@ -16,7 +16,7 @@ use ic_registry_client::helper::subnet::SubnetRegistry;
 use ic_replicated_state::ReplicatedState;
 use ic_types::{consensus::dkg, replica_config::ReplicaConfig, time::current_time, ReplicaVersion};
 use std::{
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
     time::Duration,
 };
 
@@ -27,6 +27,12 @@ use std::{
 // the block validation path can skip the expensive crypto validation.
 const VALIDATED_DEALING_AGE_THRESHOLD_MSECS: u64 = 10;
 
+// While holding back an empty block (adaptive block rate), wait for
+// EMPTY_PAYLOAD_RECHECK_INTERVAL after building an empty payload before
+// building the next one, so that we don't query the ingress and XNet pools on
+// every invocation.
+const EMPTY_PAYLOAD_RECHECK_INTERVAL: Duration = Duration::from_millis(100);
+
 /// A consensus subcomponent that is responsible for creating block proposals.
 pub struct BlockMaker {
     time_source: Arc<dyn TimeSource>,
@@ -43,6 +49,9 @@ pub struct BlockMaker {
     // block. The older is the version, the higher is the probability, that it's universally
     // available across the subnet.
     stable_registry_version_age: Duration,
+    // The height and time at which we last built an empty payload while
+    // holding back an empty block.
+    last_empty_payload: Mutex<Option<(Height, Time)>>,
 }
 
 impl BlockMaker {
@@ -73,6 +82,7 @@ impl BlockMaker {
             log,
             metrics: BlockMakerMetrics::new(metrics_registry),
             stable_registry_version_age,
+            last_empty_payload: Mutex::new(None),
         }
     }
 
@@ -103,7 +113,12 @@ impl BlockMaker {
                         self.time_source.as_ref(),
                     )
                 {
-                    self.propose_block(pool, ingress_pool, rank, parent)
+                    let hold_back_empty_block =
+                        self.is_within_empty_block_delay(pool, height, rank);
+                    if hold_back_empty_block && self.built_empty_payload_recently(height) {
+                        return None;
+                    }
+                    self.propose_block(pool, ingress_pool, rank, parent, hold_back_empty_block)
                         .map(|proposal| {
                             debug!(
                                 self.log,
@@ -147,13 +162,64 @@ impl BlockMaker {
         false
     }
 
-    /// Construct a block proposal
+    /// Return true if the subnet uses an adaptive block rate and the time since
+    /// round start is still below the block maker delay for the given rank plus
+    /// the maximum empty block delay. Until then, a block without ingress and
+    /// XNet messages is held back in the hope that some work shows up.
+    fn is_within_empty_block_delay(
+        &self,
+        pool: &PoolReader<'_>,
+        height: Height,
+        rank: Rank,
+    ) -> bool {
+        let settings = match pool.registry_version(height).and_then(|registry_version| {
+            get_notarization_delay_settings(
+                &self.log,
+                self.registry_client.as_ref(),
+                self.replica_config.subnet_id,
+                registry_version,
+            )
+        }) {
+            Some(settings) => settings,
+            None => return false,
+        };
+        if settings.max_empty_block_delay == Duration::from_millis(0) {
+            return false;
+        }
+        match pool.get_round_start_time(height) {
+            Some(start_time) => {
+                self.time_source.get_relative_time()
+                    < start_time
+                        + settings.unit_delay * rank.0 as u32
+                        + settings.max_empty_block_delay
+            }
+            None => false,
+        }
+    }
+
+    /// Return true if we built an empty payload for the given height less than
+    /// EMPTY_PAYLOAD_RECHECK_INTERVAL ago.
+    fn built_empty_payload_recently(&self, height: Height) -> bool {
+        match *self.last_empty_payload.lock().unwrap() {
+            Some((last_height, last_time)) => {
+                last_height == height
+                    && self.time_source.get_relative_time()
+                        < last_time + EMPTY_PAYLOAD_RECHECK_INTERVAL
+            }
+            None => false,
+        }
+    }
+
+    /// Construct a block proposal. If `hold_back_empty_block` is set, no
+    /// proposal is made when the block would contain neither ingress and XNet
+    /// messages nor DKG dealings.
     fn propose_block(
         &self,
         pool: &PoolReader<'_>,
         ingress_pool: &dyn IngressPoolSelect,
         rank: Rank,
         parent: Block,
+        hold_back_empty_block: bool,
     ) -> Option<BlockProposal> {
         let parent_hash = ic_crypto::crypto_hash(&parent);
         let height = parent.height.increment();
@@ -219,12 +285,15 @@ impl BlockMaker {
             rank,
             replica_version,
             registry_version,
+            hold_back_empty_block,
         )
     }
 
     /// Construct a block proposal with specified validation context, parent
     /// block, rank, and batch payload. This function completes the block by
     /// adding a DKG payload and signs the block to obtain a block proposal.
+    /// If `hold_back_empty_block` is set and the block would be empty, no
+    /// proposal is made.
     #[allow(clippy::too_many_arguments)]
     fn construct_block_proposal(
         &self,
@@ -238,6 +307,7 @@ impl BlockMaker {
         rank: Rank,
         replica_version: ReplicaVersion,
         registry_version: RegistryVersion,
+        hold_back_empty_block: bool,
     ) -> Option<BlockProposal> {
         let max_dealings_per_block = dkg_dealings_per_block(
             &*self.registry_client,
@@ -284,24 +354,31 @@ impl BlockMaker {
                         None => return None,
                         Some(payload) => payload,
                     };
-                    if replica_version != ReplicaVersion::default() {
+                    let dealings = if replica_version != ReplicaVersion::default() {
                         // Use empty DKG dealings if the (agreed) replica_version is not supported.
-                        let new_dealings = dkg::Dealings::new_empty(dealings.start_height);
-                        self.metrics.report_byte_estimate_metrics(
-                            batch_payload.xnet.count_bytes(),
-                            batch_payload.ingress.count_bytes(),
-                        );
-                        (batch_payload, new_dealings).into()
+                        dkg::Dealings::new_empty(dealings.start_height)
                     } else {
-                        self.metrics.report_byte_estimate_metrics(
-                            batch_payload.xnet.count_bytes(),
-                            batch_payload.ingress.count_bytes(),
-                        );
-                        (batch_payload, dealings).into()
+                        dealings
+                    };
+                    if hold_back_empty_block
+                        && batch_payload.is_empty()
+                        && dealings.messages.is_empty()
+                    {
+                        *self.last_empty_payload.lock().unwrap() =
+                            Some((height, self.time_source.get_relative_time()));
+                        self.metrics.empty_blocks_held_back.inc();
+                        return None;
                     }
+                    self.metrics.report_byte_estimate_metrics(
+                        batch_payload.xnet.count_bytes(),
+                        batch_payload.ingress.count_bytes(),
+                    );
+                    (batch_payload, dealings).into()
                 }
             },
         );
+        self.metrics
+            .report_block_time(payload.as_ref(), context.time - parent.context.time);
         let block = Block::new(parent_hash, payload, height, rank, context);
         let hashed_block = hashed::Hashed::new(ic_crypto::crypto_hash, block);
         match self
@@ -458,7 +535,7 @@ impl BlockMaker {
                 // If maliciously_propose_empty_blocks is set, propose only empty blocks.
                 let maybe_proposal = match maliciously_propose_empty_blocks {
                     true => self.maliciously_propose_empty_block(pool, ingress_pool, rank, parent),
-                    false => self.propose_block(pool, ingress_pool, rank, parent),
+                    false => self.propose_block(pool, ingress_pool, rank, parent, false),
                 };
 
                 if let Some(proposal) = maybe_proposal {
@@ -550,6 +627,7 @@ impl BlockMaker {
             rank,
             replica_version,
             registry_version,
+            false,
         )
     }
 }
@@ -602,12 +680,14 @@ mod tests {
     use ic_metrics::MetricsRegistry;
     use ic_test_artifact_pool::ingress_pool::TestIngressPool;
     use ic_test_utilities::{
+        metrics::{fetch_histogram_vec_count, fetch_int_counter, metric_vec},
         registry::{add_subnet_record, SubnetRecordBuilder},
         types::ids::{node_test_id, subnet_test_id},
+        types::messages::SignedIngressBuilder,
     };
     use ic_types::*;
     use ic_types::{batch::*, consensus::dkg};
-    use std::sync::{Arc, RwLock};
+    use std::sync::{Arc, Mutex, RwLock};
 
     #[test]
     fn test_block_maker() {
@@ -1039,4 +1119,109 @@ mod tests {
             );
         })
     }
+
+    #[test]
+    fn test_adaptive_block_rate() {
+        ic_test_utilities::artifact_pool_config::with_test_pool_config(|pool_config| {
+            let node_ids = [node_test_id(0)];
+            let max_empty_block_delay = Duration::from_millis(3000);
+            let record = SubnetRecordBuilder::from(&node_ids)
+                .with_dkg_interval_length(30)
+                .with_adaptive_block_rate(max_empty_block_delay.as_millis() as u64, 0)
+                .build();
+            let Dependencies {
+                registry,
+                membership,
+                crypto,
+                mut pool,
+                time_source,
+                replica_config,
+                state_manager,
+                ..
+            } = dependencies_with_subnet_params(
+                pool_config.clone(),
+                subnet_test_id(0),
+                vec![(1, record)],
+            );
+            state_manager
+                .get_mut()
+                .expect_latest_certified_height()
+                .return_const(Height::from(0));
+
+            // The payload builder returns whatever the test puts in here.
+            let batch_payload = Arc::new(Mutex::new(BatchPayload::default()));
+            let returned_payload = Arc::clone(&batch_payload);
+            let mut payload_builder = MockPayloadBuilder::new();
+            payload_builder
+                .expect_get_payload()
+                .returning(move |_, _, _| returned_payload.lock().unwrap().clone());
+
+            let metrics_registry = MetricsRegistry::new();
+            let block_maker = BlockMaker::new(
+                Arc::clone(&time_source) as Arc<_>,
+                replica_config,
+                Arc::clone(&registry) as Arc<dyn RegistryClient>,
+                membership,
+                crypto,
+                Arc::new(payload_builder),
+                Arc::new(RwLock::new(ic_artifact_pool::dkg_pool::DkgPoolImpl::new(
+                    MetricsRegistry::new(),
+                ))),
+                state_manager,
+                Duration::from_millis(0),
+                metrics_registry.clone(),
+                no_op_logger(),
+            );
+            let ingress_pool = TestIngressPool::new(pool_config);
+            let run_block_maker = |pool: &dyn ConsensusPool| {
+                block_maker.on_state_change(&PoolReader::new(pool), &ingress_pool)
+            };
+            let empty_blocks_held_back = || {
+                fetch_int_counter(
+                    &metrics_registry,
+                    "consensus_block_maker_empty_blocks_held_back",
+                )
+                .unwrap()
+            };
+
+            pool.advance_round_normal_operation();
+
+            // 1. Without any work, the rank-0 block maker holds back its block...
+            assert!(run_block_maker(&pool).is_none());
+            assert_eq!(empty_blocks_held_back(), 1);
+
+            // ...and does not build another payload right away.
+            assert!(run_block_maker(&pool).is_none());
+            assert_eq!(empty_blocks_held_back(), 1);
+
+            // 2. Once the maximum empty block delay has passed, it proposes an empty
+            // block.
+            time_source
+                .set_time(time_source.get_relative_time() + max_empty_block_delay)
+                .unwrap();
+            let proposal = run_block_maker(&pool).expect("Expected an empty block proposal");
+            assert!(proposal.as_ref().payload.as_ref().is_empty());
+
+            // 3. In the next round, work is waiting, so a block is proposed right away.
+            pool.advance_round_normal_operation();
+            *batch_payload.lock().unwrap() = BatchPayload {
+                ingress: vec![SignedIngressBuilder::new().build()].into(),
+                ..BatchPayload::default()
+            };
+            let proposal = run_block_maker(&pool).expect("Expected a block proposal");
+            assert!(!proposal.as_ref().payload.as_ref().is_empty());
+            assert_eq!(empty_blocks_held_back(), 1);
+
+            assert_eq!(
+                fetch_histogram_vec_count(
+                    &metrics_registry,
+                    "consensus_block_maker_block_time_seconds"
+                ),
+                metric_vec(&[
+                    (&[("payload_type", "empty")], 1),
+                    (&[("payload_type", "non_empty")], 1),
+                ])
+            );
+        })
+    }
 }
@@ -93,6 +93,7 @@ impl Finalizer {
         // Try to deliver finalized batches to messaging
         let _ = deliver_batches(
             &*self.message_routing,
+            &self.membership,
             pool,
             &*self.state_manager,
             &*self.registry_client,
@@ -399,6 +400,7 @@ mod tests {
             let ingress_selector = Arc::new(FakeIngressSelector::new());
             let state_manager = Arc::new(FakeStateManager::new());
 
+            let node_id = replica_config.node_id;
             let finalizer = Finalizer::new(
                 replica_config,
                 registry,
@@ -422,6 +424,14 @@ mod tests {
             let b = message_routing.batches.read().unwrap().clone();
             *message_routing.batches.write().unwrap() = Vec::new();
             assert!(!b.is_empty());
+            // The only node of the subnet made the block at rank 0.
+            assert_eq!(
+                b[0].blockmaker_metrics,
+                Some(BlockmakerMetrics {
+                    blockmaker: node_id,
+                    failed_blockmakers: vec![],
+                })
+            );
             // First block, nothing to remove.
             assert!(shares.is_empty());
 
@@ -101,6 +101,22 @@ impl Membership {
         Membership::get_block_maker_rank_from_shuffled_nodes(&node_id, &shuffled_nodes)
     }
 
+    /// Return the block makers at the given height, ordered by their rank,
+    /// using the given previous beacon.
+    pub fn get_block_makers(
+        &self,
+        height: Height,
+        previous_beacon: &RandomBeacon,
+    ) -> Result<Vec<NodeId>, MembershipError> {
+        let mut shuffled_nodes = self.get_shuffled_nodes(
+            height,
+            previous_beacon,
+            &RandomnessPurpose::BlockmakerRanking,
+        )?;
+        shuffled_nodes.truncate(get_faults_tolerated(shuffled_nodes.len()) + 1);
+        Ok(shuffled_nodes)
+    }
+
     fn get_block_maker_rank_from_shuffled_nodes(
         node_id: &NodeId,
         shuffled_nodes: &[NodeId],
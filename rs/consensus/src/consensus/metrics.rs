@@ -4,9 +4,11 @@ use ic_metrics::{
     buckets::{decimal_buckets, decimal_buckets_with_zero, linear_buckets},
     MetricsRegistry,
 };
-use ic_types::consensus::{Block, BlockProposal, HasHeight, HasRank};
-use prometheus::{GaugeVec, Histogram, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec};
-use std::sync::RwLock;
+use ic_types::consensus::{Block, BlockPayload, BlockProposal, HasHeight, HasRank};
+use prometheus::{
+    GaugeVec, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+};
+use std::{sync::RwLock, time::Duration};
 
 // For certain metrics, we record metrics based on block's rank.
 // Since we can only record limited number of them, the follow is
@@ -16,6 +18,8 @@ const RANKS_TO_RECORD: [&str; 6] = ["0", "1", "2", "3", "4", "5"];
 pub struct BlockMakerMetrics {
     pub get_payload_calls: IntCounterVec,
     pub block_size_bytes_estimate: IntGaugeVec,
+    pub block_time_seconds: HistogramVec,
+    pub empty_blocks_held_back: IntCounter,
 }
 
 impl BlockMakerMetrics {
@@ -29,7 +33,18 @@ impl BlockMakerMetrics {
             block_size_bytes_estimate: metrics_registry.int_gauge_vec(
                 "consensus_block_size_bytes_estimate", 
                 "An estimate about the block size produced by the block maker.",
-                &["payload_type"])
+                &["payload_type"]),
+            block_time_seconds: metrics_registry.histogram_vec(
+                "consensus_block_maker_block_time_seconds",
+                "The time between the validation contexts of a proposed block and its parent, in seconds",
+                // 10ms, 20ms, 50ms, 100ms, 200ms, 500ms, 1s, 2s, 5s, 10s, 20s, 50s
+                decimal_buckets(-2, 1),
+                &["payload_type"],
+            ),
+            empty_blocks_held_back: metrics_registry.int_counter(
+                "consensus_block_maker_empty_blocks_held_back",
+                "The number of times the block maker held back an empty block to wait for work (adaptive block rate)",
+            ),
         }
     }
 
@@ -44,6 +59,20 @@ impl BlockMakerMetrics {
             .get_metric_with_label_values(&["ingress"])
             .map(|gauge| gauge.set(ingress_bytes as i64));
     }
+
+    /// Reports the block time of a proposed block, by type of payload.
+    pub fn report_block_time(&self, payload: &BlockPayload, block_time: Duration) {
+        let payload_type = if payload.is_summary() {
+            "summary"
+        } else if payload.is_empty() {
+            "empty"
+        } else {
+            "non_empty"
+        };
+        self.block_time_seconds
+            .with_label_values(&[payload_type])
+            .observe(block_time.as_secs_f64());
+    }
 }
 
 pub struct ConsensusMetrics {
@@ -226,6 +255,7 @@ pub struct ValidatorMetrics {
     pub duplicate_artifact: IntCounterVec,
     pub validation_duration: HistogramVec,
     pub dkg_validator: IntCounterVec,
+    pub equivocations: IntCounterVec,
     // Used to sum the values within a single validator run
     dkg_time_per_validator_run: RwLock<f64>,
 }
@@ -260,6 +290,11 @@ impl ValidatorMetrics {
                 "DKG validator counter",
                 &["type"],
             ),
+            equivocations: metrics_registry.int_counter_vec(
+                "consensus_validator_equivocations",
+                "The number of equivocations detected by the validator, by artifact type",
+                &["type"],
+            ),
             dkg_time_per_validator_run: RwLock::new(0.0),
         }
     }
@@ -28,7 +28,7 @@ use crate::consensus::{
     metrics::NotaryMetrics,
     pool_reader::PoolReader,
     prelude::*,
-    utils::{find_lowest_ranked_proposals, get_adjusted_notary_delay},
+    utils::{find_lowest_ranked_proposals, get_adjusted_notary_delay_for_block},
     ConsensusCrypto,
 };
 use ic_interfaces::state_manager::StateManager;
@@ -83,7 +83,7 @@ impl Notary {
             }
             let height = notarized_height.increment();
             for proposal in find_lowest_ranked_proposals(pool, height) {
-                if let Some(elapsed) = self.time_to_notarize(pool, height, proposal.rank()) {
+                if let Some(elapsed) = self.time_to_notarize(pool, proposal.as_ref()) {
                     if !self.is_proposal_already_notarized_by_me(pool, &proposal) {
                         let block = proposal.as_ref();
                         if let Some(s) = self.notarize_block(pool, block) {
@@ -98,22 +98,20 @@ impl Notary {
     }
 
     /// Return the time since round start, if it is greater than required
-    /// notarization delay for the given block rank, or None otherwise.
+    /// notarization delay for the given block, or None otherwise.
     fn time_to_notarize(
         &self,
         pool: &PoolReader<'_>,
-        height: Height,
-        rank: Rank,
+        block: &Block,
     ) -> Option<std::time::Duration> {
-        let adjusted_notary_delay = get_adjusted_notary_delay(
+        let adjusted_notary_delay = get_adjusted_notary_delay_for_block(
             self.membership.as_ref(),
             pool,
             self.state_manager.as_ref(),
             &self.log,
-            height,
-            rank,
+            block,
         )?;
-        if let Some(start_time) = pool.get_round_start_time(height) {
+        if let Some(start_time) = pool.get_round_start_time(block.height) {
             let now = self.time_source.get_relative_time();
             if now >= start_time + adjusted_notary_delay {
                 return Some(now - start_time);
@@ -229,7 +227,10 @@ impl Notary {
 mod tests {
     //! Notary unit tests
     use super::*;
-    use crate::consensus::mocks::{dependencies_with_subnet_params, Dependencies};
+    use crate::consensus::{
+        mocks::{dependencies_with_subnet_params, Dependencies},
+        utils::get_adjusted_notary_delay,
+    };
     use ic_interfaces::consensus_pool::ConsensusPool;
     use ic_logger::replica_logger::no_op_logger;
     use ic_metrics::MetricsRegistry;
@@ -237,6 +238,7 @@ mod tests {
         consensus::fake::*,
         registry::SubnetRecordBuilder,
         types::ids::{node_test_id, subnet_test_id},
+        types::messages::SignedIngressBuilder,
     };
     use std::sync::Arc;
     use std::time::Duration;
@@ -442,4 +444,102 @@ mod tests {
             });
         })
     }
+
+    /// Check that rank-0 blocks with ingress messages are notarized after the
+    /// minimum block delay of the adaptive block rate, and empty blocks after
+    /// the regular notary delay
+    #[test]
+    fn test_notary_delay_with_adaptive_block_rate() {
+        ic_test_utilities::artifact_pool_config::with_test_pool_config(|pool_config| {
+            let committee = vec![node_test_id(0)];
+            let min_block_delay = Duration::from_millis(200);
+            let Dependencies {
+                mut pool,
+                membership,
+                replica_config,
+                time_source,
+                crypto,
+                state_manager,
+                ..
+            } = dependencies_with_subnet_params(
+                pool_config,
+                subnet_test_id(0),
+                vec![(
+                    1,
+                    SubnetRecordBuilder::from(&committee)
+                        .with_dkg_interval_length(30)
+                        .with_adaptive_block_rate(0, min_block_delay.as_millis() as u64)
+                        .build(),
+                )],
+            );
+            state_manager
+                .get_mut()
+                .expect_latest_certified_height()
+                .return_const(Height::new(0));
+
+            pool.advance_round_normal_operation();
+
+            let notary = Notary::new(
+                Arc::clone(&time_source) as Arc<_>,
+                replica_config,
+                membership.clone(),
+                crypto,
+                state_manager.clone(),
+                MetricsRegistry::new(),
+                no_op_logger(),
+            );
+            let run_notary = |pool: &dyn ConsensusPool| {
+                let reader = PoolReader::new(pool);
+                notary.on_state_change(&reader)
+            };
+
+            // 1. A rank-0 block with ingress messages is notarized once the minimum
+            // block delay has passed.
+            let mut block = pool.make_next_block();
+            let dealings = block.as_ref().payload.as_ref().as_data().dealings.clone();
+            let batch = BatchPayload {
+                ingress: vec![SignedIngressBuilder::new().build()].into(),
+                ..BatchPayload::default()
+            };
+            block.content.as_mut().payload =
+                Payload::new(ic_crypto::crypto_hash, (batch, dealings).into());
+            block.update_content();
+            pool.insert_validated(block.clone());
+            assert!(run_notary(&pool).is_empty());
+
+            time_source
+                .set_time(time_source.get_relative_time() + min_block_delay)
+                .unwrap();
+            assert_eq!(run_notary(&pool).len(), 1);
+
+            // 2. An empty rank-0 block still waits for the regular notary delay.
+            pool.notarize(&block);
+            pool.insert_beacon_chain(&pool.make_next_beacon(), Height::from(3));
+            let empty_block = pool.make_next_block();
+            assert!(empty_block.as_ref().payload.as_ref().is_empty());
+            pool.insert_validated(empty_block);
+
+            let round_start_time = time_source.get_relative_time();
+            time_source
+                .set_time(round_start_time + min_block_delay)
+                .unwrap();
+            assert!(run_notary(&pool).is_empty());
+
+            time_source
+                .set_time(
+                    round_start_time
+                        + get_adjusted_notary_delay(
+                            membership.as_ref(),
+                            &PoolReader::new(&pool),
+                            state_manager.as_ref(),
+                            &no_op_logger(),
+                            Height::from(3),
+                            Rank(0),
+                        )
+                        .unwrap(),
+                )
+                .unwrap();
+            assert_eq!(run_notary(&pool).len(), 1);
+        })
+    }
 }
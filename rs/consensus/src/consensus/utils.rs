@@ -158,6 +158,46 @@ pub fn get_adjusted_notary_delay(
     Some(Duration::from_millis(adjusted_delay))
 }
 
+/// Calculate the required delay for notary to notarize the given block: the
+/// adjusted notary delay of the block's rank, except that for rank-0 blocks
+/// carrying ingress or XNet messages, the initial notary delay is replaced by
+/// the minimum block delay of the adaptive block rate, if that is configured
+/// and shorter.
+pub fn get_adjusted_notary_delay_for_block(
+    membership: &Membership,
+    pool: &PoolReader<'_>,
+    state_manager: &dyn StateManager<State = ReplicatedState>,
+    log: &ReplicaLogger,
+    block: &Block,
+) -> Option<Duration> {
+    let delay = get_adjusted_notary_delay(
+        membership,
+        pool,
+        state_manager,
+        log,
+        block.height,
+        block.rank,
+    )?;
+    let payload = block.payload.as_ref();
+    if block.rank != Rank(0) || payload.is_summary() || payload.as_data().batch.is_empty() {
+        return Some(delay);
+    }
+    let NotarizationDelaySettings {
+        initial_notary_delay,
+        min_block_delay,
+        ..
+    } = get_notarization_delay_settings(
+        log,
+        &*membership.registry_client,
+        membership.subnet_id,
+        pool.registry_version(block.height)?,
+    )?;
+    if min_block_delay == Duration::from_millis(0) || min_block_delay >= initial_notary_delay {
+        return Some(delay);
+    }
+    Some(delay.checked_sub(initial_notary_delay).unwrap_or_default() + min_block_delay)
+}
+
 /// Return the validated block proposals with the lowest rank at height `h`, if
 /// there are any. Else return `None`.
 pub fn find_lowest_ranked_proposals(pool: &PoolReader<'_>, h: Height) -> Vec<BlockProposal> {
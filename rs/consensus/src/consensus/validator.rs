@@ -29,6 +29,7 @@ use ic_interfaces::{
 use ic_logger::{trace, warn, ReplicaLogger};
 use ic_replicated_state::ReplicatedState;
 use ic_types::{
+    consensus::equivocation::EquivocationEvidence,
     crypto::{threshold_sig::ni_dkg::NiDkgId, CryptoError},
     registry::RegistryClientError,
     replica_config::ReplicaConfig,
@@ -873,6 +874,70 @@ impl Validator {
             }
         }
         self.metrics.observe_and_reset_dkg_time_per_validator_run();
+        self.add_equivocation_evidence(pool_reader, change_set)
+    }
+
+    /// Append the evidence of equivocation to the given `ChangeSet`, for every
+    /// block proposal it moves to the validated pool that conflicts with one
+    /// that is validated already, or that is moved earlier in the same
+    /// `ChangeSet`. Only the first piece of evidence per signer, height and
+    /// artifact type is added, and evidence the pool holds already is skipped.
+    fn add_equivocation_evidence(
+        &self,
+        pool_reader: &PoolReader<'_>,
+        mut change_set: ChangeSet,
+    ) -> ChangeSet {
+        let pool = pool_reader.pool();
+        let mut found: Vec<EquivocationEvidence> = Vec::new();
+        for (i, action) in change_set.iter().enumerate() {
+            let earlier = change_set[..i].iter().filter_map(|action| match action {
+                ChangeAction::MoveToValidated(msg) => Some(msg),
+                _ => None,
+            });
+            let evidence = match action {
+                ChangeAction::MoveToValidated(ConsensusMessage::BlockProposal(proposal)) => pool
+                    .validated()
+                    .block_proposal()
+                    .get_by_height(proposal.height())
+                    .chain(
+                        earlier
+                            .filter_map(|msg| BlockProposal::assert(msg).cloned())
+                            .collect::<Vec<_>>(),
+                    )
+                    .find_map(|other| {
+                        EquivocationEvidence::from_block_proposals(proposal.clone(), other)
+                    }),
+                _ => None,
+            };
+            if let Some(evidence) = evidence {
+                let is_known = |other: &EquivocationEvidence| {
+                    other.signer() == evidence.signer()
+                        && other.height() == evidence.height()
+                        && other.artifact_type() == evidence.artifact_type()
+                };
+                let already_in_pool = pool
+                    .equivocation_evidence()
+                    .get_by_height(evidence.height())
+                    .any(|other| is_known(&other));
+                if !already_in_pool && !found.iter().any(is_known) {
+                    found.push(evidence);
+                }
+            }
+        }
+        for evidence in found {
+            self.metrics
+                .equivocations
+                .with_label_values(&[evidence.artifact_type()])
+                .inc();
+            warn!(
+                self.log,
+                "Node {} equivocated on {} at height {}",
+                evidence.signer(),
+                evidence.artifact_type(),
+                evidence.height()
+            );
+            change_set.push(ChangeAction::AddEquivocationEvidence(evidence));
+        }
         change_set
     }
 
@@ -2377,6 +2442,162 @@ pub mod test {
         })
     }
 
+    #[test]
+    fn test_block_proposal_equivocation() {
+        ic_test_utilities::artifact_pool_config::with_test_pool_config(|pool_config| {
+            let subnet_members = (0..4).map(node_test_id).collect::<Vec<_>>();
+            let (
+                mut payload_builder,
+                membership,
+                state_manager,
+                message_routing,
+                crypto,
+                _data_provider,
+                registry_client,
+                mut pool,
+                dkg_pool,
+                time_source,
+                replica_config,
+            ) = setup_dependencies(pool_config, &subnet_members);
+
+            Arc::get_mut(&mut payload_builder)
+                .unwrap()
+                .expect_validate_payload()
+                .returning(|_, _, _| Ok(()));
+            state_manager
+                .get_mut()
+                .expect_latest_certified_height()
+                .return_const(Height::from(0));
+
+            let prior_height = Height::from(5);
+            pool.insert_beacon_chain(&pool.make_next_beacon(), prior_height);
+            let block_chain = pool.insert_block_chain(prior_height);
+            pool.finalize(&block_chain[3]);
+            pool.notarize(&block_chain[4]);
+
+            let validator = Validator::new(
+                replica_config,
+                membership.clone(),
+                registry_client,
+                crypto,
+                payload_builder,
+                state_manager,
+                message_routing,
+                dkg_pool,
+                no_op_logger(),
+                ValidatorMetrics::new(MetricsRegistry::new()),
+                Arc::clone(&time_source) as Arc<_>,
+            );
+
+            // The block maker of rank 0 proposes three different blocks, which
+            // only differ in their time.
+            let block_time = 10000;
+            let proposals = (0..3)
+                .map(|i| {
+                    let mut proposal = make_next_block(&pool, membership.as_ref(), &subnet_members);
+                    proposal.content.as_mut().context.time =
+                        Time::from_nanos_since_unix_epoch(block_time + i);
+                    proposal.update_content();
+                    proposal
+                })
+                .collect::<Vec<_>>();
+            time_source
+                .set_time(Time::from_nanos_since_unix_epoch(block_time + 2))
+                .unwrap();
+
+            // Both blocks are validated, and the conflict between them is
+            // recorded as evidence.
+            pool.insert_unvalidated(proposals[0].clone());
+            pool.insert_unvalidated(proposals[1].clone());
+            let results = validator.validate_blocks(&PoolReader::new(&pool));
+            assert_eq!(results.len(), 3);
+            let expected = EquivocationEvidence::from_block_proposals(
+                proposals[0].clone(),
+                proposals[1].clone(),
+            )
+            .unwrap();
+            assert_eq!(
+                results[2],
+                ChangeAction::AddEquivocationEvidence(expected.clone())
+            );
+            pool.apply_changes(time_source.as_ref(), results);
+            assert_eq!(
+                pool.equivocation_evidence().get_all().collect::<Vec<_>>(),
+                vec![expected]
+            );
+
+            // A third block conflicts with the validated ones, but the pool
+            // already holds evidence against its block maker at this height.
+            pool.insert_unvalidated(proposals[2].clone());
+            let results = validator.validate_blocks(&PoolReader::new(&pool));
+            assert_block_valid(&results, &proposals[2]);
+            assert_eq!(results.len(), 1);
+        })
+    }
+
+    #[test]
+    fn test_notarization_shares_of_equivocating_block_maker() {
+        ic_test_utilities::artifact_pool_config::with_test_pool_config(|pool_config| {
+            let (
+                payload_builder,
+                membership,
+                state_manager,
+                message_routing,
+                crypto,
+                _data_provider,
+                registry_client,
+                mut pool,
+                dkg_pool,
+                time_source,
+                replica_config,
+            ) = setup_dependencies(pool_config, &(0..4).map(node_test_id).collect::<Vec<_>>());
+
+            // The block maker of rank 0 proposes two different blocks.
+            let block = pool.make_next_block();
+            let mut other_block = block.clone();
+            other_block.content.as_mut().context.time += Duration::from_nanos(1);
+            other_block.update_content();
+            pool.insert_validated(block.clone());
+            pool.insert_validated(other_block.clone());
+
+            let validator = Validator::new(
+                replica_config,
+                membership,
+                registry_client,
+                crypto,
+                payload_builder,
+                state_manager,
+                message_routing,
+                dkg_pool,
+                no_op_logger(),
+                ValidatorMetrics::new(MetricsRegistry::new()),
+                Arc::clone(&time_source) as Arc<_>,
+            );
+
+            // Honest notaries sign both blocks, as both are of the lowest rank,
+            // and none of them is reported.
+            let shares = (1..4)
+                .flat_map(|i| {
+                    vec![
+                        NotarizationShare::fake(block.as_ref(), node_test_id(i)),
+                        NotarizationShare::fake(other_block.as_ref(), node_test_id(i)),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            for share in &shares {
+                pool.insert_unvalidated(share.clone());
+            }
+            let results = validator.on_state_change(&PoolReader::new(&pool));
+            assert_eq!(results.len(), shares.len());
+            assert!(results.iter().all(|action| matches!(
+                action,
+                ChangeAction::MoveToValidated(ConsensusMessage::NotarizationShare(_))
+            )));
+            pool.apply_changes(time_source.as_ref(), results);
+            assert_eq!(pool.equivocation_evidence().get_all().count(), 0);
+        })
+    }
+
     #[test]
     fn test_notarization_requires_at_least_threshold_signatures() {
         ic_test_utilities::artifact_pool_config::with_test_pool_config(|pool_config| {
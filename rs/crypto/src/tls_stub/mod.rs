@@ -170,6 +170,60 @@ where
         );
         result
     }
+
+    fn quic_server_config(
+        &self,
+        allowed_clients: AllowedClients,
+        registry_version: RegistryVersion,
+    ) -> Result<tokio_rustls::rustls::ServerConfig, TlsServerHandshakeError> {
+        let logger = new_logger!(&self.logger;
+            crypto.trait_name => "TlsHandshake",
+            crypto.method_name => "quic_server_config",
+            crypto.registry_version => registry_version.get(),
+            crypto.allowed_tls_clients => format!("{:?}", allowed_clients),
+        );
+        debug!(logger; crypto.description => "start",);
+        let result = rustls::server_handshake::server_config(
+            &self.csp,
+            self.node_id,
+            &self.registry_client,
+            allowed_clients,
+            registry_version,
+        );
+        debug!(logger;
+            crypto.description => "end",
+            crypto.is_ok => result.is_ok(),
+            crypto.error => log_err(result.as_ref().err()),
+        );
+        result
+    }
+
+    fn quic_client_config(
+        &self,
+        server: NodeId,
+        registry_version: RegistryVersion,
+    ) -> Result<tokio_rustls::rustls::ClientConfig, TlsClientHandshakeError> {
+        let logger = new_logger!(&self.logger;
+            crypto.trait_name => "TlsHandshake",
+            crypto.method_name => "quic_client_config",
+            crypto.registry_version => registry_version.get(),
+            crypto.tls_server => format!("{}", server),
+        );
+        debug!(logger; crypto.description => "start",);
+        let result = rustls::client_handshake::client_config(
+            &self.csp,
+            self.node_id,
+            &self.registry_client,
+            server,
+            registry_version,
+        );
+        debug!(logger;
+            crypto.description => "end",
+            crypto.is_ok => result.is_ok(),
+            crypto.error => log_err(result.as_ref().err()),
+        );
+        result
+    }
 }
 
 fn node_id_from_cert_subject_common_name(
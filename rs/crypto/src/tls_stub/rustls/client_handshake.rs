@@ -23,6 +23,26 @@ pub async fn perform_tls_client_handshake<P: CspTlsHandshakeSignerProvider>(
     server: NodeId,
     registry_version: RegistryVersion,
 ) -> Result<TlsStream, TlsClientHandshakeError> {
+    let config = client_config(
+        signer_provider,
+        self_node_id,
+        registry_client,
+        server,
+        registry_version,
+    )?;
+    connect(tcp_stream, config).await
+}
+
+/// Returns the client config that authenticates the node with its TLS
+/// certificate from the registry and requires the peer to authenticate as
+/// `server`.
+pub fn client_config<P: CspTlsHandshakeSignerProvider>(
+    signer_provider: &P,
+    self_node_id: NodeId,
+    registry_client: &Arc<dyn RegistryClient>,
+    server: NodeId,
+    registry_version: RegistryVersion,
+) -> Result<ClientConfig, TlsClientHandshakeError> {
     let self_tls_cert = tls_cert_from_registry(registry_client, self_node_id, registry_version)?;
     let mut config = ClientConfig::new();
     config.versions = vec![ProtocolVersion::TLSv1_3];
@@ -41,8 +61,7 @@ pub async fn perform_tls_client_handshake<P: CspTlsHandshakeSignerProvider>(
     config
         .dangerous()
         .set_certificate_verifier(Arc::new(server_cert_verifier));
-
-    connect(tcp_stream, config).await
+    Ok(config)
 }
 
 fn static_cert_resolver(key: CertifiedKey, scheme: SignatureScheme) -> Arc<dyn ResolvesClientCert> {
@@ -28,6 +28,33 @@ pub async fn perform_tls_server_handshake<P: CspTlsHandshakeSignerProvider>(
     allowed_clients: AllowedClients,
     registry_version: RegistryVersion,
 ) -> Result<(TlsStream, AuthenticatedPeer), TlsServerHandshakeError> {
+    let config = server_config(
+        signer_provider,
+        self_node_id,
+        registry_client,
+        allowed_clients,
+        registry_version,
+    )?;
+
+    let rustls_stream = accept_connection(tcp_stream, config).await?;
+
+    let client_cert_from_handshake = single_client_cert_from_handshake(&rustls_stream)?;
+    let authenticated_peer = node_id_from_cert_subject_common_name(&client_cert_from_handshake)?;
+    let tls_stream = TlsStream::new_rustls(tokio_rustls::TlsStream::from(rustls_stream));
+
+    Ok((tls_stream, AuthenticatedPeer::Node(authenticated_peer)))
+}
+
+/// Returns the server config that authenticates the node with its TLS
+/// certificate from the registry and requires the clients to authenticate as
+/// one of the `allowed_clients`.
+pub fn server_config<P: CspTlsHandshakeSignerProvider>(
+    signer_provider: &P,
+    self_node_id: NodeId,
+    registry_client: &Arc<dyn RegistryClient>,
+    allowed_clients: AllowedClients,
+    registry_version: RegistryVersion,
+) -> Result<ServerConfig, TlsServerHandshakeError> {
     let self_tls_cert = tls_cert_from_registry(registry_client, self_node_id, registry_version)?;
     let client_cert_verifier = NodeClientCertVerifier::new_with_mandatory_client_auth(
         allowed_clients.nodes().clone(),
@@ -44,14 +71,7 @@ pub async fn perform_tls_server_handshake<P: CspTlsHandshakeSignerProvider>(
         certified_key(self_tls_cert, ed25519_signing_key),
         SignatureScheme::ED25519,
     );
-
-    let rustls_stream = accept_connection(tcp_stream, config).await?;
-
-    let client_cert_from_handshake = single_client_cert_from_handshake(&rustls_stream)?;
-    let authenticated_peer = node_id_from_cert_subject_common_name(&client_cert_from_handshake)?;
-    let tls_stream = TlsStream::new_rustls(tokio_rustls::TlsStream::from(rustls_stream));
-
-    Ok((tls_stream, AuthenticatedPeer::Node(authenticated_peer)))
+    Ok(config)
 }
 
 async fn accept_connection(
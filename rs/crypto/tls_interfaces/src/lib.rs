@@ -601,6 +601,46 @@ pub trait TlsHandshake {
         server: NodeId,
         registry_version: RegistryVersion,
     ) -> Result<TlsStream, TlsClientHandshakeError>;
+
+    /// Returns the TLS configuration with which a QUIC endpoint accepts
+    /// connections from `allowed_clients`.
+    ///
+    /// The server presents the node's TLS certificate and authenticates the
+    /// clients exactly as `perform_tls_server_handshake_with_rustls` does. The
+    /// node's secret key never leaves the secret key store: the handshake
+    /// signatures are created by the crypto component.
+    ///
+    /// # Errors
+    /// * TlsServerHandshakeError::RegistryError if the registry cannot be
+    ///   accessed.
+    /// * TlsServerHandshakeError::CertificateNotInRegistry if the node's own
+    ///   certificate is not found in the registry.
+    /// * TlsServerHandshakeError::MalformedSelfCertificate if the node's own
+    ///   server certificate is malformed.
+    fn quic_server_config(
+        &self,
+        allowed_clients: AllowedClients,
+        registry_version: RegistryVersion,
+    ) -> Result<tokio_rustls::rustls::ServerConfig, TlsServerHandshakeError>;
+
+    /// Returns the TLS configuration with which a QUIC endpoint connects to
+    /// `server`.
+    ///
+    /// The client presents the node's TLS certificate and authenticates the
+    /// server exactly as `perform_tls_client_handshake_with_rustls` does.
+    ///
+    /// # Errors
+    /// * TlsClientHandshakeError::RegistryError if the registry cannot be
+    ///   accessed.
+    /// * TlsClientHandshakeError::CertificateNotInRegistry if the node's own
+    ///   certificate is not found in the registry.
+    /// * TlsClientHandshakeError::MalformedSelfCertificate if the node's own
+    ///   client certificate is malformed.
+    fn quic_client_config(
+        &self,
+        server: NodeId,
+        registry_version: RegistryVersion,
+    ) -> Result<tokio_rustls::rustls::ClientConfig, TlsClientHandshakeError>;
 }
 
 #[derive(Clone, Debug)]
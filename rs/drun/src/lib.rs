@@ -299,6 +299,7 @@ fn build_batch(message_routing: &dyn MessageRouting, msgs: Vec<SignedIngress>) -
         registry_version: RegistryVersion::from(1),
         time: mock_time(),
         consensus_responses: vec![],
+        blockmaker_metrics: None,
     }
 }
 /// Block till the given ingress message has finished executing and
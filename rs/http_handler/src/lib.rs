@@ -11,6 +11,7 @@ mod dashboard;
 mod metrics;
 mod pprof;
 mod query;
+mod rate_limiter;
 mod read_state;
 mod status;
 mod submit;
@@ -22,7 +23,8 @@ use crate::{
     metrics::{
         LABEL_REQUEST_TYPE, LABEL_STATUS, LABEL_TYPE, REQUESTS_LABEL_NAMES, REQUESTS_NUM_LABELS,
     },
-    read_state::ReadStateService,
+    rate_limiter::IngressRateLimiter,
+    read_state::{ReadStateEndpoint, ReadStateService},
     status::StatusService,
     types::*,
 };
@@ -35,7 +37,7 @@ use ic_crypto_tls_interfaces::TlsHandshake;
 use ic_crypto_tree_hash::Path;
 use ic_interfaces::{
     consensus_pool::ConsensusPoolCache,
-    crypto::IngressSigVerifier,
+    crypto::{BasicSigner, IngressSigVerifier},
     execution_environment::{IngressFilterService, QueryExecutionService},
     p2p::IngressIngestionService,
     registry::RegistryClient,
@@ -53,10 +55,10 @@ use ic_types::{
     malicious_flags::MaliciousFlags,
     messages::{
         Blob, CertificateDelegation, HttpReadContent, HttpReadState, HttpReadStateResponse,
-        HttpRequestEnvelope, ReplicaHealthStatus,
+        HttpRequestEnvelope, QueryResponseHash, ReplicaHealthStatus,
     },
     time::current_time_and_expiry_time,
-    SubnetId,
+    NodeId, SubnetId,
 };
 use metrics::HttpHandlerMetrics;
 use rand::Rng;
@@ -136,16 +138,20 @@ const UNKNOWN_LABEL: &str = "unknown";
 #[derive(Clone)]
 struct HttpHandler {
     log: ReplicaLogger,
+    node_id: NodeId,
     subnet_id: SubnetId,
     nns_subnet_id: SubnetId,
     registry_client: Arc<dyn RegistryClient>,
     state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
     validator: Arc<dyn IngressSigVerifier + Send + Sync>,
+    query_signer: Arc<dyn BasicSigner<QueryResponseHash> + Send + Sync>,
 
     ingress_filter: Arc<Mutex<IngressFilterService>>,
     ingress_sender: Arc<Mutex<IngressIngestionService>>,
+    ingress_rate_limiter: Arc<IngressRateLimiter>,
     query_handler: Arc<Mutex<QueryExecutionService>>,
     read_state_service: LoadShed<ConcurrencyLimit<ReadStateService>>,
+    subnet_read_state_service: LoadShed<ConcurrencyLimit<ReadStateService>>,
     status_service: LoadShed<ConcurrencyLimit<StatusService>>,
     dashboard_service: LoadShed<ConcurrencyLimit<DashboardService>>,
     catch_up_package_service: LoadShed<ConcurrencyLimit<CatchUpPackageService>>,
@@ -262,6 +268,8 @@ pub async fn start_server(
     registry_client: Arc<dyn RegistryClient>,
     tls_handshake: Arc<dyn TlsHandshake + Send + Sync>,
     ingress_verifier: Arc<dyn IngressSigVerifier + Send + Sync>,
+    query_signer: Arc<dyn BasicSigner<QueryResponseHash> + Send + Sync>,
+    node_id: NodeId,
     subnet_id: SubnetId,
     nns_subnet_id: SubnetId,
     log: ReplicaLogger,
@@ -274,19 +282,27 @@ pub async fn start_server(
 
     let listen_addr = config.listen_addr;
     let port_file_path = config.port_file_path.clone();
+    let ingress_rate_limiter = Arc::new(IngressRateLimiter::new(
+        config.ingress_rate_limit_per_sender,
+        config.ingress_rate_limit_per_canister,
+        metrics.ingress_rate_limited_total.clone(),
+    ));
 
     let http_handler = HttpHandler::new(
         config,
         registry_client,
+        node_id,
         subnet_id,
         subnet_type,
         nns_subnet_id,
         log.clone(),
         Arc::new(Mutex::new(ingress_filter)),
         Arc::new(Mutex::new(ingress_sender)),
+        ingress_rate_limiter,
         Arc::new(Mutex::new(query_handler)),
         state_reader,
         ingress_verifier,
+        query_signer,
         consensus_pool_cache,
         backup_spool_path,
         malicious_flags,
@@ -411,15 +427,18 @@ impl HttpHandler {
     fn new(
         config: Config,
         registry_client: Arc<dyn RegistryClient>,
+        node_id: NodeId,
         subnet_id: SubnetId,
         subnet_type: SubnetType,
         nns_subnet_id: SubnetId,
         log: ReplicaLogger,
         ingress_filter: Arc<Mutex<IngressFilterService>>,
         ingress_sender: Arc<Mutex<IngressIngestionService>>,
+        ingress_rate_limiter: Arc<IngressRateLimiter>,
         query_handler: Arc<Mutex<QueryExecutionService>>,
         state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
         validator: Arc<dyn IngressSigVerifier + Send + Sync>,
+        query_signer: Arc<dyn BasicSigner<QueryResponseHash> + Send + Sync>,
         consensus_pool_cache: Arc<dyn ConsensusPoolCache>,
         backup_spool_path: Option<PathBuf>,
         malicious_flags: MaliciousFlags,
@@ -438,6 +457,17 @@ impl HttpHandler {
         let catch_up_package_service = CatchUpPackageService::new(consensus_pool_cache);
         let read_state_service = ReadStateService::new(
             log.clone(),
+            ReadStateEndpoint::Canister,
+            Arc::clone(&health_status),
+            Arc::clone(&delegation_from_nns),
+            Arc::clone(&state_reader),
+            Arc::clone(&validator),
+            Arc::clone(&registry_client),
+            malicious_flags.clone(),
+        );
+        let subnet_read_state_service = ReadStateService::new(
+            log.clone(),
+            ReadStateEndpoint::Subnet,
             Arc::clone(&health_status),
             Arc::clone(&delegation_from_nns),
             Arc::clone(&state_reader),
@@ -447,15 +477,19 @@ impl HttpHandler {
         );
         Self {
             log,
+            node_id,
             subnet_id,
             nns_subnet_id,
             registry_client,
             state_reader,
             validator,
+            query_signer,
             ingress_filter,
             ingress_sender,
+            ingress_rate_limiter,
             query_handler,
             read_state_service,
+            subnet_read_state_service,
             status_service,
             dashboard_service,
             catch_up_package_service,
@@ -677,6 +711,8 @@ async fn route_to_handlers(
                 Arc::clone(&http_handler.delegation_from_nns),
                 Arc::clone(&http_handler.query_handler),
                 Arc::clone(&http_handler.validator),
+                Arc::clone(&http_handler.query_signer),
+                http_handler.node_id,
                 Arc::clone(&http_handler.registry_client),
                 parsed_body,
                 http_handler.malicious_flags.clone(),
@@ -695,6 +731,17 @@ async fn route_to_handlers(
                 .map_err(|err| map_box_error_to_canonical_error(err)),
             ApiReqType::ReadState,
         ),
+        RequestType::ReadStateSubnet => (
+            http_handler
+                .subnet_read_state_service
+                .ready()
+                .await
+                .expect("The service must always be able to process requests")
+                .call(parsed_body)
+                .await
+                .map_err(|err| map_box_error_to_canonical_error(err)),
+            ApiReqType::ReadStateSubnet,
+        ),
         RequestType::Submit => (
             submit::handle(
                 http_handler.log.clone(),
@@ -703,6 +750,7 @@ async fn route_to_handlers(
                 Arc::clone(&http_handler.validator),
                 Arc::clone(&http_handler.ingress_sender),
                 Arc::clone(&http_handler.ingress_filter),
+                Arc::clone(&http_handler.ingress_rate_limiter),
                 http_handler.malicious_flags.clone(),
                 parsed_body,
             )
@@ -991,6 +1039,7 @@ fn validate_http_request_head(parts: &http::request::Parts) -> Result<RequestTyp
                 ["", "api", "v2", "canister", _, "call"] => Ok(RequestType::Submit),
                 ["", "api", "v2", "canister", _, "query"] => Ok(RequestType::Query),
                 ["", "api", "v2", "canister", _, "read_state"] => Ok(RequestType::ReadState),
+                ["", "api", "v2", "subnet", _, "read_state"] => Ok(RequestType::ReadStateSubnet),
                 ["", "_", "catch_up_package"] => Ok(RequestType::CatchUpPackage),
                 _ => Err(invalid_argument_error("URI path is not supported.")),
             }
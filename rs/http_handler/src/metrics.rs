@@ -6,7 +6,9 @@ use ic_metrics::{
 use prometheus::{HistogramVec, IntCounter, IntCounterVec, IntGauge};
 use tokio::time::Instant;
 
+pub const LABEL_CANISTER_ID: &str = "canister_id";
 pub const LABEL_DETAIL: &str = "detail";
+pub const LABEL_LIMIT: &str = "limit";
 pub const LABEL_PROTOCOL: &str = "protocol";
 pub const LABEL_REQUEST_TYPE: &str = "request_type";
 pub const LABEL_STATUS: &str = "status";
@@ -28,6 +30,7 @@ pub(crate) struct HttpHandlerMetrics {
     pub(crate) protocol_version_total: IntCounterVec,
     pub(crate) connections: IntGauge,
     pub(crate) connections_total: IntCounter,
+    pub(crate) ingress_rate_limited_total: IntCounterVec,
     connection_setup_duration: HistogramVec,
 }
 
@@ -78,6 +81,11 @@ impl HttpHandlerMetrics {
                 "replica_http_tcp_connections_total",
                 "Total number of accepted TCP connections."
             ),
+            ingress_rate_limited_total: metrics_registry.int_counter_vec(
+                "replica_http_ingress_rate_limited_total",
+                "Count of ingress messages rejected by a rate limit, by target canister and limit (sender/canister). Only a bounded number of canisters is labelled, the others are counted as 'other'.",
+                &[LABEL_CANISTER_ID, LABEL_LIMIT],
+            ),
             connection_setup_duration: metrics_registry.histogram_vec(
                 "replica_http_connection_setup_duration_seconds",
                 "HTTP connection setup durations, by status and detail (protocol on status=\"success\", error type on status=\"error\").",
@@ -3,20 +3,23 @@
 use crate::{common, ReplicaHealthStatus};
 use hyper::{Body, Response};
 use ic_interfaces::{
-    crypto::IngressSigVerifier, execution_environment::QueryExecutionService,
+    crypto::{BasicSigner, IngressSigVerifier},
+    execution_environment::QueryExecutionService,
     registry::RegistryClient,
 };
 use ic_logger::{trace, ReplicaLogger};
 use ic_types::{
     canonical_error::{
-        invalid_argument_error, permission_denied_error, unavailable_error, CanonicalError,
+        internal_error, invalid_argument_error, permission_denied_error, unavailable_error,
+        CanonicalError,
     },
     malicious_flags::MaliciousFlags,
     messages::{
-        CertificateDelegation, HttpReadContent, HttpRequest, HttpRequestEnvelope,
-        SignedRequestBytes, UserQuery,
+        Blob, CertificateDelegation, HttpReadContent, HttpRequest, HttpRequestEnvelope,
+        HttpSignedQueryResponse, NodeSignature, QueryResponseHash, SignedRequestBytes, UserQuery,
     },
     time::current_time,
+    NodeId,
 };
 use ic_validator::get_authorized_canisters;
 use std::convert::TryFrom;
@@ -32,6 +35,8 @@ pub(crate) async fn handle(
     delegation_from_nns: Arc<RwLock<Option<CertificateDelegation>>>,
     query_handler: Arc<Mutex<QueryExecutionService>>,
     validator: Arc<dyn IngressSigVerifier + Send + Sync>,
+    query_signer: Arc<dyn BasicSigner<QueryResponseHash> + Send + Sync>,
+    node_id: NodeId,
     registry_client: Arc<dyn RegistryClient>,
     body: Vec<u8>,
     malicious_flags: MaliciousFlags,
@@ -98,5 +103,26 @@ pub(crate) async fn handle(
         .expect("The service must always be able to process requests")
         .call((query.clone(), delegation_from_nns));
     let query_result = callback.await?;
-    Ok(common::cbor_response(&query_result))
+
+    // Sign the response with the node signing key, so that clients can detect
+    // responses that were tampered with or made up.
+    let timestamp = current_time();
+    let response_hash = QueryResponseHash::new(&query_result, &request.id(), timestamp);
+    let signature = query_signer
+        .sign_basic(
+            &response_hash,
+            node_id,
+            registry_client.get_latest_version(),
+        )
+        .map_err(|err| {
+            internal_error(format!("Failed to sign the query response: {}", err).as_str())
+        })?;
+    Ok(common::cbor_response(&HttpSignedQueryResponse {
+        response: query_result,
+        signatures: vec![NodeSignature {
+            timestamp: timestamp.as_nanos_since_unix_epoch(),
+            signature: Blob(signature.get().0),
+            identity: Blob(node_id.get().into_vec()),
+        }],
+    }))
 }
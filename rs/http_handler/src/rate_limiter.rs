@@ -0,0 +1,421 @@
+//! Token bucket rate limits applied to ingress messages, per sender and per
+//! target canister.
+//!
+//! The limits are enforced before messages are handed to the ingress pool, so
+//! that a single noisy sender or dapp cannot fill up the pool and get every
+//! other user throttled along with it.
+
+use ic_config::http_handler::RateLimitConfig;
+use ic_types::{CanisterId, UserId};
+use prometheus::IntCounterVec;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The most buckets tracked per generation, see `KeyedBuckets`. Bounds the
+/// memory used by the rate limiter to twice this many buckets per limit.
+const MAX_TRACKED_BUCKETS: usize = 100_000;
+
+/// The most canisters that get their own label on the rate limited metric.
+/// Canisters are labelled in the order they are first rate limited, the
+/// messages to any further canister are counted under `OTHER_CANISTERS`. This
+/// keeps the number of time series bounded.
+const MAX_LABELLED_CANISTERS: usize = 100;
+
+/// The canister label of rate limited messages to unlabelled canisters.
+const OTHER_CANISTERS: &str = "other";
+
+pub(crate) const LIMIT_SENDER: &str = "sender";
+pub(crate) const LIMIT_CANISTER: &str = "canister";
+
+/// A token bucket, see `RateLimitConfig`.
+#[derive(Clone, Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(config: &RateLimitConfig, now: Instant) -> Self {
+        Self {
+            tokens: config.burst_size as f64,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, config: &RateLimitConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * config.max_requests_per_second as f64)
+            .min(config.burst_size as f64);
+        self.last_refill = now;
+    }
+
+    /// Returns how long to wait until a token is available. Zero if a token
+    /// is available right away.
+    fn time_to_next_token(&self, config: &RateLimitConfig) -> Duration {
+        if self.tokens >= 1.0 {
+            return Duration::from_secs(0);
+        }
+        Duration::from_secs_f64((1.0 - self.tokens) / config.max_requests_per_second as f64)
+    }
+}
+
+/// A set of token buckets sharing the same configuration, one per key.
+///
+/// Buckets are kept in two generations. Every time an empty bucket would have
+/// been refilled completely, the previous generation is dropped and the
+/// current one takes its place. Buckets that were not used for a whole
+/// generation are full again and thus equivalent to fresh ones, so they are
+/// dropped without scanning all buckets. The current generation is also
+/// retired early once it holds `MAX_TRACKED_BUCKETS` buckets, which may reset
+/// some buckets that were not full yet but keeps memory bounded.
+struct KeyedBuckets<K> {
+    config: RateLimitConfig,
+    current: HashMap<K, TokenBucket>,
+    previous: HashMap<K, TokenBucket>,
+    generation_start: Instant,
+}
+
+impl<K: Eq + Hash> KeyedBuckets<K> {
+    fn new(config: RateLimitConfig, now: Instant) -> Self {
+        Self {
+            config,
+            current: HashMap::new(),
+            previous: HashMap::new(),
+            generation_start: now,
+        }
+    }
+
+    /// How long it takes an empty bucket to fill up again.
+    fn generation_length(&self) -> Duration {
+        Duration::from_secs_f64(
+            self.config.burst_size as f64 / self.config.max_requests_per_second as f64,
+        )
+    }
+
+    /// Refills the bucket of `key` and returns how long to wait until it
+    /// holds a token.
+    fn check(&mut self, key: K, now: Instant) -> Duration {
+        if self.current.len() >= MAX_TRACKED_BUCKETS
+            || now.saturating_duration_since(self.generation_start) >= self.generation_length()
+        {
+            self.previous = std::mem::take(&mut self.current);
+            self.generation_start = now;
+        }
+        let config = &self.config;
+        let bucket = match self.previous.remove(&key) {
+            Some(bucket) => self.current.entry(key).or_insert(bucket),
+            None => self
+                .current
+                .entry(key)
+                .or_insert_with(|| TokenBucket::new(config, now)),
+        };
+        bucket.refill(config, now);
+        bucket.time_to_next_token(config)
+    }
+
+    /// Takes a token from the bucket of `key`. Must only be called after
+    /// `check()` returned zero for the same key.
+    fn take(&mut self, key: &K) {
+        if let Some(bucket) = self.current.get_mut(key) {
+            bucket.tokens -= 1.0;
+        }
+    }
+}
+
+/// The error returned when an ingress message exceeds a rate limit.
+#[derive(Debug, PartialEq)]
+pub(crate) struct RateLimited {
+    /// The limit that was exceeded, `LIMIT_SENDER` or `LIMIT_CANISTER`.
+    pub(crate) limit: &'static str,
+    /// How long the client should wait before retrying.
+    pub(crate) retry_after: Duration,
+}
+
+struct Buckets {
+    per_sender: Option<KeyedBuckets<UserId>>,
+    per_canister: Option<KeyedBuckets<CanisterId>>,
+    /// The canisters with their own label on the rate limited metric.
+    labelled_canisters: HashSet<CanisterId>,
+}
+
+/// Enforces the per-sender and per-canister ingress rate limits.
+pub(crate) struct IngressRateLimiter {
+    buckets: Mutex<Buckets>,
+    rate_limited_total: IntCounterVec,
+}
+
+impl IngressRateLimiter {
+    /// Creates a rate limiter enforcing the given limits, if any.
+    /// `rate_limited_total` is incremented, by target canister and limit, for
+    /// every rejected message.
+    pub(crate) fn new(
+        per_sender: Option<RateLimitConfig>,
+        per_canister: Option<RateLimitConfig>,
+        rate_limited_total: IntCounterVec,
+    ) -> Self {
+        let now = Instant::now();
+        Self {
+            buckets: Mutex::new(Buckets {
+                per_sender: per_sender.map(|config| KeyedBuckets::new(config, now)),
+                per_canister: per_canister.map(|config| KeyedBuckets::new(config, now)),
+                labelled_canisters: HashSet::new(),
+            }),
+            rate_limited_total,
+        }
+    }
+
+    /// Accounts for an ingress message from `sender` to `canister_id`.
+    ///
+    /// A token is only taken from either bucket if both hold one, so messages
+    /// rejected because of one limit do not count against the other.
+    pub(crate) fn try_acquire(
+        &self,
+        sender: UserId,
+        canister_id: CanisterId,
+        now: Instant,
+    ) -> Result<(), RateLimited> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let Buckets {
+            per_sender,
+            per_canister,
+            labelled_canisters,
+        } = &mut *buckets;
+
+        let mut rate_limited = None;
+        if let Some(per_sender) = per_sender {
+            let retry_after = per_sender.check(sender, now);
+            if retry_after > Duration::from_secs(0) {
+                rate_limited = Some(RateLimited {
+                    limit: LIMIT_SENDER,
+                    retry_after,
+                });
+            }
+        }
+        if let Some(per_canister) = per_canister {
+            let retry_after = per_canister.check(canister_id, now);
+            if retry_after > Duration::from_secs(0)
+                && rate_limited
+                    .as_ref()
+                    .map_or(true, |r| retry_after > r.retry_after)
+            {
+                rate_limited = Some(RateLimited {
+                    limit: LIMIT_CANISTER,
+                    retry_after,
+                });
+            }
+        }
+
+        if let Some(rate_limited) = rate_limited {
+            if !labelled_canisters.contains(&canister_id)
+                && labelled_canisters.len() < MAX_LABELLED_CANISTERS
+            {
+                labelled_canisters.insert(canister_id);
+            }
+            let canister_label = if labelled_canisters.contains(&canister_id) {
+                canister_id.to_string()
+            } else {
+                OTHER_CANISTERS.to_string()
+            };
+            self.rate_limited_total
+                .with_label_values(&[&canister_label, rate_limited.limit])
+                .inc();
+            return Err(rate_limited);
+        }
+
+        if let Some(per_sender) = per_sender {
+            per_sender.take(&sender);
+        }
+        if let Some(per_canister) = per_canister {
+            per_canister.take(&canister_id);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_metrics::MetricsRegistry;
+    use ic_test_utilities::types::ids::{canister_test_id, user_test_id};
+
+    fn limit(max_requests_per_second: u32, burst_size: u32) -> Option<RateLimitConfig> {
+        Some(RateLimitConfig {
+            max_requests_per_second,
+            burst_size,
+        })
+    }
+
+    fn rate_limiter(
+        per_sender: Option<RateLimitConfig>,
+        per_canister: Option<RateLimitConfig>,
+    ) -> IngressRateLimiter {
+        let rate_limited_total = MetricsRegistry::new().int_counter_vec(
+            "test_rate_limited_total",
+            "Rate limited requests.",
+            &["canister_id", "limit"],
+        );
+        IngressRateLimiter::new(per_sender, per_canister, rate_limited_total)
+    }
+
+    #[test]
+    fn no_limits_accept_everything() {
+        let rate_limiter = rate_limiter(None, None);
+        let now = Instant::now();
+        for _ in 0..1000 {
+            assert_eq!(
+                rate_limiter.try_acquire(user_test_id(1), canister_test_id(1), now),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn sender_limit_allows_burst_then_refills() {
+        let rate_limiter = rate_limiter(limit(2, 3), None);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(
+                rate_limiter.try_acquire(user_test_id(1), canister_test_id(1), now),
+                Ok(())
+            );
+        }
+        assert_eq!(
+            rate_limiter.try_acquire(user_test_id(1), canister_test_id(1), now),
+            Err(RateLimited {
+                limit: LIMIT_SENDER,
+                retry_after: Duration::from_millis(500),
+            })
+        );
+        // Other senders are not affected.
+        assert_eq!(
+            rate_limiter.try_acquire(user_test_id(2), canister_test_id(1), now),
+            Ok(())
+        );
+        // One token is available again after 500ms.
+        let later = now + Duration::from_millis(500);
+        assert_eq!(
+            rate_limiter.try_acquire(user_test_id(1), canister_test_id(1), later),
+            Ok(())
+        );
+        assert!(rate_limiter
+            .try_acquire(user_test_id(1), canister_test_id(1), later)
+            .is_err());
+    }
+
+    #[test]
+    fn canister_limit_applies_across_senders() {
+        let rate_limiter = rate_limiter(limit(100, 100), limit(1, 2));
+        let now = Instant::now();
+        assert!(rate_limiter
+            .try_acquire(user_test_id(1), canister_test_id(1), now)
+            .is_ok());
+        assert!(rate_limiter
+            .try_acquire(user_test_id(2), canister_test_id(1), now)
+            .is_ok());
+        assert_eq!(
+            rate_limiter.try_acquire(user_test_id(3), canister_test_id(1), now),
+            Err(RateLimited {
+                limit: LIMIT_CANISTER,
+                retry_after: Duration::from_secs(1),
+            })
+        );
+        // Other canisters are not affected.
+        assert!(rate_limiter
+            .try_acquire(user_test_id(3), canister_test_id(2), now)
+            .is_ok());
+        assert_eq!(
+            rate_limiter
+                .rate_limited_total
+                .with_label_values(&[&canister_test_id(1).to_string(), LIMIT_CANISTER])
+                .get(),
+            1
+        );
+    }
+
+    #[test]
+    fn canister_label_is_bounded() {
+        let rate_limiter = rate_limiter(limit(1, 1), None);
+        let now = Instant::now();
+        // Only the first message is accepted, the following ones are rejected
+        // by the sender limit, each to a different canister.
+        assert!(rate_limiter
+            .try_acquire(user_test_id(1), canister_test_id(0), now)
+            .is_ok());
+        for canister in 1..=MAX_LABELLED_CANISTERS as u64 + 1 {
+            assert!(rate_limiter
+                .try_acquire(user_test_id(1), canister_test_id(canister), now)
+                .is_err());
+        }
+        let rate_limited = |label: &str| {
+            rate_limiter
+                .rate_limited_total
+                .with_label_values(&[label, LIMIT_SENDER])
+                .get()
+        };
+        // The first canisters that were rate limited keep their own label,
+        // including on later rejections.
+        for canister in 1..=MAX_LABELLED_CANISTERS as u64 {
+            assert_eq!(rate_limited(&canister_test_id(canister).to_string()), 1);
+        }
+        assert_eq!(rate_limited(OTHER_CANISTERS), 1);
+        assert!(rate_limiter
+            .try_acquire(user_test_id(1), canister_test_id(1), now)
+            .is_err());
+        assert_eq!(rate_limited(&canister_test_id(1).to_string()), 2);
+        assert_eq!(rate_limited(OTHER_CANISTERS), 1);
+    }
+
+    #[test]
+    fn buckets_are_carried_over_for_one_generation() {
+        // Generations last 4s, the time it takes to refill an empty bucket.
+        let rate_limiter = rate_limiter(limit(1, 4), None);
+        let now = Instant::now();
+        let at = |secs| now + Duration::from_secs(secs);
+        let acquire = |user, secs| {
+            rate_limiter
+                .try_acquire(user_test_id(user), canister_test_id(1), at(secs))
+                .is_ok()
+        };
+        let tracked = || {
+            let buckets = rate_limiter.buckets.lock().unwrap();
+            let per_sender = buckets.per_sender.as_ref().unwrap();
+            per_sender.current.len() + per_sender.previous.len()
+        };
+
+        // Use up all 4 tokens of sender 1 by the end of the first generation.
+        for _ in 0..4 {
+            assert!(acquire(1, 3));
+        }
+        assert!(!acquire(1, 3));
+
+        // Sender 2 starts a new generation, sender 1 keeps its bucket.
+        assert!(acquire(2, 4));
+        assert!(acquire(1, 4));
+        assert!(!acquire(1, 4));
+        assert_eq!(tracked(), 2);
+
+        // Buckets unused for a whole generation are dropped.
+        assert!(acquire(3, 8));
+        assert_eq!(tracked(), 3);
+        assert!(acquire(3, 12));
+        assert_eq!(tracked(), 1);
+    }
+
+    #[test]
+    fn rejected_messages_do_not_take_tokens() {
+        let rate_limiter = rate_limiter(limit(1, 1), limit(1, 1));
+        let now = Instant::now();
+        assert!(rate_limiter
+            .try_acquire(user_test_id(1), canister_test_id(1), now)
+            .is_ok());
+        // Rejected by the canister limit, so the sender's token is kept.
+        assert!(rate_limiter
+            .try_acquire(user_test_id(2), canister_test_id(1), now)
+            .is_err());
+        assert!(rate_limiter
+            .try_acquire(user_test_id(2), canister_test_id(2), now)
+            .is_ok());
+    }
+}
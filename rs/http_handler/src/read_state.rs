@@ -1,4 +1,5 @@
-//! Module that deals with requests to /api/v2/canister/.../read_state
+//! Module that deals with requests to /api/v2/canister/.../read_state and
+//! /api/v2/subnet/.../read_state
 
 use crate::{common, ReplicaHealthStatus};
 use hyper::{Body, Response};
@@ -20,7 +21,7 @@ use ic_types::{
         EXPECTED_MESSAGE_ID_LENGTH,
     },
     time::current_time,
-    UserId,
+    CanisterId, UserId,
 };
 use ic_validator::{get_authorized_canisters, CanisterIdSet};
 use std::convert::TryFrom;
@@ -33,9 +34,20 @@ use tower::{limit::ConcurrencyLimit, load_shed::LoadShed, Service, ServiceBuilde
 const MAX_READ_STATE_REQUEST_IDS: u8 = 100;
 const MAX_CONCURRENT_READ_STATE_REQUESTS: usize = 1000;
 
+/// The endpoint a `read_state` request was sent to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ReadStateEndpoint {
+    /// /api/v2/canister/.../read_state
+    Canister,
+    /// /api/v2/subnet/.../read_state, which only serves the `time` and
+    /// `subnet` subtrees.
+    Subnet,
+}
+
 #[derive(Clone)]
 pub(crate) struct ReadStateService {
     log: ReplicaLogger,
+    endpoint: ReadStateEndpoint,
     health_status: Arc<RwLock<ReplicaHealthStatus>>,
     delegation_from_nns: Arc<RwLock<Option<CertificateDelegation>>>,
     state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
@@ -45,8 +57,10 @@ pub(crate) struct ReadStateService {
 }
 
 impl ReadStateService {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         log: ReplicaLogger,
+        endpoint: ReadStateEndpoint,
         health_status: Arc<RwLock<ReplicaHealthStatus>>,
         delegation_from_nns: Arc<RwLock<Option<CertificateDelegation>>>,
         state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
@@ -56,6 +70,7 @@ impl ReadStateService {
     ) -> LoadShed<ConcurrencyLimit<ReadStateService>> {
         let base_service = Self {
             log,
+            endpoint,
             health_status,
             delegation_from_nns,
             state_reader,
@@ -128,6 +143,7 @@ impl Service<Vec<u8>> for ReadStateService {
             Ok(targets) => {
                 if let Err(err) = verify_paths(
                     self.state_reader.as_ref(),
+                    self.endpoint,
                     &read_state.source,
                     &read_state.paths,
                     &targets,
@@ -174,9 +190,11 @@ impl Service<Vec<u8>> for ReadStateService {
     }
 }
 
-// Verifies that the `user` is authorized to retrieve the `paths` requested.
+// Verifies that the `user` is authorized to retrieve the `paths` requested
+// from the given `endpoint`.
 fn verify_paths(
     state_reader: &dyn StateReader<State = ReplicatedState>,
+    endpoint: ReadStateEndpoint,
     user: &UserId,
     paths: &[Path],
     targets: &CanisterIdSet,
@@ -193,11 +211,39 @@ fn verify_paths(
     for path in paths {
         match path.as_slice() {
             [b"time"] => {}
+            [b"subnet", _subnet_id, b"public_key"] => {}
+            [b"subnet", _subnet_id, b"canister_ranges"] => {}
+            [b"subnet", _subnet_id, b"node", _node_id, b"public_key"] => {}
+            [b"subnet", _subnet_id, b"metrics"] if endpoint == ReadStateEndpoint::Subnet => {}
+            [b"subnet", _subnet_id, b"node", _node_id, b"metrics"]
+                if endpoint == ReadStateEndpoint::Subnet => {}
+            _ if endpoint == ReadStateEndpoint::Subnet => {
+                return Err(not_found_error(
+                    "Only the time and subnet paths can be requested from a subnet.",
+                ));
+            }
             [b"canister", _canister_id, b"controller"] => {}
             [b"canister", _canister_id, b"controllers"] => {}
             [b"canister", _canister_id, b"module_hash"] => {}
-            [b"subnet", _subnet_id, b"public_key"] => {}
-            [b"subnet", _subnet_id, b"canister_ranges"] => {}
+            [b"canister_status", canister_id, b"cycles_balance"]
+            | [b"canister_status", canister_id, b"memory_size"]
+            | [b"canister_status", canister_id, b"status"] => {
+                // The status of a canister is only readable by its controllers,
+                // and only if the canister is among the targets of the
+                // sender's delegations.
+                let is_controller = CanisterId::try_from(*canister_id)
+                    .ok()
+                    .filter(|canister_id| targets.contains(canister_id))
+                    .and_then(|canister_id| state.canister_state(&canister_id))
+                    .map_or(false, |canister| {
+                        canister.system_state.controllers.contains(&user.get())
+                    });
+                if !is_controller {
+                    return Err(permission_denied_error(
+                        "Only controllers can read the status of a canister.",
+                    ));
+                }
+            }
             [b"request_status", request_id] | [b"request_status", request_id, ..] => {
                 num_request_ids += 1;
 
@@ -240,8 +286,18 @@ fn verify_paths(
 
 #[cfg(test)]
 mod test {
+    use super::{verify_paths, ReadStateEndpoint};
     use crate::common::test::{array, assert_cbor_ser_equal, bytes, int};
-    use ic_crypto_tree_hash::{Digest, Label, MixedHashTree};
+    use ic_crypto_tree_hash::{Digest, Label, MixedHashTree, Path};
+    use ic_interfaces::state_manager::{CertificationScope, StateManager};
+    use ic_test_utilities::{
+        state::get_running_canister_with_args,
+        state_manager::FakeStateManager,
+        types::ids::{canister_test_id, node_test_id, subnet_test_id, user_test_id},
+    };
+    use ic_types::{canonical_error::CanonicalErrorCode, CanisterId, Cycles, Height};
+    use ic_validator::CanisterIdSet;
+    use maplit::btreeset;
 
     #[test]
     fn encoding_read_state_tree_empty() {
@@ -299,4 +355,185 @@ mod test {
             ]),
         );
     }
+
+    #[test]
+    fn subnet_endpoint_only_serves_subnet_paths() {
+        let state_manager = FakeStateManager::new();
+        let subnet_id = subnet_test_id(1).get();
+        let node_id = node_test_id(2).get();
+        let canister_id = canister_test_id(3).get();
+        let verify = |endpoint, path: Vec<&[u8]>| {
+            let path = Path::new(path.into_iter().map(Label::from).collect());
+            verify_paths(
+                &state_manager,
+                endpoint,
+                &user_test_id(4),
+                &[path],
+                &CanisterIdSet::All,
+            )
+        };
+
+        for path in vec![
+            vec![&b"time"[..]],
+            vec![&b"subnet"[..], subnet_id.as_slice(), b"public_key"],
+            vec![&b"subnet"[..], subnet_id.as_slice(), b"canister_ranges"],
+            vec![&b"subnet"[..], subnet_id.as_slice(), b"metrics"],
+            vec![
+                &b"subnet"[..],
+                subnet_id.as_slice(),
+                b"node",
+                node_id.as_slice(),
+                b"public_key",
+            ],
+            vec![
+                &b"subnet"[..],
+                subnet_id.as_slice(),
+                b"node",
+                node_id.as_slice(),
+                b"metrics",
+            ],
+        ] {
+            assert_eq!(verify(ReadStateEndpoint::Subnet, path), Ok(()));
+        }
+
+        for path in vec![
+            vec![&b"canister"[..], canister_id.as_slice(), b"controllers"],
+            vec![&b"request_status"[..], &[0; 32][..]],
+        ] {
+            assert_eq!(
+                verify(ReadStateEndpoint::Subnet, path).unwrap_err().code,
+                CanonicalErrorCode::NotFound
+            );
+        }
+
+        // Metrics are only served by the subnet endpoint.
+        assert_eq!(
+            verify(
+                ReadStateEndpoint::Canister,
+                vec![&b"subnet"[..], subnet_id.as_slice(), b"metrics"]
+            )
+            .unwrap_err()
+            .code,
+            CanonicalErrorCode::NotFound
+        );
+        assert_eq!(
+            verify(
+                ReadStateEndpoint::Canister,
+                vec![
+                    &b"subnet"[..],
+                    subnet_id.as_slice(),
+                    b"node",
+                    node_id.as_slice(),
+                    b"metrics"
+                ]
+            )
+            .unwrap_err()
+            .code,
+            CanonicalErrorCode::NotFound
+        );
+        assert_eq!(
+            verify(
+                ReadStateEndpoint::Canister,
+                vec![&b"canister"[..], canister_id.as_slice(), b"controllers"]
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn only_controllers_can_read_canister_status() {
+        let state_manager = FakeStateManager::new();
+        let canister_id = canister_test_id(3);
+        let controller = user_test_id(4);
+        let (_height, mut state) = state_manager.take_tip();
+        state.put_canister_state(get_running_canister_with_args(
+            canister_id,
+            controller.get(),
+            Cycles::new(1 << 36),
+        ));
+        state_manager.commit_and_certify(state, Height::new(1), CertificationScope::Full);
+
+        let verify_subtree = |subtree: &str, user, canister_id: CanisterId, leaf: &[u8]| {
+            let path = Path::new(vec![
+                Label::from(subtree),
+                Label::from(canister_id.get().as_slice()),
+                Label::from(leaf),
+            ]);
+            verify_paths(
+                &state_manager,
+                ReadStateEndpoint::Canister,
+                &user,
+                &[path],
+                &CanisterIdSet::All,
+            )
+        };
+
+        let verify =
+            |user, canister_id, leaf| verify_subtree("canister_status", user, canister_id, leaf);
+        for &leaf in &[&b"cycles_balance"[..], b"memory_size", b"status"] {
+            assert_eq!(verify(controller, canister_id, leaf), Ok(()));
+            assert_eq!(
+                verify(user_test_id(5), canister_id, leaf).unwrap_err().code,
+                CanonicalErrorCode::PermissionDenied
+            );
+            assert_eq!(
+                verify(controller, canister_test_id(6), leaf)
+                    .unwrap_err()
+                    .code,
+                CanonicalErrorCode::PermissionDenied
+            );
+            // The status is not part of the public canister subtree.
+            assert_eq!(
+                verify_subtree("canister", controller, canister_id, leaf)
+                    .unwrap_err()
+                    .code,
+                CanonicalErrorCode::NotFound
+            );
+        }
+        // Other canister paths stay public.
+        assert_eq!(
+            verify_subtree("canister", user_test_id(5), canister_id, b"controllers"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn canister_status_is_only_readable_for_delegation_targets() {
+        let state_manager = FakeStateManager::new();
+        let canister_id = canister_test_id(3);
+        let controller = user_test_id(4);
+        let (_height, mut state) = state_manager.take_tip();
+        state.put_canister_state(get_running_canister_with_args(
+            canister_id,
+            controller.get(),
+            Cycles::new(1 << 36),
+        ));
+        state_manager.commit_and_certify(state, Height::new(1), CertificationScope::Full);
+
+        let verify = |targets: &CanisterIdSet| {
+            let path = Path::new(vec![
+                Label::from("canister_status"),
+                Label::from(canister_id.get().as_slice()),
+                Label::from("status"),
+            ]);
+            verify_paths(
+                &state_manager,
+                ReadStateEndpoint::Canister,
+                &controller,
+                &[path],
+                targets,
+            )
+        };
+
+        assert_eq!(
+            verify(&CanisterIdSet::Some(btreeset! {canister_id})),
+            Ok(())
+        );
+        assert_eq!(
+            verify(&CanisterIdSet::Some(btreeset! {canister_test_id(6)}))
+                .unwrap_err()
+                .code,
+            CanonicalErrorCode::PermissionDenied
+        );
+    }
 }
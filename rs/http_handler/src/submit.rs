@@ -1,6 +1,10 @@
 //! Module that deals with requests to /api/v2/canister/.../call
 
-use crate::{common, IngressFilterService};
+use crate::{
+    common,
+    rate_limiter::{IngressRateLimiter, RateLimited},
+    IngressFilterService,
+};
 use hyper::{Body, Response, StatusCode};
 use ic_interfaces::crypto::IngressSigVerifier;
 use ic_interfaces::{p2p::IngressIngestionService, registry::RegistryClient};
@@ -20,6 +24,7 @@ use ic_types::{
 use ic_validator::validate_request;
 use std::convert::TryInto;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::Mutex;
 use tower::{Service, ServiceExt};
 
@@ -74,6 +79,7 @@ pub(crate) async fn handle(
     validator: Arc<dyn IngressSigVerifier + Send + Sync>,
     ingress_sender: Arc<Mutex<IngressIngestionService>>,
     ingress_filter: Arc<Mutex<IngressFilterService>>,
+    ingress_rate_limiter: Arc<IngressRateLimiter>,
     malicious_flags: MaliciousFlags,
     body: Vec<u8>,
 ) -> Result<Response<Body>, CanonicalError> {
@@ -123,6 +129,15 @@ pub(crate) async fn handle(
         .call((provisional_whitelist, msg.content().clone()));
 
     ingress_filter_callback.await?;
+
+    // Only authenticated messages that would be accepted are rate limited, so
+    // that nobody can use up the budget of another sender and messages the
+    // canister rejects do not count against the canister's budget.
+    if let Err(rate_limited) =
+        ingress_rate_limiter.try_acquire(msg.sender(), msg.canister_id(), Instant::now())
+    {
+        return Ok(too_many_requests(&rate_limited));
+    }
     let ingress_log_entry = msg.log_entry();
     let ingress_sender_callback = ingress_sender
         .lock()
@@ -148,6 +163,24 @@ pub(crate) async fn handle(
     Ok(response)
 }
 
+/// Builds a `429 Too Many Requests` response telling the client when to retry.
+fn too_many_requests(rate_limited: &RateLimited) -> Response<Body> {
+    // `Retry-After` is in whole seconds, round up to not retry too early.
+    let retry_after_secs =
+        rate_limited.retry_after.as_secs() + (rate_limited.retry_after.subsec_nanos() > 0) as u64;
+    let mut response = Response::new(Body::from(format!(
+        "Too many requests: the ingress rate limit per {} was exceeded.",
+        rate_limited.limit
+    )));
+    *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+    *response.headers_mut() = common::get_cors_headers();
+    response.headers_mut().insert(
+        hyper::header::RETRY_AFTER,
+        hyper::header::HeaderValue::from(retry_after_secs),
+    );
+    response
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -198,4 +231,17 @@ mod test {
         let message_id_2 = SignedIngress::try_from(request2).unwrap().id();
         assert_eq!(message_id_2, message_id);
     }
+
+    #[test]
+    fn too_many_requests_rounds_retry_after_up() {
+        let response = too_many_requests(&RateLimited {
+            limit: crate::rate_limiter::LIMIT_CANISTER,
+            retry_after: std::time::Duration::from_millis(1500),
+        });
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            response.headers().get(hyper::header::RETRY_AFTER).unwrap(),
+            "2"
+        );
+    }
 }
@@ -6,6 +6,8 @@
 pub(crate) enum ApiReqType {
     /// `read_state`
     ReadState,
+    /// `read_state` on a subnet
+    ReadStateSubnet,
     /// `call`
     Call,
     /// `query`
@@ -19,6 +21,7 @@ impl ApiReqType {
         use ApiReqType::*;
         match self {
             ReadState => "read_state",
+            ReadStateSubnet => "read_state_subnet",
             Call => "call",
             Query => "query",
             Unknown => "unknown",
@@ -53,6 +56,8 @@ pub(crate) enum RequestType {
     Query,
     /// A "read_state" request
     ReadState,
+    /// A "read_state" request sent to a subnet
+    ReadStateSubnet,
     /// A pre-flight OPTIONS request
     Options,
     /// A request for the dashboard, but one that required a redirection
@@ -72,6 +77,7 @@ impl RequestType {
             Status => "status",
             Submit => "submit",
             ReadState => "read_state",
+            ReadStateSubnet => "read_state_subnet",
             Query => "query",
             Options => "options",
             RedirectToDashboard => "redirect_to_dashboard",
@@ -2,6 +2,13 @@
 //! Consensus batches (PayloadBuilder). It is also used to validate the Ingress
 //! messages of Consensus payloads and to keep track of finalized Ingress
 //! Messages to ensure that no message is added to a block more than once.
+//!
+//! When building a payload, the target canisters take turns adding one message
+//! at a time, so that a canister with many messages can't crowd out the
+//! others. Canisters with a compute allocation may first fill a share of the
+//! payload among themselves, as configured in the subnet record. The order of
+//! the messages in a payload is not checked during validation, which only
+//! depends on the payload, the certified state and the registry.
 use crate::IngressManager;
 use ic_cycles_account_manager::IngressInductionCost;
 use ic_interfaces::{
@@ -23,7 +30,11 @@ use ic_types::{
     CanisterId, CountBytes, Cycles, Height, Time,
 };
 use ic_validator::{validate_request, RequestValidationError};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
+
+/// The validated pool is only walked until the candidate messages add up to
+/// this many payloads, in bytes or in number of messages.
+const MAX_CANDIDATES_PAYLOAD_MULTIPLE: usize = 4;
 
 impl<'a> IngressSelector for IngressManager {
     fn get_ingress_payload(
@@ -71,44 +82,110 @@ impl<'a> IngressSelector for IngressManager {
             .get_ingress_message_settings(context.registry_version)
             .expect("Couldn't fetch ingress message parameters from the registry.");
 
-        // Select valid ingress messages and stop once the total size
-        // becomes greater than ingress_bytes_per_block_soft_cap.
-        let mut accumulated_size = 0;
-        let mut cycles_needed: BTreeMap<CanisterId, Cycles> = BTreeMap::new();
-        let mut num_messages = 0;
-
-        let messages_in_payload = ingress_pool.select_validated(
+        // Gather the candidate messages per target canister, in the order of
+        // the pool. A canister never gets more messages into a payload than
+        // fit into an otherwise empty one, so we don't keep more than that.
+        // The walk over the pool stops once there are enough candidates for a
+        // few payloads, leaving room for candidates that turn out to be
+        // invalid. Messages that are dropped because their canister has
+        // enough candidates already don't count.
+        let max_candidate_bytes = settings
+            .ingress_bytes_per_block_soft_cap
+            .saturating_mul(MAX_CANDIDATES_PAYLOAD_MULTIPLE);
+        let max_candidate_messages = settings
+            .max_ingress_messages_per_block
+            .saturating_mul(MAX_CANDIDATES_PAYLOAD_MULTIPLE);
+        let mut candidate_bytes = 0;
+        let mut candidate_messages = 0;
+        let mut queues: BTreeMap<CanisterId, CanisterQueue> = BTreeMap::new();
+        ingress_pool.select_validated(
             expiry_range,
-            Box::new(move |ingress_obj| {
-                let result = self.validate_ingress(
-                    IngressMessageId::from(ingress_obj),
-                    &ingress_obj.signed_ingress,
-                    &state,
-                    context,
-                    &settings,
-                    &past_ingress_set,
-                    num_messages,
-                    accumulated_size,
-                    &mut cycles_needed,
-                );
-                match result {
-                    Ok(()) => {
-                        num_messages += 1;
-                        accumulated_size += ingress_obj.signed_ingress.count_bytes();
-                        SelectResult::Selected(ingress_obj.signed_ingress.clone())
-                    }
-                    Err(ValidationError::Permanent(
-                        IngressPermanentError::IngressPayloadTooBig(_, _),
-                    )) => SelectResult::Abort,
-                    Err(ValidationError::Permanent(
-                        IngressPermanentError::IngressPayloadTooManyMessages(_, _),
-                    )) => SelectResult::Abort,
-                    _ => SelectResult::Skip,
+            Box::new(|ingress_obj| {
+                if candidate_bytes > max_candidate_bytes
+                    || candidate_messages >= max_candidate_messages
+                {
+                    return SelectResult::Abort;
                 }
+                if past_ingress_set.contains(&IngressMessageId::from(ingress_obj)) {
+                    return SelectResult::Skip;
+                }
+                let queue = queues
+                    .entry(ingress_obj.signed_ingress.canister_id())
+                    .or_default();
+                if queue.messages.len() < settings.max_ingress_messages_per_block
+                    && queue.size <= settings.ingress_bytes_per_block_soft_cap
+                {
+                    let bytes = ingress_obj.signed_ingress.count_bytes();
+                    queue.size += bytes;
+                    queue.messages.push_back(ingress_obj.signed_ingress.clone());
+                    candidate_bytes += bytes;
+                    candidate_messages += 1;
+                }
+                SelectResult::Skip
             }),
         );
 
-        let payload = IngressPayload::from(messages_in_payload);
+        // The canisters take turns in an order that starts at a different
+        // canister for every certified height, so that the canisters with the
+        // lowest ids don't always get the first turn in a full payload.
+        let mut canisters: Vec<CanisterId> = queues.keys().cloned().collect();
+        if !canisters.is_empty() {
+            let first = (certified_height.get() % canisters.len() as u64) as usize;
+            canisters.rotate_left(first);
+        }
+
+        let mut selection = Selection::default();
+        let mut payload_full = false;
+
+        // Canisters with a compute allocation first fill their share of the
+        // payload among themselves.
+        if settings.compute_allocation_share_percent > 0 {
+            let allocated: Vec<CanisterId> = canisters
+                .iter()
+                .filter(|canister_id| {
+                    state
+                        .canister_state(*canister_id)
+                        .map_or(false, |canister| {
+                            canister.scheduler_state.compute_allocation.as_percent() > 0
+                        })
+                })
+                .cloned()
+                .collect();
+            let limits = SelectionLimits {
+                bytes: settings.ingress_bytes_per_block_soft_cap
+                    * settings.compute_allocation_share_percent
+                    / 100,
+                messages: settings.max_ingress_messages_per_block
+                    * settings.compute_allocation_share_percent
+                    / 100,
+            };
+            payload_full = self.select_round_robin(
+                &allocated,
+                &mut queues,
+                &limits,
+                &state,
+                context,
+                &settings,
+                &past_ingress_set,
+                &mut selection,
+            );
+        }
+
+        // The rest of the payload is shared among all canisters.
+        if !payload_full {
+            self.select_round_robin(
+                &canisters,
+                &mut queues,
+                &SelectionLimits::NONE,
+                &state,
+                context,
+                &settings,
+                &past_ingress_set,
+                &mut selection,
+            );
+        }
+
+        let payload = IngressPayload::from(selection.messages);
 
         // A last step is to validate the payload we just created. It will be
         // an error if this fails, in which case we log the error, and return
@@ -321,6 +398,106 @@ impl IngressManager {
         }
         Ok(())
     }
+
+    /// Adds messages from the `queues` of `canisters` to `selection`, in turns
+    /// of one message per canister, until the queues are empty or the
+    /// `limits` are reached. Messages that are not valid in the payload, e.g.
+    /// because their payer can no longer afford the ingress induction cost,
+    /// are dropped.
+    ///
+    /// Returns `true` if the payload is full, i.e. if no more messages can be
+    /// added to it at all.
+    #[allow(clippy::too_many_arguments)]
+    fn select_round_robin(
+        &self,
+        canisters: &[CanisterId],
+        queues: &mut BTreeMap<CanisterId, CanisterQueue>,
+        limits: &SelectionLimits,
+        state: &ReplicatedState,
+        context: &ValidationContext,
+        settings: &IngressMessageSettings,
+        past_ingress_set: &IngressSetChain<IngressHistorySet>,
+        selection: &mut Selection,
+    ) -> bool {
+        loop {
+            let mut selected_any = false;
+            for canister_id in canisters {
+                if selection.size >= limits.bytes || selection.messages.len() >= limits.messages {
+                    return false;
+                }
+                let queue = match queues.get_mut(canister_id) {
+                    Some(queue) => queue,
+                    None => continue,
+                };
+                while let Some(signed_ingress) = queue.messages.pop_front() {
+                    let result = self.validate_ingress(
+                        IngressMessageId::from(&signed_ingress),
+                        &signed_ingress,
+                        state,
+                        context,
+                        settings,
+                        past_ingress_set,
+                        selection.messages.len(),
+                        selection.size,
+                        &mut selection.cycles_needed,
+                    );
+                    match result {
+                        Ok(()) => {
+                            selection.size += signed_ingress.count_bytes();
+                            selection.messages.push(signed_ingress);
+                            selected_any = true;
+                            break;
+                        }
+                        Err(ValidationError::Permanent(
+                            IngressPermanentError::IngressPayloadTooBig(_, _),
+                        )) => return true,
+                        Err(ValidationError::Permanent(
+                            IngressPermanentError::IngressPayloadTooManyMessages(_, _),
+                        )) => return true,
+                        // Try the next message of the same canister.
+                        Err(_) => continue,
+                    }
+                }
+            }
+            if !selected_any {
+                return false;
+            }
+        }
+    }
+}
+
+/// The validated messages to one canister that are candidates for a payload,
+/// in the order of the pool.
+#[derive(Default)]
+struct CanisterQueue {
+    messages: VecDeque<SignedIngress>,
+    /// The total size of `messages` when they were added.
+    size: usize,
+}
+
+/// The messages selected for a payload so far.
+#[derive(Default)]
+struct Selection {
+    messages: Vec<SignedIngress>,
+    /// The total size of `messages`.
+    size: usize,
+    /// The sum of the ingress induction cost of `messages` per payer.
+    cycles_needed: BTreeMap<CanisterId, Cycles>,
+}
+
+/// Limits on a part of the payload, on top of the limits of the whole payload
+/// that `validate_ingress` checks.
+struct SelectionLimits {
+    bytes: usize,
+    messages: usize,
+}
+
+impl SelectionLimits {
+    /// No limits beyond those of the whole payload.
+    const NONE: SelectionLimits = SelectionLimits {
+        bytes: usize::MAX,
+        messages: usize::MAX,
+    };
 }
 
 /// An IngressSetQuery implementation based on IngressHistoryReader.
@@ -407,8 +584,11 @@ impl<'a, T: IngressSetQuery> IngressSetQuery for IngressSetChain<'a, T> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tests::{setup, setup_registry, setup_with_params};
+    use crate::tests::{
+        setup, setup_registry, setup_registry_with_subnet_record, setup_with_params,
+    };
     use assert_matches::assert_matches;
+    use ic_artifact_pool::ingress_pool::IngressPoolImpl;
     use ic_crypto::crypto_hash;
     use ic_interfaces::artifact_pool::UnvalidatedArtifact;
     use ic_interfaces::execution_environment::IngressHistoryError;
@@ -419,6 +599,7 @@ mod tests {
         cycles_account_manager::CyclesAccountManagerBuilder,
         history::MockIngressHistory,
         mock_time,
+        registry::test_subnet_record,
         state::{CanisterStateBuilder, ReplicatedStateBuilder},
         types::ids::{canister_test_id, node_test_id, subnet_test_id, user_test_id},
         types::messages::SignedIngressBuilder,
@@ -430,10 +611,10 @@ mod tests {
         ic00::{CanisterIdRecord, Payload, IC_00},
         messages::{MessageId, SignedIngress},
         time::current_time_and_expiry_time,
-        Height, RegistryVersion,
+        ComputeAllocation, Height, RegistryVersion,
     };
     use std::collections::HashSet;
-    use std::convert::TryInto;
+    use std::convert::{TryFrom, TryInto};
     use std::time::Duration;
 
     const MAX_SIZE: usize = 1000;
@@ -1734,4 +1915,183 @@ mod tests {
             },
         );
     }
+
+    // Returns a message to `canister_id` whose expiry, and thus its position in
+    // the pool, is determined by `position`.
+    fn make_ingress(canister_id: u64, position: u64) -> SignedIngress {
+        SignedIngressBuilder::new()
+            .canister_id(canister_test_id(canister_id))
+            .nonce(position)
+            .expiry_time(mock_time() + MAX_INGRESS_TTL - Duration::from_secs(100 - position))
+            .build()
+    }
+
+    fn insert_validated(ingress_pool: &mut IngressPoolImpl, messages: &[SignedIngress]) {
+        let time_source = FastForwardTimeSource::new();
+        for m in messages {
+            let message_id = IngressMessageId::from(m);
+            let attribute = IngressMessageAttribute::new(m);
+            ingress_pool.insert(UnvalidatedArtifact {
+                message: m.clone(),
+                peer_id: node_test_id(0),
+                timestamp: time_source.get_relative_time(),
+            });
+            ingress_pool.apply_changeset(vec![ChangeAction::MoveToValidated((
+                message_id,
+                node_test_id(0),
+                m.count_bytes(),
+                attribute,
+                crypto_hash(m.binary()).get(),
+            ))]);
+        }
+    }
+
+    fn count_messages_to(payload: IngressPayload, canister_id: u64) -> usize {
+        let msgs: Vec<SignedIngress> = payload.try_into().unwrap();
+        msgs.iter()
+            .filter(|m| m.canister_id() == canister_test_id(canister_id))
+            .count()
+    }
+
+    #[tokio::test]
+    // A canister with many messages early in the pool must not crowd out a
+    // canister with a single message.
+    async fn test_get_payload_round_robin_across_canisters() {
+        let subnet_id = subnet_test_id(0);
+        let mut messages: Vec<SignedIngress> = (0..4).map(|i| make_ingress(0, i)).collect();
+        messages.push(make_ingress(1, 10));
+        // Room for three messages.
+        let message_size = messages[0].count_bytes();
+        let registry = setup_registry(subnet_id, message_size * 5 / 2, MAX_SIZE);
+        setup_with_params(
+            None,
+            Some((registry, subnet_id)),
+            None,
+            Some(
+                ReplicatedStateBuilder::default()
+                    .with_canister(
+                        CanisterStateBuilder::default()
+                            .with_canister_id(canister_test_id(0))
+                            .build(),
+                    )
+                    .with_canister(
+                        CanisterStateBuilder::default()
+                            .with_canister_id(canister_test_id(1))
+                            .build(),
+                    )
+                    .build(),
+            ),
+            |ingress_manager, mut ingress_pool| {
+                insert_validated(&mut ingress_pool, &messages);
+                let payload = ingress_manager.get_ingress_payload(
+                    &ingress_pool,
+                    &HashSet::new(),
+                    &ValidationContext {
+                        time: mock_time(),
+                        registry_version: RegistryVersion::from(1),
+                        certified_height: Height::from(0),
+                    },
+                );
+                assert_eq!(payload.message_count(), 3);
+                assert_eq!(count_messages_to(payload, 1), 1);
+            },
+        )
+    }
+
+    #[tokio::test]
+    // Canisters with a compute allocation fill their share of the payload
+    // first, before the rest is shared among all canisters.
+    async fn test_get_payload_compute_allocation_share() {
+        let subnet_id = subnet_test_id(0);
+        let mut messages: Vec<SignedIngress> = (0..4).map(|i| make_ingress(0, i)).collect();
+        messages.extend((10..13).map(|i| make_ingress(1, i)));
+        // Room for three messages, more than one of which is reserved for
+        // canisters with a compute allocation.
+        let message_size = messages[0].count_bytes();
+        let mut subnet_record = test_subnet_record();
+        subnet_record.ingress_bytes_per_block_soft_cap = (message_size * 5 / 2) as u64;
+        subnet_record.ingress_compute_allocation_share_percent = 60;
+        let registry = setup_registry_with_subnet_record(subnet_id, subnet_record);
+        setup_with_params(
+            None,
+            Some((registry, subnet_id)),
+            None,
+            Some(
+                ReplicatedStateBuilder::default()
+                    .with_canister(
+                        CanisterStateBuilder::default()
+                            .with_canister_id(canister_test_id(0))
+                            .build(),
+                    )
+                    .with_canister(
+                        CanisterStateBuilder::default()
+                            .with_canister_id(canister_test_id(1))
+                            .with_compute_allocation(ComputeAllocation::try_from(50).unwrap())
+                            .build(),
+                    )
+                    .build(),
+            ),
+            |ingress_manager, mut ingress_pool| {
+                insert_validated(&mut ingress_pool, &messages);
+                let payload = ingress_manager.get_ingress_payload(
+                    &ingress_pool,
+                    &HashSet::new(),
+                    &ValidationContext {
+                        time: mock_time(),
+                        registry_version: RegistryVersion::from(1),
+                        certified_height: Height::from(0),
+                    },
+                );
+                assert_eq!(payload.message_count(), 3);
+                assert_eq!(count_messages_to(payload, 1), 2);
+            },
+        )
+    }
+
+    #[tokio::test]
+    // The pool is only walked until there are candidates for a few payloads,
+    // but the messages a canister has too many of don't count as candidates,
+    // so a canister whose messages come later in the pool still gets a turn.
+    async fn test_get_payload_bounds_candidates() {
+        let subnet_id = subnet_test_id(0);
+        let mut messages: Vec<SignedIngress> = (0..5).map(|i| make_ingress(1, i)).collect();
+        messages.push(make_ingress(0, 10));
+        // Room for a single message.
+        let message_size = messages[0].count_bytes();
+        let registry = setup_registry(subnet_id, message_size / 2, MAX_SIZE);
+        setup_with_params(
+            None,
+            Some((registry, subnet_id)),
+            None,
+            Some(
+                ReplicatedStateBuilder::default()
+                    .with_canister(
+                        CanisterStateBuilder::default()
+                            .with_canister_id(canister_test_id(0))
+                            .build(),
+                    )
+                    .with_canister(
+                        CanisterStateBuilder::default()
+                            .with_canister_id(canister_test_id(1))
+                            .build(),
+                    )
+                    .build(),
+            ),
+            |ingress_manager, mut ingress_pool| {
+                insert_validated(&mut ingress_pool, &messages);
+                // Canister 0 takes the first turn at this height.
+                let payload = ingress_manager.get_ingress_payload(
+                    &ingress_pool,
+                    &HashSet::new(),
+                    &ValidationContext {
+                        time: mock_time(),
+                        registry_version: RegistryVersion::from(1),
+                        certified_height: Height::from(0),
+                    },
+                );
+                assert_eq!(payload.message_count(), 1);
+                assert_eq!(count_messages_to(payload, 0), 1);
+            },
+        )
+    }
 }
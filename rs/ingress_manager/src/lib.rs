@@ -140,6 +140,7 @@ mod tests {
     use ic_artifact_pool::ingress_pool::IngressPoolImpl;
     use ic_interfaces::registry::RegistryClient;
     use ic_metrics::MetricsRegistry;
+    use ic_protobuf::registry::subnet::v1::SubnetRecord;
     use ic_registry_client::client::RegistryClientImpl;
     use ic_registry_common::proto_registry_data_provider::ProtoRegistryDataProvider;
     use ic_registry_keys::make_subnet_record_key;
@@ -163,11 +164,17 @@ mod tests {
         ingress_bytes_per_block_soft_cap: usize,
         max_ingress_bytes_per_message: usize,
     ) -> Arc<dyn RegistryClient> {
-        let registry_data_provider = Arc::new(ProtoRegistryDataProvider::new());
         let mut subnet_record = test_subnet_record();
         subnet_record.ingress_bytes_per_block_soft_cap = ingress_bytes_per_block_soft_cap as u64;
         subnet_record.max_ingress_bytes_per_message = max_ingress_bytes_per_message as u64;
+        setup_registry_with_subnet_record(subnet_id, subnet_record)
+    }
 
+    pub(crate) fn setup_registry_with_subnet_record(
+        subnet_id: SubnetId,
+        subnet_record: SubnetRecord,
+    ) -> Arc<dyn RegistryClient> {
+        let registry_data_provider = Arc::new(ProtoRegistryDataProvider::new());
         registry_data_provider
             .add(
                 &make_subnet_record_key(subnet_id),
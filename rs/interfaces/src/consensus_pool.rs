@@ -9,10 +9,10 @@ use ic_protobuf::types::v1 as pb;
 use ic_types::{
     artifact::ConsensusMessageId,
     consensus::{
-        catchup::CUPWithOriginalProtobuf, Block, BlockProposal, CatchUpPackage,
-        CatchUpPackageShare, ConsensusMessage, ContentEq, Finalization, FinalizationShare,
-        HasHeight, HashedBlock, Notarization, NotarizationShare, RandomBeacon, RandomBeaconShare,
-        RandomTape, RandomTapeShare,
+        catchup::CUPWithOriginalProtobuf, equivocation::EquivocationEvidence, Block, BlockProposal,
+        CatchUpPackage, CatchUpPackageShare, ConsensusMessage, ContentEq, Finalization,
+        FinalizationShare, HasHeight, HashedBlock, Notarization, NotarizationShare, RandomBeacon,
+        RandomBeaconShare, RandomTape, RandomTapeShare,
     },
     time::Time,
     Height,
@@ -33,6 +33,7 @@ pub enum ChangeAction {
     HandleInvalid(ConsensusMessage, String),
     PurgeValidatedBelow(Height),
     PurgeUnvalidatedBelow(Height),
+    AddEquivocationEvidence(EquivocationEvidence),
 }
 // end::change_set[]
 
@@ -89,6 +90,10 @@ impl ContentEq for ChangeAction {
             (ChangeAction::AddToValidated(x), ChangeAction::MoveToValidated(y)) => x.content_eq(y),
             (ChangeAction::MoveToValidated(x), ChangeAction::AddToValidated(y)) => x.content_eq(y),
             (ChangeAction::PurgeValidatedBelow(x), ChangeAction::PurgeValidatedBelow(y)) => x == y,
+            (
+                ChangeAction::AddEquivocationEvidence(x),
+                ChangeAction::AddEquivocationEvidence(y),
+            ) => x == y,
             // Default to false when comparing actions of different type
             _ => false,
         }
@@ -197,6 +202,9 @@ pub trait PoolSection<T> {
 /// - The unvalidated section contains artifacts that have been received but
 ///   haven't yet been validated. This section is in-memory only and thus
 ///   volatile.
+///
+/// Next to these, it holds the evidence of equivocations found by the
+/// validator, which is in-memory only as well.
 pub trait ConsensusPool {
     /// Return a reference to the validated PoolSection.
     fn validated(&self) -> &dyn PoolSection<ValidatedConsensusArtifact>;
@@ -206,6 +214,10 @@ pub trait ConsensusPool {
 
     /// Return a reference to the consensus cache (ConsensusPoolCache).
     fn as_cache(&self) -> &dyn ConsensusPoolCache;
+
+    /// Return a reference to the evidence of equivocations, i.e. of nodes
+    /// signing conflicting artifacts, that was collected during validation.
+    fn equivocation_evidence(&self) -> &dyn HeightIndexedPool<EquivocationEvidence>;
 }
 
 /// Mutation operations on top of ConsensusPool.
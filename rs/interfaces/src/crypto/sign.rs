@@ -31,7 +31,7 @@ use ic_types::crypto::{
     BasicSigOf, CanisterSigOf, CombinedMultiSigOf, CryptoResult, IndividualMultiSigOf,
     SignedBytesWithoutDomainSeparator, UserPublicKey,
 };
-use ic_types::messages::{Delegation, MessageId, WebAuthnEnvelope};
+use ic_types::messages::{Delegation, MessageId, QueryResponseHash, WebAuthnEnvelope};
 use ic_types::{
     consensus::{
         certification::CertificationContent, dkg::DealingContent, ecdsa::EcdsaDealing, Block,
@@ -51,6 +51,7 @@ pub mod canister_threshold_sig;
 
 const SIG_DOMAIN_IC_REQUEST_AUTH_DELEGATION: &str = "ic-request-auth-delegation";
 const SIG_DOMAIN_IC_REQUEST: &str = "ic-request";
+const SIG_DOMAIN_IC_RESPONSE: &str = "ic-response";
 
 /// `Signable` represents an object whose byte-vector representation
 /// can be signed using a digital signature scheme.
@@ -94,6 +95,7 @@ mod private {
     impl SignatureDomainSeal for WebAuthnEnvelope {}
     impl SignatureDomainSeal for Delegation {}
     impl SignatureDomainSeal for MessageId {}
+    impl SignatureDomainSeal for QueryResponseHash {}
     impl SignatureDomainSeal for CertificationContent {}
     impl SignatureDomainSeal for CatchUpContent {}
     impl SignatureDomainSeal for CatchUpContentProtobufBytes {}
@@ -151,6 +153,12 @@ impl SignatureDomain for MessageId {
     }
 }
 
+impl SignatureDomain for QueryResponseHash {
+    fn domain(&self) -> Vec<u8> {
+        domain_with_prepended_length(SIG_DOMAIN_IC_RESPONSE)
+    }
+}
+
 impl SignatureDomain for CertificationContent {
     fn domain(&self) -> Vec<u8> {
         domain_with_prepended_length(DOMAIN_CERTIFICATION_CONTENT)
@@ -22,7 +22,7 @@ use ic_types::{
 use messages::Messages;
 use prometheus::{Histogram, IntCounterVec, IntGauge};
 use std::collections::BTreeMap;
-use std::convert::{From, TryFrom, TryInto};
+use std::convert::From;
 
 const LABEL_STREAMS: &[u8] = b"streams";
 const LABEL_HEADER: &[u8] = b"header";
@@ -118,7 +118,6 @@ mod header {
     use super::{CertifiedSliceError, InvalidSlice};
     use ic_canonical_state::encoding;
     use ic_types::xnet::{StreamHeader, StreamIndex};
-    use std::convert::TryFrom;
 
     /// Wrapper around serialized header plus transient metadata.
     #[derive(Clone, Debug, PartialEq)]
@@ -131,6 +130,21 @@ mod header {
     }
 
     impl Header {
+        /// Decodes a serialized stream header, as encoded by a replica
+        /// certifying states with `certification_version`.
+        pub(super) fn decode(
+            bytes: Vec<u8>,
+            certification_version: u32,
+        ) -> Result<Self, CertifiedSliceError> {
+            let decoded = encoding::decode_stream_header(&bytes, certification_version)?;
+            if decoded.begin > decoded.end {
+                return Err(CertifiedSliceError::InvalidPayload(
+                    InvalidSlice::InvalidBounds,
+                ));
+            }
+            Ok(Header { bytes, decoded })
+        }
+
         pub(super) fn begin(&self) -> StreamIndex {
             self.decoded.begin
         }
@@ -144,20 +158,6 @@ mod header {
         }
     }
 
-    impl TryFrom<Vec<u8>> for Header {
-        type Error = CertifiedSliceError;
-
-        fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
-            let decoded = encoding::decode_stream_header(&bytes)?;
-            if decoded.begin > decoded.end {
-                return Err(CertifiedSliceError::InvalidPayload(
-                    InvalidSlice::InvalidBounds,
-                ));
-            }
-            Ok(Header { bytes, decoded })
-        }
-    }
-
     impl From<Header> for Vec<u8> {
         fn from(header: Header) -> Self {
             header.bytes
@@ -679,15 +679,15 @@ impl CountBytes for Payload {
     }
 }
 
-impl TryFrom<&[u8]> for Payload {
-    type Error = CertifiedSliceError;
-
-    fn try_from(payload_bytes: &[u8]) -> Result<Self, Self::Error> {
+impl Payload {
+    /// Decodes a serialized payload, as encoded by a replica certifying states
+    /// with `certification_version`.
+    fn decode(payload_bytes: &[u8], certification_version: u32) -> CertifiedSliceResult<Self> {
         let tree: PayloadTree = v1::LabeledTree::proxy_decode(payload_bytes)?;
         let (subnet_id, header, messages) = Self::unpack(tree)?;
 
         let header = header.ok_or(CertifiedSliceError::InvalidPayload(MissingHeader))?;
-        let header = Header::try_from(header)?;
+        let header = Header::decode(header, certification_version)?;
 
         let messages = messages.map(Messages::new).transpose()?;
         if let Some(messages) = messages.as_ref() {
@@ -731,6 +731,22 @@ pub struct UnpackedStreamSlice {
 }
 
 impl UnpackedStreamSlice {
+    /// Unpacks a `CertifiedStreamSlice` whose payload was encoded by a replica
+    /// certifying states with `certification_version`.
+    ///
+    /// Returns `Err(DecodeFailed)` or `Err(InvalidPayload)` if `packed` is
+    /// malformed.
+    pub fn decode(
+        packed: CertifiedStreamSlice,
+        certification_version: u32,
+    ) -> CertifiedSliceResult<Self> {
+        Ok(Self {
+            payload: Payload::decode(packed.payload.as_slice(), certification_version)?,
+            merkle_proof: v1::Witness::proxy_decode(&packed.merkle_proof)?,
+            certification: packed.certification,
+        })
+    }
+
     /// Takes a prefix of the slice that meets the given limits. Returns the
     /// prefix (if one can be created) and the remaining slice (if any).
     ///
@@ -883,15 +899,19 @@ impl CountBytes for UnpackedStreamSlice {
 }
 
 /// Returns a deterministic byte size estimate for the provided certified slice,
-/// the exact same as `UnpackedStreamSlice::try_from(packed)?.count_bytes()`. Or
-/// an error, if the payload cannot be unpacked.
+/// the exact same as
+/// `UnpackedStreamSlice::decode(packed, certification_version)?.count_bytes()`.
+/// Or an error, if the payload cannot be unpacked.
 ///
 /// Workaround for not being able to implement `impl CountBytes for
 /// CertifiedStreamSlice`, as both types are defined outside of the crate and
 /// the implementation requires logic defined here.
-pub fn certified_slice_count_bytes(packed: &CertifiedStreamSlice) -> CertifiedSliceResult<usize> {
+pub fn certified_slice_count_bytes(
+    packed: &CertifiedStreamSlice,
+    certification_version: u32,
+) -> CertifiedSliceResult<usize> {
     Ok(slice_count_bytes(
-        &Payload::try_from(packed.payload.as_slice())?,
+        &Payload::decode(packed.payload.as_slice(), certification_version)?,
         &packed.certification,
     ))
 }
@@ -910,18 +930,6 @@ fn slice_count_bytes(payload: &Payload, certification: &Certification) -> usize
         + certification.count_bytes()
 }
 
-impl TryFrom<CertifiedStreamSlice> for UnpackedStreamSlice {
-    type Error = CertifiedSliceError;
-
-    fn try_from(packed: CertifiedStreamSlice) -> Result<Self, Self::Error> {
-        Ok(Self {
-            payload: Payload::try_from(packed.payload.as_slice())?,
-            merkle_proof: v1::Witness::proxy_decode(&packed.merkle_proof)?,
-            certification: packed.certification,
-        })
-    }
-}
-
 impl From<UnpackedStreamSlice> for CertifiedStreamSlice {
     fn from(unpacked: UnpackedStreamSlice) -> Self {
         unpacked.pack()
@@ -1259,8 +1267,12 @@ impl CertifiedSlicePool {
         &mut self,
         subnet_id: SubnetId,
         slice: CertifiedStreamSlice,
+        certification_version: u32,
     ) -> CertifiedSliceResult<()> {
-        self.put_impl(subnet_id, slice.try_into()?)
+        self.put_impl(
+            subnet_id,
+            UnpackedStreamSlice::decode(slice, certification_version)?,
+        )
     }
 
     /// Appends a partial slice to the corresponding pool entry, trimming
@@ -1280,8 +1292,9 @@ impl CertifiedSlicePool {
         &mut self,
         subnet_id: SubnetId,
         partial: CertifiedStreamSlice,
+        certification_version: u32,
     ) -> CertifiedSliceResult<()> {
-        let partial: UnpackedStreamSlice = partial.try_into()?;
+        let partial = UnpackedStreamSlice::decode(partial, certification_version)?;
 
         let (res, slice) = match self.slices.remove(&subnet_id) {
             // We have a pooled slice, try appending to it.
@@ -31,6 +31,7 @@ use ic_replicated_state::{
 };
 use ic_types::{
     batch::Batch,
+    crypto::KeyPurpose,
     ingress::IngressStatus,
     messages::MessageId,
     registry::RegistryClientError,
@@ -451,11 +452,38 @@ impl BatchProcessorImpl {
                     }
                 };
 
+                // Nodes without a valid signing key are kept in the topology
+                // without a public key, rather than certifying an empty or
+                // bogus one for them.
+                let public_key = match self.registry.get_crypto_key_for_node(
+                    node_id,
+                    KeyPurpose::NodeSigning,
+                    registry_version,
+                )? {
+                    Some(public_key) => {
+                        match ic_crypto::ed25519_public_key_to_der(public_key.key_value) {
+                            Ok(public_key) => Some(public_key),
+                            Err(err) => {
+                                warn!(
+                                    self.log,
+                                    "Invalid node signing key for node {}: {:?}", node_id, err,
+                                );
+                                None
+                            }
+                        }
+                    }
+                    None => {
+                        warn!(self.log, "No node signing key found for node {}", node_id);
+                        None
+                    }
+                };
+
                 nodes.insert(
                     node_id,
                     NodeTopology {
                         ip_address: http_info.ip_addr,
                         http_port,
+                        public_key,
                     },
                 );
             }
@@ -966,16 +994,42 @@ impl MessageRouting for MessageRoutingImpl {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ic_protobuf::registry::{
+        crypto::v1::{AlgorithmId as AlgorithmIdProto, PublicKey as PublicKeyProto},
+        node::v1::{connection_endpoint::Protocol, ConnectionEndpoint, NodeRecord},
+    };
+    use ic_registry_keys::{make_crypto_node_key, make_node_record_key, ROOT_SUBNET_ID_KEY};
     use ic_test_utilities::{
         metrics::{fetch_int_counter_vec, metric_vec},
         notification::{Notification, WaitResult},
+        registry::{setup_registry_non_final, SubnetRecordBuilder},
         state_manager::MockStateManager,
-        types::batch::BatchBuilder,
+        types::{
+            batch::BatchBuilder,
+            ids::{node_test_id, subnet_test_id},
+        },
         with_test_replica_logger,
     };
     use std::sync::Arc;
     use std::time::Duration;
 
+    /// A `StateMachine` for tests that never execute a round.
+    struct FakeStateMachine;
+
+    impl StateMachine for FakeStateMachine {
+        fn execute_round(
+            &self,
+            _state: ReplicatedState,
+            _network_topology: NetworkTopology,
+            _batch: Batch,
+            _provisional_whitelist: ProvisionalWhitelist,
+            _subnet_features: SubnetFeatures,
+            _max_number_of_canisters: u64,
+        ) -> ReplicatedState {
+            unreachable!("No rounds are executed")
+        }
+    }
+
     /// Helper function for testing the values of the
     /// `METRIC_DELIVER_BATCH_COUNT` metric.
     fn assert_deliver_batch_count_eq(
@@ -1059,4 +1113,83 @@ mod tests {
             notification.notify(());
         });
     }
+
+    #[test]
+    fn network_topology_keeps_nodes_without_signing_key() {
+        with_test_replica_logger(|log| {
+            let subnet_id = subnet_test_id(1);
+            let node_ids = [node_test_id(1), node_test_id(2), node_test_id(3)];
+            let version = RegistryVersion::from(1);
+            let (data_provider, registry) = setup_registry_non_final(
+                subnet_id,
+                vec![(version.get(), SubnetRecordBuilder::from(&node_ids).build())],
+            );
+            data_provider
+                .add(
+                    ROOT_SUBNET_ID_KEY,
+                    version,
+                    Some(ic_types::subnet_id_into_protobuf(subnet_id)),
+                )
+                .unwrap();
+            for node_id in node_ids.iter() {
+                data_provider
+                    .add(
+                        &make_node_record_key(*node_id),
+                        version,
+                        Some(NodeRecord {
+                            http: Some(ConnectionEndpoint {
+                                ip_addr: "127.0.0.1".to_string(),
+                                port: 8080,
+                                protocol: Protocol::Http1 as i32,
+                            }),
+                            ..Default::default()
+                        }),
+                    )
+                    .unwrap();
+            }
+            // Node 1 has a valid signing key, node 2 a malformed one and node 3
+            // none at all.
+            let signing_key = vec![7; 32];
+            for (node_id, key_value) in vec![
+                (node_ids[0], signing_key.clone()),
+                (node_ids[1], vec![7; 3]),
+            ] {
+                data_provider
+                    .add(
+                        &make_crypto_node_key(node_id, KeyPurpose::NodeSigning),
+                        version,
+                        Some(PublicKeyProto {
+                            algorithm: AlgorithmIdProto::Ed25519 as i32,
+                            key_value,
+                            version: 0,
+                            proof_data: None,
+                        }),
+                    )
+                    .unwrap();
+            }
+            registry.update_to_latest_version();
+
+            let batch_processor = BatchProcessorImpl::new(
+                Arc::new(MockStateManager::new()),
+                Box::new(FakeStateMachine),
+                registry,
+                Arc::new(MessageRoutingMetrics::new(&MetricsRegistry::new())),
+                log,
+            );
+            let network_topology = batch_processor.populate_network_topology(version);
+
+            let nodes = &network_topology.subnets[&subnet_id].nodes;
+            assert_eq!(nodes.len(), node_ids.len());
+            assert_eq!(
+                nodes[&node_ids[0]].public_key,
+                Some(ic_crypto::ed25519_public_key_to_der(signing_key).unwrap())
+            );
+            assert_eq!(nodes[&node_ids[1]].public_key, None);
+            assert_eq!(nodes[&node_ids[2]].public_key, None);
+            for node in nodes.values() {
+                assert_eq!(node.ip_address, "127.0.0.1");
+                assert_eq!(node.http_port, 8080);
+            }
+        });
+    }
 }
@@ -78,6 +78,9 @@ impl StateMachine for StateMachineImpl {
         metadata.batch_time = batch.time;
         metadata.network_topology = network_topology;
         metadata.own_subnet_features = subnet_features;
+        if let Some(blockmaker_metrics) = &batch.blockmaker_metrics {
+            metadata.observe_blockmaker_metrics(blockmaker_metrics);
+        }
         state.set_system_metadata(metadata);
 
         // Preprocess messages and add messages to the induction pool through the Demux.
@@ -6,15 +6,18 @@ use crate::{
 use ic_interfaces::{execution_environment::Scheduler, state_manager::StateManager};
 use ic_metrics::MetricsRegistry;
 use ic_registry_subnet_type::SubnetType;
-use ic_replicated_state::{ReplicatedState, SubnetTopology};
+use ic_replicated_state::{
+    metadata_state::{NodeMetrics, NodeTopology},
+    ReplicatedState, SubnetTopology,
+};
 use ic_test_utilities::{
     state_manager::FakeStateManager,
     types::batch::{BatchBuilder, IngressPayloadBuilder, PayloadBuilder},
-    types::ids::subnet_test_id,
+    types::ids::{node_test_id, subnet_test_id},
     types::messages::SignedIngressBuilder,
     with_test_replica_logger,
 };
-use ic_types::messages::SignedIngress;
+use ic_types::{batch::BlockmakerMetrics, messages::SignedIngress};
 use ic_types::{Height, PrincipalId, SubnetId};
 use mockall::{mock, predicate::*, Sequence};
 use std::collections::{BTreeMap, BTreeSet};
@@ -157,6 +160,64 @@ fn state_machine_populates_network_topology() {
     });
 }
 
+#[test]
+fn state_machine_records_blockmaker_metrics() {
+    let provided_batch = BatchBuilder::new()
+        .batch_number(Height::new(1))
+        .blockmaker_metrics(BlockmakerMetrics {
+            blockmaker: node_test_id(2),
+            failed_blockmakers: vec![node_test_id(1)],
+        })
+        .build();
+    let mut fixture = test_fixture(&provided_batch);
+    let own_subnet_id = fixture.initial_state.metadata.own_subnet_id;
+    fixture.network_topology.subnets.insert(
+        own_subnet_id,
+        SubnetTopology {
+            nodes: (1..=2)
+                .map(|i| (node_test_id(i), NodeTopology::default()))
+                .collect(),
+            ..Default::default()
+        },
+    );
+
+    with_test_replica_logger(|log| {
+        let state_machine = Box::new(StateMachineImpl::new(
+            fixture.scheduler,
+            fixture.demux,
+            fixture.stream_builder,
+            log,
+            fixture.metrics,
+        ));
+
+        let state = state_machine.execute_round(
+            fixture.initial_state,
+            fixture.network_topology,
+            provided_batch,
+            ProvisionalWhitelist::Set(BTreeSet::new()),
+            Default::default(),
+            MAX_NUMBER_OF_CANISTERS,
+        );
+
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            node_test_id(1),
+            NodeMetrics {
+                blocks_proposed_total: 0,
+                failed_proposals_by_rank: vec![1],
+            },
+        );
+        expected.insert(
+            node_test_id(2),
+            NodeMetrics {
+                blocks_proposed_total: 1,
+                failed_proposals_by_rank: vec![],
+            },
+        );
+        assert_eq!(state.metadata.node_metrics, expected);
+    });
+}
+
 // Tests the processing of a batch. Ensures that the Demux, Scheduler, and
 // StreamBuilder are invoked in order and that all of them are called.
 fn test_delivered_batch(provided_batch: Batch) {
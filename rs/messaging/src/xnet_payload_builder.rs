@@ -197,7 +197,7 @@ pub struct XNetPayloadBuilderImpl {
     /// byte sizes. Always
     /// `crate::certified_slice_pool::certified_slice_count_bytes()` in
     /// production code, only replaced in unit tests.
-    count_bytes_fn: fn(&CertifiedStreamSlice) -> CertifiedSliceResult<usize>,
+    count_bytes_fn: fn(&CertifiedStreamSlice, u32) -> CertifiedSliceResult<usize>,
 
     metrics: Arc<XNetPayloadBuilderMetrics>,
 
@@ -294,6 +294,7 @@ impl XNetPayloadBuilderImpl {
         );
         let refill_task_handle = PoolRefillTask::start(
             Arc::clone(&slice_pool),
+            Arc::clone(&state_manager),
             endpoint_resolver,
             Arc::clone(&xnet_client),
             runtime_handle,
@@ -319,7 +320,7 @@ impl XNetPayloadBuilderImpl {
     #[allow(dead_code)]
     pub(crate) fn with_count_bytes_fn(
         mut self,
-        certified_slice_count_bytes: fn(&CertifiedStreamSlice) -> CertifiedSliceResult<usize>,
+        certified_slice_count_bytes: fn(&CertifiedStreamSlice, u32) -> CertifiedSliceResult<usize>,
     ) -> Self {
         self.count_bytes_fn = certified_slice_count_bytes;
         self
@@ -603,8 +604,11 @@ impl XNetPayloadBuilderImpl {
                         .map(|messages| messages.end())
                         .unwrap_or(expected.message_index),
                     signals_end: slice.header().signals_end,
-                    byte_size: (self.count_bytes_fn)(certified_slice)
-                        .expect("Failed to unpack CertifiedStresmSlice"),
+                    byte_size: (self.count_bytes_fn)(
+                        certified_slice,
+                        state.metadata.certification_version,
+                    )
+                    .expect("Failed to unpack CertifiedStresmSlice"),
                 }
             }
 
@@ -984,6 +988,10 @@ pub struct PoolRefillTask {
     /// A pool of slices, filled in the background by an async task.
     pool: Arc<Mutex<CertifiedSlicePool>>,
 
+    /// Source of the certification version that pulled slices are decoded
+    /// with.
+    state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+
     endpoint_resolver: XNetEndpointResolver,
 
     /// Async client for querying `XNetEndpoints`.
@@ -1001,6 +1009,7 @@ impl PoolRefillTask {
     /// Starts an async task that fills the slice pool in the background.
     pub fn start(
         pool: Arc<Mutex<CertifiedSlicePool>>,
+        state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
         endpoint_resolver: XNetEndpointResolver,
         xnet_client: Arc<dyn XNetClient>,
         runtime_handle: runtime::Handle,
@@ -1010,6 +1019,7 @@ impl PoolRefillTask {
         let (refill_trigger, mut refill_receiver) = mpsc::channel(1);
         let task = Self {
             pool,
+            state_manager,
             endpoint_resolver,
             xnet_client,
             runtime_handle: runtime_handle.clone(),
@@ -1094,6 +1104,14 @@ impl PoolRefillTask {
                 }
             };
 
+            // Pulled slices are decoded with the certification version of the latest state.
+            let certification_version = self
+                .state_manager
+                .get_latest_state()
+                .get_ref()
+                .metadata
+                .certification_version;
+
             // Spawn an async task to query the `XNetEndpoint` on `subnet_id`.
             let xnet_client = self.xnet_client.clone();
             let metrics = Arc::clone(&self.metrics);
@@ -1110,10 +1128,14 @@ impl PoolRefillTask {
                     Ok(slice) => {
                         let res = if witness_begin != msg_begin {
                             // Pulled a stream suffix, append to pooled slice.
-                            pool.lock().unwrap().append(subnet_id, slice)
+                            pool.lock()
+                                .unwrap()
+                                .append(subnet_id, slice, certification_version)
                         } else {
                             // Pulled a complete stream, replace polled slice (if any).
-                            pool.lock().unwrap().put(subnet_id, slice)
+                            pool.lock()
+                                .unwrap()
+                                .put(subnet_id, slice, certification_version)
                         };
                         let status = match res {
                             Ok(()) => STATUS_SUCCESS,
@@ -1469,11 +1491,17 @@ pub mod testing {
         subnet_id: SubnetId,
         slice: CertifiedStreamSlice,
     ) {
+        let certification_version = payload_builder
+            .state_manager
+            .get_latest_state()
+            .get_ref()
+            .metadata
+            .certification_version;
         payload_builder
             .slice_pool
             .lock()
             .unwrap()
-            .put(subnet_id, slice)
+            .put(subnet_id, slice, certification_version)
             .unwrap();
     }
 }
@@ -473,5 +473,5 @@ fn get_xnet_payload_builder_for_test(
         log,
     )
     // Any slice, empty or not, has byte size 1.
-    .with_count_bytes_fn(|_| Ok(1))
+    .with_count_bytes_fn(|_, _| Ok(1))
 }
@@ -427,7 +427,7 @@ impl PayloadBuilderTestFixture {
             log,
         )
         // Any slice, empty or not, has byte size 1.
-        .with_count_bytes_fn(|_| Ok(1))
+        .with_count_bytes_fn(|_, _| Ok(1))
     }
 
     /// Helper to create a vector of references from `self.payloads`.
@@ -1,5 +1,5 @@
 use assert_matches::assert_matches;
-use ic_canonical_state::LabelLike;
+use ic_canonical_state::{LabelLike, CURRENT_CERTIFICATION_VERSION};
 use ic_crypto_tree_hash::{flat_map::FlatMap, Label, LabeledTree};
 use ic_messaging::{
     certified_slice_pool::{
@@ -21,7 +21,6 @@ use ic_types::{
 };
 use maplit::btreemap;
 use proptest::prelude::*;
-use std::convert::TryFrom;
 
 mod common;
 use common::*;
@@ -36,7 +35,7 @@ proptest! {
             let fixture = StateManagerFixture::new(log).with_stream(DST_SUBNET, stream);
 
             let certified_slice = fixture.get_slice(DST_SUBNET, from, msg_count);
-            let unpacked = UnpackedStreamSlice::try_from(certified_slice.clone())
+            let unpacked = UnpackedStreamSlice::decode(certified_slice.clone(), CURRENT_CERTIFICATION_VERSION)
                 .expect("failed to unpack certified stream");
 
             assert_slices_eq(
@@ -55,7 +54,7 @@ proptest! {
             message_index: StreamIndex,
             signal_index: StreamIndex,
         ) -> Option<CertifiedStreamSlice> {
-            let unpacked = UnpackedStreamSlice::try_from(certified_slice.clone())
+            let unpacked = UnpackedStreamSlice::decode(certified_slice.clone(), CURRENT_CERTIFICATION_VERSION)
                 .expect("failed to unpack certified stream");
 
             unpacked
@@ -125,7 +124,7 @@ proptest! {
             msg_limit: Option<usize>,
             byte_limit: Option<usize>,
         ) -> (Option<CertifiedStreamSlice>, Option<CertifiedStreamSlice>) {
-            let unpacked = UnpackedStreamSlice::try_from(certified_slice.clone())
+            let unpacked = UnpackedStreamSlice::decode(certified_slice.clone(), CURRENT_CERTIFICATION_VERSION)
                 .expect("failed to unpack certified stream");
 
             let (prefix, postfix) = unpacked.take_prefix(msg_limit, byte_limit).unwrap();
@@ -139,7 +138,7 @@ proptest! {
             }
 
             // And that a longer prefix would have gone over one the limits.
-            let unpacked = UnpackedStreamSlice::try_from(certified_slice.clone()).unwrap();
+            let unpacked = UnpackedStreamSlice::decode(certified_slice.clone(), CURRENT_CERTIFICATION_VERSION).unwrap();
             match prefix.as_ref() {
                 Some(prefix) if postfix.is_some() => {
                     let prefix_len = testing::slice_len(prefix);
@@ -205,7 +204,7 @@ proptest! {
 
                 // Taking an unlimited number of messages with a byte limit just under the byte size
                 // should result in `msg_count - 1` messages and 1 message left over.
-                let byte_size = UnpackedStreamSlice::try_from(certified_slice.clone())
+                let byte_size = UnpackedStreamSlice::decode(certified_slice.clone(), CURRENT_CERTIFICATION_VERSION)
                     .expect("failed to unpack certified stream").count_bytes();
                 assert_opt_slice_pairs_eq(
                     split(&fixture, DST_SUBNET, from, msg_count - 1, 1),
@@ -252,7 +251,7 @@ proptest! {
             expected: InvalidSlice,
             invalid_slice: CertifiedStreamSlice,
         ) {
-            match UnpackedStreamSlice::try_from(invalid_slice) {
+            match UnpackedStreamSlice::decode(invalid_slice, CURRENT_CERTIFICATION_VERSION) {
                 Err(CertifiedSliceError::InvalidPayload(reason)) => assert_eq!(expected, reason),
                 actual => panic!(
                     "Expected Err(CertifiedSliceError::InvalidPayload((\"{:?}\")), got {:?}",
@@ -338,7 +337,7 @@ proptest! {
                     // Valid slice, but mismatching withess.
 
                     // Unpacking will succeed, as we're not validating against the witness.
-                    let unpacked = UnpackedStreamSlice::try_from(slice_with_extra_message.clone()).unwrap();
+                    let unpacked = UnpackedStreamSlice::decode(slice_with_extra_message.clone(), CURRENT_CERTIFICATION_VERSION).unwrap();
 
                     // But GC should fail.
                     match unpacked.garbage_collect(&ExpectedIndices {
@@ -353,7 +352,7 @@ proptest! {
                     }
 
                     // As should taking a prefix.
-                    let unpacked = UnpackedStreamSlice::try_from(slice_with_extra_message).unwrap();
+                    let unpacked = UnpackedStreamSlice::decode(slice_with_extra_message, CURRENT_CERTIFICATION_VERSION).unwrap();
                     match unpacked.take_prefix(Some(1), None) {
                         Err(CertifiedSliceError::WitnessPruningFailed(_)) => {}
                         actual => panic!(
@@ -363,7 +362,7 @@ proptest! {
                     }
                 } else {
                     // Invalid slice, begin index before stream begin index. Unpacking should fail.
-                    match UnpackedStreamSlice::try_from(slice_with_extra_message) {
+                    match UnpackedStreamSlice::decode(slice_with_extra_message, CURRENT_CERTIFICATION_VERSION) {
                         Err(CertifiedSliceError::InvalidPayload(InvalidSlice::InvalidBounds)) => {}
                         actual => panic!(
                             "Expected Err(CertifiedSliceError::InvalidPayload(InvalidBounds), got {:?}",
@@ -405,7 +404,7 @@ proptest! {
         /// `UnpackedStreamSlice` unpacked from `slice` is within 5% of the
         /// byte size of `slice`.
         fn assert_good_estimate(slice: CertifiedStreamSlice) {
-            let unpacked = UnpackedStreamSlice::try_from(slice.clone())
+            let unpacked = UnpackedStreamSlice::decode(slice.clone(), CURRENT_CERTIFICATION_VERSION)
                 .expect("failed to unpack certified stream");
 
             let packed_payload_bytes = slice.payload.len();
@@ -435,18 +434,18 @@ proptest! {
         });
     }
 
-    /// Verifies that `certified_slice_count_bytes(&slice)` (used in payload
-    /// validation) produces the exact same estimate as
-    /// `UnpackedStreamSlice::try_from(slice).unwrap().count_bytes()` (used in
-    /// payload building).
+    /// Verifies that `certified_slice_count_bytes(&slice, version)` (used in
+    /// payload validation) produces the exact same estimate as
+    /// `UnpackedStreamSlice::decode(slice, version).unwrap().count_bytes()`
+    /// (used in payload building).
     #[test]
     fn matching_count_bytes((stream, from, msg_count) in arb_stream_slice(2, 100)) {
         /// Verifies that the two ways of computing a byte size estimate produce
         /// the exact same result.
         fn assert_matching_count_bytes(slice: CertifiedStreamSlice) {
-            let fn_estimate = certified_slice_count_bytes(&slice)
+            let fn_estimate = certified_slice_count_bytes(&slice, CURRENT_CERTIFICATION_VERSION)
                 .expect("failed to unpack certified stream");
-            let unpacked = UnpackedStreamSlice::try_from(slice)
+            let unpacked = UnpackedStreamSlice::decode(slice, CURRENT_CERTIFICATION_VERSION)
                 .expect("failed to unpack certified stream");
 
             assert_eq!(unpacked.count_bytes(), fn_estimate);
@@ -522,7 +521,7 @@ proptest! {
             assert!(take_slice(SRC_SUBNET, &mut pool).is_none());
 
             // Populate the pool.
-            pool.put(SRC_SUBNET, slice.clone()).unwrap();
+            pool.put(SRC_SUBNET, slice.clone(), CURRENT_CERTIFICATION_VERSION).unwrap();
 
             // Peers and stream positions still not set.
             assert!(pool.peers().next().is_none());
@@ -538,7 +537,7 @@ proptest! {
             // Create a fresh, populated pool.
             let mut pool = CertifiedSlicePool::new(&fixture.metrics);
             pool.garbage_collect(btreemap! {SRC_SUBNET => ExpectedIndices::default()});
-            pool.put(SRC_SUBNET, slice.clone()).unwrap();
+            pool.put(SRC_SUBNET, slice.clone(), CURRENT_CERTIFICATION_VERSION).unwrap();
 
             // Sanity check that the slice is in the pool.
             {
@@ -548,7 +547,7 @@ proptest! {
 
                 pool.observe_pool_size_bytes();
                 assert_eq!(
-                    UnpackedStreamSlice::try_from(slice.clone()).unwrap().count_bytes(),
+                    UnpackedStreamSlice::decode(slice.clone(), CURRENT_CERTIFICATION_VERSION).unwrap().count_bytes(),
                     fixture.fetch_pool_size_bytes()
                 );
             }
@@ -646,7 +645,7 @@ proptest! {
                 msg_count - 2);
 
             // ...but putting back the original slice now should replace it (from the earlier index).
-            pool.put(SRC_SUBNET, slice).unwrap();
+            pool.put(SRC_SUBNET, slice, CURRENT_CERTIFICATION_VERSION).unwrap();
             assert_has_slice(SRC_SUBNET, &mut pool, Some(earlier_indices), Some(earlier_message_index), msg_count - 1);
 
             assert_eq!(
@@ -687,7 +686,7 @@ proptest! {
 
             let fixture = StateManagerFixture::new(log.clone()).with_stream(DST_SUBNET, stream.clone());
             let slice = fixture.get_slice(DST_SUBNET, from, msg_count);
-            let slice_bytes = UnpackedStreamSlice::try_from(slice.clone()).unwrap().count_bytes();
+            let slice_bytes = UnpackedStreamSlice::decode(slice.clone(), CURRENT_CERTIFICATION_VERSION).unwrap().count_bytes();
 
             // Stream position guaranteed to yield a slice, even if empty.
             let stream_position = ExpectedIndices{
@@ -698,7 +697,7 @@ proptest! {
             let mut pool = CertifiedSlicePool::new(&fixture.metrics);
 
             // `append()` with no slice present is equivalent to `put()`.
-            pool.append(SRC_SUBNET, slice.clone()).unwrap();
+            pool.append(SRC_SUBNET, slice.clone(), CURRENT_CERTIFICATION_VERSION).unwrap();
             // Note: this takes the slice and updates the cached stream position to its end indices.
             assert_opt_slices_eq(
                 Some(slice.clone()),
@@ -708,7 +707,7 @@ proptest! {
             );
 
             // Appending the same slice after taking it should be a no-op.
-            pool.append(SRC_SUBNET, slice).unwrap();
+            pool.append(SRC_SUBNET, slice, CURRENT_CERTIFICATION_VERSION).unwrap();
             let mut stream_position = ExpectedIndices{
                 message_index: to,
                 signal_index: stream.signals_end(),
@@ -724,10 +723,10 @@ proptest! {
             let new_fixture = StateManagerFixture::new(log).with_stream(DST_SUBNET, stream.clone());
             let new_slice = new_fixture.get_slice(DST_SUBNET, from, msg_count);
 
-            pool.append(SRC_SUBNET, new_slice).unwrap();
+            pool.append(SRC_SUBNET, new_slice, CURRENT_CERTIFICATION_VERSION).unwrap();
 
             let empty_slice = new_fixture.get_slice(DST_SUBNET, to, 0);
-            let empty_slice_bytes = UnpackedStreamSlice::try_from(empty_slice.clone()).unwrap().count_bytes();
+            let empty_slice_bytes = UnpackedStreamSlice::decode(empty_slice.clone(), CURRENT_CERTIFICATION_VERSION).unwrap().count_bytes();
             assert_opt_slices_eq(
                 Some(empty_slice),
                 pool.take_slice(SRC_SUBNET, Some(&stream_position), None, None)
@@ -798,14 +797,14 @@ proptest! {
 
             // Append an empty slice.
             let empty_prefix_slice = fixture.get_slice(DST_SUBNET, from, 0);
-            pool.append(SRC_SUBNET, empty_prefix_slice).unwrap();
+            pool.append(SRC_SUBNET, empty_prefix_slice, CURRENT_CERTIFICATION_VERSION).unwrap();
             assert_matches!(
                 pool.slice_stats(SRC_SUBNET),
                 (None, None, 0, byte_size) if byte_size > 0
             );
 
             // Appending the full slice should pool the full slice.
-            pool.append(SRC_SUBNET, slice.clone()).unwrap();
+            pool.append(SRC_SUBNET, slice.clone(), CURRENT_CERTIFICATION_VERSION).unwrap();
             assert_matches!(
                 pool.slice_stats(SRC_SUBNET),
                 (None, Some(messages_begin), count, byte_size)
@@ -848,7 +847,7 @@ proptest! {
 
             // Pool first half of slice.
             let prefix_slice = fixture.get_slice(DST_SUBNET, from, prefix_len);
-            pool.put(SRC_SUBNET, prefix_slice).unwrap();
+            pool.put(SRC_SUBNET, prefix_slice, CURRENT_CERTIFICATION_VERSION).unwrap();
             assert_matches!(
                 pool.slice_stats(SRC_SUBNET),
                 (None, Some(messages_begin), count, byte_size)
@@ -861,7 +860,7 @@ proptest! {
             let overlapping_suffix_slice =
                 fixture.get_partial_slice(DST_SUBNET, from, mid.decrement(), suffix_len + 1);
             assert_matches!(
-                pool.append(SRC_SUBNET, overlapping_suffix_slice),
+                pool.append(SRC_SUBNET, overlapping_suffix_slice, CURRENT_CERTIFICATION_VERSION),
                 Err(CertifiedSliceError::InvalidAppend(InvalidAppend::IndexMismatch))
             );
             // Pooled slice stays unchanged.
@@ -878,7 +877,7 @@ proptest! {
                 let gapped_suffix_slice =
                     fixture.get_partial_slice(DST_SUBNET, from, mid.increment(), suffix_len - 1);
                 assert_matches!(
-                    pool.append(SRC_SUBNET, gapped_suffix_slice),
+                    pool.append(SRC_SUBNET, gapped_suffix_slice, CURRENT_CERTIFICATION_VERSION),
                     Err(CertifiedSliceError::InvalidAppend(InvalidAppend::IndexMismatch))
                 );
                 // Pooled slice stays unchanged.
@@ -894,7 +893,7 @@ proptest! {
             // Appending the matching second half should succeed.
             let suffix_slice =
                 fixture.get_partial_slice(DST_SUBNET, from, mid, suffix_len);
-            pool.append(SRC_SUBNET, suffix_slice).unwrap();
+            pool.append(SRC_SUBNET, suffix_slice, CURRENT_CERTIFICATION_VERSION).unwrap();
             // And result in the full slice being pooled.
             assert_matches!(
                 pool.slice_stats(SRC_SUBNET),
@@ -1,6 +1,7 @@
 // Not all tests use all fixtures, prevent spurious warnings.
 #![allow(dead_code)]
 
+use ic_canonical_state::CURRENT_CERTIFICATION_VERSION;
 use ic_config::state_manager::Config;
 use ic_interfaces::{
     certification::Verifier, certified_stream_store::CertifiedStreamStore, state_manager::*,
@@ -32,7 +33,7 @@ use ic_types::{
     Height, SubnetId,
 };
 use proptest::prelude::*;
-use std::{convert::TryFrom, sync::Arc};
+use std::sync::Arc;
 use tempfile::{Builder, TempDir};
 
 pub const OWN_SUBNET: SubnetId = SUBNET_42;
@@ -246,7 +247,7 @@ fn opt_slice_to_string(slice: Option<CertifiedStreamSlice>) -> String {
 }
 
 fn slice_to_string(slice: CertifiedStreamSlice) -> String {
-    UnpackedStreamSlice::try_from(slice.clone())
+    UnpackedStreamSlice::decode(slice.clone(), CURRENT_CERTIFICATION_VERSION)
         .map(|unpacked| format!("{:?}", unpacked))
         .unwrap_or(format!("{:?}", slice))
 }
@@ -1,7 +1,10 @@
 use async_trait::async_trait;
+use ic_canonical_state::CURRENT_CERTIFICATION_VERSION;
 use ic_interfaces::{
-    certified_stream_store::CertifiedStreamStore, messaging::XNetPayloadBuilder,
+    certified_stream_store::CertifiedStreamStore,
+    messaging::XNetPayloadBuilder,
     registry::RegistryClient,
+    state_manager::{CertificationScope, StateManager},
 };
 use ic_logger::ReplicaLogger;
 use ic_messaging::{
@@ -30,6 +33,7 @@ use ic_test_utilities::{
     mock_time,
     registry::SubnetRecordBuilder,
     state::arb_stream,
+    state_manager::FakeStateManager,
     types::ids::{
         NODE_1, NODE_2, NODE_3, NODE_4, NODE_42, NODE_5, SUBNET_1, SUBNET_2, SUBNET_3, SUBNET_4,
         SUBNET_5,
@@ -45,7 +49,6 @@ use maplit::btreemap;
 use proptest::prelude::*;
 use std::{
     collections::BTreeMap,
-    convert::TryFrom,
     sync::{Arc, Mutex},
 };
 use tempfile::TempDir;
@@ -133,9 +136,10 @@ impl XNetPayloadBuilderFixture {
         log: &ReplicaLogger,
     ) -> usize {
         let certified_slice = in_slice(stream, from, from, msg_count, log);
-        let slice_size_bytes = UnpackedStreamSlice::try_from(certified_slice.clone())
-            .unwrap()
-            .count_bytes();
+        let slice_size_bytes =
+            UnpackedStreamSlice::decode(certified_slice.clone(), CURRENT_CERTIFICATION_VERSION)
+                .unwrap()
+                .count_bytes();
         pool_slice(&self.xnet_payload_builder, subnet_id, certified_slice);
         slice_size_bytes
     }
@@ -625,6 +629,16 @@ enum FakeXNetClientError {
     NoContent,
 }
 
+/// Returns a `StateManager` whose latest state has the current certification
+/// version, for the pool refill task to decode pulled slices with.
+fn local_state_manager() -> Arc<FakeStateManager> {
+    let state_manager = FakeStateManager::new();
+    let (_height, mut state) = state_manager.take_tip();
+    state.metadata.certification_version = CURRENT_CERTIFICATION_VERSION;
+    state_manager.commit_and_certify(state, Height::new(1), CertificationScope::Metadata);
+    Arc::new(state_manager)
+}
+
 proptest! {
     /// Tests refilling an empty pool.
     #[test]
@@ -666,6 +680,7 @@ proptest! {
 
             let refill_handle = PoolRefillTask::start(
                 Arc::clone(&pool),
+                local_state_manager(),
                 endpoint_resolver,
                 xnet_client,
                 runtime.handle().clone(),
@@ -735,10 +750,10 @@ proptest! {
             let pool = Arc::new(Mutex::new(CertifiedSlicePool::new(&metrics_registry)));
             let prefix_msg_count = (from - stream_begin).get() as usize;
             let prefix = in_slice(stream_begin, stream_begin, prefix_msg_count);
-            let prefix_size_bytes = UnpackedStreamSlice::try_from(prefix.clone()).unwrap().count_bytes();
+            let prefix_size_bytes = UnpackedStreamSlice::decode(prefix.clone(), CURRENT_CERTIFICATION_VERSION).unwrap().count_bytes();
             {
                 let mut pool = pool.lock().unwrap();
-                pool.put(REMOTE_SUBNET, prefix).unwrap();
+                pool.put(REMOTE_SUBNET, prefix, CURRENT_CERTIFICATION_VERSION).unwrap();
                 pool.garbage_collect(btreemap! [REMOTE_SUBNET => stream_position.clone()]);
             }
 
@@ -762,6 +777,7 @@ proptest! {
 
             let refill_handle = PoolRefillTask::start(
                 Arc::clone(&pool),
+                local_state_manager(),
                 endpoint_resolver,
                 xnet_client,
                 runtime.handle().clone(),
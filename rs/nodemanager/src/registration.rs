@@ -422,7 +422,7 @@ fn protobuf_to_vec<M: Message>(entry: M) -> Vec<u8> {
 mod tests {
     use super::*;
     use ic_test_utilities::with_test_replica_logger;
-    use ic_types::transport::TransportFlowConfig;
+    use ic_types::transport::{TransportBackend, TransportFlowConfig};
 
     #[test]
     fn default_http_config_endpoint_succeeds() {
@@ -449,6 +449,7 @@ mod tests {
                     queue_size: 1,
                 },
             ],
+            backend: TransportBackend::TlsOverTcp,
         };
 
         with_test_replica_logger(|log| {
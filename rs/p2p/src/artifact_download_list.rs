@@ -102,6 +102,8 @@ pub(crate) trait ArtifactDownloadList: Send + Sync {
 pub(crate) struct ArtifactTracker {
     /// Artifact ID
     pub artifact_id: ArtifactId,
+    /// Instant when the download was scheduled.
+    pub requested_instant: Instant,
     /// Time limit for the artifact download.
     expiry_instant: Instant,
     /// The artifact, which implements the `Chunkable` interface.
@@ -181,6 +183,7 @@ impl ArtifactDownloadList for ArtifactDownloadListImpl {
                     advert.integrity_hash.clone(),
                     ArtifactTracker {
                         artifact_id: artifact_id.clone(),
+                        requested_instant,
                         expiry_instant,
                         chunkable: chunk_tracker,
                         peer_id,
@@ -71,7 +71,7 @@ use ic_protobuf::p2p::v1 as pb;
 use ic_protobuf::proxy::ProtoProxy;
 use ic_types::{
     artifact::{Artifact, ArtifactId},
-    chunkable::{ArtifactErrorCode, ChunkId},
+    chunkable::{ArtifactChunkData, ArtifactErrorCode, ChunkId},
     crypto::CryptoHash,
     p2p::GossipAdvert,
     transport::{FlowTag, TransportClientType, TransportPayload},
@@ -87,8 +87,9 @@ use crate::{
     event_handler::P2PEventHandlerControl,
     gossip_protocol::{
         GossipAdvertAction, GossipAdvertSendRequest, GossipChunk, GossipChunkRequest,
-        GossipMessage, GossipRetransmissionRequest, Percentage,
+        GossipMessage, GossipPush, GossipRetransmissionRequest, Percentage,
     },
+    gossip_strategy::{artifact_type, build_gossip_strategy, GossipMode, GossipStrategy},
     metrics::{DownloadManagementMetrics, DownloadPrioritizerMetrics},
     utils::FlowMapper,
     P2PError, P2PErrorCode, P2PResult,
@@ -102,7 +103,7 @@ use lru::LruCache;
 use std::{
     collections::HashMap,
     error::Error,
-    sync::{Arc, Mutex, RwLock},
+    sync::{Arc, Mutex, MutexGuard, RwLock},
     time::{Instant, SystemTime},
 };
 
@@ -139,6 +140,10 @@ pub(crate) trait DownloadManager {
     /// ID.
     fn on_chunk(&self, gossip_chunk: GossipChunk, peer_id: NodeId);
 
+    /// The method reacts to an advert pushed together with the artifact chunk
+    /// by the peer with the given node ID.
+    fn on_push(&self, gossip_push: GossipPush, peer_id: NodeId);
+
     /// The method reacts to a disconnect event event for the peer with the
     /// given node ID.
     fn peer_connection_down(&self, peer_id: NodeId);
@@ -303,6 +308,8 @@ pub(crate) struct DownloadManagerImpl {
     metrics: DownloadManagementMetrics,
     /// The *Gossip* configuration.
     gossip_config: GossipConfig,
+    /// The strategy deciding which artifacts are pushed to peers.
+    gossip_strategy: Box<dyn GossipStrategy>,
     /// The cache that is used to check if an artifact has been downloaded
     /// recently.
     receive_check_caches: RwLock<HashMap<NodeId, ReceiveCheckCache>>,
@@ -331,7 +338,17 @@ impl DownloadManager for DownloadManagerImpl {
             .adverts_by_action
             .with_label_values(&[label])
             .inc_by(peers.len() as u64);
-        self.send_advert_to_peer_list(advert_request.advert, peers);
+
+        let advert = advert_request.advert;
+        match self.gossip_strategy.gossip_mode(&advert) {
+            GossipMode::Push => match self.get_push_chunk(&advert) {
+                Some(chunk) => self.send_push_to_peer_list(GossipPush { advert, chunk }, peers),
+                // The artifact is gone or consists of more than one chunk, let
+                // the peers request it.
+                None => self.send_advert_to_peer_list(advert, peers),
+            },
+            GossipMode::Pull => self.send_advert_to_peer_list(advert, peers),
+        }
     }
 
     /// The method downloads chunks for adverts with the highest priority from
@@ -340,13 +357,7 @@ impl DownloadManager for DownloadManagerImpl {
         // The precondition ensured by gossip_protocol.on_advert() is that
         // the corresponding artifact is not in the artifact pool.
         // Check if we have seen this artifact before:
-        if self
-            .receive_check_caches
-            .read()
-            .unwrap()
-            .values()
-            .any(|cache| cache.contains(&gossip_advert.integrity_hash))
-        {
+        if self.received_recently(&gossip_advert.integrity_hash) {
             // If yes, the advert is ignored.
             return;
         }
@@ -438,18 +449,9 @@ impl DownloadManager for DownloadManagerImpl {
                 integrity_hash: gossip_chunk.integrity_hash.clone(),
                 chunk_id: gossip_chunk.chunk_id,
             }) {
-                let artifact_type = match &gossip_chunk.artifact_id {
-                    ArtifactId::ConsensusMessage(_) => "consensus",
-                    ArtifactId::IngressMessage(_) => "ingress",
-                    ArtifactId::CertificationMessage(_) => "certification",
-                    ArtifactId::DkgMessage(_) => "dkg",
-                    ArtifactId::EcdsaMessage(_) => "ecdsa",
-                    ArtifactId::FileTreeSync(_) => "file_tree_sync",
-                    ArtifactId::StateSync(_) => "state_sync",
-                };
                 self.metrics
                     .chunk_delivery_time
-                    .with_label_values(&[artifact_type])
+                    .with_label_values(&[artifact_type(&gossip_chunk.artifact_id)])
                     .observe(tracker.requested_instant.elapsed().as_millis() as f64);
             } else {
                 trace!(
@@ -463,207 +465,70 @@ impl DownloadManager for DownloadManagerImpl {
             }
         }
 
-        // Check if the request has been served. If an error is
-        // returned, the artifact chunk cannot be served by this peer.
-        // In this case, the chunk download is marked as failed but
-        // the advert is still being tracked for other chunks (as this might be useful
-        // for StateSync). This situation is possible if one of the replicas
-        // misses a part of the artifact due to corruption or progress
-        // (if the peer has a higher executed height now, it might
-        // have changed its state and thus may only be able to serve
-        // some but not all chunks of the artifact the node is
-        // interested in).
-        // Allowing the rest of the artifact to be downloaded and
-        // skipping only the affected chunk increase overall
-        // resilience.
-        if let Err(error) = gossip_chunk.artifact_chunk {
-            self.metrics.chunks_not_served_from_peer.inc();
-            trace!(
-                self.log,
-                "Chunk download failed for artifact{:?} chunk {:?} from peer {:?}",
-                gossip_chunk.artifact_id,
-                gossip_chunk.chunk_id,
-                peer_id
-            );
-            if let P2PErrorCode::NotFound = error.p2p_error_code {
-                // If the artifact is not found on the sender's side, drop the
-                // advert from the context for this peer to prevent it from
-                // being requested again from this peer.
-                self.delete_advert_from_peer(
-                    peer_id,
-                    &gossip_chunk.artifact_id,
-                    &gossip_chunk.integrity_hash,
-                    self.artifacts_under_construction
-                        .write()
-                        .unwrap()
-                        .deref_mut(),
-                )
-            }
-            return;
-        }
-
-        // Increment the received chunks counter.
-        self.metrics.chunks_received.inc();
-
-        // Feed the chunk to artifact tracker-
-        let mut artifacts_under_construction = self.artifacts_under_construction.write().unwrap();
+        self.process_chunk(gossip_chunk, peer_id, GossipMode::Pull, current_peers);
+    }
 
-        // Find the tracker to feed the chunk.
-        let artifact_tracker =
-            artifacts_under_construction.get_tracker(&gossip_chunk.integrity_hash);
-        if artifact_tracker.is_none() {
-            trace!(
+    /// The method deduplicates the pushed advert like any other advert and, if
+    /// the artifact is neither received recently nor being downloaded, feeds
+    /// the pushed chunk to a new download of the artifact.
+    ///
+    /// If the download cannot be scheduled (e.g., because the peer exceeded its
+    /// quota), the pushed chunk is dropped and the artifact is requested as if
+    /// only the advert had been received.
+    ///
+    /// Pushes whose chunk does not belong to the advertised artifact are
+    /// dropped as protocol violations.
+    fn on_push(&self, gossip_push: GossipPush, peer_id: NodeId) {
+        self.metrics.pushes_received.inc();
+        let GossipPush { advert, chunk } = gossip_push;
+        if chunk.artifact_id != advert.artifact_id || chunk.integrity_hash != advert.integrity_hash
+        {
+            warn!(
+                every_n_seconds => 30,
                 self.log,
-                "Chunk received although artifact is complete or dropped from under construction list (e.g., due to priority function change) {:?} chunk {:?} from peer {:?}",
-                gossip_chunk.artifact_id,
-                gossip_chunk.chunk_id,
-                peer_id.get()
-            );
-            let _ = self.prioritizer.delete_advert_from_peer(
-                &gossip_chunk.artifact_id,
-                &gossip_chunk.integrity_hash,
+                "Dropping push from node {:?}: chunk {:?} does not match advert {:?}",
                 peer_id,
-                AdvertTrackerFinalAction::Abort,
+                chunk.artifact_id,
+                advert.artifact_id
             );
-            self.metrics.chunks_redundant_residue.inc();
+            self.metrics.pushes_invalid.inc();
             return;
         }
-        let artifact_tracker = artifact_tracker.unwrap();
-
-        // Feed the chunk to the tracker.
-        let completed_artifact = match artifact_tracker
-            .chunkable
-            .add_chunk(gossip_chunk.artifact_chunk.unwrap())
-        {
-            // Artifact assembly is complete.
-            Ok(artifact) => Some(artifact),
-            Err(ArtifactErrorCode::ChunksMoreNeeded) => None,
-            Err(ArtifactErrorCode::ChunkVerificationFailed) => {
-                trace!(
-                    self.log,
-                    "Chunk verification failed for artifact{:?} chunk {:?} from peer {:?}",
-                    gossip_chunk.artifact_id,
-                    gossip_chunk.chunk_id,
-                    peer_id
-                );
-                self.metrics.chunks_verification_failed.inc();
-                None
-            }
-        };
-
-        // Return if the artifact is complete.
-        if completed_artifact.is_none() {
+        if self.received_recently(&advert.integrity_hash) {
+            self.metrics.pushes_redundant.inc();
             return;
         }
+        self.on_advert(advert.clone(), peer_id);
 
-        // Record metrics.
-        self.metrics.artifacts_received.inc();
-
-        let completed_artifact = completed_artifact.unwrap();
-
-        // Check whether the artifact matches the advertised integrity hash.
-        let advert = match self.prioritizer.get_advert_from_peer(
-            &gossip_chunk.artifact_id,
-            &gossip_chunk.integrity_hash,
-            &peer_id,
-        ) {
-            Ok(Some(advert)) => advert,
-            Err(_) | Ok(None) => {
-                trace!(
-                self.log,
-                "The advert for {:?} chunk {:?} from peer {:?} was not found, seems the peer never sent it.",
-                gossip_chunk.artifact_id,
-                gossip_chunk.chunk_id,
-                peer_id.get()
-            );
-                return;
-            }
-        };
-        // Check if the artifact's integrity hash matches the advertised hash
-        // This construction to compute the integrity hash over all variants of an enum
-        // may be updated in the future.
-        let expected_ih = match &completed_artifact {
-            Artifact::ConsensusMessage(msg) => ic_crypto::crypto_hash(msg).get(),
-            Artifact::IngressMessage(msg) => ic_crypto::crypto_hash(msg).get(),
-            Artifact::CertificationMessage(msg) => ic_crypto::crypto_hash(msg).get(),
-            Artifact::DkgMessage(msg) => ic_crypto::crypto_hash(msg).get(),
-            Artifact::EcdsaMessage(msg) => ic_crypto::crypto_hash(msg).get(),
-            // FileTreeSync is not of ArtifactKind kind, and it's used only for testing.
-            // Thus, we make up the integrity_hash.
-            Artifact::FileTreeSync(_msg) => CryptoHash(vec![]),
-            Artifact::StateSync(msg) => ic_crypto::crypto_hash(msg).get(),
-        };
-
-        if expected_ih != advert.integrity_hash {
-            warn!(
-                self.log,
-                "The integrity hash for {:?} from peer {:?} does not match. Expected {:?}, got {:?}.",
-                gossip_chunk.artifact_id,
-                peer_id.get(),
-                expected_ih,
-                advert.integrity_hash;
-            );
-            self.metrics.integrity_hash_check_failed.inc();
-
-            // The advert is deleted from this particular peer. Gossip may fetch the
-            // artifact again from another peer.
-            let _ = self.prioritizer.delete_advert_from_peer(
-                &gossip_chunk.artifact_id,
-                &gossip_chunk.integrity_hash,
-                peer_id,
-                AdvertTrackerFinalAction::Abort,
-            );
+        let current_peers = self.current_peers.lock().unwrap();
+        if !current_peers.contains_key(&peer_id) {
             return;
         }
-
-        // Add the artifact hash to the receive check set.
-        let charged_peer = artifact_tracker.peer_id;
-        self.receive_check_caches
-            .write()
-            .unwrap()
-            .get_mut(&charged_peer)
-            .unwrap()
-            .put(advert.integrity_hash.clone(), ());
-
-        // The artifact is complete and the integrity hash is okay.
-        // Clean up the adverts for all peers:
-        let _ = self.prioritizer.delete_advert(
-            &gossip_chunk.artifact_id,
-            &gossip_chunk.integrity_hash,
-            AdvertTrackerFinalAction::Success,
-        );
-        artifacts_under_construction.remove_tracker(&gossip_chunk.integrity_hash);
-
-        // Drop the locks before calling client callbacks.
-        std::mem::drop(artifacts_under_construction);
-        std::mem::drop(current_peers);
-
-        // Client callbacks.
-        trace!(
-            self.log,
-            "Node-{:?} received artifact from Node-{:?} ->{:?}",
-            self.node_id,
-            peer_id,
-            gossip_chunk.artifact_id
-        );
-        match self
-            .artifact_manager
-            .on_artifact(completed_artifact, advert, &peer_id)
         {
-            Ok(_) => (),
-            // If this Replica is running an unexpected version, it will log
-            // an unhelpfully large volume of `ArtifactReplicaVersionError`s.
-            // Here we set the log rate at a more appropriate level.
-            Err(ArtifactPoolError(ArtifactReplicaVersionError(err))) => warn!(
-                every_n_seconds => 5,
-                self.log,
-                "Artifact is not processed successfully by Artifact Manager: {:?}", err
-            ),
-            Err(err) => warn!(
-                self.log,
-                "Artifact is not processed successfully by Artifact Manager: {:?}", err
-            ),
+            let mut artifacts_under_construction =
+                self.artifacts_under_construction.write().unwrap();
+            if artifacts_under_construction
+                .get_tracker(&advert.integrity_hash)
+                .is_some()
+            {
+                self.metrics.pushes_redundant.inc();
+                return;
+            }
+            if artifacts_under_construction
+                .schedule_download(
+                    peer_id,
+                    &advert,
+                    &self.gossip_config,
+                    1,
+                    self.artifact_manager.as_ref(),
+                )
+                .is_none()
+            {
+                return;
+            }
         }
+
+        self.process_chunk(chunk, peer_id, GossipMode::Push, current_peers);
     }
 
     /// The method reacts to a disconnect event event for the peer with the
@@ -869,6 +734,7 @@ impl DownloadManagerImpl {
         let transport_client_type = TransportClientType::P2P;
         let gossip_config =
             crate::event_handler::fetch_gossip_config(registry_client.clone(), subnet_id);
+        let gossip_strategy = build_gossip_strategy(&gossip_config, &log);
 
         let current_peers = Arc::new(Mutex::new(PeerContextDictionary::default()));
         let peer_manager = Arc::new(PeerManagerImpl::new(
@@ -900,6 +766,7 @@ impl DownloadManagerImpl {
             log,
             metrics: DownloadManagementMetrics::new(metrics_registry),
             gossip_config,
+            gossip_strategy,
             receive_check_caches: RwLock::new(HashMap::new()),
             pfn_invocation_instant: Mutex::new(Instant::now()),
             registry_refresh_instant: Mutex::new(Instant::now()),
@@ -909,6 +776,224 @@ impl DownloadManagerImpl {
         download_manager
     }
 
+    /// The method adds the given chunk received from the peer with the given
+    /// node ID to the corresponding artifact under construction and hands the
+    /// artifact over to the artifact manager once it is complete.
+    fn process_chunk(
+        &self,
+        gossip_chunk: GossipChunk,
+        peer_id: NodeId,
+        mode: GossipMode,
+        current_peers: MutexGuard<'_, PeerContextDictionary>,
+    ) {
+        // Check if the request has been served. If an error is
+        // returned, the artifact chunk cannot be served by this peer.
+        // In this case, the chunk download is marked as failed but
+        // the advert is still being tracked for other chunks (as this might be useful
+        // for StateSync). This situation is possible if one of the replicas
+        // misses a part of the artifact due to corruption or progress
+        // (if the peer has a higher executed height now, it might
+        // have changed its state and thus may only be able to serve
+        // some but not all chunks of the artifact the node is
+        // interested in).
+        // Allowing the rest of the artifact to be downloaded and
+        // skipping only the affected chunk increase overall
+        // resilience.
+        if let Err(error) = gossip_chunk.artifact_chunk {
+            self.metrics.chunks_not_served_from_peer.inc();
+            trace!(
+                self.log,
+                "Chunk download failed for artifact{:?} chunk {:?} from peer {:?}",
+                gossip_chunk.artifact_id,
+                gossip_chunk.chunk_id,
+                peer_id
+            );
+            if let P2PErrorCode::NotFound = error.p2p_error_code {
+                // If the artifact is not found on the sender's side, drop the
+                // advert from the context for this peer to prevent it from
+                // being requested again from this peer.
+                self.delete_advert_from_peer(
+                    peer_id,
+                    &gossip_chunk.artifact_id,
+                    &gossip_chunk.integrity_hash,
+                    self.artifacts_under_construction
+                        .write()
+                        .unwrap()
+                        .deref_mut(),
+                )
+            }
+            return;
+        }
+
+        // Increment the received chunks counter.
+        self.metrics.chunks_received.inc();
+
+        // Feed the chunk to artifact tracker-
+        let mut artifacts_under_construction = self.artifacts_under_construction.write().unwrap();
+
+        // Find the tracker to feed the chunk.
+        let artifact_tracker =
+            artifacts_under_construction.get_tracker(&gossip_chunk.integrity_hash);
+        if artifact_tracker.is_none() {
+            trace!(
+                self.log,
+                "Chunk received although artifact is complete or dropped from under construction list (e.g., due to priority function change) {:?} chunk {:?} from peer {:?}",
+                gossip_chunk.artifact_id,
+                gossip_chunk.chunk_id,
+                peer_id.get()
+            );
+            let _ = self.prioritizer.delete_advert_from_peer(
+                &gossip_chunk.artifact_id,
+                &gossip_chunk.integrity_hash,
+                peer_id,
+                AdvertTrackerFinalAction::Abort,
+            );
+            self.metrics.chunks_redundant_residue.inc();
+            return;
+        }
+        let artifact_tracker = artifact_tracker.unwrap();
+
+        // Feed the chunk to the tracker.
+        let completed_artifact = match artifact_tracker
+            .chunkable
+            .add_chunk(gossip_chunk.artifact_chunk.unwrap())
+        {
+            // Artifact assembly is complete.
+            Ok(artifact) => Some(artifact),
+            Err(ArtifactErrorCode::ChunksMoreNeeded) => None,
+            Err(ArtifactErrorCode::ChunkVerificationFailed) => {
+                trace!(
+                    self.log,
+                    "Chunk verification failed for artifact{:?} chunk {:?} from peer {:?}",
+                    gossip_chunk.artifact_id,
+                    gossip_chunk.chunk_id,
+                    peer_id
+                );
+                self.metrics.chunks_verification_failed.inc();
+                None
+            }
+        };
+
+        // Return if the artifact is complete.
+        if completed_artifact.is_none() {
+            return;
+        }
+
+        // Record metrics.
+        self.metrics.artifacts_received.inc();
+
+        let completed_artifact = completed_artifact.unwrap();
+
+        // Check whether the artifact matches the advertised integrity hash.
+        let advert = match self.prioritizer.get_advert_from_peer(
+            &gossip_chunk.artifact_id,
+            &gossip_chunk.integrity_hash,
+            &peer_id,
+        ) {
+            Ok(Some(advert)) => advert,
+            Err(_) | Ok(None) => {
+                trace!(
+                self.log,
+                "The advert for {:?} chunk {:?} from peer {:?} was not found, seems the peer never sent it.",
+                gossip_chunk.artifact_id,
+                gossip_chunk.chunk_id,
+                peer_id.get()
+            );
+                return;
+            }
+        };
+        // Check if the artifact's integrity hash matches the advertised hash
+        // This construction to compute the integrity hash over all variants of an enum
+        // may be updated in the future.
+        let expected_ih = match &completed_artifact {
+            Artifact::ConsensusMessage(msg) => ic_crypto::crypto_hash(msg).get(),
+            Artifact::IngressMessage(msg) => ic_crypto::crypto_hash(msg).get(),
+            Artifact::CertificationMessage(msg) => ic_crypto::crypto_hash(msg).get(),
+            Artifact::DkgMessage(msg) => ic_crypto::crypto_hash(msg).get(),
+            Artifact::EcdsaMessage(msg) => ic_crypto::crypto_hash(msg).get(),
+            // FileTreeSync is not of ArtifactKind kind, and it's used only for testing.
+            // Thus, we make up the integrity_hash.
+            Artifact::FileTreeSync(_msg) => CryptoHash(vec![]),
+            Artifact::StateSync(msg) => ic_crypto::crypto_hash(msg).get(),
+        };
+
+        if expected_ih != advert.integrity_hash {
+            warn!(
+                self.log,
+                "The integrity hash for {:?} from peer {:?} does not match. Expected {:?}, got {:?}.",
+                gossip_chunk.artifact_id,
+                peer_id.get(),
+                expected_ih,
+                advert.integrity_hash;
+            );
+            self.metrics.integrity_hash_check_failed.inc();
+
+            // The advert is deleted from this particular peer. Gossip may fetch the
+            // artifact again from another peer.
+            let _ = self.prioritizer.delete_advert_from_peer(
+                &gossip_chunk.artifact_id,
+                &gossip_chunk.integrity_hash,
+                peer_id,
+                AdvertTrackerFinalAction::Abort,
+            );
+            return;
+        }
+
+        self.metrics
+            .artifact_delivery_time
+            .with_label_values(&[artifact_type(&gossip_chunk.artifact_id), mode.as_str()])
+            .observe(artifact_tracker.requested_instant.elapsed().as_millis() as f64);
+
+        // Add the artifact hash to the receive check set.
+        let charged_peer = artifact_tracker.peer_id;
+        self.receive_check_caches
+            .write()
+            .unwrap()
+            .get_mut(&charged_peer)
+            .unwrap()
+            .put(advert.integrity_hash.clone(), ());
+
+        // The artifact is complete and the integrity hash is okay.
+        // Clean up the adverts for all peers:
+        let _ = self.prioritizer.delete_advert(
+            &gossip_chunk.artifact_id,
+            &gossip_chunk.integrity_hash,
+            AdvertTrackerFinalAction::Success,
+        );
+        artifacts_under_construction.remove_tracker(&gossip_chunk.integrity_hash);
+
+        // Drop the locks before calling client callbacks.
+        std::mem::drop(artifacts_under_construction);
+        std::mem::drop(current_peers);
+
+        // Client callbacks.
+        trace!(
+            self.log,
+            "Node-{:?} received artifact from Node-{:?} ->{:?}",
+            self.node_id,
+            peer_id,
+            gossip_chunk.artifact_id
+        );
+        match self
+            .artifact_manager
+            .on_artifact(completed_artifact, advert, &peer_id)
+        {
+            Ok(_) => (),
+            // If this Replica is running an unexpected version, it will log
+            // an unhelpfully large volume of `ArtifactReplicaVersionError`s.
+            // Here we set the log rate at a more appropriate level.
+            Err(ArtifactPoolError(ArtifactReplicaVersionError(err))) => warn!(
+                every_n_seconds => 5,
+                self.log,
+                "Artifact is not processed successfully by Artifact Manager: {:?}", err
+            ),
+            Err(err) => warn!(
+                self.log,
+                "Artifact is not processed successfully by Artifact Manager: {:?}", err
+            ),
+        }
+    }
+
     /// This helper method returns a list of tasks to be performed by this timer
     /// invocation.
     fn get_timer_tasks(&self) -> (bool, bool, bool) {
@@ -1082,6 +1167,57 @@ impl DownloadManagerImpl {
         }
     }
 
+    /// The method returns the chunk to push along with the given advert, if the
+    /// advertised artifact consists of a single chunk.
+    fn get_push_chunk(&self, advert: &GossipAdvert) -> Option<GossipChunk> {
+        // Single-chunk artifacts are identified by chunk ID 0.
+        let chunk_id = ChunkId::from(0);
+        let artifact_chunk = self
+            .artifact_manager
+            .get_validated_by_identifier(&advert.artifact_id)?
+            .get_chunk(chunk_id)?;
+        match artifact_chunk.artifact_chunk_data {
+            ArtifactChunkData::UnitChunkData(_) => Some(GossipChunk {
+                artifact_id: advert.artifact_id.clone(),
+                integrity_hash: advert.integrity_hash.clone(),
+                chunk_id,
+                artifact_chunk: Ok(artifact_chunk),
+            }),
+            ArtifactChunkData::SemiStructuredChunkData(_) => None,
+        }
+    }
+
+    /// The method sends the given push to the given list of peers.
+    fn send_push_to_peer_list(&self, gossip_push: GossipPush, peer_ids: Vec<NodeId>) {
+        let message = GossipMessage::Push(gossip_push);
+        let flow_tag = self.flow_mapper.map(&message);
+        for peer_id in peer_ids {
+            self.transport_send(message.clone(), peer_id, flow_tag)
+                .map(|_| self.metrics.pushes_sent.inc())
+                .unwrap_or_else(|_e| {
+                    // Ignore push send failures, like advert send failures.
+                    self.metrics.push_send_failed.inc();
+                });
+            trace!(
+                self.log,
+                "Node-{:?} pushed artifact ->{:?} {:?}",
+                self.node_id,
+                peer_id,
+                message
+            );
+        }
+    }
+
+    /// The method checks whether the artifact with the given integrity hash
+    /// was received recently.
+    fn received_recently(&self, integrity_hash: &CryptoHash) -> bool {
+        self.receive_check_caches
+            .read()
+            .unwrap()
+            .values()
+            .any(|cache| cache.contains(integrity_hash))
+    }
+
     /// The method sends the given chunk requests to the given peer.
     fn send_chunk_requests(&self, requests: Vec<GossipChunkRequest>, peer_id: NodeId) {
         for request in requests {
@@ -2353,6 +2489,81 @@ pub mod tests {
         assert!(new_chunks_to_be_downloaded.is_empty());
     }
 
+    /// This test verifies that a pushed artifact is processed without
+    /// requesting it and that pushes of already received artifacts are
+    /// dropped.
+    #[tokio::test]
+    async fn push_test() {
+        // Initialize the logger and download manager for the test.
+        let logger = p2p_test_setup_logger();
+        let download_manager = new_test_download_manager(2, &logger);
+        let node_id = node_test_id(1);
+        let advert = receive_check_test_create_adverts(0..1).pop().unwrap();
+        let chunk = receive_check_test_create_chunk(
+            ChunkId::from(0),
+            advert.artifact_id.clone(),
+            0,
+            advert.integrity_hash.clone(),
+        );
+        let gossip_push = GossipPush {
+            advert: advert.clone(),
+            chunk,
+        };
+
+        download_manager.on_push(gossip_push.clone(), node_id);
+
+        // The artifact was received without requesting any chunks.
+        let receive_check_caches = download_manager.receive_check_caches.read().unwrap();
+        let cache = &receive_check_caches.get(&node_id).unwrap();
+        assert!(cache.contains(&advert.integrity_hash));
+        std::mem::drop(receive_check_caches);
+        assert!(download_manager
+            .download_next_compute_work(node_id)
+            .unwrap()
+            .is_empty());
+
+        // Pushing the same artifact again has no effect.
+        download_manager.on_push(gossip_push, node_id);
+        assert_eq!(2, download_manager.metrics.pushes_received.get());
+        assert_eq!(1, download_manager.metrics.pushes_redundant.get());
+    }
+
+    /// This test verifies that pushes with a chunk that does not belong to the
+    /// advertised artifact are dropped.
+    #[tokio::test]
+    async fn push_with_mismatched_chunk_test() {
+        // Initialize the logger and download manager for the test.
+        let logger = p2p_test_setup_logger();
+        let download_manager = new_test_download_manager(2, &logger);
+        let node_id = node_test_id(1);
+        let mut adverts = receive_check_test_create_adverts(0..2);
+        let advert = adverts.remove(0);
+        let other_advert = adverts.remove(0);
+        let chunk = receive_check_test_create_chunk(
+            ChunkId::from(0),
+            other_advert.artifact_id.clone(),
+            1,
+            other_advert.integrity_hash.clone(),
+        );
+
+        download_manager.on_push(
+            GossipPush {
+                advert: advert.clone(),
+                chunk,
+            },
+            node_id,
+        );
+        assert_eq!(1, download_manager.metrics.pushes_invalid.get());
+
+        // Neither artifact was received or scheduled for download.
+        assert!(!download_manager.received_recently(&advert.integrity_hash));
+        assert!(!download_manager.received_recently(&other_advert.integrity_hash));
+        assert!(download_manager
+            .download_next_compute_work(node_id)
+            .unwrap()
+            .is_empty());
+    }
+
     /// This test will verify that artifacts with incorrect integrity hashes
     /// will not be processed.
     #[tokio::test]
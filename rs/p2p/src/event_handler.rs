@@ -71,7 +71,7 @@ use crate::{
     advert_utils::AdvertRequestBuilder,
     gossip_protocol::{
         Gossip, GossipAdvertSendRequest, GossipChunk, GossipChunkRequest, GossipMessage,
-        GossipRetransmissionRequest,
+        GossipPush, GossipRetransmissionRequest,
     },
     metrics::EventHandlerMetrics,
     P2PErrorCode, P2PResult,
@@ -165,6 +165,8 @@ enum FlowType {
     Transport,
     /// Send advert variant.
     SendAdvert,
+    /// Push variant.
+    Push,
 }
 
 /// The message sent to the receive threads (in the process_message() loop).
@@ -378,6 +380,8 @@ struct PeerFlows {
     send_advert: PeerFlowQueueMap<GossipAdvertSendRequest>,
     /// The current flows of transport notifications.
     transport: PeerFlowQueueMap<TransportNotification>,
+    /// The current flows of received pushes.
+    push: PeerFlowQueueMap<GossipPush>,
 }
 
 impl PeerFlows {
@@ -388,7 +392,8 @@ impl PeerFlows {
             chunk: PeerFlowQueueMap::<GossipChunk>::new(rt_handle.clone()),
             retransmission: PeerFlowQueueMap::<GossipRetransmissionRequest>::new(rt_handle.clone()),
             send_advert: PeerFlowQueueMap::<GossipAdvertSendRequest>::new(rt_handle.clone()),
-            transport: PeerFlowQueueMap::<TransportNotification>::new(rt_handle),
+            transport: PeerFlowQueueMap::<TransportNotification>::new(rt_handle.clone()),
+            push: PeerFlowQueueMap::<GossipPush>::new(rt_handle),
         }
     }
 
@@ -432,6 +437,11 @@ impl PeerFlows {
                     self.send_advert
                         .start(move |item, _peer_id| c_gossip.broadcast_advert(item));
                 }
+                FlowType::Push => {
+                    self.push.start(move |item, peer_id| {
+                        c_gossip.on_push(item, peer_id);
+                    });
+                }
             }
         }
     }
@@ -455,6 +465,7 @@ impl PeerFlows {
                 FlowType::SendAdvert => self
                     .send_advert
                     .add_node(node_id, channel_config.map[flow_type]),
+                FlowType::Push => self.push.add_node(node_id, channel_config.map[flow_type]),
             };
         }
     }
@@ -470,6 +481,7 @@ impl PeerFlows {
                 FlowType::Retransmission => self.retransmission.stop(),
                 FlowType::Transport => self.transport.stop(),
                 FlowType::SendAdvert => self.send_advert.stop(),
+                FlowType::Push => self.push.stop(),
             };
         }
     }
@@ -506,6 +518,8 @@ pub(crate) const MAX_ADVERT_BUFFER: usize = 100_000;
 pub(crate) const MAX_TRANSPORT_BUFFER: usize = 1000;
 /// The maximum number of buffered retransmission requests.
 pub(crate) const MAX_RETRANSMISSION_BUFFER: usize = 1000;
+/// The maximum number of buffered pushes.
+pub(crate) const MAX_PUSH_BUFFER: usize = 1000;
 
 /// The channel configuration, containing the maximum number of messages for
 /// each flow type.
@@ -532,6 +546,7 @@ impl From<GossipConfig> for ChannelConfig {
                     FlowType::Retransmission => (flow_type, MAX_RETRANSMISSION_BUFFER),
                     FlowType::Transport => (flow_type, MAX_TRANSPORT_BUFFER),
                     FlowType::SendAdvert => (flow_type, MAX_ADVERT_BUFFER),
+                    FlowType::Push => (flow_type, MAX_PUSH_BUFFER),
                 })
                 .collect(),
         }
@@ -703,6 +718,31 @@ impl AsyncTransportEventHandler for P2PEventHandlerImpl {
                     }
                 })
             }
+            GossipMessage::Push(msg) => {
+                let sender = {
+                    let send_map = self.peer_flows.push.send_map.read().unwrap();
+                    send_map
+                        .get(&flow.peer_id)
+                        .ok_or(SendError::EndpointNotFound)?
+                        .clone()
+                };
+                ("Push", {
+                    match sender.try_send(msg) {
+                        Err(e) => {
+                            let msg = match e {
+                                TrySendError::Full(a) => a,
+                                TrySendError::Closed(a) => a,
+                            };
+                            self.metrics.pushes_blocked.inc();
+                            sender
+                                .send(msg)
+                                .await
+                                .map_err(|_| SendError::EndpointClosed)
+                        }
+                        Ok(_) => Ok(()),
+                    }
+                })
+            }
         };
         self.metrics
             .send_message_duration_ms
@@ -940,6 +980,11 @@ pub mod tests {
             TestGossip::increment_or_set(&self.num_chunks, peer_id);
         }
 
+        /// The method is called when a push is received.
+        fn on_push(&self, _gossip_push: GossipPush, _peer_id: NodeId) {
+            unimplemented!()
+        }
+
         /// The method is called when a user ingress message is received.
         fn on_user_ingress(
             &self,
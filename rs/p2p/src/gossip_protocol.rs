@@ -117,6 +117,10 @@ pub trait Gossip {
     /// the artifact manager.
     fn on_chunk(&self, gossip_chunk: Self::GossipChunk, peer_id: Self::NodeId);
 
+    /// The method handles the given advert pushed by the peer with the given
+    /// node ID together with the chunk of the advertised artifact.
+    fn on_push(&self, gossip_push: GossipPush, peer_id: Self::NodeId);
+
     /// The method handles the received user ingress message.
     fn on_user_ingress(
         &self,
@@ -182,6 +186,16 @@ pub struct GossipChunk {
     pub(crate) artifact_chunk: P2PResult<ArtifactChunk>,
 }
 
+/// An advert pushed to a peer together with the only chunk of the advertised
+/// artifact, saving the peer a chunk request round trip.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GossipPush {
+    /// The advert.
+    pub(crate) advert: GossipAdvert,
+    /// The chunk of the advertised artifact.
+    pub(crate) chunk: GossipChunk,
+}
+
 /// This is the message exchanged on the wire with other peers.  This
 /// enum is private to the gossip layer because lower layers like
 /// *Transport* do not need to interpret the content.
@@ -196,6 +210,8 @@ pub(crate) enum GossipMessage {
     Chunk(GossipChunk),
     /// The retransmission request variant.
     RetransmissionRequest(GossipRetransmissionRequest),
+    /// The push variant.
+    Push(GossipPush),
 }
 
 /// Request from artifact manager to send adverts for newly added validated
@@ -443,6 +459,22 @@ impl Gossip for GossipImpl {
         let _ = self.download_manager.download_next(peer_id);
     }
 
+    /// The method handles an advert pushed together with the artifact chunk.
+    ///
+    /// Pushes for artifacts that are available locally are dropped, like
+    /// the corresponding adverts would be.
+    fn on_push(&self, gossip_push: GossipPush, peer_id: NodeId) {
+        if self
+            .artifact_manager
+            .has_artifact(&gossip_push.advert.artifact_id)
+        {
+            return;
+        }
+
+        self.download_manager.on_push(gossip_push, peer_id);
+        let _ = self.download_manager.download_next(peer_id);
+    }
+
     /// The method handles the received user ingress message.
     fn on_user_ingress(
         &self,
@@ -547,6 +579,9 @@ impl From<GossipMessage> for pb::GossipMessage {
             GossipMessage::RetransmissionRequest(r) => Self {
                 body: Some(Body::RetransmissionRequest(r.into())),
             },
+            GossipMessage::Push(p) => Self {
+                body: Some(Body::Push(p.into())),
+            },
         }
     }
 }
@@ -563,6 +598,7 @@ impl TryFrom<pb::GossipMessage> for GossipMessage {
             Body::ChunkRequest(r) => Self::ChunkRequest(r.try_into()?),
             Body::Chunk(c) => Self::Chunk(c.try_into()?),
             Body::RetransmissionRequest(r) => Self::RetransmissionRequest(r.try_into()?),
+            Body::Push(p) => Self::Push(p.try_into()?),
         };
         Ok(message)
     }
@@ -638,6 +674,29 @@ impl TryFrom<pb::GossipChunk> for GossipChunk {
     }
 }
 
+/// A push can be converted into a `pb::GossipPush`.
+impl From<GossipPush> for pb::GossipPush {
+    /// The function converts the given push into the Protobuf equivalent.
+    fn from(gossip_push: GossipPush) -> Self {
+        Self {
+            advert: Some(gossip_push.advert.into()),
+            chunk: Some(gossip_push.chunk.into()),
+        }
+    }
+}
+
+/// A `pb::GossipPush` can be converted into a push.
+impl TryFrom<pb::GossipPush> for GossipPush {
+    type Error = ProxyDecodeError;
+    /// The function attempts to convert a Protobuf push into a GossipPush.
+    fn try_from(gossip_push: pb::GossipPush) -> Result<Self, Self::Error> {
+        Ok(Self {
+            advert: try_from_option_field(gossip_push.advert, "GossipPush.advert")?,
+            chunk: try_from_option_field(gossip_push.chunk, "GossipPush.chunk")?,
+        })
+    }
+}
+
 /// The function returns a new artifact chunk with the given chunk ID
 /// and the same chunk data as the given artifact chunk.
 fn add_chunk_id(artifact_chunk: ArtifactChunk, chunk_id: ChunkId) -> ArtifactChunk {
@@ -0,0 +1,150 @@
+//! Strategies deciding how artifacts are disseminated to peers.
+//!
+//! By default, *Gossip* only sends adverts and waits for peers to request the
+//! advertised artifact chunks. For small artifacts, such as notarization and
+//! finalization shares, the request round trip dominates the delivery time.
+//! Such artifacts may instead be pushed to peers together with their adverts,
+//! as configured per artifact type by the `GossipPushConfig` of the subnet.
+
+use ic_logger::replica_logger::ReplicaLogger;
+use ic_logger::{error, warn};
+use ic_protobuf::registry::subnet::v1::{GossipConfig, GossipPushConfig};
+use ic_types::{artifact::ArtifactId, p2p::GossipAdvert};
+use std::collections::BTreeMap;
+
+/// The artifact types that may be pushed. State sync artifacts consist of
+/// many chunks and are always pulled.
+const PUSHABLE_ARTIFACT_TYPES: [&str; 5] =
+    ["consensus", "certification", "dkg", "ecdsa", "ingress"];
+
+/// The way an artifact is delivered to a peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum GossipMode {
+    /// Only the advert is sent and the peer requests the artifact.
+    Pull,
+    /// The artifact is sent together with its advert.
+    Push,
+}
+
+impl GossipMode {
+    /// Returns the metric label of the mode.
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            GossipMode::Pull => "pull",
+            GossipMode::Push => "push",
+        }
+    }
+}
+
+/// Decides how the artifacts advertised to peers are delivered.
+pub(crate) trait GossipStrategy: Send + Sync {
+    /// Returns the mode in which the artifact advertised by `advert` is
+    /// delivered to peers.
+    fn gossip_mode(&self, advert: &GossipAdvert) -> GossipMode;
+}
+
+/// Always waits for peers to request artifacts.
+pub(crate) struct PullStrategy;
+
+impl GossipStrategy for PullStrategy {
+    fn gossip_mode(&self, _advert: &GossipAdvert) -> GossipMode {
+        GossipMode::Pull
+    }
+}
+
+/// Pushes artifacts no larger than the threshold configured for their type,
+/// and waits for peers to request all other artifacts.
+pub(crate) struct PushSmallArtifactsStrategy {
+    /// The maximum push size, by artifact type.
+    thresholds: BTreeMap<String, u32>,
+}
+
+impl PushSmallArtifactsStrategy {
+    pub(crate) fn new(push_config: &GossipPushConfig) -> Self {
+        Self {
+            thresholds: push_config
+                .thresholds
+                .iter()
+                .map(|threshold| (threshold.artifact_type.clone(), threshold.max_push_size))
+                .collect(),
+        }
+    }
+}
+
+impl GossipStrategy for PushSmallArtifactsStrategy {
+    fn gossip_mode(&self, advert: &GossipAdvert) -> GossipMode {
+        match self.thresholds.get(artifact_type(&advert.artifact_id)) {
+            Some(max_push_size) if advert.size <= *max_push_size as usize => GossipMode::Push,
+            _ => GossipMode::Pull,
+        }
+    }
+}
+
+/// Returns the strategy configured by `gossip_config`.
+///
+/// Pushing is disabled if the push config is missing or invalid.
+pub(crate) fn build_gossip_strategy(
+    gossip_config: &GossipConfig,
+    log: &ReplicaLogger,
+) -> Box<dyn GossipStrategy> {
+    let push_config = match &gossip_config.push_config {
+        Some(push_config) => push_config,
+        None => return Box::new(PullStrategy),
+    };
+    warn!(
+        log,
+        "build_gossip_strategy(): push_config = {:?}", push_config
+    );
+
+    if let Err(e) = validate_push_config(push_config, gossip_config.max_chunk_size) {
+        error!(log, "build_gossip_strategy(): invalid config = {:?}", e);
+        return Box::new(PullStrategy);
+    }
+    Box::new(PushSmallArtifactsStrategy::new(push_config))
+}
+
+pub(crate) fn validate_push_config(
+    config: &GossipPushConfig,
+    max_chunk_size: u32,
+) -> Result<(), String> {
+    let mut artifact_types = Vec::new();
+    for threshold in config.thresholds.iter() {
+        if !PUSHABLE_ARTIFACT_TYPES.contains(&threshold.artifact_type.as_str()) {
+            return Err(format!(
+                "Invalid push artifact type: {}",
+                threshold.artifact_type
+            ));
+        }
+        if artifact_types.contains(&&threshold.artifact_type) {
+            return Err(format!(
+                "Duplicate push artifact type: {}",
+                threshold.artifact_type
+            ));
+        }
+        artifact_types.push(&threshold.artifact_type);
+
+        // Pushed artifacts must fit into a single chunk.
+        if threshold.max_push_size == 0 || threshold.max_push_size > max_chunk_size {
+            return Err(format!(
+                "Invalid max push size for {}: {}",
+                threshold.artifact_type, threshold.max_push_size
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the artifact type of the given artifact ID, as used in metric
+/// labels and push configs.
+pub(crate) fn artifact_type(artifact_id: &ArtifactId) -> &'static str {
+    match artifact_id {
+        ArtifactId::ConsensusMessage(_) => "consensus",
+        ArtifactId::IngressMessage(_) => "ingress",
+        ArtifactId::CertificationMessage(_) => "certification",
+        ArtifactId::DkgMessage(_) => "dkg",
+        ArtifactId::EcdsaMessage(_) => "ecdsa",
+        ArtifactId::FileTreeSync(_) => "file_tree_sync",
+        ArtifactId::StateSync(_) => "state_sync",
+    }
+}
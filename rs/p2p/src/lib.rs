@@ -105,6 +105,7 @@ mod download_management;
 mod download_prioritization;
 pub mod event_handler;
 pub mod gossip_protocol;
+mod gossip_strategy;
 mod malicious_gossip;
 mod metrics;
 
@@ -282,10 +283,18 @@ pub mod tests {
     use crate::advert_utils::{validate_advert_config, AdvertRequestBuilder};
     use crate::download_prioritization::test::make_gossip_advert;
     use crate::gossip_protocol::{GossipAdvertAction, GossipAdvertSendRequest, Percentage};
+    use crate::gossip_strategy::{
+        build_gossip_strategy, validate_push_config, GossipMode, GossipStrategy,
+        PushSmallArtifactsStrategy,
+    };
     use ic_metrics::MetricsRegistry;
-    use ic_protobuf::registry::subnet::v1::GossipAdvertConfig;
+    use ic_protobuf::registry::subnet::v1::{
+        GossipAdvertConfig, GossipPushConfig, GossipPushThreshold,
+    };
     use ic_test_utilities::p2p::p2p_test_setup_logger;
-    use ic_types::artifact::AdvertClass;
+    use ic_types::artifact::{AdvertClass, ArtifactAttribute, ArtifactId, DkgMessageAttribute};
+    use ic_types::crypto::{CryptoHash, CryptoHashOf};
+    use ic_types::p2p::{build_default_gossip_config, GossipAdvert};
 
     #[test]
     fn test_advert_config_validation() {
@@ -383,4 +392,102 @@ pub mod tests {
         );
         assert!(builder.advert_config.is_none());
     }
+
+    fn make_push_config(thresholds: &[(&str, u32)]) -> GossipPushConfig {
+        GossipPushConfig {
+            thresholds: thresholds
+                .iter()
+                .map(|(artifact_type, max_push_size)| GossipPushThreshold {
+                    artifact_type: artifact_type.to_string(),
+                    max_push_size: *max_push_size,
+                })
+                .collect(),
+        }
+    }
+
+    fn make_dkg_advert(size: usize) -> GossipAdvert {
+        GossipAdvert {
+            artifact_id: ArtifactId::DkgMessage(CryptoHashOf::from(CryptoHash(vec![1]))),
+            attribute: ArtifactAttribute::DkgMessage(DkgMessageAttribute {
+                interval_start_height: Default::default(),
+            }),
+            size,
+            integrity_hash: CryptoHash(vec![1]),
+        }
+    }
+
+    #[test]
+    fn test_push_config_validation() {
+        assert!(validate_push_config(&make_push_config(&[]), 1024).is_ok());
+        assert!(validate_push_config(
+            &make_push_config(&[("consensus", 512), ("dkg", 1024)]),
+            1024
+        )
+        .is_ok());
+
+        assert_eq!(
+            validate_push_config(&make_push_config(&[("state_sync", 512)]), 1024)
+                .err()
+                .unwrap(),
+            "Invalid push artifact type: state_sync"
+        );
+        assert_eq!(
+            validate_push_config(&make_push_config(&[("dkg", 512), ("dkg", 256)]), 1024)
+                .err()
+                .unwrap(),
+            "Duplicate push artifact type: dkg"
+        );
+        assert_eq!(
+            validate_push_config(&make_push_config(&[("dkg", 0)]), 1024)
+                .err()
+                .unwrap(),
+            "Invalid max push size for dkg: 0"
+        );
+        assert_eq!(
+            validate_push_config(&make_push_config(&[("dkg", 2048)]), 1024)
+                .err()
+                .unwrap(),
+            "Invalid max push size for dkg: 2048"
+        );
+    }
+
+    #[test]
+    fn test_push_small_artifacts_strategy() {
+        let strategy = PushSmallArtifactsStrategy::new(&make_push_config(&[("dkg", 100)]));
+
+        assert_eq!(strategy.gossip_mode(&make_dkg_advert(0)), GossipMode::Push);
+        assert_eq!(
+            strategy.gossip_mode(&make_dkg_advert(100)),
+            GossipMode::Push
+        );
+        assert_eq!(
+            strategy.gossip_mode(&make_dkg_advert(101)),
+            GossipMode::Pull
+        );
+        // Artifact types without a threshold are always pulled.
+        assert_eq!(
+            strategy.gossip_mode(&make_gossip_advert(10)),
+            GossipMode::Pull
+        );
+    }
+
+    #[test]
+    fn test_push_disabled() {
+        let log = p2p_test_setup_logger().root.clone().into();
+        let mut gossip_config = build_default_gossip_config();
+
+        // No push config.
+        let strategy = build_gossip_strategy(&gossip_config, &log);
+        assert_eq!(strategy.gossip_mode(&make_dkg_advert(0)), GossipMode::Pull);
+
+        // Invalid push config.
+        gossip_config.push_config = Some(make_push_config(&[("dkg", 0)]));
+        let strategy = build_gossip_strategy(&gossip_config, &log);
+        assert_eq!(strategy.gossip_mode(&make_dkg_advert(0)), GossipMode::Pull);
+
+        // Valid push config.
+        gossip_config.push_config = Some(make_push_config(&[("dkg", 100)]));
+        let strategy = build_gossip_strategy(&gossip_config, &log);
+        assert_eq!(strategy.gossip_mode(&make_dkg_advert(0)), GossipMode::Push);
+    }
 }
@@ -79,6 +79,23 @@ pub struct DownloadManagementMetrics {
     pub chunks_redundant_residue: IntCounter,
     /// The number of failures to verify a chunk.
     pub chunks_verification_failed: IntCounter,
+    /// The times it took to receive artifacts after scheduling their download,
+    /// by artifact type and gossip mode.
+    pub artifact_delivery_time: HistogramVec,
+
+    // Push fields.
+    /// The number of sent pushes.
+    pub pushes_sent: IntCounter,
+    /// The number of failures to send pushes.
+    pub push_send_failed: IntCounter,
+    /// The number of received pushes.
+    pub pushes_received: IntCounter,
+    /// The number of received pushes for artifacts that were already received
+    /// or are being downloaded.
+    pub pushes_redundant: IntCounter,
+    /// The number of received pushes whose chunk does not belong to the
+    /// advertised artifact.
+    pub pushes_invalid: IntCounter,
 
     // Advert fields.
     /// The number of sent adverts(total).
@@ -173,6 +190,16 @@ impl DownloadManagementMetrics {
                 ],
                 &["artifact_type"],
             ),
+            artifact_delivery_time: metrics_registry.histogram_vec(
+                "gossip_artifact_delivery_time",
+                "Time it took to receive an artifact after its download was scheduled, by gossip mode (in milliseconds)",
+                vec![
+                    1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 300.0, 400.0, 500.0, 600.0,
+                    700.0, 800.0, 900.0, 1000.0, 1200.0, 1400.0, 1600.0, 1800.0, 2000.0, 2500.0,
+                    3000.0, 4000.0, 5000.0, 7000.0, 10000.0, 20000.0,
+                ],
+                &["artifact_type", "mode"],
+            ),
             chunks_sent: metrics_registry
                 .int_counter("gossip_chunks_sent", "Number of chunks sent"),
             chunk_send_failed: metrics_registry
@@ -233,6 +260,26 @@ impl DownloadManagementMetrics {
                 "Number of adverts that were dropped",
             ),
 
+            // Push fields.
+            pushes_sent: metrics_registry.int_counter(
+                "gossip_pushes_sent",
+                "Number of adverts sent together with the artifact",
+            ),
+            push_send_failed: metrics_registry
+                .int_counter("gossip_push_send_failed", "Number of push send failures"),
+            pushes_received: metrics_registry.int_counter(
+                "gossip_pushes_received",
+                "Number of adverts received together with the artifact",
+            ),
+            pushes_redundant: metrics_registry.int_counter(
+                "gossip_pushes_redundant",
+                "Number of pushes for artifacts already received or being downloaded",
+            ),
+            pushes_invalid: metrics_registry.int_counter(
+                "gossip_pushes_invalid",
+                "Number of pushes with a chunk not matching the advertised artifact",
+            ),
+
             // Retransmission fields.
             retransmission_requests_sent: metrics_registry.int_counter(
                 "retransmission_requests_sent",
@@ -338,6 +385,8 @@ pub struct EventHandlerMetrics {
     pub chunks_blocked: IntCounter,
     /// The number of times retransmission delivery was blocked.
     pub retransmissions_blocked: IntCounter,
+    /// The number of times push delivery was blocked.
+    pub pushes_blocked: IntCounter,
 }
 
 impl EventHandlerMetrics {
@@ -361,6 +410,10 @@ impl EventHandlerMetrics {
                 "retransmissions_blocked",
                 "Number of times retransmissions delivery blocked",
             ),
+            pushes_blocked: metrics_registry.int_counter(
+                "gossip_pushes_blocked",
+                "Number of times push delivery blocked",
+            ),
         }
     }
 }
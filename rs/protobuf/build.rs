@@ -277,6 +277,8 @@ fn build_messaging_proto() {
 fn build_state_proto() {
     let mut config = base_config();
     config.out_dir("gen/state");
+    // Allows dumping checkpoint files as JSON, see `ic-state-tool`.
+    config.type_attribute(".state", "#[derive(serde::Serialize, serde::Deserialize)]");
 
     let state_files = [
         "def/state/ingress/v1/ingress.proto",
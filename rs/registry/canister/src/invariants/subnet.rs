@@ -21,6 +21,8 @@ const MAX_NUM_SSH_KEYS: usize = 50;
 ///    * Each subnet contains at least one node
 ///    * There is at least one system subnet
 ///    * Each subnet in the registry occurs in the subnet list and vice versa
+///    * The minimum block delay is disabled or below the initial notary delay
+///    * The ingress share reserved for compute allocation is at most 100%
 pub(crate) fn check_subnet_invariants(
     snapshot: &RegistrySnapshot,
 ) -> Result<(), InvariantCheckError> {
@@ -98,6 +100,32 @@ pub(crate) fn check_subnet_invariants(
         if subnet_record.subnet_type == i32::from(SubnetType::System) {
             system_subnet_count += 1;
         }
+        // A minimum block delay not below the initial notary delay would
+        // silently be ignored by consensus.
+        if subnet_record.min_block_delay_millis != 0
+            && subnet_record.min_block_delay_millis >= subnet_record.initial_notary_delay_millis
+        {
+            return Err(InvariantCheckError {
+                msg: format!(
+                    "The minimum block delay of subnet {} ({} ms) must be below its initial \
+                    notary delay ({} ms)",
+                    subnet_id,
+                    subnet_record.min_block_delay_millis,
+                    subnet_record.initial_notary_delay_millis
+                ),
+                source: None,
+            });
+        }
+        if subnet_record.ingress_compute_allocation_share_percent > 100 {
+            return Err(InvariantCheckError {
+                msg: format!(
+                    "The ingress share reserved for compute allocation of subnet {} ({}%) must \
+                    not exceed 100%",
+                    subnet_id, subnet_record.ingress_compute_allocation_share_percent
+                ),
+                source: None,
+            });
+        }
         assert!(
             subnet_record.max_instructions_per_message <= subnet_record.max_instructions_per_round,
             "The message instruction limit should not exceed \
@@ -136,3 +164,70 @@ fn get_subnet_records_map(snapshot: &RegistrySnapshot) -> BTreeMap<Vec<u8>, Subn
     }
     subnets
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_base_types::SubnetId;
+    use ic_nns_common::registry::encode_or_panic;
+    use ic_protobuf::registry::{node::v1::NodeRecord, subnet::v1::SubnetListRecord};
+    use ic_registry_keys::make_subnet_list_record_key;
+
+    // Returns a snapshot with a single system subnet, consisting of a single
+    // node, with the given subnet record.
+    fn snapshot_with_subnet_record(subnet_record: SubnetRecord) -> RegistrySnapshot {
+        let subnet_id = SubnetId::from(PrincipalId::new_subnet_test_id(1));
+        let node_id = NodeId::from(PrincipalId::new_node_test_id(1));
+        let mut snapshot = RegistrySnapshot::new();
+        snapshot.insert(
+            make_subnet_list_record_key().into_bytes(),
+            encode_or_panic(&SubnetListRecord {
+                subnets: vec![subnet_id.get().to_vec()],
+            }),
+        );
+        snapshot.insert(
+            make_node_record_key(node_id).into_bytes(),
+            encode_or_panic(&NodeRecord::default()),
+        );
+        snapshot.insert(
+            make_subnet_record_key(subnet_id).into_bytes(),
+            encode_or_panic(&SubnetRecord {
+                membership: vec![node_id.get().to_vec()],
+                subnet_type: i32::from(SubnetType::System),
+                ..subnet_record
+            }),
+        );
+        snapshot
+    }
+
+    #[test]
+    fn min_block_delay_must_be_below_initial_notary_delay() {
+        let check = |min_block_delay_millis| {
+            check_subnet_invariants(&snapshot_with_subnet_record(SubnetRecord {
+                initial_notary_delay_millis: 1500,
+                min_block_delay_millis,
+                ..Default::default()
+            }))
+        };
+
+        // Disabled.
+        assert!(check(0).is_ok());
+        assert!(check(1000).is_ok());
+        assert!(check(1500).is_err());
+        assert!(check(2000).is_err());
+    }
+
+    #[test]
+    fn ingress_compute_allocation_share_must_not_exceed_100_percent() {
+        let check = |ingress_compute_allocation_share_percent| {
+            check_subnet_invariants(&snapshot_with_subnet_record(SubnetRecord {
+                ingress_compute_allocation_share_percent,
+                ..Default::default()
+            }))
+        };
+
+        assert!(check(0).is_ok());
+        assert!(check(100).is_ok());
+        assert!(check(101).is_err());
+    }
+}
@@ -206,6 +206,9 @@ pub struct CreateSubnetPayload {
     pub replica_version_id: std::string::String,
     pub dkg_interval_length: u64,
     pub dkg_dealings_per_block: u64,
+    pub max_empty_block_delay_millis: u64,
+    pub min_block_delay_millis: u64,
+    pub ingress_compute_allocation_share_percent: u32,
 
     pub gossip_max_artifact_streams_per_peer: u32,
     pub gossip_max_chunk_wait_ms: u32,
@@ -267,6 +270,7 @@ impl From<CreateSubnetPayload> for SubnetRecord {
                     .map(|val| GossipAdvertConfig {
                         best_effort_percentage: val,
                     }),
+                push_config: None,
             }),
 
             start_as_nns: val.start_as_nns,
@@ -283,6 +287,9 @@ impl From<CreateSubnetPayload> for SubnetRecord {
             ssh_readonly_access: val.ssh_readonly_access,
             ssh_backup_access: val.ssh_backup_access,
             ecdsa_config: None,
+            max_empty_block_delay_millis: val.max_empty_block_delay_millis,
+            min_block_delay_millis: val.min_block_delay_millis,
+            ingress_compute_allocation_share_percent: val.ingress_compute_allocation_share_percent,
         }
     }
 }
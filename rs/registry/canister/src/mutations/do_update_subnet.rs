@@ -58,6 +58,9 @@ pub struct UpdateSubnetPayload {
     pub initial_notary_delay_millis: Option<u64>,
     pub dkg_interval_length: Option<u64>,
     pub dkg_dealings_per_block: Option<u64>,
+    pub max_empty_block_delay_millis: Option<u64>,
+    pub min_block_delay_millis: Option<u64>,
+    pub ingress_compute_allocation_share_percent: Option<u32>,
 
     pub max_artifact_streams_per_peer: Option<u32>,
     pub max_chunk_wait_ms: Option<u32>,
@@ -151,6 +154,9 @@ fn merge_subnet_record(
         initial_notary_delay_millis,
         dkg_interval_length,
         dkg_dealings_per_block,
+        max_empty_block_delay_millis,
+        min_block_delay_millis,
+        ingress_compute_allocation_share_percent,
         max_artifact_streams_per_peer,
         max_chunk_wait_ms,
         max_duplicity,
@@ -180,6 +186,9 @@ fn merge_subnet_record(
     maybe_set!(subnet_record, initial_notary_delay_millis);
     maybe_set!(subnet_record, dkg_interval_length);
     maybe_set!(subnet_record, dkg_dealings_per_block);
+    maybe_set!(subnet_record, max_empty_block_delay_millis);
+    maybe_set!(subnet_record, min_block_delay_millis);
+    maybe_set!(subnet_record, ingress_compute_allocation_share_percent);
 
     // Set a default gossip config if it was requested...
     if set_gossip_config_to_default {
@@ -255,6 +264,7 @@ mod tests {
                 registry_poll_period_ms: 100,
                 retransmission_request_ms: 100,
                 advert_config: None,
+                push_config: None,
             }),
             start_as_nns: false,
             subnet_type: SubnetType::Application.into(),
@@ -267,6 +277,9 @@ mod tests {
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
+            max_empty_block_delay_millis: 0,
+            min_block_delay_millis: 0,
+            ingress_compute_allocation_share_percent: 0,
         };
 
         let payload = UpdateSubnetPayload {
@@ -283,6 +296,9 @@ mod tests {
             initial_notary_delay_millis: Some(200),
             dkg_interval_length: Some(8),
             dkg_dealings_per_block: Some(1),
+            max_empty_block_delay_millis: Some(1000),
+            min_block_delay_millis: Some(100),
+            ingress_compute_allocation_share_percent: Some(50),
             max_artifact_streams_per_peer: Some(0),
             max_chunk_wait_ms: Some(10),
             max_duplicity: Some(5),
@@ -333,6 +349,7 @@ mod tests {
                     advert_config: Some(GossipAdvertConfig {
                         best_effort_percentage: 50
                     }),
+                    push_config: None,
                 }),
                 start_as_nns: true,
                 subnet_type: SubnetType::Application.into(),
@@ -351,6 +368,9 @@ mod tests {
                 ssh_readonly_access: vec!["pub_key_0".to_string()],
                 ssh_backup_access: vec!["pub_key_1".to_string()],
                 ecdsa_config: None,
+                max_empty_block_delay_millis: 1000,
+                min_block_delay_millis: 100,
+                ingress_compute_allocation_share_percent: 50,
             }
         );
     }
@@ -380,6 +400,7 @@ mod tests {
                 advert_config: Some(GossipAdvertConfig {
                     best_effort_percentage: 10,
                 }),
+                push_config: None,
             }),
             start_as_nns: false,
             subnet_type: SubnetType::Application.into(),
@@ -392,6 +413,9 @@ mod tests {
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
+            max_empty_block_delay_millis: 0,
+            min_block_delay_millis: 0,
+            ingress_compute_allocation_share_percent: 0,
         };
 
         let payload = UpdateSubnetPayload {
@@ -408,6 +432,9 @@ mod tests {
             initial_notary_delay_millis: None,
             dkg_interval_length: Some(2),
             dkg_dealings_per_block: Some(1),
+            max_empty_block_delay_millis: None,
+            min_block_delay_millis: None,
+            ingress_compute_allocation_share_percent: None,
             max_artifact_streams_per_peer: Some(0),
             max_chunk_wait_ms: Some(10),
             max_duplicity: None,
@@ -453,6 +480,7 @@ mod tests {
                     registry_poll_period_ms: 100,
                     retransmission_request_ms: 100,
                     advert_config: None,
+                    push_config: None,
                 }),
                 start_as_nns: false,
                 subnet_type: SubnetType::Application.into(),
@@ -465,6 +493,9 @@ mod tests {
                 ssh_readonly_access: vec![],
                 ssh_backup_access: vec![],
                 ecdsa_config: None,
+                max_empty_block_delay_millis: 0,
+                min_block_delay_millis: 0,
+                ingress_compute_allocation_share_percent: 0,
             }
         );
     }
@@ -498,6 +529,9 @@ mod tests {
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
+            max_empty_block_delay_millis: 0,
+            min_block_delay_millis: 0,
+            ingress_compute_allocation_share_percent: 0,
         };
 
         let payload = UpdateSubnetPayload {
@@ -514,6 +548,9 @@ mod tests {
             initial_notary_delay_millis: None,
             dkg_interval_length: Some(2),
             dkg_dealings_per_block: Some(1),
+            max_empty_block_delay_millis: None,
+            min_block_delay_millis: None,
+            ingress_compute_allocation_share_percent: None,
             max_artifact_streams_per_peer: Some(0),
             max_chunk_wait_ms: Some(10),
             max_duplicity: None,
@@ -564,6 +601,9 @@ mod tests {
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
+            max_empty_block_delay_millis: 0,
+            min_block_delay_millis: 0,
+            ingress_compute_allocation_share_percent: 0,
         };
 
         let payload = UpdateSubnetPayload {
@@ -580,6 +620,9 @@ mod tests {
             initial_notary_delay_millis: None,
             dkg_interval_length: None,
             dkg_dealings_per_block: None,
+            max_empty_block_delay_millis: None,
+            min_block_delay_millis: None,
+            ingress_compute_allocation_share_percent: None,
             max_artifact_streams_per_peer: Some(0),
             max_chunk_wait_ms: Some(100),
             max_duplicity: None,
@@ -627,6 +670,7 @@ mod tests {
                     advert_config: Some(GossipAdvertConfig {
                         best_effort_percentage: 30
                     }),
+                    push_config: None,
                 }),
                 start_as_nns: false,
                 subnet_type: SubnetType::Application.into(),
@@ -639,6 +683,9 @@ mod tests {
                 ssh_readonly_access: vec![],
                 ssh_backup_access: vec![],
                 ecdsa_config: None,
+                max_empty_block_delay_millis: 0,
+                min_block_delay_millis: 0,
+                ingress_compute_allocation_share_percent: 0,
             }
         );
     }
@@ -668,6 +715,7 @@ mod tests {
                 advert_config: Some(GossipAdvertConfig {
                     best_effort_percentage: 10,
                 }),
+                push_config: None,
             }),
             start_as_nns: false,
             subnet_type: SubnetType::Application.into(),
@@ -680,6 +728,9 @@ mod tests {
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
+            max_empty_block_delay_millis: 0,
+            min_block_delay_millis: 0,
+            ingress_compute_allocation_share_percent: 0,
         };
 
         let payload = UpdateSubnetPayload {
@@ -696,6 +747,9 @@ mod tests {
             initial_notary_delay_millis: None,
             dkg_interval_length: Some(2),
             dkg_dealings_per_block: Some(1),
+            max_empty_block_delay_millis: None,
+            min_block_delay_millis: None,
+            ingress_compute_allocation_share_percent: None,
             max_artifact_streams_per_peer: Some(0),
             max_chunk_wait_ms: Some(10),
             max_duplicity: None,
@@ -743,6 +797,7 @@ mod tests {
                     advert_config: Some(GossipAdvertConfig {
                         best_effort_percentage: 100
                     }),
+                    push_config: None,
                 }),
                 start_as_nns: false,
                 subnet_type: SubnetType::Application.into(),
@@ -755,6 +810,9 @@ mod tests {
                 ssh_readonly_access: vec![],
                 ssh_backup_access: vec![],
                 ecdsa_config: None,
+                max_empty_block_delay_millis: 0,
+                min_block_delay_millis: 0,
+                ingress_compute_allocation_share_percent: 0,
             }
         );
     }
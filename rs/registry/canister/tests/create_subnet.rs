@@ -112,6 +112,9 @@ fn test_the_anonymous_user_cannot_create_a_subnet() {
             replica_version_id: "1337".to_string(),
             dkg_interval_length: 0,
             dkg_dealings_per_block: 1,
+            max_empty_block_delay_millis: 0,
+            min_block_delay_millis: 0,
+            ingress_compute_allocation_share_percent: 0,
             gossip_max_artifact_streams_per_peer: 0,
             gossip_max_chunk_wait_ms: 0,
             gossip_max_duplicity: 0,
@@ -201,6 +204,9 @@ fn test_a_canister_other_than_the_proposals_canister_cannot_create_a_subnet() {
             replica_version_id: "1337".to_string(),
             dkg_interval_length: 0,
             dkg_dealings_per_block: 1,
+            max_empty_block_delay_millis: 0,
+            min_block_delay_millis: 0,
+            ingress_compute_allocation_share_percent: 0,
             gossip_max_artifact_streams_per_peer: 0,
             gossip_max_chunk_wait_ms: 0,
             gossip_max_duplicity: 0,
@@ -300,6 +306,9 @@ fn test_accepted_proposal_mutates_the_registry_no_subnet_apriori() {
             replica_version_id: "version_42".to_string(),
             dkg_interval_length: 0,
             dkg_dealings_per_block: 1,
+            max_empty_block_delay_millis: 0,
+            min_block_delay_millis: 0,
+            ingress_compute_allocation_share_percent: 0,
             gossip_max_artifact_streams_per_peer: 0,
             gossip_max_chunk_wait_ms: 0,
             gossip_max_duplicity: 0,
@@ -455,6 +464,9 @@ fn test_accepted_proposal_mutates_the_registry_some_subnets_present() {
             replica_version_id: "version_42".to_string(),
             dkg_interval_length: 0,
             dkg_dealings_per_block: 1,
+            max_empty_block_delay_millis: 1000,
+            min_block_delay_millis: 500,
+            ingress_compute_allocation_share_percent: 50,
             gossip_max_artifact_streams_per_peer: 0,
             gossip_max_chunk_wait_ms: 0,
             gossip_max_duplicity: 0,
@@ -508,6 +520,9 @@ fn test_accepted_proposal_mutates_the_registry_some_subnets_present() {
                 .await;
         // Check if some fields are equal
         assert_eq!(subnet_record.replica_version_id, payload.replica_version_id);
+        assert_eq!(subnet_record.max_empty_block_delay_millis, 1000);
+        assert_eq!(subnet_record.min_block_delay_millis, 500);
+        assert_eq!(subnet_record.ingress_compute_allocation_share_percent, 50);
         assert_eq!(
             subnet_record.membership,
             node_ids
@@ -48,6 +48,9 @@ fn test_the_anonymous_user_cannot_update_a_subnets_configuration() {
             initial_notary_delay_millis: None,
             dkg_interval_length: None,
             dkg_dealings_per_block: None,
+            max_empty_block_delay_millis: None,
+            min_block_delay_millis: None,
+            ingress_compute_allocation_share_percent: None,
             max_artifact_streams_per_peer: Some(0),
             max_chunk_wait_ms: Some(0),
             max_duplicity: Some(0),
@@ -132,6 +135,7 @@ fn test_a_canister_other_than_the_proposals_canister_cannot_update_a_subnets_con
                 registry_poll_period_ms: 0,
                 retransmission_request_ms: 0,
                 advert_config: None,
+                push_config: None,
             }),
             start_as_nns: false,
             subnet_type: SubnetType::Application.into(),
@@ -144,6 +148,9 @@ fn test_a_canister_other_than_the_proposals_canister_cannot_update_a_subnets_con
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
+            max_empty_block_delay_millis: 0,
+            min_block_delay_millis: 0,
+            ingress_compute_allocation_share_percent: 0,
         };
 
         // An attacker got a canister that is trying to pass for the proposals
@@ -180,6 +187,9 @@ fn test_a_canister_other_than_the_proposals_canister_cannot_update_a_subnets_con
             initial_notary_delay_millis: None,
             dkg_interval_length: None,
             dkg_dealings_per_block: None,
+            max_empty_block_delay_millis: None,
+            min_block_delay_millis: None,
+            ingress_compute_allocation_share_percent: None,
             max_artifact_streams_per_peer: Some(0),
             max_chunk_wait_ms: Some(0),
             max_duplicity: Some(0),
@@ -265,6 +275,7 @@ fn test_the_proposals_canister_can_update_a_subnets_configuration() {
                                 registry_poll_period_ms: 0,
                                 retransmission_request_ms: 0,
                                 advert_config: None,
+                                push_config: None,
                             }),
                             start_as_nns: false,
                             subnet_type: SubnetType::Application.into(),
@@ -277,6 +288,9 @@ fn test_the_proposals_canister_can_update_a_subnets_configuration() {
                             ssh_readonly_access: vec![],
                             ssh_backup_access: vec![],
                             ecdsa_config: None,
+                            max_empty_block_delay_millis: 0,
+                            min_block_delay_millis: 0,
+                            ingress_compute_allocation_share_percent: 0,
                         }),
                     )],
                     preconditions: vec![],
@@ -304,6 +318,9 @@ fn test_the_proposals_canister_can_update_a_subnets_configuration() {
             initial_notary_delay_millis: None,
             dkg_interval_length: Some(2),
             dkg_dealings_per_block: Some(1),
+            max_empty_block_delay_millis: Some(1000),
+            min_block_delay_millis: Some(500),
+            ingress_compute_allocation_share_percent: Some(50),
             max_artifact_streams_per_peer: Some(0),
             max_chunk_wait_ms: Some(10),
             max_duplicity: Some(0),
@@ -365,6 +382,7 @@ fn test_the_proposals_canister_can_update_a_subnets_configuration() {
                     registry_poll_period_ms: 0,
                     retransmission_request_ms: 0,
                     advert_config: None,
+                    push_config: None,
                 }),
                 start_as_nns: false,
                 subnet_type: SubnetType::Application.into(),
@@ -377,6 +395,9 @@ fn test_the_proposals_canister_can_update_a_subnets_configuration() {
                 ssh_readonly_access: vec!["pub_key_0".to_string()],
                 ssh_backup_access: vec!["pub_key_1".to_string()],
                 ecdsa_config: None,
+                max_empty_block_delay_millis: 1000,
+                min_block_delay_millis: 500,
+                ingress_compute_allocation_share_percent: 50,
             }
         );
 
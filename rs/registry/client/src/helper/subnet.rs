@@ -21,6 +21,12 @@ use std::time::Duration;
 pub struct NotarizationDelaySettings {
     pub unit_delay: Duration,
     pub initial_notary_delay: Duration,
+    /// The longest time a block maker holds back a block without ingress and
+    /// XNet messages, on top of the delay of its rank. Zero if disabled.
+    pub max_empty_block_delay: Duration,
+    /// The notary delay for rank-0 blocks that carry ingress or XNet
+    /// messages. Zero if disabled.
+    pub min_block_delay: Duration,
 }
 
 pub struct IngressMessageSettings {
@@ -35,6 +41,10 @@ pub struct IngressMessageSettings {
     /// Maximum number of messages per block. This is a hard cap, which means
     /// blocks will never have more than this number of messages.
     pub max_ingress_messages_per_block: usize,
+    /// Share of the ingress payload, in percent, that is first filled with
+    /// messages to canisters with a compute allocation. At most 100; 0 if
+    /// disabled.
+    pub compute_allocation_share_percent: usize,
 }
 
 /// A helper trait that wraps a RegistryClient and provides utility methods for
@@ -84,7 +94,8 @@ pub trait SubnetRegistry {
     /// Returns notarization delay settings:
     /// - the unit delay for blockmaker;
     /// - the initial delay for notary, to give time to rank-0 block
-    /// propagation.
+    /// propagation;
+    /// - the bounds of the adaptive block rate.
     fn get_notarization_delay_settings(
         &self,
         subnet_id: SubnetId,
@@ -218,6 +229,9 @@ impl<T: RegistryClient + ?Sized> SubnetRegistry for T {
                         as usize,
                     max_ingress_bytes_per_message: subnet.max_ingress_bytes_per_message as usize,
                     max_ingress_messages_per_block: subnet.max_ingress_messages_per_block as usize,
+                    compute_allocation_share_percent: subnet
+                        .ingress_compute_allocation_share_percent
+                        .min(100) as usize,
                 }
             }),
         )
@@ -254,6 +268,10 @@ impl<T: RegistryClient + ?Sized> SubnetRegistry for T {
                 NotarizationDelaySettings {
                     unit_delay: Duration::from_millis(subnet.unit_delay_millis),
                     initial_notary_delay: Duration::from_millis(subnet.initial_notary_delay_millis),
+                    max_empty_block_delay: Duration::from_millis(
+                        subnet.max_empty_block_delay_millis,
+                    ),
+                    min_block_delay: Duration::from_millis(subnet.min_block_delay_millis),
                 }
             }),
         )
@@ -0,0 +1,552 @@
+//! Rebuilds the replicated state of a subnet from a checkpoint and the
+//! consensus backup spool.
+//!
+//! The state manager loads the latest checkpoint from the state root of the
+//! given replica config. The artifacts backed up at the heights following the
+//! checkpoint are read from the spool and added to a temporary consensus pool,
+//! starting with the CUP at the checkpoint height. Each finalization is
+//! verified before it is added, and the finalized blocks are delivered to
+//! Message Routing exactly as the replica's batch delivery would.
+//!
+//! The hash of every replayed state is printed, and the full state hash is
+//! compared against the CUPs found along the way. Optionally, the replay stops
+//! at a given height and writes a checkpoint at that height.
+//!
+//! The blocks are replayed with the replica version recorded in the subnet
+//! record at the registry version of the checkpoint CUP. The replay fails if
+//! it reaches a block that requires another replica version.
+
+use ic_artifact_pool::{backup::read_artifacts_at_height, consensus_pool::ConsensusPoolImpl};
+use ic_config::{
+    artifact_pool::ArtifactPoolConfig, crypto::CryptoConfig, subnet_config::SubnetConfigs, Config,
+};
+use ic_consensus::{
+    certification::VerifierImpl,
+    consensus::{batch_delivery::deliver_batches, pool_reader::PoolReader, Membership},
+};
+use ic_crypto::CryptoComponent;
+use ic_cycles_account_manager::CyclesAccountManager;
+use ic_execution_environment::setup_execution;
+use ic_interfaces::{
+    consensus_pool::{ChangeAction, MutableConsensusPool},
+    registry::RegistryClient,
+    state_manager::{StateHashError, StateManager, StateReader},
+    time_source::SysTimeSource,
+};
+use ic_logger::{info, LoggerImpl, ReplicaLogger};
+use ic_messaging::MessageRoutingImpl;
+use ic_metrics::MetricsRegistry;
+use ic_registry_client::{
+    client::{create_data_provider, RegistryClientImpl},
+    helper::subnet::SubnetRegistry,
+};
+use ic_registry_subnet_type::SubnetType;
+use ic_replicated_state::ReplicatedState;
+use ic_state_manager::StateManagerImpl;
+use ic_types::{
+    consensus::{
+        CUPWithOriginalProtobuf, CatchUpPackage, ConsensusMessage, Finalization, HasHeight,
+    },
+    malicious_flags::MaliciousFlags,
+    CryptoHashOfState, Height, NodeId, PrincipalId, RegistryVersion, ReplicaVersion, SubnetId,
+};
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::{thread::sleep, time::Duration};
+
+mod validation;
+
+// How long to wait between polls of the state manager.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct ReplayOptions {
+    /// The config of the replica whose state is replayed.
+    pub cfg: Config,
+    pub subnet_id: SubnetId,
+    /// The backup spool path of the replica, i.e. the parent directory of the
+    /// subnet backup directory.
+    pub backup_spool_path: PathBuf,
+    /// Stop after replaying the given height.
+    pub replay_until_height: Option<Height>,
+    /// Write a checkpoint at `replay_until_height`.
+    pub write_checkpoint: bool,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The registry could not be read.
+    Registry(String),
+    /// The backup spool could not be read.
+    Backup(std::io::Error),
+    /// There is no backed up CUP at the height of the loaded checkpoint.
+    MissingCatchUpPackage(Height),
+    /// No backed up finalization at the given height could be verified.
+    InvalidFinalization { height: Height, reason: String },
+    /// The batch of the given height could not be delivered.
+    BatchDelivery { height: Height, reason: String },
+    /// The hash of the state at the given height could not be computed.
+    StateHash { height: Height, reason: String },
+    /// The hash of the state at the given height does not match the CUP.
+    StateHashMismatch {
+        height: Height,
+        expected: CryptoHashOfState,
+        computed: CryptoHashOfState,
+    },
+    /// The default replica version of the process was already set to another
+    /// version than the one the subnet runs.
+    ReplicaVersionAlreadySet {
+        default: ReplicaVersion,
+        required: ReplicaVersion,
+    },
+    /// The batch at the given height requires another replica version than
+    /// the one being replayed.
+    UnsupportedReplicaVersion {
+        height: Height,
+        replica_version: ReplicaVersion,
+    },
+    /// The backup ends before the requested height.
+    IncompleteReplay { requested: Height, replayed: Height },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Registry(err) => write!(f, "Failed to read the registry: {}", err),
+            ReplayError::Backup(err) => write!(f, "Failed to read the backup: {}", err),
+            ReplayError::MissingCatchUpPackage(height) => write!(
+                f,
+                "No CUP was backed up at the checkpoint height {}",
+                height
+            ),
+            ReplayError::InvalidFinalization { height, reason } => {
+                write!(f, "Invalid finalization at height {}: {}", height, reason)
+            }
+            ReplayError::BatchDelivery { height, reason } => write!(
+                f,
+                "Failed to deliver the batch at height {}: {}",
+                height, reason
+            ),
+            ReplayError::StateHash { height, reason } => write!(
+                f,
+                "Failed to compute the state hash at height {}: {}",
+                height, reason
+            ),
+            ReplayError::StateHashMismatch {
+                height,
+                expected,
+                computed,
+            } => write!(
+                f,
+                "State hash at height {} diverges from the CUP: expected {}, computed {}",
+                height,
+                hex::encode(&expected.get_ref().0),
+                hex::encode(&computed.get_ref().0)
+            ),
+            ReplayError::ReplicaVersionAlreadySet { default, required } => write!(
+                f,
+                "The replica version is already set to {}, the subnet runs {}",
+                default, required
+            ),
+            ReplayError::UnsupportedReplicaVersion {
+                height,
+                replica_version,
+            } => write!(
+                f,
+                "The batch at height {} was not delivered, the subnet was upgraded away from \
+                 replica version {}",
+                height, replica_version
+            ),
+            ReplayError::IncompleteReplay {
+                requested,
+                replayed,
+            } => write!(
+                f,
+                "The backup ends at height {}, before the requested height {}",
+                replayed, requested
+            ),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(err: std::io::Error) -> Self {
+        ReplayError::Backup(err)
+    }
+}
+
+/// Replays the backed up finalized blocks on top of the latest checkpoint and
+/// returns the last replayed height.
+pub fn replay(options: ReplayOptions) -> Result<Height, ReplayError> {
+    let ReplayOptions {
+        cfg,
+        subnet_id,
+        backup_spool_path,
+        replay_until_height,
+        write_checkpoint,
+    } = options;
+    let backup_path = backup_spool_path.join(subnet_id.to_string());
+
+    let logger = LoggerImpl::new(&cfg.logger, "ic-replay".to_string());
+    let log = ReplicaLogger::new(logger.root.clone().into());
+    let metrics_registry = MetricsRegistry::new();
+
+    let registry = setup_registry(&cfg)?;
+    let subnet_type = get_subnet_type(registry.as_ref(), subnet_id)?;
+    let subnet_config = SubnetConfigs::default().own_subnet_config(subnet_type);
+
+    // Verification only requires the public keys from the registry, so the
+    // crypto component uses temporary secret keys and a dummy node ID.
+    let (crypto_config, _crypto_dir) = CryptoConfig::new_in_temp_dir();
+    let crypto = Arc::new(CryptoComponent::new_with_fake_node_id(
+        &crypto_config,
+        Arc::clone(&registry) as Arc<_>,
+        NodeId::from(PrincipalId::new_node_test_id(1)),
+        log.clone(),
+    ));
+
+    let state_manager = Arc::new(StateManagerImpl::new(
+        Arc::new(VerifierImpl::new(Arc::clone(&crypto) as Arc<_>)),
+        subnet_id,
+        subnet_type,
+        log.clone(),
+        &metrics_registry,
+        &cfg.state_manager,
+        MaliciousFlags::default(),
+    ));
+    let checkpoint_height = state_manager.latest_state_height();
+    info!(log, "Loaded the checkpoint at height {}", checkpoint_height);
+
+    // The pool is initialized with the CUP of the checkpoint, which must
+    // certify the loaded state.
+    let cup = read_artifacts_at_height(&backup_path, checkpoint_height)?
+        .into_iter()
+        .find_map(|artifact| match artifact {
+            ConsensusMessage::CatchUpPackage(cup) => Some(cup),
+            _ => None,
+        })
+        .ok_or(ReplayError::MissingCatchUpPackage(checkpoint_height))?;
+    check_cup_state_hash(state_manager.as_ref(), &cup)?;
+
+    // Blocks and batches are checked against the default replica version, so
+    // it must be set before the pool is created.
+    let replica_version =
+        get_replica_version(registry.as_ref(), subnet_id, cup.content.registry_version())?;
+    if ReplicaVersion::set_default_version(replica_version.clone()).is_err()
+        && ReplicaVersion::default() != replica_version
+    {
+        return Err(ReplayError::ReplicaVersionAlreadySet {
+            default: ReplicaVersion::default(),
+            required: replica_version,
+        });
+    }
+    info!(log, "Replaying with replica version {}", replica_version);
+
+    let pool_dir = tempfile::Builder::new().prefix("ic_replay_").tempdir()?;
+    let mut pool = ConsensusPoolImpl::new(
+        subnet_id,
+        CUPWithOriginalProtobuf::from_cup(cup),
+        ArtifactPoolConfig::new(pool_dir.path().to_path_buf()),
+        metrics_registry.clone(),
+        log.clone(),
+    );
+    let membership = Membership::new(pool.get_cache(), Arc::clone(&registry) as Arc<_>, subnet_id);
+    let time_source = SysTimeSource::new();
+
+    let cycles_account_manager = Arc::new(CyclesAccountManager::new(
+        subnet_config.scheduler_config.max_instructions_per_message,
+        cfg.hypervisor.max_cycles_per_canister,
+        subnet_type,
+        subnet_id,
+        subnet_config.cycles_account_manager_config,
+    ));
+    let (_, ingress_history_writer, _, _, _, scheduler) = setup_execution(
+        log.clone(),
+        &metrics_registry,
+        subnet_id,
+        subnet_type,
+        subnet_config.scheduler_config,
+        cfg.hypervisor.clone(),
+        Arc::clone(&cycles_account_manager),
+        Arc::clone(&state_manager) as Arc<_>,
+    );
+    let message_routing = MessageRoutingImpl::new(
+        Arc::clone(&state_manager) as Arc<_>,
+        Arc::clone(&state_manager) as Arc<_>,
+        Arc::clone(&ingress_history_writer) as Arc<_>,
+        scheduler,
+        cfg.hypervisor,
+        cycles_account_manager,
+        subnet_id,
+        &metrics_registry,
+        log.clone(),
+        Arc::clone(&registry) as Arc<_>,
+    );
+
+    let mut last_height = checkpoint_height;
+    let mut height = checkpoint_height.increment();
+    while replay_until_height.map_or(true, |until| height <= until) {
+        let (finalizations, artifacts): (Vec<_>, Vec<_>) =
+            read_artifacts_at_height(&backup_path, height)?
+                .into_iter()
+                .partition(|artifact| matches!(artifact, ConsensusMessage::Finalization(_)));
+        if finalizations.is_empty() {
+            info!(log, "No finalization was backed up at height {}", height);
+            break;
+        }
+        let cups: Vec<_> = artifacts
+            .iter()
+            .filter_map(|artifact| match artifact {
+                ConsensusMessage::CatchUpPackage(cup) => Some(cup.clone()),
+                _ => None,
+            })
+            .collect();
+        pool.apply_changes(
+            &time_source,
+            artifacts
+                .into_iter()
+                .map(ChangeAction::AddToValidated)
+                .collect(),
+        );
+
+        // Only the finalized block is delivered, so a single finalization
+        // suffices.
+        let finalization = find_valid_finalization(
+            &PoolReader::new(&pool),
+            &membership,
+            crypto.as_ref(),
+            finalizations,
+        )
+        .map_err(|reason| ReplayError::InvalidFinalization { height, reason })?;
+        pool.apply_changes(
+            &time_source,
+            vec![ChangeAction::AddToValidated(
+                ConsensusMessage::Finalization(finalization),
+            )],
+        );
+
+        let persist_batch = write_checkpoint && replay_until_height == Some(height);
+        let delivered_height = deliver_batches(
+            &message_routing,
+            &membership,
+            &PoolReader::new(&pool),
+            state_manager.as_ref(),
+            registry.as_ref(),
+            subnet_id,
+            replica_version.clone(),
+            &log,
+            persist_batch,
+            Some(height.get()),
+            None,
+        )
+        .map_err(|err| ReplayError::BatchDelivery {
+            height,
+            reason: format!("{:?}", err),
+        })?;
+        if delivered_height < height {
+            // Batch delivery stops before blocks requiring another replica
+            // version.
+            return Err(ReplayError::UnsupportedReplicaVersion {
+                height,
+                replica_version,
+            });
+        }
+        while state_manager.latest_state_height() < height {
+            sleep(POLL_INTERVAL);
+        }
+        print_state_hash(state_manager.as_ref(), height);
+        for cup in &cups {
+            check_cup_state_hash(state_manager.as_ref(), cup)?;
+        }
+
+        last_height = height;
+        height = height.increment();
+    }
+
+    if let Some(requested) = replay_until_height {
+        if last_height < requested {
+            return Err(ReplayError::IncompleteReplay {
+                requested,
+                replayed: last_height,
+            });
+        }
+    }
+    if write_checkpoint {
+        let state_hash = wait_for_state_hash(state_manager.as_ref(), last_height)?;
+        println!(
+            "Checkpoint written at height {} with state hash {}",
+            last_height,
+            hex::encode(&state_hash.get_ref().0)
+        );
+    }
+    Ok(last_height)
+}
+
+/// Returns the first of the given finalizations that verifies, or the reason
+/// why the last one failed to verify.
+fn find_valid_finalization(
+    pool: &PoolReader<'_>,
+    membership: &Membership,
+    crypto: &CryptoComponent,
+    finalizations: Vec<ConsensusMessage>,
+) -> Result<Finalization, String> {
+    let mut reason = String::new();
+    for artifact in finalizations {
+        if let ConsensusMessage::Finalization(finalization) = artifact {
+            match validation::verify_finalization(pool, membership, crypto, &finalization) {
+                Ok(()) => return Ok(finalization),
+                Err(err) => reason = err,
+            }
+        }
+    }
+    Err(reason)
+}
+
+fn setup_registry(cfg: &Config) -> Result<Arc<RegistryClientImpl>, ReplayError> {
+    let data_provider_config = cfg.registry_client.data_provider.as_ref().ok_or_else(|| {
+        ReplayError::Registry("No data provider in the registry client config".to_string())
+    })?;
+    let registry = Arc::new(RegistryClientImpl::new(
+        create_data_provider(data_provider_config, None),
+        None,
+    ));
+    // The replay only needs the registry versions that are available when it
+    // starts, so the registry is not polled for updates.
+    registry
+        .poll_once()
+        .map_err(|err| ReplayError::Registry(format!("{:?}", err)))?;
+    Ok(registry)
+}
+
+fn get_subnet_type(
+    registry: &dyn RegistryClient,
+    subnet_id: SubnetId,
+) -> Result<SubnetType, ReplayError> {
+    let record = registry
+        .get_subnet_record(subnet_id, registry.get_latest_version())
+        .map_err(|err| ReplayError::Registry(format!("{:?}", err)))?
+        .ok_or_else(|| ReplayError::Registry(format!("Subnet {} not found", subnet_id)))?;
+    SubnetType::try_from(record.subnet_type)
+        .map_err(|err| ReplayError::Registry(format!("{:?}", err)))
+}
+
+/// Returns the replica version recorded in the subnet record at the given
+/// registry version.
+fn get_replica_version(
+    registry: &dyn RegistryClient,
+    subnet_id: SubnetId,
+    registry_version: RegistryVersion,
+) -> Result<ReplicaVersion, ReplayError> {
+    registry
+        .get_replica_version(subnet_id, registry_version)
+        .map_err(|err| ReplayError::Registry(format!("{:?}", err)))?
+        .ok_or_else(|| {
+            ReplayError::Registry(format!(
+                "No replica version for subnet {} at registry version {}",
+                subnet_id, registry_version
+            ))
+        })
+}
+
+/// Waits until the full hash of the state at the given height was computed.
+fn wait_for_state_hash(
+    state_manager: &dyn StateManager<State = ReplicatedState>,
+    height: Height,
+) -> Result<CryptoHashOfState, ReplayError> {
+    loop {
+        match state_manager.get_state_hash_at(height) {
+            Ok(state_hash) => return Ok(state_hash),
+            Err(StateHashError::Transient(_)) => sleep(POLL_INTERVAL),
+            Err(StateHashError::Permanent(err)) => {
+                return Err(ReplayError::StateHash {
+                    height,
+                    reason: err.to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// Checks that the state at the height of the given CUP has the hash certified
+/// by the CUP.
+fn check_cup_state_hash(
+    state_manager: &dyn StateManager<State = ReplicatedState>,
+    cup: &CatchUpPackage,
+) -> Result<(), ReplayError> {
+    let height = cup.content.height();
+    let computed = wait_for_state_hash(state_manager, height)?;
+    if computed != cup.content.state_hash {
+        return Err(ReplayError::StateHashMismatch {
+            height,
+            expected: cup.content.state_hash.clone(),
+            computed,
+        });
+    }
+    println!(
+        "Height {}: state hash {} matches the CUP",
+        height,
+        hex::encode(&computed.get_ref().0)
+    );
+    Ok(())
+}
+
+/// Prints the hash of the certified part of the state at the given height.
+fn print_state_hash(state_manager: &dyn StateManager<State = ReplicatedState>, height: Height) {
+    if let Some((_, hash)) = state_manager
+        .list_state_hashes_to_certify()
+        .into_iter()
+        .find(|(h, _)| *h == height)
+    {
+        println!(
+            "Height {}: certified state hash {}",
+            height,
+            hex::encode(&hash.get_ref().0)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_config::registry_client::DataProviderConfig;
+
+    fn replay_with_config(cfg: Config) -> Result<Height, ReplayError> {
+        let backup_dir = tempfile::Builder::new().prefix("backup").tempdir().unwrap();
+        replay(ReplayOptions {
+            cfg,
+            subnet_id: SubnetId::from(PrincipalId::new_subnet_test_id(1)),
+            backup_spool_path: backup_dir.path().to_path_buf(),
+            replay_until_height: Some(Height::from(10)),
+            write_checkpoint: true,
+        })
+    }
+
+    #[test]
+    fn replay_fails_without_registry_data_provider() {
+        let result = Config::run_with_temp_config(replay_with_config);
+        assert!(
+            matches!(result, Err(ReplayError::Registry(_))),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn replay_fails_for_unknown_subnet() {
+        let local_store = tempfile::Builder::new()
+            .prefix("registry")
+            .tempdir()
+            .unwrap();
+        let result = Config::run_with_temp_config(|mut cfg| {
+            cfg.registry_client.data_provider = Some(DataProviderConfig::LocalStore(
+                local_store.path().to_path_buf(),
+            ));
+            replay_with_config(cfg)
+        });
+        match result {
+            Err(ReplayError::Registry(err)) => assert!(err.contains("not found"), "{}", err),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+}
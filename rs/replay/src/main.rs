@@ -0,0 +1,97 @@
+use clap::{App, Arg, ArgMatches};
+use ic_config::{Config, ConfigSource};
+use ic_replay::{replay, ReplayOptions};
+use ic_types::{Height, PrincipalId, SubnetId};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const ARG_CONF: &str = "config";
+const ARG_SUBNET_ID: &str = "subnet-id";
+const ARG_BACKUP_SPOOL_PATH: &str = "backup-spool-path";
+const ARG_REPLAY_UNTIL_HEIGHT: &str = "replay-until-height";
+const ARG_WRITE_CHECKPOINT: &str = "write-checkpoint";
+
+fn main() {
+    let matches = get_arg_matches();
+    let result = Config::run_with_temp_config(|default_config| {
+        let source = ConfigSource::File(PathBuf::from(matches.value_of(ARG_CONF).unwrap()));
+        let cfg = Config::load_with_default(&source, default_config).unwrap_or_else(|err| {
+            eprintln!("Failed to load config:\n  {}", err);
+            std::process::exit(1);
+        });
+
+        let subnet_id = PrincipalId::from_str(matches.value_of(ARG_SUBNET_ID).unwrap())
+            .map(SubnetId::from)
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to parse {}\n  {}", ARG_SUBNET_ID, err);
+                std::process::exit(1);
+            });
+
+        let replay_until_height = matches.value_of(ARG_REPLAY_UNTIL_HEIGHT).map(|arg| {
+            arg.parse().map(Height::from).unwrap_or_else(|err| {
+                eprintln!("Failed to parse {}\n  {}", ARG_REPLAY_UNTIL_HEIGHT, err);
+                std::process::exit(1);
+            })
+        });
+
+        replay(ReplayOptions {
+            cfg,
+            subnet_id,
+            backup_spool_path: PathBuf::from(matches.value_of(ARG_BACKUP_SPOOL_PATH).unwrap()),
+            replay_until_height,
+            write_checkpoint: matches.is_present(ARG_WRITE_CHECKPOINT),
+        })
+    });
+
+    match result {
+        Ok(height) => println!("Replayed the subnet state up to height {}", height),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn get_arg_matches() -> ArgMatches<'static> {
+    App::new("ic-replay")
+        .about(
+            "Rebuilds the replicated state of a subnet from the latest checkpoint and the \
+             consensus backup spool.",
+        )
+        .arg(
+            Arg::with_name(ARG_CONF)
+                .required(true)
+                .value_name("CONFIG_FILE")
+                .help("Configuration of the replica whose state is replayed, e.g. ic.json5."),
+        )
+        .arg(
+            Arg::with_name(ARG_SUBNET_ID)
+                .long(ARG_SUBNET_ID)
+                .value_name("SUBNET_ID")
+                .help("ID of the subnet whose state is replayed.")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_BACKUP_SPOOL_PATH)
+                .long(ARG_BACKUP_SPOOL_PATH)
+                .value_name("PATH")
+                .help("Spool path of the consensus backup of the replica.")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_REPLAY_UNTIL_HEIGHT)
+                .long(ARG_REPLAY_UNTIL_HEIGHT)
+                .value_name("HEIGHT")
+                .help("Stop after replaying the given height (default: replay all backed up heights).")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_WRITE_CHECKPOINT)
+                .long(ARG_WRITE_CHECKPOINT)
+                .help("Write a checkpoint at the height the replay stops at.")
+                .requires(ARG_REPLAY_UNTIL_HEIGHT),
+        )
+        .get_matches()
+}
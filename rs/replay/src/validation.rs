@@ -0,0 +1,62 @@
+//! Verification of the finalizations read from the consensus backup.
+
+use ic_consensus::consensus::{pool_reader::PoolReader, Membership};
+use ic_interfaces::crypto::MultiSigVerifier;
+use ic_types::consensus::{Committee, Finalization, FinalizationContent, HasHeight};
+use std::collections::{BTreeSet, HashSet};
+
+/// Verifies the given finalization the way the consensus validator does. It
+/// checks that:
+/// * the finalized block is notarized,
+/// * the signers are unique,
+/// * the number of signers is not less than the required threshold,
+/// * the signers are in the notary committee,
+/// * the signature is valid.
+pub(crate) fn verify_finalization(
+    pool: &PoolReader<'_>,
+    membership: &Membership,
+    crypto: &dyn MultiSigVerifier<FinalizationContent>,
+    finalization: &Finalization,
+) -> Result<(), String> {
+    let height = finalization.height();
+    pool.get_notarized_block(&finalization.content.block, height)
+        .map_err(|_| "finalized block is missing or not notarized".to_string())?;
+    let previous_beacon = pool
+        .get_random_beacon(height.decrement())
+        .ok_or_else(|| "previous random beacon is missing".to_string())?;
+
+    let signers = &finalization.signature.signers;
+    let threshold = membership
+        .get_committee_threshold(height, Committee::Notarization)
+        .map_err(|err| format!("failed to get the notarization threshold: {:?}", err))?;
+    if signers.iter().collect::<HashSet<_>>().len() < signers.len() {
+        return Err("repeated signer".to_string());
+    }
+    if signers.is_empty() || signers.len() < threshold {
+        return Err(format!(
+            "insufficient signatures: {} < {}",
+            signers.len(),
+            threshold
+        ));
+    }
+    for node_id in signers {
+        let is_notary = membership
+            .node_belongs_to_notarization_committee(height, &previous_beacon, *node_id)
+            .map_err(|err| format!("failed to get the notarization committee: {:?}", err))?;
+        if !is_notary {
+            return Err(format!("signer {} is not in the notary committee", node_id));
+        }
+    }
+
+    let registry_version = pool
+        .registry_version(height)
+        .ok_or_else(|| "registry version is unknown".to_string())?;
+    crypto
+        .verify_multi_sig_combined(
+            &finalization.signature.signature,
+            &finalization.content,
+            signers.iter().cloned().collect::<BTreeSet<_>>(),
+            registry_version,
+        )
+        .map_err(|err| format!("invalid signature: {}", err))
+}
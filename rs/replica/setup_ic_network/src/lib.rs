@@ -7,7 +7,7 @@ use ic_artifact_manager::{manager, processors};
 use ic_artifact_pool::{
     certification_pool::CertificationPoolImpl, consensus_pool::ConsensusPoolImpl,
     dkg_pool::DkgPoolImpl, ensure_persistent_pool_replica_version_compatibility,
-    ingress_pool::IngressPoolImpl,
+    ingress_pool::IngressPoolImpl, pool_migration::migrate_persistent_pool,
 };
 use ic_base_thread::async_safe_block_on_await;
 use ic_config::{artifact_pool::ArtifactPoolConfig, consensus::ConsensusConfig};
@@ -347,6 +347,13 @@ fn setup_artifact_manager(
     ensure_persistent_pool_replica_version_compatibility(
         artifact_pool_config.persistent_pool_db_path(),
     );
+    // Switch the persistent pool to the configured backend, if necessary.
+    migrate_persistent_pool(&artifact_pool_config, &replica_logger).unwrap_or_else(|err| {
+        panic!(
+            "Failed to migrate the persistent pool to the configured backend: {}",
+            err
+        )
+    });
 
     let (ingress_pool, consensus_pool, cert_pool, dkg_pool) = init_artifact_pools(
         subnet_id,
@@ -5,7 +5,7 @@ use ic_config::registry_client::DataProviderConfig;
 use ic_config::{subnet_config::SubnetConfigs, Config};
 use ic_crypto_sha::Sha256;
 use ic_crypto_tls_interfaces::TlsHandshake;
-use ic_interfaces::crypto::IngressSigVerifier;
+use ic_interfaces::crypto::{BasicSigner, IngressSigVerifier};
 use ic_interfaces::registry::{LocalStoreCertifiedTimeReader, RegistryClient};
 use ic_logger::info;
 use ic_metrics::MetricsRegistry;
@@ -13,7 +13,10 @@ use ic_metrics_exporter::MetricsRuntimeImpl;
 use ic_registry_client::helper::subnet::SubnetRegistry;
 use ic_replica::{args::ReplicaArgs, setup};
 use ic_sys::PAGE_SIZE;
-use ic_types::{replica_version::REPLICA_BINARY_HASH, PrincipalId, ReplicaVersion, SubnetId};
+use ic_types::{
+    messages::QueryResponseHash, replica_version::REPLICA_BINARY_HASH, PrincipalId, ReplicaVersion,
+    SubnetId,
+};
 use ic_utils::ic_features::*;
 use nix::unistd::{setpgid, Pid};
 use static_assertions::assert_eq_size;
@@ -329,6 +332,8 @@ async fn run() -> io::Result<()> {
         registry,
         Arc::clone(&crypto) as Arc<dyn TlsHandshake + Send + Sync>,
         Arc::clone(&crypto) as Arc<dyn IngressSigVerifier + Send + Sync>,
+        Arc::clone(&crypto) as Arc<dyn BasicSigner<QueryResponseHash> + Send + Sync>,
+        node_id,
         subnet_id,
         root_subnet_id,
         logger.clone(),
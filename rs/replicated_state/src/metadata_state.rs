@@ -16,6 +16,7 @@ use ic_registry_routing_table::RoutingTable;
 use ic_registry_subnet_features::SubnetFeatures;
 use ic_registry_subnet_type::SubnetType;
 use ic_types::{
+    batch::BlockmakerMetrics,
     crypto::CryptoHash,
     ingress::{IngressStatus, MAX_INGRESS_TTL},
     messages::{MessageId, RequestOrResponse},
@@ -115,6 +116,10 @@ pub struct SystemMetadata {
     /// needed to calculate how much time should be charged for when charging
     /// does occur.
     pub time_of_last_allocation_charge: Time,
+
+    /// Block maker statistics of the nodes of this subnet, e.g. for reducing
+    /// the rewards of unreliable nodes. Nodes leaving the subnet are dropped.
+    pub node_metrics: BTreeMap<NodeId, NodeMetrics>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -238,6 +243,10 @@ impl TryFrom<pb_metadata::SubnetTopology> for SubnetTopology {
 pub struct NodeTopology {
     pub ip_address: String,
     pub http_port: u16,
+    /// The DER-encoded node signing public key, used to verify the signatures
+    /// of the node on query responses. `None` if the registry holds no valid
+    /// signing key for the node.
+    pub public_key: Option<Vec<u8>>,
 }
 
 impl From<&NodeTopology> for pb_metadata::NodeTopology {
@@ -245,6 +254,7 @@ impl From<&NodeTopology> for pb_metadata::NodeTopology {
         Self {
             ip_address: item.ip_address.clone(),
             http_port: item.http_port as u32,
+            public_key: item.public_key.clone().unwrap_or_default(),
         }
     }
 }
@@ -255,10 +265,46 @@ impl TryFrom<pb_metadata::NodeTopology> for NodeTopology {
         Ok(Self {
             ip_address: item.ip_address,
             http_port: item.http_port as u16,
+            // A valid DER-encoded key is never empty.
+            public_key: Some(item.public_key).filter(|public_key| !public_key.is_empty()),
         })
     }
 }
 
+/// Block maker statistics of a single node, accumulated over all the
+/// finalized blocks since the node joined the subnet.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NodeMetrics {
+    /// The number of blocks proposed by the node that were finalized.
+    ///
+    /// This counts both the blocks proposed and the blocks finalized: which
+    /// proposals a replica sees before a block of the round is finalized is
+    /// not agreed upon, so proposals that did not get finalized can't be
+    /// accounted in the replicated state.
+    pub blocks_proposed_total: u64,
+    /// The number of times the node failed to propose a block that got
+    /// finalized, indexed by the rank it had as a block maker.
+    pub failed_proposals_by_rank: Vec<u64>,
+}
+
+impl From<&NodeMetrics> for pb_metadata::NodeMetrics {
+    fn from(item: &NodeMetrics) -> Self {
+        Self {
+            blocks_proposed_total: item.blocks_proposed_total,
+            failed_proposals_by_rank: item.failed_proposals_by_rank.clone(),
+        }
+    }
+}
+
+impl From<pb_metadata::NodeMetrics> for NodeMetrics {
+    fn from(item: pb_metadata::NodeMetrics) -> Self {
+        Self {
+            blocks_proposed_total: item.blocks_proposed_total,
+            failed_proposals_by_rank: item.failed_proposals_by_rank,
+        }
+    }
+}
+
 impl From<&SystemMetadata> for pb_metadata::SystemMetadata {
     fn from(item: &SystemMetadata) -> Self {
         // We do not store the subnet type when we serialize SystemMetadata. We rely on
@@ -291,6 +337,14 @@ impl From<&SystemMetadata> for pb_metadata::SystemMetadata {
                     .time_of_last_allocation_charge
                     .as_nanos_since_unix_epoch(),
             }),
+            node_metrics: item
+                .node_metrics
+                .iter()
+                .map(|(node_id, node_metrics)| pb_metadata::NodeMetricsEntry {
+                    node_id: Some(node_id_into_protobuf(*node_id)),
+                    node_metrics: Some(node_metrics.into()),
+                })
+                .collect(),
         }
     }
 }
@@ -308,6 +362,16 @@ impl TryFrom<pb_metadata::SystemMetadata> for SystemMetadata {
                 try_from_option_field(entry.subnet_stream, "SystemMetadata::streams::V")?,
             );
         }
+        let mut node_metrics = BTreeMap::<NodeId, NodeMetrics>::new();
+        for entry in item.node_metrics {
+            node_metrics.insert(
+                node_id_try_from_protobuf(try_from_option_field(
+                    entry.node_id,
+                    "SystemMetadata::node_metrics::K",
+                )?)?,
+                entry.node_metrics.unwrap_or_default().into(),
+            );
+        }
         Ok(Self {
             own_subnet_id: subnet_id_try_from_protobuf(try_from_option_field(
                 item.own_subnet_id,
@@ -347,6 +411,7 @@ impl TryFrom<pb_metadata::SystemMetadata> for SystemMetadata {
                 ),
                 None => Time::from_nanos_since_unix_epoch(item.batch_time_nanos),
             },
+            node_metrics,
         })
     }
 }
@@ -371,6 +436,7 @@ impl SystemMetadata {
             certification_version: 0,
             heap_delta_estimate: NumBytes::from(0),
             time_of_last_allocation_charge: UNIX_EPOCH,
+            node_metrics: Default::default(),
         }
     }
 
@@ -397,6 +463,31 @@ impl SystemMetadata {
     pub fn streams(&self) -> &Streams {
         &self.streams
     }
+
+    /// Accounts the block makers of a finalized block in `node_metrics`, and
+    /// drops the statistics of the nodes that are no longer part of this
+    /// subnet according to `network_topology`.
+    pub fn observe_blockmaker_metrics(&mut self, blockmaker_metrics: &BlockmakerMetrics) {
+        self.node_metrics
+            .entry(blockmaker_metrics.blockmaker)
+            .or_default()
+            .blocks_proposed_total += 1;
+        for (rank, node_id) in blockmaker_metrics.failed_blockmakers.iter().enumerate() {
+            let failed_proposals = &mut self
+                .node_metrics
+                .entry(*node_id)
+                .or_default()
+                .failed_proposals_by_rank;
+            if failed_proposals.len() <= rank {
+                failed_proposals.resize(rank + 1, 0);
+            }
+            failed_proposals[rank] += 1;
+        }
+        if let Some(own_subnet) = self.network_topology.subnets.get(&self.own_subnet_id) {
+            self.node_metrics
+                .retain(|node_id, _| own_subnet.nodes.contains_key(node_id));
+        }
+    }
 }
 
 /// Stream is the state of bi-directional communication session with a remote
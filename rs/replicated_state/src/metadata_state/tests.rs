@@ -2,7 +2,7 @@ use super::*;
 use ic_test_utilities::{
     mock_time,
     types::{
-        ids::{canister_test_id, message_test_id, user_test_id, SUBNET_1},
+        ids::{canister_test_id, message_test_id, node_test_id, user_test_id, SUBNET_1},
         messages::RequestBuilder,
     },
     types::{
@@ -14,6 +14,7 @@ use ic_types::{
     ingress::{WasmResult, MAX_INGRESS_TTL},
     messages::Payload,
 };
+use maplit::btreemap;
 
 #[test]
 fn can_prune_old_ingress_history_entries() {
@@ -194,3 +195,70 @@ fn streams_stats_after_deserialization() {
         deserialized_system_metadata.streams.responses_size_bytes()
     );
 }
+
+#[test]
+fn node_metrics_accumulate_and_survive_deserialization() {
+    let mut system_metadata = SystemMetadata::new(SUBNET_0, SubnetType::Application);
+    system_metadata.network_topology.subnets.insert(
+        SUBNET_0,
+        SubnetTopology {
+            nodes: (1..=3)
+                .map(|i| (node_test_id(i), NodeTopology::default()))
+                .collect(),
+            ..Default::default()
+        },
+    );
+
+    // Node 3 made the block at rank 2 after nodes 1 and 2 failed, then node 1
+    // made the block at rank 0.
+    system_metadata.observe_blockmaker_metrics(&BlockmakerMetrics {
+        blockmaker: node_test_id(3),
+        failed_blockmakers: vec![node_test_id(1), node_test_id(2)],
+    });
+    system_metadata.observe_blockmaker_metrics(&BlockmakerMetrics {
+        blockmaker: node_test_id(1),
+        failed_blockmakers: vec![],
+    });
+    assert_eq!(
+        system_metadata.node_metrics,
+        btreemap! {
+            node_test_id(1) => NodeMetrics {
+                blocks_proposed_total: 1,
+                failed_proposals_by_rank: vec![1],
+            },
+            node_test_id(2) => NodeMetrics {
+                blocks_proposed_total: 0,
+                failed_proposals_by_rank: vec![0, 1],
+            },
+            node_test_id(3) => NodeMetrics {
+                blocks_proposed_total: 1,
+                failed_proposals_by_rank: vec![],
+            },
+        }
+    );
+
+    let system_metadata_proto: ic_protobuf::state::system_metadata::v1::SystemMetadata =
+        (&system_metadata).into();
+    let deserialized_system_metadata: SystemMetadata = system_metadata_proto.try_into().unwrap();
+    assert_eq!(
+        system_metadata.node_metrics,
+        deserialized_system_metadata.node_metrics
+    );
+
+    // The statistics of nodes that left the subnet are dropped.
+    system_metadata
+        .network_topology
+        .subnets
+        .get_mut(&SUBNET_0)
+        .unwrap()
+        .nodes
+        .remove(&node_test_id(2));
+    system_metadata.observe_blockmaker_metrics(&BlockmakerMetrics {
+        blockmaker: node_test_id(1),
+        failed_blockmakers: vec![],
+    });
+    assert_eq!(
+        system_metadata.node_metrics.keys().collect::<Vec<_>>(),
+        vec![&node_test_id(1), &node_test_id(3)]
+    );
+}
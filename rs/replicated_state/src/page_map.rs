@@ -1,8 +1,8 @@
 mod checkpoint;
 pub mod int_map;
 mod page_allocator;
+mod storage;
 
-use checkpoint::Checkpoint;
 use ic_sys::PageBytes;
 pub use ic_sys::{PageIndex, PAGE_SIZE};
 pub use page_allocator::allocated_pages_count;
@@ -15,10 +15,12 @@ use ic_types::Height;
 use int_map::IntMap;
 use page_allocator::{Page, PageAllocator};
 use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
+use std::fs::OpenOptions;
 use std::ops::Range;
 use std::os::unix::io::RawFd;
 use std::path::Path;
+use storage::Storage;
+pub use storage::{list_overlays, merge_overlays, remove_overlays};
 
 /// `PageDelta` represents a changeset of the module heap.
 #[derive(Clone, Default, Debug)]
@@ -55,89 +57,6 @@ impl PageDelta {
     fn iter(&self) -> impl Iterator<Item = (PageIndex, &'_ Page)> {
         self.0.iter().map(|(idx, page)| (PageIndex::new(idx), page))
     }
-
-    /// Applies this delta to the specified file.
-    /// Precondition: `file` is seekable and writeable.
-    ///
-    /// The given `page_allocator` must be the same as the one used for
-    /// allocating pages in this `PageDelta`. It serves as a witness that
-    /// the contents of the page is still valid.
-    fn apply_to_file(
-        &self,
-        file: &mut File,
-        path: &Path,
-        page_allocator: &PageAllocator,
-    ) -> Result<(), PersistenceError> {
-        use std::io::{Seek, SeekFrom};
-
-        for (index, page) in self.iter() {
-            let offset = index.get() * PAGE_SIZE as u64;
-            file.seek(SeekFrom::Start(offset as u64)).map_err(|err| {
-                PersistenceError::FileSystemError {
-                    path: path.display().to_string(),
-                    context: format!("Failed to seek to {}", offset),
-                    internal_error: err.to_string(),
-                }
-            })?;
-            let mut contents = page.contents(page_allocator) as &[u8];
-            std::io::copy(&mut contents, file).map_err(|err| {
-                PersistenceError::FileSystemError {
-                    path: path.display().to_string(),
-                    context: format!("Failed to copy page #{}", index),
-                    internal_error: err.to_string(),
-                }
-            })?;
-        }
-        Ok(())
-    }
-
-    /// Persists this delta to the specified destination.
-    ///
-    /// The given `page_allocator` must be the same as the one used for
-    /// allocating pages in this `PageDelta`. It serves as a witness that
-    /// the contents of the page is still valid.
-    fn persist(&self, dst: &Path, page_allocator: &PageAllocator) -> Result<(), PersistenceError> {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(dst)
-            .map_err(|err| PersistenceError::FileSystemError {
-                path: dst.display().to_string(),
-                context: "Failed to open file".to_string(),
-                internal_error: err.to_string(),
-            })?;
-        self.apply_to_file(&mut file, dst, page_allocator)?;
-        Ok(())
-    }
-
-    /// Persists this delta to the specified destination and flushes it.
-    ///
-    /// The given `page_allocator` must be the same as the one used for
-    /// allocating pages in this `PageDelta`. It serves as a witness that
-    /// the contents of the page is still valid.
-    fn persist_and_sync(
-        &self,
-        dst: &Path,
-        page_allocator: &PageAllocator,
-    ) -> Result<(), PersistenceError> {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(dst)
-            .map_err(|err| PersistenceError::FileSystemError {
-                path: dst.display().to_string(),
-                context: "Failed to open file".to_string(),
-                internal_error: err.to_string(),
-            })?;
-        self.apply_to_file(&mut file, dst, page_allocator)?;
-        file.sync_all()
-            .map_err(|err| PersistenceError::FileSystemError {
-                path: dst.display().to_string(),
-                context: "Failed to sync file".to_string(),
-                internal_error: err.to_string(),
-            })?;
-        Ok(())
-    }
 }
 
 /// Errors that can happen when one saves or loads a PageMap.
@@ -161,6 +80,8 @@ pub enum PersistenceError {
         file_size: usize,
         page_size: usize,
     },
+    /// Overlay file is truncated or its page indices are not sorted.
+    InvalidOverlayFile { path: String, file_size: usize },
     /// (Slice) size is not equal to page size.
     BadPageSize { expected: usize, actual: usize },
 }
@@ -195,6 +116,11 @@ impl std::fmt::Display for PersistenceError {
                 "Size of heap file {} is {}, which is not a multiple of the page size {}",
                 path, file_size, page_size
             ),
+            PersistenceError::InvalidOverlayFile { path, file_size } => write!(
+                f,
+                "Overlay file {} of size {} is malformed",
+                path, file_size
+            ),
             PersistenceError::BadPageSize { expected, actual } => write!(
                 f,
                 "Bad slice size: expected {}, actual {}",
@@ -214,9 +140,10 @@ pub struct FileDescriptor {
 /// largest contiguous page range that contains the given page such that all
 /// pages share the same backing store. There are three possible cases:
 /// - The page is not in the current `PageMap` and it is zero initialized.
-/// - The page maps to the checkpoint file.
-/// - The page is in the page delta of the current `PageMap`. In this case the
-///   range is a singleton and its contents need to be copied out.
+/// - The page maps to the checkpoint base file.
+/// - The page is in the page delta of the current `PageMap` or in one of the
+///   checkpoint overlays. In this case the range is a singleton and its
+///   contents need to be copied out.
 pub enum MemoryRegion<'a> {
     Zeros(Range<PageIndex>),
     BackedByFile(Range<PageIndex>, FileDescriptor),
@@ -240,25 +167,18 @@ pub enum MemoryRegion<'a> {
 /// versioned.
 #[derive(Clone, Default)]
 pub struct PageMap {
-    /// The checkpoint that is used for all the pages that can not be found in
-    /// the `page_delta`.
-    checkpoint: Checkpoint,
+    /// The checkpoint base file and overlays that are used for all the pages
+    /// that can not be found in the `page_delta`.
+    storage: Storage,
 
     /// The height of the checkpoint that backs the page map.
     pub base_height: Option<Height>,
 
-    /// The map containing pages overriding pages from the `checkpoint`.
+    /// The map containing pages overriding pages from the `storage`.
     /// We need these pages to be able to reconstruct the full heap.
     /// It is reset when `strip_all_deltas()` method is called.
     page_delta: PageDelta,
 
-    /// The map containing deltas accumulated since the beginning of the
-    /// execution round.  It is reset when `strip_round_delta()` or
-    /// `strip_all_deltas()` methods are called.
-    ///
-    /// Invariant: round_delta ⊆ page_delta
-    round_delta: PageDelta,
-
     /// The allocator for PageDelta pages.
     /// It is reset when `strip_all_deltas()` method is called.
     page_allocator: PageAllocator,
@@ -272,16 +192,16 @@ impl PageMap {
         Default::default()
     }
 
-    /// Creates a page map backed by the provided heap file.
+    /// Creates a page map backed by the provided heap file and the overlays
+    /// stacked on top of it.
     ///
-    /// Note that the file is assumed to be read-only.
+    /// Note that the files are assumed to be read-only.
     pub fn open(heap_file: &Path, base_height: Option<Height>) -> Result<Self, PersistenceError> {
-        let checkpoint = Checkpoint::open(heap_file)?;
+        let storage = Storage::load(heap_file)?;
         Ok(Self {
-            checkpoint,
+            storage,
             base_height,
             page_delta: Default::default(),
-            round_delta: Default::default(),
             page_allocator: Default::default(),
         })
     }
@@ -292,22 +212,61 @@ impl PageMap {
         self.apply(page_delta);
     }
 
-    /// Persists the heap delta contained in this page map to the specified
-    /// destination.
-    pub fn persist_delta(&self, dst: &Path) -> Result<(), PersistenceError> {
-        self.page_delta.persist(dst, &self.page_allocator)
+    /// Persists this page map as of checkpoint `height` to the specified base
+    /// file and fsyncs the written files to disk.
+    ///
+    /// If the page map is backed by a checkpoint, `base_file` and its overlays
+    /// are assumed to hold the contents of that checkpoint and only the page
+    /// delta is written as a new overlay for `height`. Otherwise the base file
+    /// is rewritten with the full contents of the page map and all overlays
+    /// are removed.
+    pub fn persist_delta(&self, base_file: &Path, height: Height) -> Result<(), PersistenceError> {
+        if self.base_height.is_some() {
+            if self.page_delta.0.is_empty() {
+                return Ok(());
+            }
+            storage::write_overlay(
+                &storage::overlay_path(base_file, height),
+                self.page_delta
+                    .iter()
+                    .map(|(index, page)| (index, page.contents(&self.page_allocator))),
+            )
+        } else {
+            self.persist_full(base_file)?;
+            remove_overlays(base_file)
+        }
     }
 
-    /// Persists the heap delta contained in this page map to the specified
-    /// destination and fsync the file to disk.
-    pub fn persist_and_sync_delta(&self, dst: &Path) -> Result<(), PersistenceError> {
-        self.page_delta.persist_and_sync(dst, &self.page_allocator)
-    }
+    // Writes all non-zero pages of this page map to a fresh sparse file that
+    // replaces `dst`.
+    fn persist_full(&self, dst: &Path) -> Result<(), PersistenceError> {
+        use std::os::unix::fs::FileExt;
 
-    /// Persists the round delta contained in this page map to the specified
-    /// destination.
-    pub fn persist_round_delta(&self, dst: &Path) -> Result<(), PersistenceError> {
-        self.round_delta.persist(dst, &self.page_allocator)
+        let fs_error = |context: &str, err: std::io::Error| PersistenceError::FileSystemError {
+            path: dst.display().to_string(),
+            context: context.to_string(),
+            internal_error: err.to_string(),
+        };
+        let tmp = ic_utils::fs::get_tmp_for_path(dst);
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp)
+            .map_err(|err| fs_error("Failed to open file", err))?;
+        let num_pages = self.num_host_pages();
+        file.set_len((num_pages * PAGE_SIZE) as u64)
+            .map_err(|err| fs_error("Failed to resize file", err))?;
+        for (index, contents) in self.host_pages_iter() {
+            if contents.iter().any(|b| *b != 0) {
+                file.write_all_at(contents, index.get() * PAGE_SIZE as u64)
+                    .map_err(|err| fs_error(&format!("Failed to write page #{}", index), err))?;
+            }
+        }
+        file.sync_all()
+            .map_err(|err| fs_error("Failed to sync file", err))?;
+        std::fs::rename(&tmp, dst).map_err(|err| fs_error("Failed to rename file", err))?;
+        Ok(())
     }
 
     /// Returns the iterator over host pages managed by this `PageMap`.
@@ -322,7 +281,7 @@ impl PageMap {
     pub fn get_page(&self, page_index: PageIndex) -> &PageBytes {
         match self.page_delta.get_page(page_index, &self.page_allocator) {
             Some(page) => page,
-            None => self.checkpoint.get_page(page_index),
+            None => self.storage.get_page(page_index),
         }
     }
 
@@ -337,7 +296,7 @@ impl PageMap {
                     None => PageIndex::new(0),
                     Some(start) => {
                         // Here `start` is a page in `page_delta`. We need to skip that page to
-                        // get to the start of the checkpoint region that contains `page_index`.
+                        // get to the start of the storage region that contains `page_index`.
                         PageIndex::new(start.get() + 1)
                     }
                 };
@@ -351,17 +310,18 @@ impl PageMap {
                 };
                 let range = Range { start, end };
                 assert!(range.contains(&page_index));
-                self.checkpoint.get_memory_region(page_index, range)
+                self.storage.get_memory_region(page_index, range)
             }
         }
     }
 
-    /// Returns the whole checkpoint memory region.
+    /// Returns the memory region of the whole checkpoint base file.
+    ///
+    /// Pages stored in checkpoint overlays or in the page delta take
+    /// precedence over this region and are reported by
+    /// `get_memory_region()` as `BackedByPage`.
     pub fn get_checkpoint_memory_region(&self) -> MemoryRegion {
-        let start = PageIndex::new(0);
-        let end = PageIndex::new(u64::MAX);
-        self.checkpoint
-            .get_memory_region(start, Range { start, end })
+        self.storage.get_base_memory_region()
     }
 
     /// Removes the page delta from this page map.
@@ -372,16 +332,10 @@ impl PageMap {
         // a good property to maintain.
         {
             std::mem::take(&mut self.page_delta);
-            std::mem::take(&mut self.round_delta);
         }
         std::mem::take(&mut self.page_allocator);
     }
 
-    /// Removes the round delta from this page map.
-    pub fn strip_round_delta(&mut self) {
-        std::mem::take(&mut self.round_delta);
-    }
-
     pub fn get_page_delta_indices(&self) -> Vec<PageIndex> {
         self.page_delta.iter().map(|(index, _)| index).collect()
     }
@@ -394,7 +348,7 @@ impl PageMap {
     /// ∀ n . n ≥ self.num_host_pages() ⇒ self.get_page(n) = ZERO_PAGE
     /// ```
     pub fn num_host_pages(&self) -> usize {
-        let pages_in_checkpoint = self.checkpoint.num_pages();
+        let pages_in_checkpoint = self.storage.num_pages();
 
         pages_in_checkpoint.max(
             self.page_delta
@@ -411,9 +365,7 @@ impl PageMap {
         I: IntoIterator<Item = (PageIndex, Page)>,
     {
         let delta = PageDelta(delta.into_iter().map(|(i, p)| (i.get(), p)).collect());
-        // Delta is a persistent data structure and is cheap to clone.
-        self.page_delta.update(delta.clone());
-        self.round_delta.update(delta)
+        self.page_delta.update(delta)
     }
 
     // Copies the page with the given index. This is used by `Buffer`.
@@ -0,0 +1,401 @@
+//! Layered on-disk representation of a `PageMap`.
+//!
+//! A page map is persisted as a _base_ heap file plus a stack of _overlay_
+//! files stored next to it. Every checkpoint writes a single overlay that
+//! contains only the pages modified since the previous checkpoint, so the cost
+//! of a checkpoint is proportional to the amount of modified memory rather
+//! than to the size of the heap.
+//!
+//! An overlay for base file `<name>` created at height `h` is called
+//! `<name>.<hex(h)>.overlay`. Its layout is:
+//!
+//! ```text
+//! ┌───────────┬─────┬─────────────┬────────────────┬─────┬──────────────────┬───────────┐
+//! │ page 0    │ ... │ page N-1    │ index 0 (u64)  │ ... │ index N-1 (u64)  │ N (u64)   │
+//! │ PAGE_SIZE │     │ PAGE_SIZE   │ little-endian  │     │ little-endian    │ LE        │
+//! └───────────┴─────┴─────────────┴────────────────┴─────┴──────────────────┴───────────┘
+//! ```
+//!
+//! The page indices are strictly increasing. Newer overlays take precedence
+//! over older ones, and all overlays take precedence over the base file.
+//!
+//! Overlays are compacted by `merge_overlays`, which replaces all overlays of
+//! a file with a single overlay named after the most recent height. If the
+//! merged overlay would exceed `1 / FOLD_OVERLAY_SIZE_DIVISOR` of the size of
+//! the base file, its pages are written into (a copy of) the base file instead
+//! and no overlay is left, so that overlays don't grow unboundedly next to the
+//! base file. The merge result is a function of the base and overlay files
+//! only, so all replicas applying the same policy end up with identical files.
+
+use crate::page_map::{checkpoint::Checkpoint, MemoryRegion, PageIndex, PersistenceError};
+use ic_sys::{mmap::ScopedMmap, page_bytes_from_ptr, PageBytes, PAGE_SIZE};
+use ic_types::Height;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const OVERLAY_EXTENSION: &str = "overlay";
+const INDEX_SIZE: usize = std::mem::size_of::<u64>();
+
+/// Merged overlays larger than the base file size divided by this number are
+/// folded into the base file.
+const FOLD_OVERLAY_SIZE_DIVISOR: u64 = 2;
+
+/// Returns the path of the overlay of `base_file` created at `height`.
+pub(crate) fn overlay_path(base_file: &Path, height: Height) -> PathBuf {
+    let mut file_name = base_file
+        .file_name()
+        .expect("base file must have a name")
+        .to_os_string();
+    file_name.push(format!(".{:016x}.{}", height.get(), OVERLAY_EXTENSION));
+    base_file.with_file_name(file_name)
+}
+
+/// Returns the overlays of the specified base file ordered by height, oldest
+/// first.
+pub fn list_overlays(base_file: &Path) -> Result<Vec<(Height, PathBuf)>, PersistenceError> {
+    let dir = match base_file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = match base_file.file_name().and_then(|name| name.to_str()) {
+        Some(name) => format!("{}.", name),
+        None => return Ok(vec![]),
+    };
+    let suffix = format!(".{}", OVERLAY_EXTENSION);
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(fs_error(dir, "Failed to list directory", err)),
+    };
+
+    let mut overlays = BTreeMap::new();
+    for entry in entries {
+        let entry = entry.map_err(|err| fs_error(dir, "Failed to read directory entry", err))?;
+        let file_name = entry.file_name();
+        let height = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix(prefix.as_str()))
+            .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+            .filter(|hex| hex.len() == 16)
+            .and_then(|hex| u64::from_str_radix(hex, 16).ok());
+        if let Some(height) = height {
+            overlays.insert(Height::new(height), entry.path());
+        }
+    }
+    Ok(overlays.into_iter().collect())
+}
+
+/// Writes the given pages as an overlay file at the specified path.
+///
+/// Precondition: `pages` are sorted by index and the indices are unique.
+pub(crate) fn write_overlay<'a, I>(path: &Path, pages: I) -> Result<(), PersistenceError>
+where
+    I: IntoIterator<Item = (PageIndex, &'a PageBytes)>,
+{
+    ic_utils::fs::write_using_tmp_file(path, |writer| {
+        let mut indices: Vec<u64> = Vec::new();
+        for (index, contents) in pages {
+            debug_assert!(indices.last().map_or(true, |last| *last < index.get()));
+            writer.write_all(contents)?;
+            indices.push(index.get());
+        }
+        for index in indices.iter() {
+            writer.write_all(&index.to_le_bytes())?;
+        }
+        writer.write_all(&(indices.len() as u64).to_le_bytes())
+    })
+    .map_err(|err| fs_error(path, "Failed to write overlay file", err))
+}
+
+/// Replaces all overlays of `base_file` with a single overlay that is named
+/// after the most recent one and contains the latest version of every page
+/// stored in any of the overlays. If the merged overlay would be larger than
+/// `1 / FOLD_OVERLAY_SIZE_DIVISOR` of the base file, the pages are written
+/// into the base file instead and all overlays are removed.
+///
+/// Returns the number of overlays that were merged. Nothing is done if there
+/// are fewer than two overlays.
+pub fn merge_overlays(base_file: &Path) -> Result<usize, PersistenceError> {
+    let overlays = list_overlays(base_file)?;
+    if overlays.len() < 2 {
+        return Ok(0);
+    }
+
+    let files = overlays
+        .iter()
+        .map(|(_, path)| OverlayFile::open(path))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut pages: BTreeMap<u64, &PageBytes> = BTreeMap::new();
+    for file in files.iter() {
+        for (index, contents) in file.iter() {
+            pages.insert(index, contents);
+        }
+    }
+
+    let base_size = match std::fs::metadata(base_file) {
+        Ok(metadata) => metadata.len(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(err) => return Err(fs_error(base_file, "Failed to retrieve file metadata", err)),
+    };
+    let merged_size = (pages.len() * PAGE_SIZE) as u64;
+
+    let obsolete_overlays = if merged_size > base_size / FOLD_OVERLAY_SIZE_DIVISOR {
+        fold_into_base(base_file, base_size, pages)?;
+        overlays.len()
+    } else {
+        let (_, newest) = overlays.last().unwrap();
+        write_overlay(
+            newest,
+            pages
+                .into_iter()
+                .map(|(index, contents)| (PageIndex::new(index), contents)),
+        )?;
+        overlays.len() - 1
+    };
+
+    for (_, path) in overlays.iter().take(obsolete_overlays) {
+        std::fs::remove_file(path)
+            .map_err(|err| fs_error(path, "Failed to remove overlay", err))?;
+    }
+    Ok(overlays.len())
+}
+
+/// Writes the given pages into a copy of `base_file` that then replaces it.
+///
+/// The base file is not modified in place because it might be memory mapped
+/// by a `PageMap`.
+fn fold_into_base(
+    base_file: &Path,
+    base_size: u64,
+    pages: BTreeMap<u64, &PageBytes>,
+) -> Result<(), PersistenceError> {
+    use std::os::unix::fs::FileExt;
+
+    let tmp = ic_utils::fs::get_tmp_for_path(base_file);
+    if base_size > 0 {
+        ic_utils::fs::copy_file_sparse(base_file, &tmp)
+            .map_err(|err| fs_error(base_file, "Failed to copy file", err))?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .open(&tmp)
+        .map_err(|err| fs_error(&tmp, "Failed to open file", err))?;
+    for (index, contents) in pages {
+        file.write_all_at(contents, index * PAGE_SIZE as u64)
+            .map_err(|err| fs_error(&tmp, &format!("Failed to write page #{}", index), err))?;
+    }
+    file.sync_all()
+        .map_err(|err| fs_error(&tmp, "Failed to sync file", err))?;
+    std::fs::rename(&tmp, base_file)
+        .map_err(|err| fs_error(base_file, "Failed to rename file", err))
+}
+
+/// Removes all overlays of the specified base file.
+pub fn remove_overlays(base_file: &Path) -> Result<(), PersistenceError> {
+    for (_, path) in list_overlays(base_file)? {
+        std::fs::remove_file(&path)
+            .map_err(|err| fs_error(&path, "Failed to remove overlay", err))?;
+    }
+    Ok(())
+}
+
+fn fs_error(path: &Path, context: &str, err: std::io::Error) -> PersistenceError {
+    PersistenceError::FileSystemError {
+        path: path.display().to_string(),
+        context: context.to_string(),
+        internal_error: err.to_string(),
+    }
+}
+
+/// A memory-mapped overlay file.
+struct OverlayFile {
+    mmap: ScopedMmap,
+    /// Sorted indices of the pages stored in the file. The contents of page
+    /// `indices[i]` are stored at offset `i * PAGE_SIZE`.
+    indices: Vec<u64>,
+}
+
+impl OverlayFile {
+    fn open(path: &Path) -> Result<Self, PersistenceError> {
+        let file = File::open(path).map_err(|err| fs_error(path, "Failed to open file", err))?;
+        let len = file
+            .metadata()
+            .map_err(|err| fs_error(path, "Failed to retrieve file metadata", err))?
+            .len() as usize;
+
+        let invalid = || PersistenceError::InvalidOverlayFile {
+            path: path.display().to_string(),
+            file_size: len,
+        };
+        if len < INDEX_SIZE {
+            return Err(invalid());
+        }
+
+        let mmap = ScopedMmap::from_readonly_file(&file, len).map_err(|err| {
+            PersistenceError::MmapError {
+                path: path.display().to_string(),
+                len,
+                internal_error: err.to_string(),
+            }
+        })?;
+        let bytes = mmap.as_slice();
+
+        let read_u64 = |offset: usize| {
+            let mut buf = [0u8; INDEX_SIZE];
+            buf.copy_from_slice(&bytes[offset..offset + INDEX_SIZE]);
+            u64::from_le_bytes(buf)
+        };
+
+        let num_pages = read_u64(len - INDEX_SIZE) as usize;
+        let expected_len = num_pages
+            .checked_mul(PAGE_SIZE + INDEX_SIZE)
+            .and_then(|n| n.checked_add(INDEX_SIZE));
+        if expected_len != Some(len) {
+            return Err(invalid());
+        }
+
+        let indices_start = num_pages * PAGE_SIZE;
+        let indices: Vec<u64> = (0..num_pages)
+            .map(|i| read_u64(indices_start + i * INDEX_SIZE))
+            .collect();
+        if indices.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(invalid());
+        }
+
+        Ok(Self { mmap, indices })
+    }
+
+    fn page_at(&self, position: usize) -> &PageBytes {
+        // SAFETY: The file was validated to contain `indices.len()` pages
+        // starting at offset 0, and the memory is read-only and valid for the
+        // lifetime of `self`.
+        unsafe { page_bytes_from_ptr(self, self.mmap.addr().add(position * PAGE_SIZE)) }
+    }
+
+    fn get_page(&self, page_index: PageIndex) -> Option<&PageBytes> {
+        self.indices
+            .binary_search(&page_index.get())
+            .ok()
+            .map(|position| self.page_at(position))
+    }
+
+    /// Returns the closest pages stored in this overlay that are strictly
+    /// below and strictly above the given page.
+    fn bounds(&self, page_index: PageIndex) -> (Option<u64>, Option<u64>) {
+        let position = match self.indices.binary_search(&page_index.get()) {
+            Ok(position) | Err(position) => position,
+        };
+        let lower = position
+            .checked_sub(1)
+            .and_then(|p| self.indices.get(p).copied());
+        let upper = self.indices[position..]
+            .iter()
+            .copied()
+            .find(|index| *index > page_index.get());
+        (lower, upper)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (u64, &PageBytes)> + '_ {
+        self.indices
+            .iter()
+            .enumerate()
+            .map(move |(position, index)| (*index, self.page_at(position)))
+    }
+
+    fn num_pages(&self) -> usize {
+        self.indices.last().map_or(0, |last| *last as usize + 1)
+    }
+}
+
+/// The persisted part of a `PageMap`: a base checkpoint file with overlays
+/// stacked on top of it.
+#[derive(Clone, Default)]
+pub(crate) struct Storage {
+    base: Checkpoint,
+    /// Overlays ordered from the oldest to the newest.
+    overlays: Vec<Arc<OverlayFile>>,
+}
+
+impl Storage {
+    /// Opens the specified base file together with all its overlays.
+    pub fn load(base_file: &Path) -> Result<Self, PersistenceError> {
+        let base = Checkpoint::open(base_file)?;
+        let overlays = list_overlays(base_file)?
+            .iter()
+            .map(|(_, path)| OverlayFile::open(path).map(Arc::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { base, overlays })
+    }
+
+    /// Returns the page with the specified index.
+    pub fn get_page(&self, page_index: PageIndex) -> &PageBytes {
+        self.overlays
+            .iter()
+            .rev()
+            .find_map(|overlay| overlay.get_page(page_index))
+            .unwrap_or_else(|| self.base.get_page(page_index))
+    }
+
+    /// See the comments of `PageMap::get_memory_region()`.
+    ///
+    /// Pages stored in overlays are returned as `BackedByPage`, all other
+    /// pages are served by the base file within `page_range` narrowed down so
+    /// that it contains no overlay pages.
+    pub fn get_memory_region(
+        &self,
+        page_index: PageIndex,
+        page_range: Range<PageIndex>,
+    ) -> MemoryRegion {
+        assert!(page_range.contains(&page_index));
+        if let Some(page) = self
+            .overlays
+            .iter()
+            .rev()
+            .find_map(|overlay| overlay.get_page(page_index))
+        {
+            return MemoryRegion::BackedByPage(page);
+        }
+
+        let mut start = page_range.start.get();
+        let mut end = page_range.end.get();
+        for overlay in self.overlays.iter() {
+            let (lower, upper) = overlay.bounds(page_index);
+            if let Some(lower) = lower {
+                start = start.max(lower + 1);
+            }
+            if let Some(upper) = upper {
+                end = end.min(upper);
+            }
+        }
+        let range = Range {
+            start: PageIndex::new(start),
+            end: PageIndex::new(end),
+        };
+        self.base.get_memory_region(page_index, range)
+    }
+
+    /// Returns the memory region of the whole base file.
+    ///
+    /// Note that pages stored in overlays take precedence over the pages of
+    /// this region, see `get_memory_region()`.
+    pub fn get_base_memory_region(&self) -> MemoryRegion {
+        let start = PageIndex::new(0);
+        let end = PageIndex::new(u64::MAX);
+        self.base.get_memory_region(start, Range { start, end })
+    }
+
+    /// Returns the max number of (possibly) non-zero pages in this storage.
+    pub fn num_pages(&self) -> usize {
+        self.overlays
+            .iter()
+            .map(|overlay| overlay.num_pages())
+            .fold(self.base.num_pages(), usize::max)
+    }
+}
@@ -1,5 +1,9 @@
-use super::{checkpoint::Checkpoint, Buffer, PageIndex, PageMap};
+use super::{
+    checkpoint::Checkpoint, list_overlays, merge_overlays, Buffer, MemoryRegion, PageIndex,
+    PageMap, PersistenceError,
+};
 use ic_sys::PAGE_SIZE;
+use ic_types::Height;
 use std::fs::OpenOptions;
 
 #[test]
@@ -84,7 +88,9 @@ fn persisted_map_is_equivalent_to_the_original() {
     let mut original_map = PageMap::default();
     original_map.update(pages);
 
-    original_map.persist_delta(&heap_file).unwrap();
+    original_map
+        .persist_delta(&heap_file, Height::new(1))
+        .unwrap();
     let persisted_map = PageMap::open(&heap_file, None).unwrap();
 
     assert_eq!(persisted_map, original_map);
@@ -99,7 +105,9 @@ fn can_persist_and_load_an_empty_page_map() {
     let heap_file = tmp.path().join("heap");
 
     let original_map = PageMap::default();
-    original_map.persist_delta(&heap_file).unwrap();
+    original_map
+        .persist_delta(&heap_file, Height::new(1))
+        .unwrap();
     let persisted_map =
         PageMap::open(&heap_file, None).expect("opening an empty page map must succeed");
 
@@ -167,3 +175,226 @@ fn can_use_buffer_to_modify_page_map() {
         }
     }
 }
+
+/// Persists the page delta of `page_map` as of `height` and reopens it.
+fn persist_and_reopen(page_map: &PageMap, heap_file: &std::path::Path, height: u64) -> PageMap {
+    page_map
+        .persist_delta(heap_file, Height::new(height))
+        .unwrap();
+    PageMap::open(heap_file, Some(Height::new(height))).unwrap()
+}
+
+#[test]
+fn persisting_a_backed_page_map_writes_an_overlay() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("heap");
+
+    let mut page_map = PageMap::default();
+    page_map.update(&[
+        (PageIndex::new(0), &[1u8; PAGE_SIZE]),
+        (PageIndex::new(5), &[1u8; PAGE_SIZE]),
+    ]);
+    let mut page_map_1 = persist_and_reopen(&page_map, &heap_file, 1);
+    assert!(list_overlays(&heap_file).unwrap().is_empty());
+    let base_contents = std::fs::read(&heap_file).unwrap();
+
+    page_map_1.update(&[
+        (PageIndex::new(5), &[2u8; PAGE_SIZE]),
+        (PageIndex::new(9), &[2u8; PAGE_SIZE]),
+    ]);
+    let page_map_2 = persist_and_reopen(&page_map_1, &heap_file, 2);
+
+    // The base file is left intact and only the two modified pages are written.
+    assert_eq!(std::fs::read(&heap_file).unwrap(), base_contents);
+    let overlays = list_overlays(&heap_file).unwrap();
+    assert_eq!(
+        overlays
+            .iter()
+            .map(|(height, _)| *height)
+            .collect::<Vec<_>>(),
+        vec![Height::new(2)]
+    );
+    assert_eq!(
+        std::fs::metadata(&overlays[0].1).unwrap().len() as usize,
+        2 * PAGE_SIZE + 3 * std::mem::size_of::<u64>()
+    );
+
+    assert_eq!(page_map_2, page_map_1);
+    assert_eq!(page_map_2.num_host_pages(), 10);
+
+    // A backed page map without modifications does not create an overlay.
+    persist_and_reopen(&page_map_2, &heap_file, 3);
+    assert_eq!(list_overlays(&heap_file).unwrap().len(), 1);
+}
+
+#[test]
+fn newer_overlays_take_precedence() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("heap");
+
+    let mut page_map = persist_and_reopen(&PageMap::default(), &heap_file, 1);
+    for height in 2..5u64 {
+        page_map.update(&[(PageIndex::new(3), &[height as u8; PAGE_SIZE])]);
+        page_map = persist_and_reopen(&page_map, &heap_file, height);
+    }
+
+    assert_eq!(list_overlays(&heap_file).unwrap().len(), 3);
+    assert_eq!(page_map.get_page(PageIndex::new(3)), &[4u8; PAGE_SIZE]);
+    assert_eq!(page_map.get_page(PageIndex::new(2)), &[0u8; PAGE_SIZE]);
+}
+
+#[test]
+fn merging_overlays_preserves_contents() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("heap");
+
+    // The base file is large enough for the merged overlay not to be folded
+    // into it.
+    let mut page_map = PageMap::default();
+    page_map.update(&[
+        (PageIndex::new(1), &[1u8; PAGE_SIZE]),
+        (PageIndex::new(100), &[100u8; PAGE_SIZE]),
+    ]);
+    let mut page_map = persist_and_reopen(&page_map, &heap_file, 1);
+    for height in 2..6u64 {
+        page_map.update(&[
+            (PageIndex::new(height), &[height as u8; PAGE_SIZE]),
+            (PageIndex::new(1), &[height as u8; PAGE_SIZE]),
+        ]);
+        page_map = persist_and_reopen(&page_map, &heap_file, height);
+    }
+    assert_eq!(list_overlays(&heap_file).unwrap().len(), 4);
+
+    assert_eq!(merge_overlays(&heap_file).unwrap(), 4);
+
+    let overlays = list_overlays(&heap_file).unwrap();
+    assert_eq!(overlays.len(), 1);
+    assert_eq!(overlays[0].0, Height::new(5));
+    let merged = PageMap::open(&heap_file, Some(Height::new(5))).unwrap();
+    assert_eq!(merged, page_map);
+
+    // Merging a single overlay is a no-op.
+    assert_eq!(merge_overlays(&heap_file).unwrap(), 0);
+}
+
+#[test]
+fn merging_large_overlays_folds_them_into_the_base_file() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("heap");
+
+    let mut page_map = PageMap::default();
+    page_map.update(&[(PageIndex::new(1), &[1u8; PAGE_SIZE])]);
+    let mut page_map = persist_and_reopen(&page_map, &heap_file, 1);
+    for height in 2..6u64 {
+        page_map.update(&[
+            (PageIndex::new(height), &[height as u8; PAGE_SIZE]),
+            (PageIndex::new(1), &[height as u8; PAGE_SIZE]),
+        ]);
+        page_map = persist_and_reopen(&page_map, &heap_file, height);
+    }
+    assert_eq!(list_overlays(&heap_file).unwrap().len(), 4);
+
+    assert_eq!(merge_overlays(&heap_file).unwrap(), 4);
+
+    assert!(list_overlays(&heap_file).unwrap().is_empty());
+    assert_eq!(
+        std::fs::metadata(&heap_file).unwrap().len(),
+        6 * PAGE_SIZE as u64
+    );
+    let folded = PageMap::open(&heap_file, Some(Height::new(5))).unwrap();
+    assert_eq!(folded, page_map);
+}
+
+#[test]
+fn persisting_an_unbacked_page_map_removes_overlays() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("heap");
+
+    let mut page_map = persist_and_reopen(&PageMap::default(), &heap_file, 1);
+    page_map.update(&[(PageIndex::new(7), &[7u8; PAGE_SIZE])]);
+    persist_and_reopen(&page_map, &heap_file, 2);
+    assert_eq!(list_overlays(&heap_file).unwrap().len(), 1);
+
+    // E.g. the canister was reinstalled and got a fresh page map.
+    let mut fresh = PageMap::default();
+    fresh.update(&[(PageIndex::new(2), &[2u8; PAGE_SIZE])]);
+    let reopened = persist_and_reopen(&fresh, &heap_file, 3);
+
+    assert!(list_overlays(&heap_file).unwrap().is_empty());
+    assert_eq!(reopened, fresh);
+}
+
+#[test]
+fn overlay_pages_are_excluded_from_file_backed_regions() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("heap");
+
+    let mut page_map = PageMap::default();
+    page_map.update(&[(PageIndex::new(9), &[1u8; PAGE_SIZE])]);
+    let mut page_map = persist_and_reopen(&page_map, &heap_file, 1);
+    page_map.update(&[(PageIndex::new(4), &[2u8; PAGE_SIZE])]);
+    let page_map = persist_and_reopen(&page_map, &heap_file, 2);
+
+    match page_map.get_memory_region(PageIndex::new(4)) {
+        MemoryRegion::BackedByPage(contents) => assert_eq!(contents, &[2u8; PAGE_SIZE]),
+        _ => panic!("Expected the overlay page to be backed by page"),
+    }
+    match page_map.get_memory_region(PageIndex::new(2)) {
+        MemoryRegion::BackedByFile(range, _) => {
+            assert_eq!(range, PageIndex::new(0)..PageIndex::new(4))
+        }
+        _ => panic!("Expected the page to be backed by file"),
+    }
+    match page_map.get_memory_region(PageIndex::new(6)) {
+        MemoryRegion::BackedByFile(range, _) => {
+            assert_eq!(range, PageIndex::new(5)..PageIndex::new(10))
+        }
+        _ => panic!("Expected the page to be backed by file"),
+    }
+}
+
+#[test]
+fn returns_an_error_if_overlay_is_truncated() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("heap");
+
+    let mut page_map = persist_and_reopen(&PageMap::default(), &heap_file, 1);
+    page_map.update(&[(PageIndex::new(1), &[1u8; PAGE_SIZE])]);
+    persist_and_reopen(&page_map, &heap_file, 2);
+
+    let (_, overlay) = list_overlays(&heap_file).unwrap().pop().unwrap();
+    let len = std::fs::metadata(&overlay).unwrap().len();
+    OpenOptions::new()
+        .write(true)
+        .open(&overlay)
+        .unwrap()
+        .set_len(len - 1)
+        .unwrap();
+
+    match PageMap::open(&heap_file, Some(Height::new(2))) {
+        Err(PersistenceError::InvalidOverlayFile { .. }) => (),
+        Err(err) => panic!("Expected invalid overlay file error, got {:?}", err),
+        Ok(_) => panic!("Expected invalid overlay file error, got Ok(_)"),
+    }
+}
@@ -1,7 +1,7 @@
 use crate::state_layout::CheckpointManager;
 use crate::utils::do_copy;
 use ic_logger::ReplicaLogger;
-use ic_utils::fs::{sync_and_mark_files_readonly, sync_path};
+use ic_utils::fs::{copy_mtime, sync_and_mark_files_readonly, sync_path};
 use ic_utils::thread::parallel_map;
 use std::convert::identity;
 use std::io::Error;
@@ -424,7 +424,9 @@ fn copy_recursively_respecting_tombstones(
 }
 
 /// Copies the given file and ensures that the `read/write` permission of the
-/// target file match the given permission.
+/// target file match the given permission. The modification time of the
+/// source is preserved, so that files that did not change between checkpoints
+/// can be recognized by their metadata.
 fn copy_and_sync_file(
     log: &ReplicaLogger,
     src: &Path,
@@ -432,6 +434,7 @@ fn copy_and_sync_file(
     dst_permissions: FilePermissions,
 ) -> std::io::Result<()> {
     do_copy(log, src, dst)?;
+    copy_mtime(src, dst)?;
 
     // We keep the directory writable though to make sure we can rename
     // them or delete the files.
@@ -201,6 +201,7 @@ pub struct CanisterStateBits {
 /// │       └── <hex(canister_id)>
 /// │           ├── queues.pbuf
 /// │           ├── vmemory_0.bin
+/// │           ├── vmemory_0.bin.<hex(round)>.overlay
 /// │           ├── canister.pbuf
 /// │           ├── stable_memory.(pbuf|bin)
 /// │           └── software.wasm
@@ -213,6 +214,7 @@ pub struct CanisterStateBits {
 /// │          └── <hex(canister_id)>
 /// │              ├── queues.pbuf
 /// │              ├── vmemory_0.bin
+/// │              ├── vmemory_0.bin.<hex(round)>.overlay
 /// │              ├── canister.pbuf
 /// │              ├── stable_memory.(pbuf|bin)
 /// │              └── software.wasm
@@ -802,6 +804,13 @@ where
             )
         });
 
+        // Leave the file untouched if it already has the right contents. This
+        // preserves its modification time, which allows the state manager to
+        // reuse the hashes of unchanged files when computing manifests.
+        if std::fs::read(&self.path).ok().as_ref() == Some(&serialized) {
+            return Ok(());
+        }
+
         let file = open_for_write(&self.path)?;
         let mut writer = std::io::BufWriter::new(file);
         writer
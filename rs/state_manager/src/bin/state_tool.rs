@@ -0,0 +1,383 @@
+//! Offline inspection of the checkpoints written by the state manager.
+//!
+//! Checkpoints are referred to by height, in decimal. Diverged checkpoints
+//! and backups are referred to by prefixing the height with `diverged:` and
+//! `backup:` respectively, e.g. `ic-state-tool manifest <ROOT> diverged:300`.
+//!
+//! During subnet recovery, `export` packs a checkpoint into a single archive
+//! that `import` validates and installs into the state root of another node.
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use ic_logger::{LoggerImpl, ReplicaLogger};
+use ic_metrics::MetricsRegistry;
+use ic_protobuf::state::{
+    queues::v1::CanisterQueues, system_metadata::v1::SystemMetadata as PbSystemMetadata,
+};
+use ic_registry_subnet_type::SubnetType;
+use ic_state_layout::{CheckpointLayout, ReadOnly, StateLayout};
+use ic_state_manager::{
+    checkpoint::load_checkpoint,
+    checkpoint_archive::{export_checkpoint, import_checkpoint},
+    manifest::{
+        compute_manifest, diff_manifest, manifest_hash, validate_manifest, DEFAULT_CHUNK_SIZE,
+    },
+    ManifestMetrics,
+};
+use ic_types::{
+    crypto::CryptoHash,
+    state_sync::{FileInfo, Manifest},
+    CryptoHashOfState, Height, PrincipalId,
+};
+use prost::Message;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+fn main() {
+    let root_arg = || {
+        Arg::with_name("ROOT")
+            .help("Path to the state root directory, e.g. /var/lib/ic/data/ic_state")
+            .required(true)
+    };
+    let checkpoint_arg = |name| {
+        Arg::with_name(name)
+            .help("Checkpoint height, optionally prefixed with `diverged:` or `backup:`")
+            .required(true)
+    };
+    let matches = App::new("ic-state-tool")
+        .version("0.1")
+        .about("IC State Inspection Utility")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("List checkpoints, diverged checkpoints and backups")
+                .arg(root_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("manifest")
+                .about("Print the manifest and root hash of a checkpoint")
+                .arg(root_arg())
+                .arg(checkpoint_arg("CHECKPOINT")),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Print the differences between the manifests of two checkpoints")
+                .arg(root_arg())
+                .arg(checkpoint_arg("FROM"))
+                .arg(checkpoint_arg("TO")),
+        )
+        .subcommand(
+            SubCommand::with_name("decode")
+                .about(
+                    "Print a system_metadata.pbuf, subnet_queues.pbuf or queues.pbuf file as JSON",
+                )
+                .arg(Arg::with_name("FILE").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Load a checkpoint and check that it matches the expected state hash")
+                .arg(root_arg())
+                .arg(checkpoint_arg("CHECKPOINT"))
+                .arg(
+                    Arg::with_name("STATE_HASH")
+                        .help("Expected state (manifest root) hash, hex-encoded")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Write a checkpoint into a verifiable archive")
+                .arg(root_arg())
+                .arg(checkpoint_arg("CHECKPOINT"))
+                .arg(
+                    Arg::with_name("ARCHIVE")
+                        .help("Path of the archive to write")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Validate an archive and install the checkpoint it contains")
+                .arg(root_arg())
+                .arg(
+                    Arg::with_name("ARCHIVE")
+                        .help("Path of the archive to read")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("STATE_HASH")
+                        .help("Expected state hash, hex-encoded, e.g. from the recovery CUP"),
+                ),
+        )
+        .get_matches();
+
+    let result = match matches.subcommand() {
+        ("list", Some(matches)) => list(&state_layout(matches)),
+        ("manifest", Some(matches)) => print_manifest(&state_layout(matches), matches),
+        ("diff", Some(matches)) => diff(&state_layout(matches), matches),
+        ("decode", Some(matches)) => decode(Path::new(matches.value_of("FILE").unwrap())),
+        ("verify", Some(matches)) => verify(&state_layout(matches), matches),
+        ("export", Some(matches)) => export(&state_layout(matches), matches),
+        ("import", Some(matches)) => import(&state_layout(matches), matches),
+        _ => unreachable!("clap only accepts the subcommands declared above"),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn logger() -> ReplicaLogger {
+    let logger = LoggerImpl::new(&Default::default(), "ic_state_tool".to_string());
+    ReplicaLogger::new(logger.root.clone().into())
+}
+
+fn state_layout(matches: &ArgMatches) -> StateLayout {
+    StateLayout::new(logger(), PathBuf::from(matches.value_of("ROOT").unwrap()))
+}
+
+/// Parses a checkpoint reference (`<height>`, `diverged:<height>` or
+/// `backup:<height>`) into the layout of the referenced checkpoint.
+fn checkpoint_layout(
+    layout: &StateLayout,
+    checkpoint: &str,
+) -> Result<CheckpointLayout<ReadOnly>, String> {
+    let parse_height = |h: &str| {
+        h.parse::<u64>()
+            .map(Height::new)
+            .map_err(|err| format!("Invalid checkpoint height {}: {}", h, err))
+    };
+    let (path, height) = if let Some(h) = checkpoint.strip_prefix("diverged:") {
+        let height = parse_height(h)?;
+        (layout.diverged_checkpoint_path(height), height)
+    } else if let Some(h) = checkpoint.strip_prefix("backup:") {
+        let height = parse_height(h)?;
+        (layout.backup_checkpoint_path(height), height)
+    } else {
+        let height = parse_height(checkpoint)?;
+        return layout
+            .checkpoint(height)
+            .map_err(|err| format!("Failed to open checkpoint {}: {}", checkpoint, err));
+    };
+    CheckpointLayout::new(path, height)
+        .map_err(|err| format!("Failed to open checkpoint {}: {}", checkpoint, err))
+}
+
+fn list(layout: &StateLayout) -> Result<(), String> {
+    let sections = [
+        ("checkpoints", layout.checkpoint_heights()),
+        ("diverged checkpoints", layout.diverged_checkpoint_heights()),
+        ("backups", layout.backup_heights()),
+    ];
+    for (name, heights) in sections.iter() {
+        let heights = heights
+            .as_ref()
+            .map_err(|err| format!("Failed to list {}: {}", name, err))?;
+        println!("{}:", name);
+        for height in heights {
+            println!("  {}", height);
+        }
+    }
+    Ok(())
+}
+
+/// Computes the manifest of the given checkpoint, using the state sync
+/// version recorded in its metadata.
+fn checkpoint_manifest(checkpoint: &CheckpointLayout<ReadOnly>) -> Result<Manifest, String> {
+    let metadata = checkpoint
+        .system_metadata()
+        .deserialize()
+        .map_err(|err| format!("Failed to read the system metadata: {}", err))?;
+    compute_manifest(
+        &ManifestMetrics::new(&MetricsRegistry::new()),
+        &logger(),
+        metadata.state_sync_version,
+        checkpoint.raw_path(),
+        DEFAULT_CHUNK_SIZE,
+        None,
+    )
+    .map_err(|err| {
+        format!(
+            "Failed to compute the manifest of {}: {}",
+            checkpoint.raw_path().display(),
+            err
+        )
+    })
+}
+
+fn print_manifest(layout: &StateLayout, matches: &ArgMatches) -> Result<(), String> {
+    let checkpoint = checkpoint_layout(layout, matches.value_of("CHECKPOINT").unwrap())?;
+    let manifest = checkpoint_manifest(&checkpoint)?;
+    println!("{}", manifest);
+    println!("Root hash: {}", hex::encode(manifest_hash(&manifest)));
+    Ok(())
+}
+
+/// Returns the part of the state a file belongs to: the canister directory
+/// for canister files, the file itself otherwise.
+fn owner(file: &FileInfo) -> String {
+    let mut components = file.relative_path.components();
+    match components.next() {
+        Some(first) if first.as_os_str() == "canister_states" => match components.next() {
+            Some(canister) => {
+                // Canister directories are named after the hex-encoded canister id.
+                let dir = canister.as_os_str().to_string_lossy();
+                match hex::decode(dir.as_ref())
+                    .ok()
+                    .and_then(|bytes| PrincipalId::try_from(&bytes[..]).ok())
+                {
+                    Some(canister_id) => format!("canister {}", canister_id),
+                    None => format!("canister {}", dir),
+                }
+            }
+            None => file.relative_path.display().to_string(),
+        },
+        _ => file.relative_path.display().to_string(),
+    }
+}
+
+fn diff(layout: &StateLayout, matches: &ArgMatches) -> Result<(), String> {
+    let from = checkpoint_manifest(&checkpoint_layout(
+        layout,
+        matches.value_of("FROM").unwrap(),
+    )?)?;
+    let to = checkpoint_manifest(&checkpoint_layout(layout, matches.value_of("TO").unwrap())?)?;
+
+    let files = |manifest: &Manifest| -> BTreeMap<PathBuf, FileInfo> {
+        manifest
+            .file_table
+            .iter()
+            .map(|f| (f.relative_path.clone(), f.clone()))
+            .collect()
+    };
+    let (from_files, to_files) = (files(&from), files(&to));
+    let paths: BTreeSet<&PathBuf> = from_files.keys().chain(to_files.keys()).collect();
+
+    // Changes, grouped by the canister (or top-level file) they belong to.
+    let mut changes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for path in paths {
+        let (change, file) = match (from_files.get(path), to_files.get(path)) {
+            (Some(f), None) => ("removed", f),
+            (None, Some(f)) => ("added", f),
+            (Some(f), Some(t)) if f.hash != t.hash => ("changed", t),
+            _ => continue,
+        };
+        changes.entry(owner(file)).or_default().push(format!(
+            "  {} {} ({} bytes)",
+            change,
+            path.display(),
+            file.size_bytes
+        ));
+    }
+
+    if changes.is_empty() {
+        println!("The manifests are identical.");
+    }
+    for (owner, changes) in changes {
+        println!("{}:", owner);
+        for change in changes {
+            println!("{}", change);
+        }
+    }
+
+    // What state sync would have to do to turn `FROM` into `TO`.
+    let diff_script = diff_manifest(&from, &HashSet::new(), &to);
+    println!(
+        "\nState sync: {} files copied, {} chunks copied, {} chunks fetched, {} zero chunks",
+        diff_script.copy_files.len(),
+        diff_script.copy_chunks.len(),
+        diff_script.fetch_chunks.len(),
+        diff_script.zeros_chunks
+    );
+    println!("Root hash FROM: {}", hex::encode(manifest_hash(&from)));
+    println!("Root hash TO:   {}", hex::encode(manifest_hash(&to)));
+    Ok(())
+}
+
+fn decode(path: &Path) -> Result<(), String> {
+    let bytes =
+        std::fs::read(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    let decode_err =
+        |err: prost::DecodeError| format!("Failed to decode {}: {}", path.display(), err);
+    let json = match path.file_name().and_then(|name| name.to_str()) {
+        Some("system_metadata.pbuf") => {
+            serde_json::to_string_pretty(&PbSystemMetadata::decode(&bytes[..]).map_err(decode_err)?)
+        }
+        Some("subnet_queues.pbuf") | Some("queues.pbuf") => {
+            serde_json::to_string_pretty(&CanisterQueues::decode(&bytes[..]).map_err(decode_err)?)
+        }
+        _ => {
+            return Err(format!(
+                "Don't know how to decode {}: expected system_metadata.pbuf, subnet_queues.pbuf or queues.pbuf",
+                path.display()
+            ))
+        }
+    }
+    .map_err(|err| format!("Failed to convert {} to JSON: {}", path.display(), err))?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn verify(layout: &StateLayout, matches: &ArgMatches) -> Result<(), String> {
+    let checkpoint_ref = matches.value_of("CHECKPOINT").unwrap();
+    let expected_hash = hex::decode(matches.value_of("STATE_HASH").unwrap())
+        .map_err(|err| format!("Invalid state hash: {}", err))?;
+    let checkpoint = checkpoint_layout(layout, checkpoint_ref)?;
+
+    let state = load_checkpoint(&checkpoint, SubnetType::Application, None)
+        .map_err(|err| format!("Failed to load checkpoint {}: {}", checkpoint_ref, err))?;
+    println!(
+        "Loaded checkpoint {} with {} canisters",
+        checkpoint_ref,
+        state.canister_states.len()
+    );
+
+    let manifest = checkpoint_manifest(&checkpoint)?;
+    validate_manifest(
+        &manifest,
+        &CryptoHashOfState::from(CryptoHash(expected_hash)),
+    )
+    .map_err(|err| format!("Checkpoint {} does not match: {}", checkpoint_ref, err))?;
+    println!(
+        "Checkpoint {} matches state hash {}",
+        checkpoint_ref,
+        hex::encode(manifest_hash(&manifest))
+    );
+    Ok(())
+}
+
+fn export(layout: &StateLayout, matches: &ArgMatches) -> Result<(), String> {
+    let checkpoint_ref = matches.value_of("CHECKPOINT").unwrap();
+    let archive = Path::new(matches.value_of("ARCHIVE").unwrap());
+    let checkpoint = checkpoint_layout(layout, checkpoint_ref)?;
+    let root_hash = export_checkpoint(&logger(), &checkpoint, archive)
+        .map_err(|err| format!("Failed to export checkpoint {}: {}", checkpoint_ref, err))?;
+    println!(
+        "Exported checkpoint {} with state hash {} to {}",
+        checkpoint_ref,
+        hex::encode(&root_hash.get_ref().0),
+        archive.display()
+    );
+    Ok(())
+}
+
+fn import(layout: &StateLayout, matches: &ArgMatches) -> Result<(), String> {
+    let archive = Path::new(matches.value_of("ARCHIVE").unwrap());
+    let expected_hash = matches
+        .value_of("STATE_HASH")
+        .map(|h| {
+            hex::decode(h)
+                .map(|h| CryptoHashOfState::from(CryptoHash(h)))
+                .map_err(|err| format!("Invalid state hash: {}", err))
+        })
+        .transpose()?;
+    let (height, root_hash) = import_checkpoint(layout, archive, expected_hash.as_ref())
+        .map_err(|err| format!("Failed to import {}: {}", archive.display(), err))?;
+    println!(
+        "Imported checkpoint {} with state hash {}",
+        height,
+        hex::encode(&root_hash.get_ref().0)
+    );
+    Ok(())
+}
@@ -4,8 +4,10 @@ use ic_cow_state::{CowMemoryManager, CowMemoryManagerImpl, MappedState};
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::Memory;
 use ic_replicated_state::{
-    canister_state::execution_state::WasmBinary, page_map::PageMap, CanisterMetrics, CanisterState,
-    ExecutionState, NumWasmPages64, ReplicatedState, SchedulerState, SystemState,
+    canister_state::execution_state::WasmBinary,
+    page_map::{self, PageMap, PersistenceError},
+    CanisterMetrics, CanisterState, ExecutionState, NumWasmPages64, ReplicatedState,
+    SchedulerState, SystemState,
 };
 use ic_state_layout::{
     CanisterStateBits, CheckpointLayout, ExecutionStateBits, ReadPolicy, ReadWritePolicy, RwPolicy,
@@ -16,8 +18,16 @@ use ic_utils::ic_features::*;
 use ic_utils::thread::parallel_map;
 use std::collections::BTreeMap;
 use std::convert::{From, TryFrom};
+use std::path::Path;
 use std::sync::Arc;
 
+/// The number of overlay files a canister memory file can accumulate in the
+/// tip before all overlays are merged into one.
+///
+/// The merge policy must be the same on all replicas because the overlay files
+/// end up in checkpoints and hence in the manifest.
+pub const MAX_OVERLAYS: usize = 8;
+
 /// Creates a checkpoint of the node state using specified directory
 /// layout. Returns a new state that is equivalent the to given one
 /// and a result of the operation.
@@ -42,7 +52,7 @@ pub fn make_checkpoint(
             .step_duration
             .with_label_values(&["serialize_to_tip"])
             .start_timer();
-        serialize_to_tip(state, &tip, thread_pool)?;
+        serialize_to_tip(state, height, &tip, thread_pool)?;
     }
 
     let cp = {
@@ -66,6 +76,7 @@ pub fn make_checkpoint(
 
 fn serialize_to_tip(
     state: &ReplicatedState,
+    height: Height,
     tip: &CheckpointLayout<RwPolicy>,
     thread_pool: &mut scoped_threadpool::Pool,
 ) -> Result<(), CheckpointError> {
@@ -76,7 +87,7 @@ fn serialize_to_tip(
         .serialize((state.subnet_queues()).into())?;
 
     let results = parallel_map(thread_pool, state.canisters_iter(), |canister_state| {
-        serialize_canister_to_tip(canister_state, tip)
+        serialize_canister_to_tip(canister_state, height, tip)
     });
 
     for result in results.into_iter() {
@@ -87,6 +98,7 @@ fn serialize_to_tip(
 
 fn serialize_canister_to_tip(
     canister_state: &CanisterState,
+    height: Height,
     tip: &CheckpointLayout<RwPolicy>,
 ) -> Result<(), CheckpointError> {
     let canister_layout = tip.canister(&canister_state.canister_id())?;
@@ -99,14 +111,19 @@ fn serialize_canister_to_tip(
             canister_layout
                 .wasm()
                 .serialize(&execution_state.wasm_binary.binary)?;
-            execution_state
-                .wasm_memory
-                .page_map
-                .persist_and_sync_delta(&canister_layout.vmemory_0())?;
-            execution_state
-                .stable_memory
-                .page_map
-                .persist_and_sync_delta(&canister_layout.stable_memory_blob())?;
+            for (page_map, path) in &[
+                (
+                    &execution_state.wasm_memory.page_map,
+                    canister_layout.vmemory_0(),
+                ),
+                (
+                    &execution_state.stable_memory.page_map,
+                    canister_layout.stable_memory_blob(),
+                ),
+            ] {
+                merge_overlays_if_needed(path)?;
+                page_map.persist_delta(path, height)?;
+            }
 
             execution_state.cow_mem_mgr.checkpoint();
 
@@ -117,7 +134,17 @@ fn serialize_canister_to_tip(
                 last_executed_round: execution_state.last_executed_round,
             })
         }
-        None => None,
+        None => {
+            // The canister was uninstalled, make sure its memory does not
+            // leak into the checkpoint.
+            for path in &[
+                canister_layout.vmemory_0(),
+                canister_layout.stable_memory_blob(),
+            ] {
+                truncate_memory_file(path)?;
+            }
+            None
+        }
     };
     canister_layout
         .canister()
@@ -163,6 +190,56 @@ fn serialize_canister_to_tip(
         .map_err(CheckpointError::from)
 }
 
+/// Merges the overlays of the specified memory file if there are at least
+/// `MAX_OVERLAYS` of them. Returns true if the overlays were merged.
+fn merge_overlays_if_needed(path: &Path) -> Result<bool, PersistenceError> {
+    if page_map::list_overlays(path)?.len() >= MAX_OVERLAYS {
+        page_map::merge_overlays(path)?;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Truncates the specified memory file if it exists and removes its overlays.
+fn truncate_memory_file(path: &Path) -> Result<(), CheckpointError> {
+    if std::fs::metadata(path).map_or(false, |metadata| metadata.len() > 0) {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_len(0))
+            .map_err(|err| CheckpointError::IoError {
+                path: path.to_path_buf(),
+                message: "Failed to truncate memory file".to_string(),
+                io_err: err.to_string(),
+            })?;
+    }
+    page_map::remove_overlays(path)?;
+    Ok(())
+}
+
+/// Merges the overlays of all canister memory files in the tip according to
+/// the same policy that `make_checkpoint` applies, so that the next checkpoint
+/// doesn't have to do it synchronously.
+///
+/// Returns the number of memory files whose overlays were merged.
+pub fn merge_tip_overlays<P: ReadWritePolicy>(
+    tip: &CheckpointLayout<P>,
+) -> Result<usize, CheckpointError> {
+    let mut merged = 0;
+    for canister_id in tip.canister_ids()? {
+        let canister_layout = tip.canister(&canister_id)?;
+        for path in &[
+            canister_layout.vmemory_0(),
+            canister_layout.stable_memory_blob(),
+        ] {
+            if merge_overlays_if_needed(path)? {
+                merged += 1;
+            }
+        }
+    }
+    Ok(merged)
+}
+
 /// loads the node state heighted with `height` using the specified
 /// directory layout.
 pub fn load_checkpoint<P: ReadPolicy + Send + Sync>(
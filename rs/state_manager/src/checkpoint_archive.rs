@@ -0,0 +1,367 @@
+//! Export of checkpoints into self-contained archives and their verified
+//! import, used to carry a recovered state between machines.
+//!
+//! An archive is a tar file with the following entries, in this order:
+//!
+//! ```text
+//! height                       decimal height of the checkpoint
+//! root_hash                    hex-encoded root hash of the manifest
+//! manifest.pbuf                the manifest, see `encode_manifest`
+//! state/<relative path>        one entry per file of the manifest file table,
+//! ...                          in the order of the file table
+//! ```
+//!
+//! The import validates the manifest against the root hash and every chunk of
+//! every file against the manifest before the checkpoint is moved into the
+//! `checkpoints` directory, so a successfully imported checkpoint is known to
+//! have the state hash reported by the import.
+
+use crate::{
+    manifest::{
+        compute_manifest, manifest_hash, validate_chunk, validate_manifest, ChunkValidationError,
+        ManifestValidationError, DEFAULT_CHUNK_SIZE,
+    },
+    CheckpointError, ManifestMetrics,
+};
+use ic_logger::ReplicaLogger;
+use ic_metrics::MetricsRegistry;
+use ic_state_layout::{error::LayoutError, CheckpointLayout, ReadOnly, RwPolicy, StateLayout};
+use ic_types::{
+    crypto::CryptoHash,
+    state_sync::{decode_manifest, encode_manifest, Manifest},
+    CryptoHashOfState, Height,
+};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+const HEIGHT_ENTRY: &str = "height";
+const ROOT_HASH_ENTRY: &str = "root_hash";
+const MANIFEST_ENTRY: &str = "manifest.pbuf";
+const STATE_DIR: &str = "state";
+
+/// Errors that can happen when a checkpoint is exported or imported.
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// Failed to read or write checkpoint or archive files.
+    Checkpoint(CheckpointError),
+    /// The archive does not have the structure described in the module docs.
+    Malformed(String),
+    /// The manifest in the archive does not match the root hash.
+    InvalidManifest(ManifestValidationError),
+    /// The contents of a file in the archive do not match the manifest.
+    InvalidChunk {
+        relative_path: PathBuf,
+        err: ChunkValidationError,
+    },
+    /// The archive contains a valid state, but not the expected one.
+    UnexpectedRootHash {
+        expected: CryptoHashOfState,
+        actual: CryptoHashOfState,
+    },
+}
+
+impl std::error::Error for ArchiveError {}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveError::Checkpoint(err) => write!(f, "{}", err),
+            ArchiveError::Malformed(message) => write!(f, "malformed archive: {}", message),
+            ArchiveError::InvalidManifest(err) => write!(f, "invalid manifest: {}", err),
+            ArchiveError::InvalidChunk { relative_path, err } => write!(
+                f,
+                "invalid contents of file {}: {}",
+                relative_path.display(),
+                err
+            ),
+            ArchiveError::UnexpectedRootHash { expected, actual } => write!(
+                f,
+                "archived state has root hash {}, expected {}",
+                hex::encode(&actual.get_ref().0),
+                hex::encode(&expected.get_ref().0)
+            ),
+        }
+    }
+}
+
+impl From<CheckpointError> for ArchiveError {
+    fn from(err: CheckpointError) -> Self {
+        ArchiveError::Checkpoint(err)
+    }
+}
+
+impl From<LayoutError> for ArchiveError {
+    fn from(err: LayoutError) -> Self {
+        ArchiveError::Checkpoint(err.into())
+    }
+}
+
+fn io_error(path: &Path, message: &str, err: std::io::Error) -> ArchiveError {
+    ArchiveError::Checkpoint(CheckpointError::IoError {
+        path: path.to_path_buf(),
+        message: message.to_string(),
+        io_err: err.to_string(),
+    })
+}
+
+/// Writes the checkpoint into an archive at `dst` and returns the root hash
+/// of its manifest.
+///
+/// The manifest is computed from scratch using the state sync version
+/// recorded in the checkpoint.
+pub fn export_checkpoint(
+    log: &ReplicaLogger,
+    checkpoint: &CheckpointLayout<ReadOnly>,
+    dst: &Path,
+) -> Result<CryptoHashOfState, ArchiveError> {
+    let version = checkpoint
+        .system_metadata()
+        .deserialize()?
+        .state_sync_version;
+    let manifest = compute_manifest(
+        &ManifestMetrics::new(&MetricsRegistry::new()),
+        log,
+        version,
+        checkpoint.raw_path(),
+        DEFAULT_CHUNK_SIZE,
+        None,
+    )?;
+    let root_hash = CryptoHashOfState::from(CryptoHash(manifest_hash(&manifest).to_vec()));
+
+    ic_utils::fs::write_atomically(dst, |writer| {
+        let mut builder = tar::Builder::new(writer);
+        let mut append = |path: &Path, size: u64, data: &mut dyn Read| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(size);
+            header.set_mode(0o444);
+            // Keep archives of the same checkpoint byte-for-byte identical.
+            header.set_mtime(0);
+            builder.append_data(&mut header, path, data)
+        };
+
+        let height = checkpoint.height().get().to_string();
+        append(
+            Path::new(HEIGHT_ENTRY),
+            height.len() as u64,
+            &mut height.as_bytes(),
+        )?;
+        let hash = hex::encode(&root_hash.get_ref().0);
+        append(
+            Path::new(ROOT_HASH_ENTRY),
+            hash.len() as u64,
+            &mut hash.as_bytes(),
+        )?;
+        let encoded_manifest = encode_manifest(&manifest);
+        append(
+            Path::new(MANIFEST_ENTRY),
+            encoded_manifest.len() as u64,
+            &mut &encoded_manifest[..],
+        )?;
+
+        for file_info in manifest.file_table.iter() {
+            let mut file = File::open(checkpoint.raw_path().join(&file_info.relative_path))?;
+            append(
+                &Path::new(STATE_DIR).join(&file_info.relative_path),
+                file_info.size_bytes,
+                &mut file,
+            )?;
+        }
+        builder.finish()
+    })
+    .map_err(|err| io_error(dst, "Failed to write checkpoint archive", err))?;
+
+    Ok(root_hash)
+}
+
+/// Validates the archive at `src` and moves the checkpoint it contains into
+/// the `checkpoints` directory of `layout`. Returns the height and the root
+/// hash of the imported checkpoint.
+///
+/// If `expected_root_hash` is specified (e.g. the state hash of a recovery
+/// CUP), archives containing any other state are rejected.
+pub fn import_checkpoint(
+    layout: &StateLayout,
+    src: &Path,
+    expected_root_hash: Option<&CryptoHashOfState>,
+) -> Result<(Height, CryptoHashOfState), ArchiveError> {
+    let file = File::open(src).map_err(|err| io_error(src, "Failed to open archive", err))?;
+    let mut archive = tar::Archive::new(file);
+    let mut entries = archive
+        .entries()
+        .map_err(|err| io_error(src, "Failed to read archive", err))?;
+
+    let height = read_entry(
+        next_entry(&mut entries, src, HEIGHT_ENTRY)?,
+        src,
+        HEIGHT_ENTRY,
+    )?;
+    let height = std::str::from_utf8(&height)
+        .ok()
+        .and_then(|h| h.trim().parse::<u64>().ok())
+        .map(Height::new)
+        .ok_or_else(|| ArchiveError::Malformed("invalid height".to_string()))?;
+
+    let root_hash = read_entry(
+        next_entry(&mut entries, src, ROOT_HASH_ENTRY)?,
+        src,
+        ROOT_HASH_ENTRY,
+    )?;
+    let root_hash = std::str::from_utf8(&root_hash)
+        .ok()
+        .and_then(|h| hex::decode(h.trim()).ok())
+        .map(|h| CryptoHashOfState::from(CryptoHash(h)))
+        .ok_or_else(|| ArchiveError::Malformed("invalid root hash".to_string()))?;
+    if let Some(expected) = expected_root_hash {
+        if *expected != root_hash {
+            return Err(ArchiveError::UnexpectedRootHash {
+                expected: expected.clone(),
+                actual: root_hash,
+            });
+        }
+    }
+
+    let manifest = read_entry(
+        next_entry(&mut entries, src, MANIFEST_ENTRY)?,
+        src,
+        MANIFEST_ENTRY,
+    )?;
+    let manifest = decode_manifest(&manifest).map_err(ArchiveError::Malformed)?;
+    validate_manifest(&manifest, &root_hash).map_err(ArchiveError::InvalidManifest)?;
+
+    let scratchpad = layout
+        .tmp()?
+        .join(format!("import_checkpoint_{:016x}", height.get()));
+    if scratchpad.exists() {
+        std::fs::remove_dir_all(&scratchpad)
+            .map_err(|err| io_error(&scratchpad, "Failed to remove old scratchpad", err))?;
+    }
+
+    let result = extract_files(&manifest, &mut entries, src, &scratchpad).and_then(|()| {
+        if let Some(entry) = entries.next() {
+            let path = entry
+                .and_then(|entry| entry.path().map(|path| path.into_owned()))
+                .map_err(|err| io_error(src, "Failed to read archive", err))?;
+            return Err(ArchiveError::Malformed(format!(
+                "unexpected entry {}",
+                path.display()
+            )));
+        }
+        let scratchpad_layout = CheckpointLayout::<RwPolicy>::new(scratchpad.clone(), height)?;
+        layout.scratchpad_to_checkpoint(scratchpad_layout, height)?;
+        Ok(())
+    });
+
+    if result.is_err() && scratchpad.exists() {
+        let _ = std::fs::remove_dir_all(&scratchpad);
+    }
+    result.map(|()| (height, root_hash))
+}
+
+/// Returns the next entry of the archive at `src`, which is expected to be
+/// the entry called `expected`.
+fn next_entry<'a>(
+    entries: &mut tar::Entries<'a, File>,
+    src: &Path,
+    expected: &str,
+) -> Result<tar::Entry<'a, File>, ArchiveError> {
+    match entries.next() {
+        Some(entry) => entry.map_err(|err| io_error(src, "Failed to read archive", err)),
+        None => Err(ArchiveError::Malformed(format!(
+            "archive ended, expected entry {}",
+            expected
+        ))),
+    }
+}
+
+/// Checks that the entry is called `name` and returns its contents.
+fn read_entry(
+    mut entry: tar::Entry<'_, File>,
+    src: &Path,
+    name: &str,
+) -> Result<Vec<u8>, ArchiveError> {
+    let path = entry
+        .path()
+        .map_err(|err| io_error(src, "Failed to read archive entry path", err))?
+        .into_owned();
+    if path != Path::new(name) {
+        return Err(ArchiveError::Malformed(format!(
+            "expected entry {}, found {}",
+            name,
+            path.display()
+        )));
+    }
+    let mut bytes = Vec::new();
+    entry
+        .read_to_end(&mut bytes)
+        .map_err(|err| io_error(src, "Failed to read archive entry", err))?;
+    Ok(bytes)
+}
+
+/// Writes the files of the manifest read from consecutive archive entries into
+/// `dst`, validating each chunk against the manifest.
+fn extract_files(
+    manifest: &Manifest,
+    entries: &mut tar::Entries<'_, File>,
+    src: &Path,
+    dst: &Path,
+) -> Result<(), ArchiveError> {
+    let mut chunk_index = 0;
+    for (file_index, file_info) in manifest.file_table.iter().enumerate() {
+        let relative_path = &file_info.relative_path;
+        // The manifest might not be the one the caller expects, make sure it
+        // cannot be used to write outside of the scratchpad.
+        if !relative_path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(ArchiveError::Malformed(format!(
+                "invalid file path {} in manifest",
+                relative_path.display()
+            )));
+        }
+
+        let expected_path = Path::new(STATE_DIR).join(relative_path);
+        let mut entry = next_entry(entries, src, &expected_path.display().to_string())?;
+        let path = entry
+            .path()
+            .map_err(|err| io_error(src, "Failed to read archive entry path", err))?
+            .into_owned();
+        if path != expected_path || entry.size() != file_info.size_bytes {
+            return Err(ArchiveError::Malformed(format!(
+                "expected entry {} of size {}, found {} of size {}",
+                expected_path.display(),
+                file_info.size_bytes,
+                path.display(),
+                entry.size()
+            )));
+        }
+
+        let dst_path = dst.join(relative_path);
+        if let Some(parent) = dst_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|err| io_error(parent, "Failed to create directory", err))?;
+        }
+        let mut file = File::create(&dst_path)
+            .map_err(|err| io_error(&dst_path, "Failed to create file", err))?;
+
+        while chunk_index < manifest.chunk_table.len()
+            && manifest.chunk_table[chunk_index].file_index as usize == file_index
+        {
+            let mut bytes = vec![0; manifest.chunk_table[chunk_index].size_bytes as usize];
+            entry
+                .read_exact(&mut bytes)
+                .map_err(|err| io_error(src, "Failed to read archive entry", err))?;
+            validate_chunk(chunk_index, &bytes, manifest).map_err(|err| {
+                ArchiveError::InvalidChunk {
+                    relative_path: relative_path.clone(),
+                    err,
+                }
+            })?;
+            file.write_all(&bytes)
+                .map_err(|err| io_error(&dst_path, "Failed to write file", err))?;
+            chunk_index += 1;
+        }
+    }
+    Ok(())
+}
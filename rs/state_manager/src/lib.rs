@@ -1,6 +1,7 @@
 // Needs to be `pub` so that the benchmarking code in `state_manager/benches`
 // can access it.
 pub mod checkpoint;
+pub mod checkpoint_archive;
 pub mod labeled_tree_visitor;
 pub mod manifest;
 pub mod state_sync;
@@ -15,7 +16,7 @@ use ic_canonical_state::{
     hash_tree::{hash_lazy_tree, HashTree},
     lazy_tree::{materialize::materialize_partial, LazyTree},
 };
-use ic_config::state_manager::Config;
+use ic_config::state_manager::{Config, RetentionPolicy};
 use ic_cow_state::CowMemoryManager;
 use ic_crypto_tree_hash::{recompute_digest, Digest, LabeledTree, MixedHashTree, Witness};
 use ic_interfaces::{
@@ -40,12 +41,12 @@ use ic_types::{
     consensus::certification::Certification,
     crypto::CryptoHash,
     malicious_flags::MaliciousFlags,
-    state_sync::Manifest,
+    state_sync::{encode_manifest, Manifest, MetaManifest},
     xnet::{CertifiedStreamSlice, StreamIndex, StreamSlice},
     CryptoHashOfPartialState, CryptoHashOfState, ExecutionRound, Height, RegistryVersion, SubnetId,
 };
 use ic_utils::{ic_features::*, thread::JoinOnDrop};
-use prometheus::{HistogramVec, IntCounter, IntCounterVec, IntGauge};
+use prometheus::{Gauge, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge};
 use prost::Message;
 use std::convert::{From, TryFrom};
 use std::fmt;
@@ -55,7 +56,7 @@ use std::sync::{
     atomic::{AtomicBool, AtomicU64, Ordering},
     Arc,
 };
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime};
 use std::{
     collections::{BTreeMap, BTreeSet, VecDeque},
     sync::Mutex,
@@ -79,6 +80,9 @@ pub struct StateManagerMetrics {
     state_sync_size: IntCounterVec,
     state_sync_duration: HistogramVec,
     state_size: IntGauge,
+    available_disk_space: IntGauge,
+    reclaimed_bytes: IntCounterVec,
+    reclaim_count: IntCounterVec,
     checkpoint_metrics: CheckpointMetrics,
     manifest_metrics: ManifestMetrics,
 }
@@ -87,6 +91,9 @@ pub struct StateManagerMetrics {
 pub struct ManifestMetrics {
     hashed_chunk_bytes: IntCounter,
     reused_chunk_bytes: IntCounter,
+    unchanged_file_bytes: IntCounter,
+    hashing_duration: Histogram,
+    hashing_time_saved: Gauge,
     reused_chunk_hash_error_count: IntCounter,
 }
 
@@ -178,12 +185,12 @@ impl StateManagerMetrics {
 
         let state_sync_size = metrics_registry.int_counter_vec(
             "state_sync_size_bytes_total",
-            "Size of chunks synchronized by different operations ('fetch', 'copy', 'preallocate') during all the state sync in bytes.",
+            "Size of chunks synchronized by different operations ('fetch', 'copy', 'preallocate') during all the state sync in bytes. 'fetch_compressed' is the size of the fetched chunks on the wire.",
             &["op"],
         );
 
         // Note [Metrics preallocation]
-        for op in &["fetch", "copy"] {
+        for op in &["fetch", "fetch_compressed", "copy"] {
             state_sync_size.with_label_values(&[*op]);
         }
 
@@ -205,6 +212,35 @@ impl StateManagerMetrics {
             state_sync_duration.with_label_values(&[*status]);
         }
 
+        let available_disk_space = metrics_registry.int_gauge(
+            "state_manager_available_disk_space_bytes",
+            "Free space on the disk holding the state root in bytes.",
+        );
+
+        let reclaimed_bytes = metrics_registry.int_counter_vec(
+            "state_manager_reclaimed_bytes_total",
+            "Size of optional data ('state_sync_cache', 'backup', 'diverged_checkpoint') deleted by the state manager in bytes, indexed by the reason of the deletion ('retention', 'disk_pressure').",
+            &["data", "reason"],
+        );
+
+        let reclaim_count = metrics_registry.int_counter_vec(
+            "state_manager_reclaims_total",
+            "Number of deletions of optional data ('state_sync_cache', 'backup', 'diverged_checkpoint'), indexed by the reason of the deletion ('retention', 'disk_pressure').",
+            &["data", "reason"],
+        );
+
+        // Note [Metrics preallocation]
+        for data in &[
+            STATE_SYNC_CACHE_LABEL,
+            BACKUP_LABEL,
+            DIVERGED_CHECKPOINT_LABEL,
+        ] {
+            for reason in &[RETENTION_LABEL, DISK_PRESSURE_LABEL] {
+                reclaimed_bytes.with_label_values(&[*data, *reason]);
+                reclaim_count.with_label_values(&[*data, *reason]);
+            }
+        }
+
         Self {
             state_manager_error_count,
             checkpoint_op_duration,
@@ -218,6 +254,9 @@ impl StateManagerMetrics {
             state_sync_size,
             state_sync_duration,
             state_size,
+            available_disk_space,
+            reclaimed_bytes,
+            reclaim_count,
             checkpoint_metrics: CheckpointMetrics::new(metrics_registry),
             manifest_metrics: ManifestMetrics::new(metrics_registry),
         }
@@ -235,6 +274,20 @@ impl ManifestMetrics {
                 "state_manager_manifest_reused_chunk_bytes",
                 "Size of chunks that we didn't need to hash during the manifest computation.",
             ),
+            unchanged_file_bytes: metrics_registry.int_counter(
+                "state_manager_manifest_unchanged_file_bytes",
+                "Size of files found unchanged since the base checkpoint by their metadata.",
+            ),
+            hashing_duration: metrics_registry.histogram(
+                "state_manager_manifest_hashing_duration_seconds",
+                "Time spent hashing files during the manifest computation.",
+                // 1ms, 2ms, 5ms, 10ms, 20ms, 50ms, …, 100s, 200s, 500s
+                decimal_buckets(-3, 2),
+            ),
+            hashing_time_saved: metrics_registry.gauge(
+                "state_manager_manifest_hashing_time_saved_seconds",
+                "Estimated hashing time saved by reusing chunk hashes in the last manifest computation.",
+            ),
             // Count of the chunks which have a mismatch between the recomputed hash and the reused
             // one.
             reused_chunk_hash_error_count: metrics_registry
@@ -262,12 +315,40 @@ struct StateMetadata {
     // None before the values are computed.
     root_hash: Option<CryptoHashOfState>,
     manifest: Option<Manifest>,
+    // Derived from the manifest, for manifest versions that are transferred
+    // as sub-manifests.
+    meta_manifest: Option<Arc<MetaManifest>>,
+    // The encoded manifest the sub-manifests are sliced from, set together
+    // with the meta-manifest.
+    encoded_manifest: Option<Arc<Vec<u8>>>,
+    // Metadata of the checkpoint files, set together with the manifest.
+    file_stats: Option<Arc<manifest::FileStatCache>>,
+}
+
+/// Builds the meta-manifest of `manifest` if its version is transferred as
+/// sub-manifests during state sync. Also returns the encoded manifest, so
+/// that sub-manifests can be served without encoding the manifest again.
+fn maybe_build_meta_manifest(
+    manifest: &Manifest,
+) -> (Option<Arc<MetaManifest>>, Option<Arc<Vec<u8>>>) {
+    if manifest.version >= manifest::STATE_SYNC_V3 {
+        let encoded_manifest = encode_manifest(manifest);
+        let meta_manifest =
+            manifest::build_meta_manifest_from_encoded(manifest.version, &encoded_manifest);
+        (
+            Some(Arc::new(meta_manifest)),
+            Some(Arc::new(encoded_manifest)),
+        )
+    } else {
+        (None, None)
+    }
 }
 
 impl From<&StateMetadata> for pb::StateMetadata {
     fn from(metadata: &StateMetadata) -> Self {
         Self {
             manifest: metadata.manifest.as_ref().map(|m| m.clone().into()),
+            file_stats: metadata.file_stats.as_ref().map(|s| s.as_ref().into()),
         }
     }
 }
@@ -283,10 +364,14 @@ impl TryFrom<pb::StateMetadata> for StateMetadata {
                 let root_hash = CryptoHashOfState::from(CryptoHash(
                     crate::manifest::manifest_hash(&manifest).to_vec(),
                 ));
+                let (meta_manifest, encoded_manifest) = maybe_build_meta_manifest(&manifest);
                 Ok(Self {
                     checkpoint_ref: None,
+                    meta_manifest,
+                    encoded_manifest,
                     manifest: Some(manifest),
                     root_hash: Some(root_hash),
+                    file_stats: proto.file_stats.map(|s| Arc::new(s.into())),
                 })
             }
         }
@@ -483,15 +568,33 @@ impl SharedState {
 // the cost of deallocation over a longer period of time, and avoid long pauses.
 type Deallocation = Box<dyn std::any::Any + Send + 'static>;
 
+/// Work done by the background cleanup thread, off the critical path of the
+/// state manager's callers.
+enum CleanupRequest {
+    /// Frees the given object.
+    Deallocate(Deallocation),
+    /// Applies the retention policy to diverged states and backups and
+    /// reclaims disk space if needed, at most once per
+    /// `RETENTION_POLICY_INTERVAL`.
+    ApplyRetentionPolicy,
+}
+
 // We will not use the deallocation thread when the number of pending
 // deallocation objects goes above the threshold.
 const DEALLOCATION_BACKLOG_THRESHOLD: usize = 500;
 
-/// The number of diverged states to keep before we start deleting the old ones.
-const MAX_DIVERGED_STATES_TO_KEEP: usize = 2;
+/// The minimum time between two runs of the retention policy by the cleanup
+/// thread. This also limits how often a lack of disk space is logged.
+const RETENTION_POLICY_INTERVAL: Duration = Duration::from_secs(60);
+
+// Labels of the optional data deleted by the state manager.
+const STATE_SYNC_CACHE_LABEL: &str = "state_sync_cache";
+const BACKUP_LABEL: &str = "backup";
+const DIVERGED_CHECKPOINT_LABEL: &str = "diverged_checkpoint";
 
-/// The number of extra checkpoints to keep for state sync.
-const EXTRA_CHECKPOINTS_TO_KEEP: usize = 2;
+// Labels of the reasons to delete optional data.
+const RETENTION_LABEL: &str = "retention";
+const DISK_PRESSURE_LABEL: &str = "disk_pressure";
 
 pub struct StateManagerImpl {
     log: ReplicaLogger,
@@ -501,8 +604,9 @@ pub struct StateManagerImpl {
     verifier: Arc<dyn Verifier>,
     own_subnet_id: SubnetId,
     own_subnet_type: SubnetType,
+    retention_policy: RetentionPolicy,
     compute_manifest_request_sender: Sender<ComputeManifestRequest>,
-    deallocation_sender: Sender<Deallocation>,
+    cleanup_sender: Sender<CleanupRequest>,
     // Cached latest state height.  We cache it separately because it's
     // requested quite often and this causes high contention on the lock.
     latest_state_height: AtomicU64,
@@ -511,8 +615,11 @@ pub struct StateManagerImpl {
     requested_to_remove_states_below: AtomicU64,
     state_sync_refs: StateSyncRefs,
     checkpoint_thread_pool: Arc<Mutex<scoped_threadpool::Pool>>,
+    // Handle of the thread merging overlay files in the tip after the last
+    // checkpoint, see `start_tip_merge`.
+    tip_merge_handle: Mutex<Option<JoinOnDrop<()>>>,
     _state_hasher_handle: JoinOnDrop<()>,
-    _deallocation_handle: JoinOnDrop<()>,
+    _cleanup_handle: JoinOnDrop<()>,
 }
 
 fn load_checkpoint(
@@ -572,33 +679,211 @@ fn load_checkpoint_as_tip(
     tip
 }
 
-/// Deletes obsolete diverged states and state backups, keeping at most
-/// MAX_DIVERGED_STATES_TO_KEEP.
-fn cleanup_diverged_states(log: &ReplicaLogger, layout: &StateLayout) {
-    if let Ok(diverged_heights) = layout.diverged_checkpoint_heights() {
-        if diverged_heights.len() > MAX_DIVERGED_STATES_TO_KEEP {
-            let to_remove = diverged_heights.len() - MAX_DIVERGED_STATES_TO_KEEP;
-            for h in diverged_heights.iter().take(to_remove) {
-                match layout.remove_diverged_checkpoint(*h) {
-                    Ok(()) => info!(log, "Successfully removed diverged state {}", *h),
-                    Err(err) => info!(log, "{}", err),
-                }
+/// Checkpoints that the state manager keeps for debugging only.
+#[derive(Clone, Copy)]
+enum DebugCheckpoints {
+    Diverged,
+    Backups,
+}
+
+impl DebugCheckpoints {
+    fn label(self) -> &'static str {
+        match self {
+            DebugCheckpoints::Diverged => DIVERGED_CHECKPOINT_LABEL,
+            DebugCheckpoints::Backups => BACKUP_LABEL,
+        }
+    }
+
+    fn heights(self, layout: &StateLayout) -> Result<Vec<Height>, LayoutError> {
+        match self {
+            DebugCheckpoints::Diverged => layout.diverged_checkpoint_heights(),
+            DebugCheckpoints::Backups => layout.backup_heights(),
+        }
+    }
+
+    fn path(self, layout: &StateLayout, height: Height) -> PathBuf {
+        match self {
+            DebugCheckpoints::Diverged => layout.diverged_checkpoint_path(height),
+            DebugCheckpoints::Backups => layout.backup_checkpoint_path(height),
+        }
+    }
+
+    /// Deletes the checkpoint at `height` and records the reclaimed space
+    /// under `reason`.
+    fn remove(
+        self,
+        log: &ReplicaLogger,
+        metrics: &StateManagerMetrics,
+        layout: &StateLayout,
+        height: Height,
+        reason: &str,
+    ) {
+        let size = dir_size(&self.path(layout, height));
+        let result = match self {
+            DebugCheckpoints::Diverged => layout.remove_diverged_checkpoint(height),
+            DebugCheckpoints::Backups => layout.remove_backup(height),
+        };
+        match result {
+            Ok(()) => {
+                info!(
+                    log,
+                    "Removed {} @{} ({} bytes, reason: {})",
+                    self.label(),
+                    height,
+                    size,
+                    reason
+                );
+                record_reclaim(metrics, self.label(), reason, size);
             }
+            Err(err) => warn!(
+                log,
+                "Failed to remove {} @{}: {}",
+                self.label(),
+                height,
+                err
+            ),
         }
     }
-    if let Ok(backup_heights) = layout.backup_heights() {
-        if backup_heights.len() > MAX_DIVERGED_STATES_TO_KEEP {
-            let to_remove = backup_heights.len() - MAX_DIVERGED_STATES_TO_KEEP;
-            for h in backup_heights.iter().take(to_remove) {
-                match layout.remove_backup(*h) {
-                    Ok(()) => info!(log, "Successfully removed backup {}", *h),
-                    Err(err) => info!(log, "Failed to remove backup {}", err),
-                }
+}
+
+fn record_reclaim(metrics: &StateManagerMetrics, data: &str, reason: &str, size: u64) {
+    metrics
+        .reclaimed_bytes
+        .with_label_values(&[data, reason])
+        .inc_by(size);
+    metrics
+        .reclaim_count
+        .with_label_values(&[data, reason])
+        .inc();
+}
+
+/// Returns the total size of the files under `path`, ignoring files that
+/// cannot be read.
+fn dir_size(path: &Path) -> u64 {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| dir_size(&entry.path()))
+                    .sum()
+            })
+            .unwrap_or(0),
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Deletes diverged states and state backups that are not covered by the
+/// retention policy: all but the latest `diverged_states_to_keep` of each
+/// kind, and those older than `diverged_state_retention_days`.
+fn cleanup_diverged_states(
+    log: &ReplicaLogger,
+    metrics: &StateManagerMetrics,
+    layout: &StateLayout,
+    policy: &RetentionPolicy,
+) {
+    let max_age = Duration::from_secs(policy.diverged_state_retention_days * 24 * 60 * 60);
+    let now = SystemTime::now();
+    for kind in &[DebugCheckpoints::Diverged, DebugCheckpoints::Backups] {
+        let heights = match kind.heights(layout) {
+            Ok(heights) => heights,
+            Err(err) => {
+                warn!(log, "Failed to enumerate {}s: {}", kind.label(), err);
+                continue;
+            }
+        };
+        let excess = heights.len().saturating_sub(policy.diverged_states_to_keep);
+        for (i, h) in heights.into_iter().enumerate() {
+            let expired = kind
+                .path(layout, h)
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|mtime| now.duration_since(mtime).ok())
+                .map_or(false, |age| age > max_age);
+            if i < excess || expired {
+                kind.remove(log, metrics, layout, h, RETENTION_LABEL);
             }
         }
     }
 }
 
+/// Deletes optional data while less than `min_free_disk_space_percent` of the
+/// disk holding the state root is free: first the state sync cache, then
+/// backups and finally diverged checkpoints, oldest first.
+fn reclaim_disk_space(
+    log: &ReplicaLogger,
+    metrics: &StateManagerMetrics,
+    layout: &StateLayout,
+    policy: &RetentionPolicy,
+    state_sync_refs: &StateSyncRefs,
+) {
+    let under_pressure = || match ic_sys::fs::disk_space(layout.raw_path()) {
+        Ok(space) => {
+            metrics
+                .available_disk_space
+                .set(space.available_bytes as i64);
+            space.available_bytes.saturating_mul(100)
+                < space
+                    .total_bytes
+                    .saturating_mul(policy.min_free_disk_space_percent)
+        }
+        Err(err) => {
+            warn!(
+                log,
+                "Failed to get the free disk space at {}: {}",
+                layout.raw_path().display(),
+                err
+            );
+            false
+        }
+    };
+
+    if !under_pressure() {
+        return;
+    }
+    warn!(
+        log,
+        "Less than {}% of the disk is free, deleting optional state data",
+        policy.min_free_disk_space_percent
+    );
+
+    let cache_entry = state_sync_refs.cache.write().take();
+    if let Some(entry) = cache_entry {
+        let path = entry.path().to_path_buf();
+        let size = dir_size(&path);
+        // The data is deleted as soon as no state sync uses the entry anymore.
+        drop(entry);
+        if !path.exists() {
+            info!(
+                log,
+                "Removed state sync cache at {} ({} bytes)",
+                path.display(),
+                size
+            );
+            record_reclaim(metrics, STATE_SYNC_CACHE_LABEL, DISK_PRESSURE_LABEL, size);
+        }
+        if !under_pressure() {
+            return;
+        }
+    }
+
+    for kind in &[DebugCheckpoints::Backups, DebugCheckpoints::Diverged] {
+        for h in kind.heights(layout).unwrap_or_default() {
+            kind.remove(log, metrics, layout, h, DISK_PRESSURE_LABEL);
+            if !under_pressure() {
+                return;
+            }
+        }
+    }
+    warn!(
+        log,
+        "Less than {}% of the disk is free after deleting all optional state data",
+        policy.min_free_disk_space_percent
+    );
+}
+
 fn report_last_diverged_checkpoint(
     log: &ReplicaLogger,
     metrics: &StateManagerMetrics,
@@ -607,8 +892,6 @@ fn report_last_diverged_checkpoint(
     match state_layout.diverged_checkpoint_heights() {
         Err(e) => warn!(log, "failed to enumerate diverged checkpoints: {}", e),
         Ok(heights) => {
-            use std::time::SystemTime;
-
             let mut last_time = SystemTime::UNIX_EPOCH;
             for h in heights {
                 let p = state_layout.diverged_checkpoint_path(h);
@@ -637,43 +920,47 @@ fn report_last_diverged_checkpoint(
     }
 }
 
-pub type DirtyPages = BTreeMap<CanisterId, (Height, Vec<PageIndex>)>;
+/// Identifies a page map of a canister.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PageMapType {
+    WasmMemory,
+    StableMemory,
+}
+
+pub type DirtyPages = BTreeMap<(CanisterId, PageMapType), (Height, Vec<PageIndex>)>;
 
-/// Get dirty pages of canister heap backed by a checkpoint file.
+/// Get dirty pages of canister heaps and stable memories backed by a
+/// checkpoint file.
 pub fn get_dirty_pages(state: &ReplicatedState) -> DirtyPages {
     let mut dirty_pages: DirtyPages = Default::default();
     for canister in state.canisters_iter() {
         if let Some(execution_state) = &canister.execution_state {
-            // When `base_height` is None, the page map is not backed by a checkpoint
-            // and we don't know what changed since the checkpoint.
-            if let Some(height) = execution_state.wasm_memory.page_map.base_height {
-                let page_delta_indexes = execution_state
-                    .wasm_memory
-                    .page_map
-                    .get_page_delta_indices();
-                dirty_pages.insert(
-                    canister.system_state.canister_id,
-                    (height, page_delta_indexes),
-                );
+            for (page_map_type, page_map) in [
+                (
+                    PageMapType::WasmMemory,
+                    &execution_state.wasm_memory.page_map,
+                ),
+                (
+                    PageMapType::StableMemory,
+                    &execution_state.stable_memory.page_map,
+                ),
+            ]
+            .iter()
+            {
+                // When `base_height` is None, the page map is not backed by a checkpoint
+                // and we don't know what changed since the checkpoint.
+                if let Some(height) = page_map.base_height {
+                    dirty_pages.insert(
+                        (canister.system_state.canister_id, *page_map_type),
+                        (height, page_map.get_page_delta_indices()),
+                    );
+                }
             }
         }
     }
     dirty_pages
 }
 
-/// Strips away the deltas from all page maps of the replicated state.
-/// We execute this procedure before making a checkpoint because we
-/// don't want those deltas to be persisted to TIP as we apply deltas
-/// incrementally after every round.
-fn strip_page_map_deltas(state: &mut ReplicatedState) {
-    for canister in state.canisters_iter_mut() {
-        if let Some(execution_state) = &mut canister.execution_state {
-            execution_state.wasm_memory.page_map.strip_all_deltas();
-            execution_state.stable_memory.page_map.strip_all_deltas();
-        }
-    }
-}
-
 /// Copy heap and stable memory of the canisters stored in page maps from the
 /// source state to the destination state.
 ///
@@ -778,7 +1065,19 @@ impl StateManagerImpl {
             .cleanup_tip()
             .unwrap_or_else(|err| fatal!(&log, "Failed to cleanup old tip {:?}", err));
 
-        cleanup_diverged_states(&log, &state_layout);
+        // Report diverged states before the retention policy deletes any.
+        report_last_diverged_checkpoint(&log, &metrics, &state_layout);
+
+        let retention_policy = config.retention_policy().clone();
+        let state_sync_refs = StateSyncRefs::new(log.clone());
+        cleanup_diverged_states(&log, &metrics, &state_layout, &retention_policy);
+        reclaim_disk_space(
+            &log,
+            &metrics,
+            &state_layout,
+            &retention_policy,
+            &state_sync_refs,
+        );
 
         let (certifications_metadata, compute_manifest_requests) = Self::populate_missing_metadata(
             &log,
@@ -877,20 +1176,50 @@ impl StateManagerImpl {
                 .expect("failed to spawn background state hasher"),
         );
 
-        let (deallocation_sender, deallocation_receiver) = unbounded();
-        let _deallocation_handle = JoinOnDrop::new(
+        let (cleanup_sender, cleanup_receiver) = unbounded();
+        let _cleanup_handle = JoinOnDrop::new(
             std::thread::Builder::new()
                 .name("StateDeallocation".to_string())
                 .spawn({
+                    let log = log.clone();
+                    let metrics = metrics.clone();
+                    let state_layout = state_layout.clone();
+                    let retention_policy = retention_policy.clone();
+                    let state_sync_refs = state_sync_refs.clone();
+                    // The retention policy was just applied on startup.
+                    let mut last_retention_run = Instant::now();
                     move || {
-                        while let Ok(object) = deallocation_receiver.recv() {
-                            std::mem::drop(object);
-                            // The sleep below is to spread out the load on memory allocator
-                            std::thread::sleep(std::time::Duration::from_millis(1));
+                        while let Ok(request) = cleanup_receiver.recv() {
+                            match request {
+                                CleanupRequest::Deallocate(object) => {
+                                    std::mem::drop(object);
+                                    // The sleep below is to spread out the load on memory allocator
+                                    std::thread::sleep(std::time::Duration::from_millis(1));
+                                }
+                                CleanupRequest::ApplyRetentionPolicy => {
+                                    if last_retention_run.elapsed() < RETENTION_POLICY_INTERVAL {
+                                        continue;
+                                    }
+                                    cleanup_diverged_states(
+                                        &log,
+                                        &metrics,
+                                        &state_layout,
+                                        &retention_policy,
+                                    );
+                                    reclaim_disk_space(
+                                        &log,
+                                        &metrics,
+                                        &state_layout,
+                                        &retention_policy,
+                                        &state_sync_refs,
+                                    );
+                                    last_retention_run = Instant::now();
+                                }
+                            }
                         }
                     }
                 })
-                .expect("failed to spawn background deallocation thread"),
+                .expect("failed to spawn background cleanup thread"),
         );
 
         for req in compute_manifest_requests {
@@ -899,27 +1228,27 @@ impl StateManagerImpl {
                 .expect("failed to send ComputeManifestRequest");
         }
 
-        report_last_diverged_checkpoint(&log, &metrics, &state_layout);
-
         Self {
-            log: log.clone(),
+            log,
             metrics,
             state_layout,
             states,
             verifier,
             own_subnet_id,
             own_subnet_type,
+            retention_policy,
             compute_manifest_request_sender,
-            deallocation_sender,
+            cleanup_sender,
             latest_state_height,
             latest_certified_height,
             requested_to_remove_states_below: AtomicU64::new(oldest_required_state.get()),
-            state_sync_refs: StateSyncRefs::new(log),
+            state_sync_refs,
             checkpoint_thread_pool: Arc::new(Mutex::new(scoped_threadpool::Pool::new(
                 NUMBER_OF_CHECKPOINT_THREADS,
             ))),
+            tip_merge_handle: Mutex::new(None),
             _state_hasher_handle,
-            _deallocation_handle,
+            _cleanup_handle,
         }
     }
 
@@ -941,7 +1270,7 @@ impl StateManagerImpl {
             id.height,
             id.hash.clone(),
             self.state_layout.clone(),
-            self.latest_manifest(),
+            self.local_manifests(),
             self.metrics.clone(),
             self.own_subnet_type,
             self.state_sync_refs.clone(),
@@ -1136,6 +1465,20 @@ impl StateManagerImpl {
             "Computed manifest of state @{} in {:?}", height, elapsed
         );
 
+        // Failing to collect the file stats only makes the next manifest
+        // computation slower, so it's not fatal.
+        let file_stats =
+            match crate::manifest::compute_file_stats(checkpoint_layout.raw_path(), &manifest) {
+                Ok(file_stats) => Some(Arc::new(file_stats)),
+                Err(err) => {
+                    warn!(
+                        log,
+                        "Failed to collect file stats of checkpoint @{}: {}", height, err
+                    );
+                    None
+                }
+            };
+
         let state_size_bytes: i64 = manifest
             .file_table
             .iter()
@@ -1160,10 +1503,23 @@ impl StateManagerImpl {
 
         if let Some(metadata) = states.states_metadata.get_mut(&height) {
             metadata.root_hash = Some(root_hash);
+            let (meta_manifest, encoded_manifest) = maybe_build_meta_manifest(&manifest);
+            metadata.meta_manifest = meta_manifest;
+            metadata.encoded_manifest = encoded_manifest;
             metadata.manifest = Some(manifest);
+            metadata.file_stats = file_stats;
         }
     }
 
+    /// Returns the certification version recorded in the `SystemMetadata` of
+    /// the latest state, which is used to decode incoming stream slices.
+    fn certification_version(&self) -> u32 {
+        self.get_latest_state()
+            .get_ref()
+            .metadata
+            .certification_version
+    }
+
     fn latest_certified_state(
         &self,
     ) -> Option<(Arc<ReplicatedState>, Certification, Arc<HashTree>)> {
@@ -1184,23 +1540,26 @@ impl StateManagerImpl {
         Some((state.take(), certification, hash_tree))
     }
 
-    /// Returns the manifest of the latest checkpoint on disk with its
-    /// checkpoint ref.
-    fn latest_manifest(&self) -> Option<(Manifest, CheckpointRef)> {
-        self.state_layout
+    /// Returns the manifests of all checkpoints on disk with their checkpoint
+    /// refs, latest first.
+    fn local_manifests(&self) -> Vec<(Manifest, CheckpointRef)> {
+        let heights = self
+            .state_layout
             .checkpoint_heights()
             .unwrap_or_else(|err| {
                 fatal!(self.log, "Failed to gather checkpoint heights: {:?}", err)
-            })
+            });
+        let states = self.states.read();
+        heights
             .iter()
             .rev()
-            .find_map(|checkpointed_height| {
-                let states = self.states.read();
+            .filter_map(|checkpointed_height| {
                 let metadata = states.states_metadata.get(checkpointed_height)?;
                 let manifest = metadata.manifest.clone()?;
                 let checkpoint_ref = metadata.checkpoint_ref.clone()?;
                 Some((manifest, checkpoint_ref))
             })
+            .collect()
     }
 
     fn compute_certification_metadata(
@@ -1290,7 +1649,10 @@ impl StateManagerImpl {
                 StateMetadata {
                     checkpoint_ref: Some(checkpoint_ref),
                     manifest: None,
+                    meta_manifest: None,
+                    encoded_manifest: None,
                     root_hash: None,
+                    file_stats: None,
                 },
             );
         }
@@ -1347,59 +1709,51 @@ impl StateManagerImpl {
         }
     }
 
-    /// Flushes to disk all the canister heap deltas accumulated in memory
-    /// during one round of execution.
-    fn flush_page_maps(&self, tip_state: &mut ReplicatedState) {
-        let tip_layout = self
-            .state_layout
-            .tip()
-            .unwrap_or_else(|err| fatal!(self.log, "Failed to access @TIP: {}", err));
+    /// Starts merging the overlay files of canister memories in the tip in
+    /// the background.
+    ///
+    /// The merge applies the same deterministic policy as `make_checkpoint`,
+    /// so it only takes work off the next checkpoint and never affects its
+    /// contents.
+    fn start_tip_merge(&self) {
+        let mut tip_merge_handle = self.tip_merge_handle.lock().unwrap();
+        if let Some(handle) = tip_merge_handle.take() {
+            let _ = handle.join();
+        }
 
-        for canister in tip_state.canisters_iter_mut() {
-            let canister_id = canister.canister_id();
+        let log = self.log.clone();
+        let state_layout = self.state_layout.clone();
+        let metrics = self.metrics.checkpoint_metrics.clone();
+        *tip_merge_handle = Some(JoinOnDrop::new(
+            std::thread::Builder::new()
+                .name("TipOverlayMerge".to_string())
+                .spawn(move || {
+                    let _timer = metrics
+                        .step_duration
+                        .with_label_values(&["merge_tip_overlays"])
+                        .start_timer();
+                    match state_layout
+                        .tip()
+                        .map_err(CheckpointError::from)
+                        .and_then(|tip| checkpoint::merge_tip_overlays(&tip))
+                    {
+                        Ok(0) => (),
+                        Ok(merged) => {
+                            info!(log, "Merged overlays of {} memory files in tip", merged)
+                        }
+                        // The next checkpoint merges the overlays synchronously.
+                        Err(err) => warn!(log, "Failed to merge overlays in tip: {}", err),
+                    }
+                })
+                .expect("failed to spawn tip overlay merge thread"),
+        ));
+    }
 
-            let canister_layout = tip_layout.canister(&canister_id).unwrap_or_else(|err| {
-                fatal!(
-                    self.log,
-                    "Failed to access canister {} layout @TIP {}: {}",
-                    canister_id,
-                    tip_layout.raw_path().display(),
-                    err
-                )
-            });
-            if let Some(execution_state) = &mut canister.execution_state {
-                let memory_path = &canister_layout.vmemory_0();
-                execution_state
-                    .wasm_memory
-                    .page_map
-                    .persist_round_delta(memory_path)
-                    .unwrap_or_else(|err| {
-                        fatal!(
-                            self.log,
-                            "Failed to persist page delta of canister {} to file {}: {}",
-                            canister_id,
-                            memory_path.display(),
-                            err
-                        )
-                    });
-                execution_state.wasm_memory.page_map.strip_round_delta();
-
-                let stable_memory_path = &canister_layout.stable_memory_blob();
-                execution_state
-                    .stable_memory
-                    .page_map
-                    .persist_round_delta(stable_memory_path)
-                    .unwrap_or_else(|err| {
-                        fatal!(
-                            self.log,
-                            "Failed to persist stable page delta of canister {} to file {}: {}",
-                            canister_id,
-                            stable_memory_path.display(),
-                            err
-                        )
-                    });
-                execution_state.stable_memory.page_map.strip_round_delta();
-            }
+    /// Waits for the background merge started by `start_tip_merge` to
+    /// complete. Must be called before the tip is modified or replaced.
+    fn wait_for_tip_merge(&self) {
+        if let Some(handle) = self.tip_merge_handle.lock().unwrap().take() {
+            let _ = handle.join();
         }
     }
 
@@ -1452,6 +1806,22 @@ impl StateManagerImpl {
             }
         }
 
+        let file_stats = match self
+            .state_layout
+            .checkpoint(height)
+            .map_err(CheckpointError::from)
+            .and_then(|cp| manifest::compute_file_stats(cp.raw_path(), &manifest))
+        {
+            Ok(file_stats) => Some(Arc::new(file_stats)),
+            Err(err) => {
+                warn!(
+                    self.log,
+                    "Failed to collect file stats of synced checkpoint @{}: {}", height, err
+                );
+                None
+            }
+        };
+
         let mut states = self.states.write();
         states.disable_state_fetch_below(height);
 
@@ -1485,12 +1855,16 @@ impl StateManagerImpl {
             },
         );
 
+        let (meta_manifest, encoded_manifest) = maybe_build_meta_manifest(&manifest);
         states.states_metadata.insert(
             height,
             StateMetadata {
+                meta_manifest,
+                encoded_manifest,
                 manifest: Some(manifest),
                 checkpoint_ref: Some(self.new_checkpoint_ref(height)),
                 root_hash: Some(root_hash),
+                file_stats,
             },
         );
 
@@ -1599,6 +1973,7 @@ impl StateManager for StateManagerImpl {
             // This can happen if state sync fetched a fresh state in the
             // background.
             if *checkpoint_height > tip_height {
+                self.wait_for_tip_merge();
                 let new_tip = load_checkpoint_as_tip(
                     &self.log,
                     &self.state_layout,
@@ -1795,8 +2170,8 @@ impl StateManager for StateManagerImpl {
             .range_mut(Self::INITIAL_STATE_HEIGHT..certification_height)
         {
             if let Some(tree) = certification_metadata.hash_tree.take() {
-                self.deallocation_sender
-                    .send(Box::new(tree))
+                self.cleanup_sender
+                    .send(CleanupRequest::Deallocate(Box::new(tree)))
                     .expect("failed to send object to deallocation thread");
             }
         }
@@ -1855,6 +2230,10 @@ impl StateManager for StateManagerImpl {
         self.requested_to_remove_states_below
             .store(requested_height.get(), Ordering::Relaxed);
 
+        self.cleanup_sender
+            .send(CleanupRequest::ApplyRetentionPolicy)
+            .expect("failed to send request to cleanup thread");
+
         let checkpoint_heights = self
             .state_layout
             .checkpoint_heights()
@@ -1896,7 +2275,7 @@ impl StateManager for StateManagerImpl {
         let mut checkpoints_to_keep: BTreeSet<Height> = checkpoint_heights
             .iter()
             .rev()
-            .take(EXTRA_CHECKPOINTS_TO_KEEP + 1)
+            .take(self.retention_policy.checkpoints_to_keep.max(1))
             .cloned()
             .collect();
         if let Some(h) = oldest_checkpoint_to_keep {
@@ -1907,9 +2286,9 @@ impl StateManager for StateManagerImpl {
 
         // Send object to deallocation thread if it has capacity.
         let deallocate = |x| {
-            if self.deallocation_sender.len() < DEALLOCATION_BACKLOG_THRESHOLD {
-                self.deallocation_sender
-                    .send(x)
+            if self.cleanup_sender.len() < DEALLOCATION_BACKLOG_THRESHOLD {
+                self.cleanup_sender
+                    .send(CleanupRequest::Deallocate(x))
                     .expect("failed to send object to deallocation thread");
             } else {
                 std::mem::drop(x);
@@ -2043,7 +2422,6 @@ impl StateManager for StateManagerImpl {
         }
 
         self.populate_extra_metadata(&mut state, height);
-        self.flush_page_maps(&mut state);
         let mut dirty_pages = None;
         let checkpointed_state = match scope {
             CertificationScope::Full => {
@@ -2052,9 +2430,7 @@ impl StateManager for StateManagerImpl {
                     // The api of dirty pages is only supported by PageMap currently.
                     dirty_pages = Some(get_dirty_pages(&state));
                 }
-                // We don't need to persist the deltas to the tip because we
-                // flush deltas separately every round, see flush_page_maps.
-                strip_page_map_deltas(&mut state);
+                self.wait_for_tip_merge();
                 let result = {
                     let mut thread_pool = self.checkpoint_thread_pool.lock().unwrap();
                     checkpoint::make_checkpoint(
@@ -2071,6 +2447,7 @@ impl StateManager for StateManagerImpl {
                 match result {
                     Ok(checkpointed_state) => {
                         copy_page_maps(&mut state, &checkpointed_state);
+                        self.start_tip_merge();
                         info!(self.log, "Created checkpoint @{} in {:?}", height, elapsed);
                         self.metrics
                             .checkpoint_op_duration
@@ -2084,7 +2461,8 @@ impl StateManager for StateManagerImpl {
                                 "Failed to create checkpoint @{} because it already exists, re-loading the checkpoint from disk", height
                             );
 
-                        self.state_layout
+                        let checkpointed_state = self
+                            .state_layout
                             .checkpoint(height)
                             .map_err(|e| e.into())
                             .and_then(|layout| {
@@ -2103,7 +2481,11 @@ impl StateManager for StateManagerImpl {
                                     height,
                                     err
                                 )
-                            })
+                            });
+                        // The page deltas have been written to the tip as overlays
+                        // at this height, so they must not be persisted again.
+                        copy_page_maps(&mut state, &checkpointed_state);
+                        checkpointed_state
                     }
                     Err(err) => fatal!(
                         self.log,
@@ -2162,6 +2544,7 @@ impl StateManager for StateManagerImpl {
                     // Will crash if it's not a checkpoint, which is reasonable
                     // for now as we can only get fresher states from state
                     // sync.
+                    self.wait_for_tip_merge();
                     (
                         latest_snapshot.height,
                         load_checkpoint_as_tip(
@@ -2180,20 +2563,19 @@ impl StateManager for StateManagerImpl {
                 });
 
                 if scope == CertificationScope::Full {
-                    let manifest_delta = dirty_pages.and_then(|dirty_memory_pages| {
-                        let (base_manifest, base_height) =
-                            states.states_metadata.iter().rev().find_map(
-                                |(base_height, state_metadata)| {
-                                    let base_manifest = state_metadata.manifest.clone()?;
-                                    Some((base_manifest, *base_height))
-                                },
-                            )?;
-                        Some(manifest::ManifestDelta {
-                            base_manifest,
-                            base_height,
-                            dirty_memory_pages,
-                        })
-                    });
+                    // Without dirty pages, the heap and stable memory files are only
+                    // reused if their metadata did not change.
+                    let manifest_delta = states.states_metadata.iter().rev().find_map(
+                        |(base_height, state_metadata)| {
+                            let base_manifest = state_metadata.manifest.clone()?;
+                            Some(manifest::ManifestDelta {
+                                base_manifest,
+                                base_height: *base_height,
+                                dirty_memory_pages: dirty_pages.take().unwrap_or_default(),
+                                base_file_stats: state_metadata.file_stats.clone(),
+                            })
+                        },
+                    );
 
                     let checkpoint_ref = self.new_checkpoint_ref(height);
                     states.states_metadata.insert(
@@ -2201,7 +2583,10 @@ impl StateManager for StateManagerImpl {
                         StateMetadata {
                             checkpoint_ref: Some(checkpoint_ref.clone()),
                             manifest: None,
+                            meta_manifest: None,
+                            encoded_manifest: None,
                             root_hash: None,
+                            file_stats: None,
                         },
                     );
 
@@ -2462,7 +2847,8 @@ impl CertifiedStreamStore for StateManagerImpl {
 
         // The function `decode_stream_slice` already checks internally whether the
         // slice only contains a stream for a single destination subnet.
-        let (subnet_id, slice) = stream_encoding::decode_slice_from_tree(&tree)?;
+        let (subnet_id, slice) =
+            stream_encoding::decode_slice_from_tree(&tree, self.certification_version())?;
 
         if subnet_id != self.own_subnet_id {
             return Err(DecodeStreamError::InvalidDestination {
@@ -2496,7 +2882,10 @@ impl CertifiedStreamStore for StateManagerImpl {
         &self,
         certified_slice: &CertifiedStreamSlice,
     ) -> Result<StreamSlice, DecodeStreamError> {
-        let (_subnet, slice) = stream_encoding::decode_stream_slice(&certified_slice.payload)?;
+        let (_subnet, slice) = stream_encoding::decode_stream_slice(
+            &certified_slice.payload,
+            self.certification_version(),
+        )?;
         Ok(slice)
     }
 
@@ -4,33 +4,65 @@ pub mod hash;
 mod tests;
 
 use super::CheckpointError;
-use crate::{DirtyPages, ManifestMetrics};
+use crate::{DirtyPages, ManifestMetrics, PageMapType};
 use bit_vec::BitVec;
 use hash::{
-    chunk_hasher, cow_chunk_hasher, cow_file_hasher, file_hasher, manifest_hasher, ManifestHash,
+    chunk_hasher, cow_chunk_hasher, cow_file_hasher, file_hasher, manifest_hasher,
+    meta_manifest_hasher, sub_manifest_hasher, ManifestHash,
 };
 use ic_cow_state::{CowMemoryManager, CowMemoryManagerImpl, MappedState};
 use ic_crypto_sha::Sha256;
 use ic_logger::{warn, ReplicaLogger};
+use ic_protobuf::state::v1 as pb;
 use ic_state_layout::{CheckpointLayout, ReadOnly};
 use ic_sys::{mmap::ScopedMmap, PAGE_SIZE};
 use ic_types::{
-    state_sync::{ChunkInfo, FileInfo, Manifest},
+    state_sync::{
+        encode_manifest, ChunkInfo, FileInfo, Manifest, MetaManifest, MANIFEST_CHUNK_ID_OFFSET,
+        MAX_SUB_MANIFEST_SIZE,
+    },
     CryptoHashOfState, Height,
 };
-use std::collections::{BTreeMap, HashMap, HashSet};
+use rand::Rng;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
 use std::ops::Range;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
 
 pub const STATE_SYNC_V1: u32 = 1;
 
+/// Same manifest format as `STATE_SYNC_V1`, but the chunks of states with
+/// this version are compressed on the wire, see
+/// `state_sync::chunkable::compress_chunk`.
+///
+/// The version is part of the manifest hash, so all replicas agree on whether
+/// the chunks of a given state are served compressed.
+pub const STATE_SYNC_V2: u32 = 2;
+
+/// Like `STATE_SYNC_V2`, but the manifest is transferred as a meta-manifest
+/// listing the hashes of the sub-manifests, which are fetched separately. The
+/// root hash of the manifest is the hash of the meta-manifest.
+/// See note [Manifest Hash].
+pub const STATE_SYNC_V3: u32 = 3;
+
 /// The version of StateSync protocol that should be used for all newly produced
 /// states.
-pub const CURRENT_STATE_SYNC_VERSION: u32 = STATE_SYNC_V1;
+pub const CURRENT_STATE_SYNC_VERSION: u32 = STATE_SYNC_V3;
 
 pub const DEFAULT_CHUNK_SIZE: u32 = 1 << 20; // 1 MiB.
 
+/// Release builds recompute one in this many reused chunk hashes and compare
+/// it to the reused hash, to detect bugs in hash reuse without reading every
+/// file.  Debug builds recompute all reused chunk hashes.
+const REUSED_CHUNK_HASH_SAMPLING_RATE: usize = 100;
+
+/// Upper bound on the granularity of file modification times.
+/// See note [Racy file stats].
+const MTIME_GRANULARITY_NANOS: u64 = 1_000_000_000;
+
 #[derive(Debug, PartialEq)]
 pub enum ManifestValidationError {
     InvalidRootHash {
@@ -42,6 +74,10 @@ pub enum ManifestValidationError {
         expected_hash: Vec<u8>,
         actual_hash: Vec<u8>,
     },
+    TooManyChunks {
+        chunk_count: usize,
+        max_chunk_count: usize,
+    },
 }
 
 impl fmt::Display for ManifestValidationError {
@@ -67,6 +103,14 @@ impl fmt::Display for ManifestValidationError {
                 hex::encode(&expected_hash[..]),
                 hex::encode(&actual_hash[..])
             ),
+            Self::TooManyChunks {
+                chunk_count,
+                max_chunk_count,
+            } => write!(
+                f,
+                "manifest has {} chunks, at most {} are supported",
+                chunk_count, max_chunk_count
+            ),
         }
     }
 }
@@ -136,35 +180,130 @@ pub struct DiffScript {
     /// Copy some files from the old state.
     /// Keys are indices of the file table in the new manifest file,
     /// values are indices of the file table in the old manifest file.
-    pub(crate) copy_files: HashMap<NewIndex, OldIndex>,
+    pub copy_files: HashMap<NewIndex, OldIndex>,
 
     /// Re-use existing chunks from the old state.
     /// Chunks that belong to the `copy_files` key space are excluded.
     /// Keys are indices of the chunk table in the new manifest file,
     /// values are indices of the chunk table in the old manifest file.
-    pub(crate) copy_chunks: HashMap<NewIndex, OldIndex>,
+    pub copy_chunks: HashMap<NewIndex, OldIndex>,
 
     /// Fetch this set of chunks from the peers and apply them.
-    pub(crate) fetch_chunks: HashSet<NewIndex>,
+    pub fetch_chunks: HashSet<NewIndex>,
 
     /// Number of all-zero chunks used for metrics.
-    pub(crate) zeros_chunks: u32,
+    pub zeros_chunks: u32,
 }
 
-/// ManifestDelta contains a manifest of an old state and indices of all the
-/// memory pages that changed (became "dirty") since that state.
+/// ManifestDelta contains a manifest of an old state, indices of all the
+/// memory pages that changed (became "dirty") since that state and the file
+/// system metadata of the old checkpoint files.
 ///
 /// This data allows us to speed up manifest computation: we can map dirty page
-/// indices back to chunks and avoid re-computing chunks that haven't changed
-/// since the previous manifest computation.
+/// indices back to chunks, recognize files that were not written at all, and
+/// avoid re-computing chunks that haven't changed since the previous manifest
+/// computation.
 pub struct ManifestDelta {
     /// Manifest of the state at `base_height`.
     pub(crate) base_manifest: Manifest,
     /// Height of the base state.
     pub(crate) base_height: Height,
-    /// Wasm memory pages that might have changed since the state at
+    /// Wasm and stable memory pages that might have changed since the state at
     /// `base_height`.
     pub(crate) dirty_memory_pages: DirtyPages,
+    /// Metadata of the files of the checkpoint at `base_height`, if known.
+    pub(crate) base_file_stats: Option<Arc<FileStatCache>>,
+}
+
+/// File system metadata of a checkpoint file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileStat {
+    pub size_bytes: u64,
+    /// Modification time in nanoseconds since the Unix epoch.
+    pub mtime_nanos: u64,
+}
+
+// Note [Racy file stats]
+// ======================
+//
+// Checkpoints are created by copying the tip, and the copies keep the
+// modification times of the tip files.  A tip file that was not written
+// between two checkpoints therefore has the same size and mtime in both of
+// them, and the chunk hashes computed for the older checkpoint can be reused
+// without reading the file.  This works even though inodes differ, as every
+// checkpoint gets fresh copies.
+//
+// File systems record modification times with a coarse granularity, so a file
+// written twice within one clock tick can have the same mtime before and
+// after the second write.  The tip is only written after the checkpoint copy
+// is complete, which is after the last update of the checkpoint root
+// directory.  So we only trust a cached entry if the file was modified at
+// least `MTIME_GRANULARITY_NANOS` before the mtime of the checkpoint root
+// directory; a later write cannot then end up with the same mtime.
+
+/// File system metadata of all files of a checkpoint.  Persisted next to the
+/// manifest of the checkpoint, it acts as a hash cache: files of a newer
+/// checkpoint that match a cached entry have the same contents, so their chunk
+/// hashes can be taken from the manifest.  See note [Racy file stats].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileStatCache {
+    /// Modification time of the checkpoint root directory in nanoseconds
+    /// since the Unix epoch.
+    pub checkpoint_mtime_nanos: u64,
+    pub files: BTreeMap<PathBuf, FileStat>,
+}
+
+impl FileStatCache {
+    /// Returns true if a file with the given metadata is known to have the
+    /// same contents as the cached file at `relative_path`.
+    fn is_unchanged(&self, relative_path: &Path, stat: &FileStat) -> bool {
+        match self.files.get(relative_path) {
+            Some(cached) => {
+                cached == stat
+                    && cached.mtime_nanos.saturating_add(MTIME_GRANULARITY_NANOS)
+                        < self.checkpoint_mtime_nanos
+            }
+            None => false,
+        }
+    }
+}
+
+impl From<&FileStatCache> for pb::FileStatCache {
+    fn from(cache: &FileStatCache) -> Self {
+        Self {
+            checkpoint_mtime_nanos: cache.checkpoint_mtime_nanos,
+            files: cache
+                .files
+                .iter()
+                .map(|(relative_path, stat)| pb::FileStat {
+                    relative_path: relative_path.to_string_lossy().into_owned(),
+                    size_bytes: stat.size_bytes,
+                    mtime_nanos: stat.mtime_nanos,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<pb::FileStatCache> for FileStatCache {
+    fn from(cache: pb::FileStatCache) -> Self {
+        Self {
+            checkpoint_mtime_nanos: cache.checkpoint_mtime_nanos,
+            files: cache
+                .files
+                .into_iter()
+                .map(|stat| {
+                    (
+                        PathBuf::from(stat.relative_path),
+                        FileStat {
+                            size_bytes: stat.size_bytes,
+                            mtime_nanos: stat.mtime_nanos,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
 }
 
 fn write_chunk_hash(hasher: &mut Sha256, chunk_info: &ChunkInfo) {
@@ -190,7 +329,12 @@ fn uses_chunk_size(manifest: &Manifest, max_chunk_size: u32) -> bool {
 }
 
 /// Updates manifest computation statistics.
-fn update_metrics(metrics: &ManifestMetrics, chunk_actions: &[ChunkAction], chunks: &[ChunkInfo]) {
+fn update_metrics(
+    metrics: &ManifestMetrics,
+    chunk_actions: &[ChunkAction],
+    chunks: &[ChunkInfo],
+    hashing_duration: f64,
+) {
     let mut hashed_bytes = 0;
     let mut reused_bytes = 0;
 
@@ -207,6 +351,18 @@ fn update_metrics(metrics: &ManifestMetrics, chunk_actions: &[ChunkAction], chun
 
     metrics.hashed_chunk_bytes.inc_by(hashed_bytes);
     metrics.reused_chunk_bytes.inc_by(reused_bytes);
+    metrics.hashing_duration.observe(hashing_duration);
+
+    // Estimate the time it would have taken to hash the reused chunks from
+    // the hashing throughput observed so far.
+    let total_hashed_bytes = metrics.hashed_chunk_bytes.get();
+    if total_hashed_bytes > 0 {
+        let seconds_per_byte =
+            metrics.hashing_duration.get_sample_sum() / total_hashed_bytes as f64;
+        metrics
+            .hashing_time_saved
+            .set(reused_bytes as f64 * seconds_per_byte);
+    }
 }
 
 /// Build a chunk table from the file table.
@@ -218,10 +374,18 @@ fn build_chunk_table(
     max_chunk_size: u32,
     chunk_actions: Vec<ChunkAction>,
 ) -> (Vec<FileInfo>, Vec<ChunkInfo>) {
+    let start = Instant::now();
     let mut chunk_table = Vec::new();
     let mut file_table = Vec::new();
     let mut chunk_index: usize = 0;
 
+    // The sampled chunks change from one manifest computation to the next, so
+    // that all reused hashes are eventually double-checked.
+    let sample_offset = rand::thread_rng().gen_range(0, REUSED_CHUNK_HASH_SAMPLING_RATE);
+    let verify_reused_hash = |chunk_index: usize| {
+        cfg!(debug_assertions) || chunk_index % REUSED_CHUNK_HASH_SAMPLING_RATE == sample_offset
+    };
+
     for (file_index, FileWithSize(relative_path, size_bytes)) in files.into_iter().enumerate() {
         let mut file_hash = if relative_path.ends_with("state_file") {
             cow_file_hasher()
@@ -233,6 +397,11 @@ fn build_chunk_table(
 
         let num_chunks = count_chunks(size_bytes, max_chunk_size);
 
+        // Files whose chunk hashes are all reused and none of them sampled
+        // for verification do not need to be read.
+        let needs_contents = (chunk_index..chunk_index + num_chunks)
+            .any(|i| chunk_actions[i] == ChunkAction::Recompute || verify_reused_hash(i));
+
         (num_chunks as u32).update_hash(&mut file_hash);
 
         let compute_file_chunk_hashes = |data: &[u8]| {
@@ -255,14 +424,13 @@ fn build_chunk_table(
                 assert!(chunk_index < chunk_actions.len());
 
                 let chunk_hash = match chunk_actions[chunk_index] {
-                    ChunkAction::UseHash(reused_chunk_hash) => {
-                        // In the first stage, we always recompute the chunk hash here and compare
-                        // it to the hash to be reused.
-                        // If it is different, we increase the error counter metric.
-                        // After monitoring it for sufficiently long time, we will move to the next
-                        // stage to reuse the chunk hash.
+                    ChunkAction::UseHash(reused_chunk_hash) if verify_reused_hash(chunk_index) => {
+                        // Recompute the sampled chunk hash and compare it to the hash to be
+                        // reused.  If it is different, we increase the error counter metric
+                        // and log the mismatch, but still use the reused hash: the sampled
+                        // chunks differ between replicas, so replacing the hash would make
+                        // the manifest depend on which chunks were sampled.
                         let recomputed_chunk_hash = recompute_chunk_hash();
-                        debug_assert_eq!(recomputed_chunk_hash, reused_chunk_hash);
                         if recomputed_chunk_hash != reused_chunk_hash {
                             metrics.reused_chunk_hash_error_count.inc();
                             warn!(
@@ -274,8 +442,9 @@ fn build_chunk_table(
                                 reused_chunk_hash
                             );
                         }
-                        recomputed_chunk_hash
+                        reused_chunk_hash
                     }
+                    ChunkAction::UseHash(reused_chunk_hash) => reused_chunk_hash,
                     ChunkAction::Recompute => recompute_chunk_hash(),
                 };
 
@@ -301,7 +470,9 @@ fn build_chunk_table(
             });
         };
 
-        if relative_path.ends_with("state_file") {
+        if !needs_contents {
+            compute_file_chunk_hashes(&[]);
+        } else if relative_path.ends_with("state_file") {
             let absolute_path = root.join(&relative_path);
             let cow_base_dir = absolute_path.parent().unwrap();
             let cow_mgr = CowMemoryManagerImpl::open_readonly(cow_base_dir.to_path_buf());
@@ -321,7 +492,12 @@ fn build_chunk_table(
 
     assert_eq!(chunk_table.len(), chunk_actions.len());
 
-    update_metrics(metrics, &chunk_actions, &chunk_table);
+    update_metrics(
+        metrics,
+        &chunk_actions,
+        &chunk_table,
+        start.elapsed().as_secs_f64(),
+    );
 
     (file_table, chunk_table)
 }
@@ -381,6 +557,66 @@ fn files_with_sizes(
     Ok(())
 }
 
+/// Returns the file system metadata of the file at `absolute_path`.
+fn stat_file(absolute_path: &Path) -> Result<FileStat, CheckpointError> {
+    let metadata = absolute_path
+        .metadata()
+        .map_err(|io_err| CheckpointError::IoError {
+            path: absolute_path.to_path_buf(),
+            message: "failed to get metadata".to_string(),
+            io_err: io_err.to_string(),
+        })?;
+    Ok(FileStat {
+        size_bytes: metadata.len(),
+        mtime_nanos: (metadata.mtime() as u64)
+            .saturating_mul(1_000_000_000)
+            .saturating_add(metadata.mtime_nsec() as u64),
+    })
+}
+
+/// Collects the file system metadata of all the files listed in the manifest
+/// of the checkpoint located at `checkpoint_root_path`.
+///
+/// Copy-on-write state files are skipped, their size in the manifest is not
+/// the size of the file.
+pub fn compute_file_stats(
+    checkpoint_root_path: &Path,
+    manifest: &Manifest,
+) -> Result<FileStatCache, CheckpointError> {
+    let mut files = BTreeMap::new();
+    for file_info in manifest.file_table.iter() {
+        if file_info.relative_path.ends_with("state_file") {
+            continue;
+        }
+        let stat = stat_file(&checkpoint_root_path.join(&file_info.relative_path))?;
+        files.insert(file_info.relative_path.clone(), stat);
+    }
+    Ok(FileStatCache {
+        checkpoint_mtime_nanos: stat_file(checkpoint_root_path)?.mtime_nanos,
+        files,
+    })
+}
+
+/// Returns the files of the checkpoint at `checkpoint_root_path` that have the
+/// same contents as in the checkpoint described by `base_file_stats`.
+fn unchanged_files(
+    base_file_stats: &FileStatCache,
+    checkpoint_root_path: &Path,
+    files: &[FileWithSize],
+) -> Result<BTreeSet<PathBuf>, CheckpointError> {
+    let mut unchanged = BTreeSet::new();
+    for FileWithSize(relative_path, _) in files.iter() {
+        if relative_path.ends_with("state_file") {
+            continue;
+        }
+        let stat = stat_file(&checkpoint_root_path.join(relative_path))?;
+        if base_file_stats.is_unchanged(relative_path, &stat) {
+            unchanged.insert(relative_path.clone());
+        }
+    }
+    Ok(unchanged)
+}
+
 /// Returns the range of chunks belonging to the file with the specified index.
 ///
 /// If the file is empty and doesn't have any chunks, returns an empty range.
@@ -405,10 +641,14 @@ pub fn file_chunk_range(manifest: &Manifest, file_index: usize) -> Range<usize>
 
 /// Makes a "hash plan": an instruction how to compute the hash of each chunk of
 /// the new manifest.
+///
+/// Chunks of files in `dirty_file_chunks` are recomputed if they are marked
+/// dirty, chunks of `unchanged_files` are never recomputed.
 fn hash_plan(
     base_manifest: &Manifest,
     files: &[FileWithSize],
     dirty_file_chunks: BTreeMap<PathBuf, BitVec>,
+    unchanged_files: &BTreeSet<PathBuf>,
     max_chunk_size: u32,
 ) -> Vec<ChunkAction> {
     debug_assert!(uses_chunk_size(base_manifest, max_chunk_size));
@@ -418,8 +658,14 @@ fn hash_plan(
     for FileWithSize(relative_path, size_bytes) in files.iter() {
         let num_chunks = count_chunks(*size_bytes, max_chunk_size);
 
-        let compute_dirty_chunk_bitmap = || -> Option<(&BitVec, usize)> {
-            let dirty_chunk_bitmap = dirty_file_chunks.get(relative_path)?;
+        let compute_dirty_chunk_bitmap = || -> Option<(BitVec, usize)> {
+            let dirty_chunk_bitmap = match dirty_file_chunks.get(relative_path) {
+                Some(dirty_chunk_bitmap) => dirty_chunk_bitmap.clone(),
+                None if unchanged_files.contains(relative_path) => {
+                    BitVec::from_elem(num_chunks, false)
+                }
+                None => return None,
+            };
 
             let base_file_index = base_manifest
                 .file_table
@@ -505,24 +751,43 @@ fn dirty_pages_to_dirty_chunks(
     );
 
     // The field `height` of the checkpoint layout is not used here.
-    // The checkpoint layout is only used to get the file path of canister heap.
+    // The checkpoint layout is only used to get the file paths of canister
+    // memories.
     let checkpoint_layout: CheckpointLayout<ReadOnly> =
         CheckpointLayout::new(PathBuf::from(checkpoint_root_path), Height::from(0))?;
 
     let mut dirty_chunks: BTreeMap<PathBuf, BitVec> = Default::default();
-    for (canister_id, (height, page_indices)) in manifest_delta.dirty_memory_pages.iter() {
+    for ((canister_id, page_map_type), (height, page_indices)) in
+        manifest_delta.dirty_memory_pages.iter()
+    {
         if *height != manifest_delta.base_height {
             continue;
         }
 
         if let Ok(canister_layout) = checkpoint_layout.canister(canister_id) {
-            let vmemory_0 = canister_layout.vmemory_0();
-            let vmemory_relative_path = vmemory_0
+            let memory_path = match page_map_type {
+                PageMapType::WasmMemory => canister_layout.vmemory_0(),
+                PageMapType::StableMemory => canister_layout.stable_memory_blob(),
+            };
+            let memory_relative_path = memory_path
                 .strip_prefix(checkpoint_root_path)
                 .expect("failed to strip path prefix");
 
+            // A memory file missing from the base manifest was created after the base
+            // state, all its chunks have to be hashed.
+            let base_file_index =
+                match manifest_delta
+                    .base_manifest
+                    .file_table
+                    .binary_search_by(|file_info| {
+                        file_info.relative_path.as_path().cmp(memory_relative_path)
+                    }) {
+                    Ok(base_file_index) => base_file_index,
+                    Err(_) => continue,
+                };
+
             if let Ok(index) = files.binary_search_by(|FileWithSize(file_path, _)| {
-                file_path.as_path().cmp(vmemory_relative_path)
+                file_path.as_path().cmp(memory_relative_path)
             }) {
                 let size_bytes = files[index].1;
                 let num_chunks = count_chunks(size_bytes, max_chunk_size);
@@ -541,14 +806,6 @@ fn dirty_pages_to_dirty_chunks(
                 // implementation of PageMap, but we don't want to rely too much on these
                 // implementation details.  So we mark the expanded area as dirty explicitly
                 // instead.
-                let base_file_index = manifest_delta
-                    .base_manifest
-                    .file_table
-                    .binary_search_by(|file_info| {
-                        file_info.relative_path.as_path().cmp(vmemory_relative_path)
-                    })
-                    .expect("couldn't find a file in the base manifest");
-
                 let base_file_size =
                     manifest_delta.base_manifest.file_table[base_file_index].size_bytes;
 
@@ -559,7 +816,7 @@ fn dirty_pages_to_dirty_chunks(
                     }
                 }
 
-                dirty_chunks.insert(vmemory_relative_path.to_path_buf(), chunks_bitmap);
+                dirty_chunks.insert(memory_relative_path.to_path_buf(), chunks_bitmap);
             }
         }
     }
@@ -594,10 +851,25 @@ pub fn compute_manifest(
                     &files,
                     max_chunk_size,
                 )?;
+                let unchanged_files = match &manifest_delta.base_file_stats {
+                    Some(base_file_stats) => {
+                        unchanged_files(base_file_stats, checkpoint_root_path, &files)?
+                    }
+                    None => BTreeSet::new(),
+                };
+                let unchanged_file_bytes: u64 = files
+                    .iter()
+                    .filter(|FileWithSize(relative_path, _)| {
+                        unchanged_files.contains(relative_path)
+                    })
+                    .map(|FileWithSize(_, size_bytes)| size_bytes)
+                    .sum();
+                metrics.unchanged_file_bytes.inc_by(unchanged_file_bytes);
                 hash_plan(
                     &manifest_delta.base_manifest,
                     &files,
                     dirty_file_chunks,
+                    &unchanged_files,
                     max_chunk_size,
                 )
             } else {
@@ -663,6 +935,16 @@ pub fn validate_manifest(
         }
     }
 
+    // Chunk ids starting at `MANIFEST_CHUNK_ID_OFFSET` identify sub-manifests.
+    if manifest.version >= STATE_SYNC_V3
+        && manifest.chunk_table.len() >= MANIFEST_CHUNK_ID_OFFSET as usize
+    {
+        return Err(ManifestValidationError::TooManyChunks {
+            chunk_count: manifest.chunk_table.len(),
+            max_chunk_count: MANIFEST_CHUNK_ID_OFFSET as usize - 1,
+        });
+    }
+
     let hash = manifest_hash(manifest);
 
     if root_hash.get_ref().0 != hash {
@@ -714,9 +996,90 @@ pub fn validate_chunk(
     Ok(())
 }
 
+/// Validates the meta-manifest against the root hash of the state.
+pub fn validate_meta_manifest(
+    meta_manifest: &MetaManifest,
+    root_hash: &CryptoHashOfState,
+) -> Result<(), ManifestValidationError> {
+    let hash = meta_manifest_hash(meta_manifest);
+
+    if root_hash.get_ref().0 != hash {
+        return Err(ManifestValidationError::InvalidRootHash {
+            expected_hash: root_hash.get_ref().0.clone(),
+            actual_hash: hash.to_vec(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Validates the sub-manifest with index `ix` against its hash in the
+/// meta-manifest.
+pub fn validate_sub_manifest(
+    ix: usize,
+    bytes: &[u8],
+    meta_manifest: &MetaManifest,
+) -> Result<(), ChunkValidationError> {
+    let mut hasher = sub_manifest_hasher();
+    hasher.write(bytes);
+    let hash = hasher.finish();
+
+    if hash != meta_manifest.sub_manifest_hashes[ix] {
+        return Err(ChunkValidationError::InvalidChunkHash {
+            chunk_ix: ix,
+            expected_hash: meta_manifest.sub_manifest_hashes[ix].to_vec(),
+            actual_hash: hash.to_vec(),
+        });
+    }
+    Ok(())
+}
+
+/// Splits the encoded manifest into sub-manifests and builds the
+/// meta-manifest listing their hashes.
+/// See note [Manifest Hash].
+pub fn build_meta_manifest(manifest: &Manifest) -> MetaManifest {
+    build_meta_manifest_from_encoded(manifest.version, &encode_manifest(manifest))
+}
+
+/// Same as `build_meta_manifest`, for a manifest of the given version that is
+/// already encoded.
+pub fn build_meta_manifest_from_encoded(version: u32, encoded_manifest: &[u8]) -> MetaManifest {
+    let sub_manifest_hashes = encoded_manifest
+        .chunks(MAX_SUB_MANIFEST_SIZE)
+        .map(|sub_manifest| {
+            let mut hasher = sub_manifest_hasher();
+            hasher.write(sub_manifest);
+            hasher.finish()
+        })
+        .collect();
+
+    MetaManifest {
+        version,
+        sub_manifest_hashes,
+    }
+}
+
+/// Computes the hash of the meta-manifest.
+/// See note [Manifest Hash].
+pub fn meta_manifest_hash(meta_manifest: &MetaManifest) -> [u8; 32] {
+    let mut hash = meta_manifest_hasher();
+
+    meta_manifest.version.update_hash(&mut hash);
+    (meta_manifest.sub_manifest_hashes.len() as u32).update_hash(&mut hash);
+    for sub_manifest_hash in meta_manifest.sub_manifest_hashes.iter() {
+        sub_manifest_hash[..].update_hash(&mut hash);
+    }
+
+    hash.finish()
+}
+
 /// Computes root hash of the manifest.
 /// See note [Manifest Hash].
 pub fn manifest_hash(manifest: &Manifest) -> [u8; 32] {
+    if manifest.version >= STATE_SYNC_V3 {
+        return meta_manifest_hash(&build_meta_manifest(manifest));
+    }
+
     let mut hash = manifest_hasher();
 
     if manifest.version >= STATE_SYNC_V1 {
@@ -840,6 +1203,48 @@ pub fn diff_manifest(
     }
 }
 
+/// Computes a `DiffScript` that copies from `manifest_old` those of the
+/// `fetch_chunks` (indices into the chunk table of `manifest_new`) that are
+/// available there, i.e. not listed in `missing_chunks_old`.
+///
+/// Used to look for chunks in further local states once the bulk of a new
+/// state was initialized using `diff_manifest`, so no whole files are copied.
+/// The `fetch_chunks` of the result are the chunks that remain to be fetched.
+pub fn diff_manifest_chunks(
+    manifest_old: &Manifest,
+    missing_chunks_old: &HashSet<usize>,
+    manifest_new: &Manifest,
+    fetch_chunks: &HashSet<NewIndex>,
+) -> DiffScript {
+    let chunk_hash_to_index: HashMap<[u8; 32], OldIndex> = manifest_old
+        .chunk_table
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !missing_chunks_old.contains(index))
+        .map(|(chunk_index, chunk_info)| (chunk_info.hash, chunk_index))
+        .collect();
+
+    let mut copy_chunks: HashMap<NewIndex, OldIndex> = Default::default();
+    let mut remaining_chunks: HashSet<NewIndex> = Default::default();
+    for chunk_index in fetch_chunks {
+        match chunk_hash_to_index.get(&manifest_new.chunk_table[*chunk_index].hash) {
+            Some(index) => {
+                copy_chunks.insert(*chunk_index, *index);
+            }
+            None => {
+                remaining_chunks.insert(*chunk_index);
+            }
+        }
+    }
+
+    DiffScript {
+        copy_files: Default::default(),
+        copy_chunks,
+        fetch_chunks: remaining_chunks,
+        zeros_chunks: 0,
+    }
+}
+
 /// Filters out all-zero chunks in the manifest chunk table and returns the set
 /// of remaining chunks indices.
 pub fn filter_out_zero_chunks(manifest: &Manifest) -> HashSet<usize> {
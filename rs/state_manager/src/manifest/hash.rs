@@ -69,3 +69,11 @@ pub fn chunk_hasher() -> Sha256 {
 pub fn cow_chunk_hasher() -> Sha256 {
     hasher_for_domain("ic-state-cow-chunk")
 }
+
+pub fn sub_manifest_hasher() -> Sha256 {
+    hasher_for_domain("ic-state-sub-manifest")
+}
+
+pub fn meta_manifest_hasher() -> Sha256 {
+    hasher_for_domain("ic-state-meta-manifest")
+}
@@ -1,7 +1,10 @@
 use super::{
-    compute_manifest, diff_manifest, file_chunk_range, filter_out_zero_chunks, hash::ManifestHash,
-    manifest_hash, validate_chunk, validate_manifest, ChunkValidationError, DiffScript,
-    ManifestValidationError, CURRENT_STATE_SYNC_VERSION, STATE_SYNC_V1,
+    build_meta_manifest, build_meta_manifest_from_encoded, compute_file_stats, compute_manifest,
+    diff_manifest, diff_manifest_chunks, file_chunk_range, filter_out_zero_chunks,
+    hash::ManifestHash, manifest_hash, pb, stat_file, validate_chunk, validate_manifest,
+    validate_meta_manifest, validate_sub_manifest, ChunkValidationError, DiffScript, FileStatCache,
+    ManifestDelta, ManifestValidationError, CURRENT_STATE_SYNC_VERSION, MTIME_GRANULARITY_NANOS,
+    STATE_SYNC_V1, STATE_SYNC_V3,
 };
 use crate::ManifestMetrics;
 
@@ -9,13 +12,18 @@ use ic_crypto_sha::Sha256;
 use ic_metrics::MetricsRegistry;
 use ic_types::{
     crypto::CryptoHash,
-    state_sync::{decode_manifest, encode_manifest, ChunkInfo, FileInfo, Manifest},
-    CryptoHashOfState,
+    state_sync::{
+        decode_manifest, encode_manifest, ChunkInfo, FileInfo, Manifest, MAX_SUB_MANIFEST_SIZE,
+    },
+    CryptoHashOfState, Height,
 };
 
 use ic_logger::replica_logger::no_op_logger;
 use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
 macro_rules! hash_concat {
     ($( $x:expr ),*) => {
@@ -222,6 +230,83 @@ fn simple_manifest_passes_validation() {
     );
 }
 
+#[test]
+fn meta_manifest_hash_is_root_hash_of_v3_manifest() {
+    let manifest = Manifest {
+        version: STATE_SYNC_V3,
+        ..simple_manifest().1
+    };
+    let encoded_manifest = encode_manifest(&manifest);
+    assert!(encoded_manifest.len() <= MAX_SUB_MANIFEST_SIZE);
+
+    let sub_manifest_hash = hash_concat!(21u8, b"ic-state-sub-manifest", &encoded_manifest[..]);
+    let expected_hash = hash_concat!(
+        22u8,
+        b"ic-state-meta-manifest",
+        STATE_SYNC_V3,
+        1u32,
+        &sub_manifest_hash[..]
+    );
+    assert_eq!(expected_hash, manifest_hash(&manifest));
+
+    let root_hash = CryptoHashOfState::from(CryptoHash(expected_hash.to_vec()));
+    let meta_manifest = build_meta_manifest(&manifest);
+    assert_eq!(meta_manifest.sub_manifest_hashes, vec![sub_manifest_hash]);
+    assert_eq!(Ok(()), validate_meta_manifest(&meta_manifest, &root_hash));
+    assert_eq!(
+        Ok(()),
+        validate_sub_manifest(0, &encoded_manifest, &meta_manifest)
+    );
+    assert_eq!(Ok(()), validate_manifest(&manifest, &root_hash));
+}
+
+#[test]
+fn bad_sub_manifest_detected() {
+    let manifest = Manifest {
+        version: STATE_SYNC_V3,
+        ..simple_manifest().1
+    };
+    let meta_manifest = build_meta_manifest(&manifest);
+    let mut encoded_manifest = encode_manifest(&manifest);
+    encoded_manifest[0] ^= 1;
+
+    assert_matches::assert_matches!(
+        validate_sub_manifest(0, &encoded_manifest, &meta_manifest),
+        Err(ChunkValidationError::InvalidChunkHash { chunk_ix: 0, .. })
+    );
+}
+
+#[test]
+fn large_manifest_is_split_into_sub_manifests() {
+    let (_, simple_manifest) = simple_manifest();
+    let chunk = simple_manifest.chunk_table[1].clone();
+    let manifest = Manifest {
+        version: STATE_SYNC_V3,
+        file_table: simple_manifest.file_table,
+        chunk_table: vec![chunk; 50_000],
+    };
+    let encoded_manifest = encode_manifest(&manifest);
+    let meta_manifest = build_meta_manifest(&manifest);
+    assert_eq!(
+        meta_manifest,
+        build_meta_manifest_from_encoded(manifest.version, &encoded_manifest)
+    );
+
+    let sub_manifests: Vec<&[u8]> = encoded_manifest.chunks(MAX_SUB_MANIFEST_SIZE).collect();
+    assert!(sub_manifests.len() > 1);
+    assert_eq!(sub_manifests.len(), meta_manifest.sub_manifest_hashes.len());
+    for (ix, sub_manifest) in sub_manifests.iter().enumerate() {
+        assert_eq!(
+            Ok(()),
+            validate_sub_manifest(ix, sub_manifest, &meta_manifest)
+        );
+    }
+    assert_eq!(
+        decode_manifest(&sub_manifests.concat()).expect("failed to decode manifest"),
+        manifest
+    );
+}
+
 #[test]
 fn bad_root_hash_detected() {
     let (manifest_hash, manifest) = simple_manifest();
@@ -447,6 +532,102 @@ fn test_diff_manifest() {
     );
 }
 
+#[test]
+fn file_stats_allow_reusing_hashes_of_unchanged_files() {
+    let metrics_registry = MetricsRegistry::new();
+    let manifest_metrics = ManifestMetrics::new(&metrics_registry);
+    let dir = tempfile::TempDir::new().expect("failed to create a temporary directory");
+    let root = dir.path();
+
+    fs::write(root.join("root.bin"), vec![2u8; 1000 * 1024])
+        .expect("failed to create file 'root.bin'");
+
+    let subdir = root.join("subdir");
+    fs::create_dir_all(&subdir).expect("failed to create dir 'subdir'");
+    fs::write(subdir.join("memory"), vec![1u8; 2048 * 1024])
+        .expect("failed to create file 'memory'");
+    fs::write(subdir.join("queue"), vec![3u8; 1050 * 1024]).expect("failed to create file 'queue'");
+
+    let base_manifest = compute_manifest(
+        &manifest_metrics,
+        &no_op_logger(),
+        STATE_SYNC_V1,
+        root,
+        1024 * 1024,
+        None,
+    )
+    .expect("failed to compute manifest");
+
+    let mut base_file_stats =
+        compute_file_stats(root, &base_manifest).expect("failed to compute file stats");
+    assert_eq!(
+        FileStatCache::from(pb::FileStatCache::from(&base_file_stats)),
+        base_file_stats
+    );
+
+    let manifest_delta = |base_file_stats: &FileStatCache| ManifestDelta {
+        base_manifest: base_manifest.clone(),
+        base_height: Height::from(1),
+        dirty_memory_pages: Default::default(),
+        base_file_stats: Some(Arc::new(base_file_stats.clone())),
+    };
+
+    // The files were written right before the checkpoint directory was last
+    // modified, so their stats can't be trusted.
+    compute_manifest(
+        &manifest_metrics,
+        &no_op_logger(),
+        STATE_SYNC_V1,
+        root,
+        1024 * 1024,
+        Some(manifest_delta(&base_file_stats)),
+    )
+    .expect("failed to compute manifest");
+    assert_eq!(manifest_metrics.unchanged_file_bytes.get(), 0);
+
+    // Pretend that the files were written long before the checkpoint was
+    // created.
+    base_file_stats.checkpoint_mtime_nanos += 2 * MTIME_GRANULARITY_NANOS;
+
+    // Change the contents of 'queue' without changing its size.
+    let queue = subdir.join("queue");
+    let base_queue_mtime = base_file_stats.files[Path::new("subdir/queue")].mtime_nanos;
+    loop {
+        fs::write(&queue, vec![4u8; 1050 * 1024]).expect("failed to write file 'queue'");
+        if stat_file(&queue).unwrap().mtime_nanos != base_queue_mtime {
+            break;
+        }
+        // The modification time has a coarse granularity.
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    let manifest = compute_manifest(
+        &manifest_metrics,
+        &no_op_logger(),
+        STATE_SYNC_V1,
+        root,
+        1024 * 1024,
+        Some(manifest_delta(&base_file_stats)),
+    )
+    .expect("failed to compute manifest");
+    assert_eq!(
+        manifest_metrics.unchanged_file_bytes.get(),
+        (1000 + 2048) * 1024
+    );
+
+    let manifest_from_scratch = compute_manifest(
+        &manifest_metrics,
+        &no_op_logger(),
+        STATE_SYNC_V1,
+        root,
+        1024 * 1024,
+        None,
+    )
+    .expect("failed to compute manifest");
+    assert_eq!(manifest, manifest_from_scratch);
+    assert_ne!(manifest, base_manifest);
+}
+
 #[test]
 fn test_filter_all_zero_chunks() {
     let metrics_registry = MetricsRegistry::new();
@@ -541,6 +722,29 @@ fn test_missing_simple_manifest() {
     );
 }
 
+#[test]
+fn test_diff_manifest_chunks() {
+    let (_, manifest_old) = simple_manifest();
+    let manifest_new = manifest_old.clone();
+
+    // Chunks 3 and 4 are the only chunks of file 2, but only chunks that are
+    // still to be fetched are considered, and never whole files.
+    let missing_chunks = maplit::hashset! {2, 3};
+    let fetch_chunks = maplit::hashset! {1, 3, 4};
+
+    assert_eq!(
+        diff_manifest_chunks(&manifest_old, &missing_chunks, &manifest_new, &fetch_chunks),
+        DiffScript {
+            copy_files: Default::default(),
+            copy_chunks: maplit::hashmap! {
+                1 => 1, 4 => 4,
+            },
+            fetch_chunks: maplit::hashset! {3},
+            zeros_chunks: 0,
+        }
+    );
+}
+
 #[test]
 fn test_simple_manifest_encoding_roundtrip() {
     let (_hash, manifest) = simple_manifest();
@@ -650,6 +854,58 @@ fn test_hash_plan() {
     assert_eq!(manifest_new, incremental_manifest);
 }
 
+#[test]
+fn wrong_reused_chunk_hashes_are_detected() {
+    use crate::manifest::{build_chunk_table, files_with_sizes, ChunkAction};
+
+    let metrics_registry = MetricsRegistry::new();
+    let manifest_metrics = ManifestMetrics::new(&metrics_registry);
+    let dir = tempfile::TempDir::new().expect("failed to create a temporary directory");
+    let root = dir.path();
+
+    fs::write(root.join("root.bin"), vec![2u8; 3000]).expect("failed to create file 'root.bin'");
+
+    let max_chunk_size = 1024;
+    let manifest = compute_manifest(
+        &manifest_metrics,
+        &no_op_logger(),
+        CURRENT_STATE_SYNC_VERSION,
+        root,
+        max_chunk_size,
+        None,
+    )
+    .expect("failed to compute manifest");
+
+    let mut files = Vec::new();
+    files_with_sizes(root, "".into(), &mut files).expect("failed to traverse the files");
+    let chunk_actions = vec![ChunkAction::UseHash([0; 32]); manifest.chunk_table.len()];
+
+    // Debug builds (and thus tests) verify all reused hashes.
+    let (file_table, chunk_table) = build_chunk_table(
+        &manifest_metrics,
+        &no_op_logger(),
+        root,
+        files,
+        max_chunk_size,
+        chunk_actions,
+    );
+
+    // Mismatches are only reported, the reused hashes are kept so that the
+    // manifest does not depend on which chunks were sampled.
+    assert_eq!(manifest.file_table.len(), file_table.len());
+    assert_eq!(manifest.chunk_table.len(), chunk_table.len());
+    for (expected, actual) in manifest.chunk_table.iter().zip(chunk_table.iter()) {
+        assert_eq!(expected.file_index, actual.file_index);
+        assert_eq!(expected.size_bytes, actual.size_bytes);
+        assert_eq!(expected.offset, actual.offset);
+        assert_eq!(actual.hash, [0; 32]);
+    }
+    assert_eq!(
+        manifest.chunk_table.len() as u64,
+        manifest_metrics.reused_chunk_hash_error_count.get()
+    );
+}
+
 #[test]
 fn test_file_chunk_range() {
     let manifest = simple_manifest().1;
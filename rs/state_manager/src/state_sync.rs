@@ -1,7 +1,6 @@
 pub(crate) mod chunkable;
 
 use super::StateManagerImpl;
-use crate::EXTRA_CHECKPOINTS_TO_KEEP;
 use ic_crypto::crypto_hash;
 use ic_interfaces::{
     artifact_manager::{ArtifactAcceptance, ArtifactClient, ArtifactProcessor, ProcessingResult},
@@ -92,8 +91,10 @@ impl ArtifactClient<StateSyncArtifact> for StateManagerImpl {
                         root_hash: msg_id.hash.clone(),
                         checkpoint_root: checkpoint_root.raw_path().to_path_buf(),
                         manifest: manifest.clone(),
+                        meta_manifest: metadata.meta_manifest.clone(),
+                        encoded_manifest: metadata.encoded_manifest.clone(),
                         get_state_sync_chunk: Some(
-                            crate::state_sync::chunkable::get_state_sync_chunk,
+                            crate::state_sync::chunkable::state_sync_chunk_getter(manifest.version),
                         ),
                     })
                 } else {
@@ -142,8 +143,10 @@ impl ArtifactClient<StateSyncArtifact> for StateManagerImpl {
                         root_hash: metadata.root_hash.as_ref()?.clone(),
                         checkpoint_root: checkpoint_root.raw_path().to_path_buf(),
                         manifest: manifest.clone(),
+                        meta_manifest: metadata.meta_manifest.clone(),
+                        encoded_manifest: metadata.encoded_manifest.clone(),
                         get_state_sync_chunk: Some(
-                            crate::state_sync::chunkable::get_state_sync_chunk,
+                            crate::state_sync::chunkable::state_sync_chunk_getter(manifest.version),
                         ),
                     };
                     Some(StateSyncArtifact::message_to_advert(&msg))
@@ -164,6 +167,8 @@ impl ArtifactClient<StateSyncArtifact> for StateManagerImpl {
         let latest_height = self.latest_state_height();
         let fetch_state = self.states.read().fetch_state.clone();
         let state_sync_refs = self.state_sync_refs.clone();
+        let extra_checkpoints_to_keep =
+            self.retention_policy.checkpoints_to_keep.saturating_sub(1) as u64;
         let log = self.log.clone();
 
         Some(Box::new(move |_artifact_id, attr| {
@@ -189,14 +194,13 @@ impl ArtifactClient<StateSyncArtifact> for StateManagerImpl {
                     }
 
                     // To keep the active state sync for longer time, we wait for another
-                    // `EXTRA_CHECKPOINTS_TO_KEEP` CUPs. Then a CUP beyond that can drop the
+                    // `extra_checkpoints_to_keep` CUPs. Then a CUP beyond that can drop the
                     // active state sync.
                     //
                     // Note: CUP interval length may change, and we can't predict future intervals.
                     // The condition below is only a heuristic.
                     if *max_sync_height
-                        > attr.height
-                            + cup_interval_length.increment() * EXTRA_CHECKPOINTS_TO_KEEP as u64
+                        > attr.height + cup_interval_length.increment() * extra_checkpoints_to_keep
                     {
                         return Priority::Drop;
                     } else {
@@ -1,7 +1,8 @@
 use crate::{
-    manifest::{filter_out_zero_chunks, DiffScript},
+    manifest::{filter_out_zero_chunks, DiffScript, STATE_SYNC_V2, STATE_SYNC_V3},
     CheckpointRef, StateManagerMetrics, StateSyncRefs,
 };
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
 use ic_cow_state::{CowMemoryManager, CowMemoryManagerImpl, MappedState};
 use ic_logger::{debug, fatal, info, trace, warn, ReplicaLogger};
 use ic_registry_subnet_type::SubnetType;
@@ -16,14 +17,18 @@ use ic_types::{
         ChunkId, Chunkable,
     },
     crypto::CryptoHash,
-    state_sync::{decode_manifest, Manifest, MANIFEST_CHUNK},
+    state_sync::{
+        decode_manifest, decode_manifest_version, decode_meta_manifest, is_sub_manifest_chunk,
+        Manifest, MetaManifest, MANIFEST_CHUNK, MANIFEST_CHUNK_ID_OFFSET, MAX_SUB_MANIFEST_SIZE,
+    },
     CryptoHashOfState, Height,
 };
+use std::io::{Read, Write};
 use std::os::unix::fs::FileExt;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     sync::Arc,
 };
 
@@ -34,6 +39,15 @@ pub mod cache;
 enum DownloadState {
     /// Haven't received any chunks yet, waiting for the manifest chunk.
     Blank,
+    /// Received the meta-manifest, loading the sub-manifests.
+    LoadingManifest {
+        /// The received meta-manifest
+        meta_manifest: MetaManifest,
+        /// The sub-manifests received so far, by index.
+        sub_manifests: BTreeMap<usize, Vec<u8>>,
+        /// Set of sub-manifest chunk ids that still need to be fetched.
+        fetch_chunks: HashSet<usize>,
+    },
     /// In the process of loading chunks, have some more to load.
     Loading {
         /// The received manifest
@@ -62,7 +76,9 @@ pub struct IncompleteState {
     height: Height,
     root_hash: CryptoHashOfState,
     state: DownloadState,
-    manifest_with_checkpoint_ref: Option<(Manifest, CheckpointRef)>,
+    /// The manifests of the local checkpoints, latest first. Chunks are copied
+    /// from these checkpoints instead of being fetched whenever possible.
+    manifests_with_checkpoint_refs: Vec<(Manifest, CheckpointRef)>,
     metrics: StateManagerMetrics,
     started_at: Instant,
     own_subnet_type: SubnetType,
@@ -82,6 +98,7 @@ impl Drop for IncompleteState {
         // passing it to the cache might alter the download state
         let description = match self.state {
             DownloadState::Blank => "aborted before receiving any chunks",
+            DownloadState::LoadingManifest { .. } => "aborted before receiving the manifest",
             DownloadState::Loading { .. } => "aborted before receiving all the chunks",
             DownloadState::Complete(_) => "completed successfully",
         };
@@ -116,6 +133,62 @@ pub(crate) fn get_state_sync_chunk(
     }
 }
 
+/// Like `get_state_sync_chunk`, but returns the chunk compressed with
+/// `compress_chunk`.
+fn get_compressed_state_sync_chunk(
+    file_path: PathBuf,
+    offset: u64,
+    len: u32,
+) -> std::io::Result<Vec<u8>> {
+    get_state_sync_chunk(file_path, offset, len).map(|bytes| compress_chunk(&bytes))
+}
+
+/// Returns the function serving the chunks of a state with the given manifest
+/// version: chunks of states with version `STATE_SYNC_V2` or later are sent
+/// compressed.
+pub(crate) fn state_sync_chunk_getter(
+    manifest_version: u32,
+) -> fn(PathBuf, u64, u32) -> std::io::Result<Vec<u8>> {
+    if manifest_version >= STATE_SYNC_V2 {
+        get_compressed_state_sync_chunk
+    } else {
+        get_state_sync_chunk
+    }
+}
+
+/// Compresses a state sync chunk for sending it to a peer.
+pub(crate) fn compress_chunk(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+    encoder
+        .write_all(bytes)
+        .expect("failed to write to an in-memory buffer");
+    encoder
+        .finish()
+        .expect("failed to write to an in-memory buffer")
+}
+
+/// Decompresses a chunk compressed with `compress_chunk`, failing if it does
+/// not decompress to exactly `size_bytes` bytes. Never inflates more than
+/// `size_bytes + 1` bytes, so peers cannot make us allocate arbitrary amounts
+/// of memory.
+pub(crate) fn decompress_chunk(bytes: &[u8], size_bytes: u32) -> std::io::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(size_bytes as usize);
+    DeflateDecoder::new(bytes)
+        .take(size_bytes as u64 + 1)
+        .read_to_end(&mut buf)?;
+    if buf.len() != size_bytes as usize {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "chunk decompressed to {} bytes, expected {}",
+                buf.len(),
+                size_bytes
+            ),
+        ));
+    }
+    Ok(buf)
+}
+
 impl IncompleteState {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -123,7 +196,7 @@ impl IncompleteState {
         height: Height,
         root_hash: CryptoHashOfState,
         state_layout: StateLayout,
-        manifest_with_checkpoint_ref: Option<(Manifest, CheckpointRef)>,
+        manifests_with_checkpoint_refs: Vec<(Manifest, CheckpointRef)>,
         metrics: StateManagerMetrics,
         own_subnet_type: SubnetType,
         state_sync_refs: StateSyncRefs,
@@ -145,7 +218,7 @@ impl IncompleteState {
             height,
             root_hash,
             state: DownloadState::Blank,
-            manifest_with_checkpoint_ref,
+            manifests_with_checkpoint_refs,
             metrics,
             started_at: Instant::now(),
             own_subnet_type,
@@ -541,6 +614,7 @@ impl IncompleteState {
         root_hash: CryptoHashOfState,
         manifest: &Manifest,
     ) -> Artifact {
+        let (meta_manifest, encoded_manifest) = crate::maybe_build_meta_manifest(manifest);
         Artifact::StateSync(StateSyncMessage {
             height,
             root_hash,
@@ -550,7 +624,9 @@ impl IncompleteState {
                 .raw_path()
                 .to_path_buf(),
             manifest: manifest.clone(),
-            get_state_sync_chunk: Some(get_state_sync_chunk),
+            meta_manifest,
+            encoded_manifest,
+            get_state_sync_chunk: Some(state_sync_chunk_getter(manifest.version)),
         })
     }
 
@@ -683,88 +759,64 @@ impl IncompleteState {
             height_old: Height,
         }
 
-        // Get a DiffData from the cache or checkpoint_ref, or neither
-        let diff_data: Option<DiffData> =
-            match (cache.as_ref(), self.manifest_with_checkpoint_ref.as_ref()) {
-                (Some(cache_entry), Some((checkpoint_manifest, checkpoint_ref))) => {
-                    let cache_height = cache_entry.height;
-                    let checkpoint_height = checkpoint_ref.0.height;
-                    if cache_height > checkpoint_height {
-                        // The cache will have missing chunks. However, it likely started from a
-                        // DiffScript with the same checkpoint we have now, so there should be more
-                        // relevant chunks in the cache than in the checkpoint.
-                        // This is just a heuristic however, as the cached chunks might have been
-                        // initialized with a DiffScript from an older checkpoint.
-                        Some(DiffData {
-                            manifest_old: &cache_entry.manifest,
-                            missing_chunks: cache_entry.missing_chunks.clone(),
-                            // The data at root_old will live at least as long as the
-                            // StateSyncCacheEntry, so cloning the path is safe
-                            root_old: cache_entry.path().to_path_buf(),
-                            height_old: cache_entry.height,
-                        })
-                    } else {
-                        // This should be a special case that can only happen if the source of the
-                        // checkpoint is outside of state sync, as otherwise we would have cleared
-                        // the cache upon successfully syncing a state.
-                        let checkpoint_old = checkpoint_ref
-                            .0
-                            .state_layout
-                            .checkpoint(checkpoint_height)
-                            .unwrap_or_else(|err| {
-                                fatal!(
-                                    &self.log,
-                                    "Failed to get checkpoint path for height {}: {}",
-                                    checkpoint_height,
-                                    err
-                                );
-                            });
-                        Some(DiffData {
-                            manifest_old: checkpoint_manifest,
-                            missing_chunks: Default::default(),
-                            root_old: checkpoint_old.raw_path().to_path_buf(),
-                            height_old: checkpoint_height,
-                        })
-                    }
-                }
-                (Some(cache_entry), None) => Some(DiffData {
-                    manifest_old: &cache_entry.manifest,
-                    missing_chunks: cache_entry.missing_chunks.clone(),
-                    root_old: cache_entry.path().to_path_buf(),
-                    height_old: cache_entry.height,
-                }),
-                (None, Some((checkpoint_manifest, checkpoint_ref))) => {
-                    let checkpoint_height = checkpoint_ref.0.height;
-                    let checkpoint_old = checkpoint_ref
-                        .0
-                        .state_layout
-                        .checkpoint(checkpoint_height)
-                        .unwrap_or_else(|err| {
-                            fatal!(
-                                &self.log,
-                                "Failed to get checkpoint path for height {}: {}",
-                                checkpoint_height,
-                                err
-                            );
-                        });
-                    Some(DiffData {
-                        manifest_old: checkpoint_manifest,
-                        missing_chunks: Default::default(),
-                        // The data in root_old will live at least as long as self.checkpoint_ref,
-                        // so cloning here is safe
-                        root_old: checkpoint_old.raw_path().to_path_buf(),
-                        height_old: checkpoint_height,
-                    })
-                }
-                (None, None) => None,
+        // All the local states we can copy chunks from, most relevant first.
+        let mut sources: Vec<DiffData> = Vec::new();
+        for (checkpoint_manifest, checkpoint_ref) in self.manifests_with_checkpoint_refs.iter() {
+            let checkpoint_height = checkpoint_ref.0.height;
+            let checkpoint_old = checkpoint_ref
+                .0
+                .state_layout
+                .checkpoint(checkpoint_height)
+                .unwrap_or_else(|err| {
+                    fatal!(
+                        &self.log,
+                        "Failed to get checkpoint path for height {}: {}",
+                        checkpoint_height,
+                        err
+                    );
+                });
+            sources.push(DiffData {
+                manifest_old: checkpoint_manifest,
+                missing_chunks: Default::default(),
+                // The data in root_old will live at least as long as the checkpoint ref,
+                // so cloning here is safe
+                root_old: checkpoint_old.raw_path().to_path_buf(),
+                height_old: checkpoint_height,
+            });
+        }
+        if let Some(cache_entry) = cache.as_ref() {
+            let cache_data = DiffData {
+                manifest_old: &cache_entry.manifest,
+                missing_chunks: cache_entry.missing_chunks.clone(),
+                // The data at root_old will live at least as long as the
+                // StateSyncCacheEntry, so cloning the path is safe
+                root_old: cache_entry.path().to_path_buf(),
+                height_old: cache_entry.height,
             };
+            // The cache will have missing chunks. However, if it is newer than the
+            // latest checkpoint, it likely started from a DiffScript with the same
+            // checkpoint we have now, so there should be more relevant chunks in the
+            // cache than in the checkpoint.
+            // This is just a heuristic however, as the cached chunks might have been
+            // initialized with a DiffScript from an older checkpoint.
+            //
+            // A cache older than the latest checkpoint should be a special case that
+            // can only happen if the source of the checkpoint is outside of state
+            // sync, as otherwise we would have cleared the cache upon successfully
+            // syncing a state.
+            match sources.first() {
+                Some(latest) if latest.height_old >= cache_entry.height => sources.push(cache_data),
+                _ => sources.insert(0, cache_data),
+            }
+        }
+        let mut sources = sources.into_iter();
 
         if let Some(DiffData {
             manifest_old,
             missing_chunks,
             root_old,
             height_old,
-        }) = diff_data
+        }) = sources.next()
         {
             info!(
                 self.log,
@@ -787,7 +839,8 @@ impl IncompleteState {
             // diff_script contains indices into the manifest chunk table, but p2p
             // counts the manifest itself as chunk 0, so all other chunk indices are
             // shifted by 1
-            let mut fetch_chunks = diff_script.fetch_chunks.iter().map(|i| *i + 1).collect();
+            let mut fetch_chunks: HashSet<usize> =
+                diff_script.fetch_chunks.iter().map(|i| *i + 1).collect();
 
             Self::copy_files(
                 &self.log,
@@ -809,10 +862,51 @@ impl IncompleteState {
                 &mut fetch_chunks,
             );
 
-            let diff_bytes: u64 = diff_script
-                .fetch_chunks
+            // Look for the chunks we still miss in the other local states, e.g. the
+            // older checkpoints after a short outage.
+            for DiffData {
+                manifest_old,
+                missing_chunks,
+                root_old,
+                height_old,
+            } in sources
+            {
+                if fetch_chunks.is_empty() {
+                    break;
+                }
+                let remaining_chunks: HashSet<usize> =
+                    fetch_chunks.iter().map(|i| *i - 1).collect();
+                let diff_script = crate::manifest::diff_manifest_chunks(
+                    manifest_old,
+                    &missing_chunks,
+                    manifest_new,
+                    &remaining_chunks,
+                );
+                if diff_script.copy_chunks.is_empty() {
+                    continue;
+                }
+                debug!(
+                    self.log,
+                    "Copying {} more chunks from the state at height {}",
+                    diff_script.copy_chunks.len(),
+                    height_old
+                );
+
+                fetch_chunks = diff_script.fetch_chunks.iter().map(|i| *i + 1).collect();
+                Self::copy_chunks(
+                    &self.log,
+                    &root_old,
+                    &self.root,
+                    manifest_old,
+                    manifest_new,
+                    &diff_script,
+                    &mut fetch_chunks,
+                );
+            }
+
+            let diff_bytes: u64 = fetch_chunks
                 .iter()
-                .map(|i| manifest_new.chunk_table[*i].size_bytes as u64)
+                .map(|i| manifest_new.chunk_table[*i - 1].size_bytes as u64)
                 .sum();
 
             let preallocate_bytes = diff_script.zeros_chunks * crate::manifest::DEFAULT_CHUNK_SIZE;
@@ -841,6 +935,65 @@ impl IncompleteState {
     }
 }
 
+impl IncompleteState {
+    /// Validates the received manifest and starts loading the chunks that are
+    /// not available locally.
+    ///
+    /// A manifest that fails validation resets the state sync, so that the
+    /// manifest is fetched again.
+    fn on_manifest_received(&mut self, manifest: Manifest) -> Result<Artifact, ArtifactErrorCode> {
+        if let Err(err) = crate::manifest::validate_manifest(&manifest, &self.root_hash) {
+            warn!(self.log, "Received invalid manifest: {}", err);
+            self.state = DownloadState::Blank;
+            return Err(ChunkVerificationFailed);
+        }
+
+        debug!(
+            self.log,
+            "Received MANIFEST chunk for state {}, got {} more chunks to download",
+            self.height,
+            manifest.chunk_table.len()
+        );
+
+        trace!(self.log, "Received manifest:\n{}", manifest);
+
+        let fetch_chunks = self.initialize_state_on_disk(&manifest);
+
+        if fetch_chunks.is_empty() {
+            debug!(
+                self.log,
+                "No chunks need to be fetched for state {}", self.height
+            );
+
+            Self::make_checkpoint(
+                &self.log,
+                &self.metrics,
+                self.started_at,
+                &self.root,
+                self.height,
+                &self.state_layout,
+                self.own_subnet_type,
+            );
+
+            let artifact = Self::build_artifact(
+                &self.state_layout,
+                self.height,
+                self.root_hash.clone(),
+                &manifest,
+            );
+
+            self.state = DownloadState::Complete(Box::new(artifact.clone()));
+            Ok(artifact)
+        } else {
+            self.state = DownloadState::Loading {
+                manifest,
+                fetch_chunks,
+            };
+            Err(ChunksMoreNeeded)
+        }
+    }
+}
+
 impl Chunkable for IncompleteState {
     fn get_artifact_hash(&self) -> CryptoHash {
         self.root_hash.get_ref().clone()
@@ -849,6 +1002,16 @@ impl Chunkable for IncompleteState {
     fn chunks_to_download(&self) -> Box<dyn Iterator<Item = ChunkId>> {
         match self.state {
             DownloadState::Blank => Box::new(std::iter::once(MANIFEST_CHUNK)),
+            DownloadState::LoadingManifest {
+                ref fetch_chunks, ..
+            } => {
+                #[allow(clippy::needless_collect)]
+                let ids: Vec<_> = fetch_chunks
+                    .iter()
+                    .map(|id| ChunkId::new(*id as u32))
+                    .collect();
+                Box::new(ids.into_iter())
+            }
             DownloadState::Complete(_) => Box::new(std::iter::empty()),
             DownloadState::Loading {
                 manifest: _,
@@ -890,7 +1053,23 @@ impl Chunkable for IncompleteState {
             }
 
             DownloadState::Blank => {
-                if artifact_chunk.chunk_id == MANIFEST_CHUNK {
+                if artifact_chunk.chunk_id != MANIFEST_CHUNK {
+                    warn!(
+                        self.log,
+                        "Received non-manifest chunk {} on blank state {}", ix, self.height
+                    );
+                    return Err(ChunkVerificationFailed);
+                }
+
+                let version = decode_manifest_version(payload).map_err(|err| {
+                    warn!(
+                        self.log,
+                        "Failed to decode manifest chunk for state {}: {}", self.height, err
+                    );
+                    ChunkVerificationFailed
+                })?;
+
+                if version < STATE_SYNC_V3 {
                     let manifest = decode_manifest(payload).map_err(|err| {
                         warn!(
                             self.log,
@@ -898,64 +1077,75 @@ impl Chunkable for IncompleteState {
                         );
                         ChunkVerificationFailed
                     })?;
+                    return self.on_manifest_received(manifest);
+                }
 
-                    crate::manifest::validate_manifest(&manifest, &self.root_hash).map_err(
-                        |err| {
-                            warn!(self.log, "Received invalid manifest: {}", err);
-                            ChunkVerificationFailed
-                        },
-                    )?;
-
-                    debug!(
+                let meta_manifest = decode_meta_manifest(payload).map_err(|err| {
+                    warn!(
                         self.log,
-                        "Received MANIFEST chunk for state {}, got {} more chunks to download",
-                        self.height,
-                        manifest.chunk_table.len()
+                        "Failed to decode meta-manifest chunk for state {}: {}", self.height, err
                     );
+                    ChunkVerificationFailed
+                })?;
 
-                    trace!(self.log, "Received manifest:\n{}", manifest);
+                crate::manifest::validate_meta_manifest(&meta_manifest, &self.root_hash).map_err(
+                    |err| {
+                        warn!(self.log, "Received invalid meta-manifest: {}", err);
+                        ChunkVerificationFailed
+                    },
+                )?;
 
-                    let fetch_chunks = self.initialize_state_on_disk(&manifest);
+                debug!(
+                    self.log,
+                    "Received META-MANIFEST chunk for state {}, got {} sub-manifests to download",
+                    self.height,
+                    meta_manifest.sub_manifest_hashes.len()
+                );
 
-                    if fetch_chunks.is_empty() {
-                        debug!(
-                            self.log,
-                            "No chunks need to be fetched for state {}", self.height
-                        );
+                let fetch_chunks = (0..meta_manifest.sub_manifest_hashes.len())
+                    .map(|i| MANIFEST_CHUNK_ID_OFFSET as usize + i)
+                    .collect();
+                self.state = DownloadState::LoadingManifest {
+                    meta_manifest,
+                    sub_manifests: BTreeMap::new(),
+                    fetch_chunks,
+                };
+                Err(ChunksMoreNeeded)
+            }
+            DownloadState::LoadingManifest {
+                ref meta_manifest,
+                ref mut sub_manifests,
+                ref mut fetch_chunks,
+            } => {
+                if !fetch_chunks.contains(&ix) {
+                    return Err(ChunksMoreNeeded);
+                }
 
-                        Self::make_checkpoint(
-                            &self.log,
-                            &self.metrics,
-                            self.started_at,
-                            &self.root,
-                            self.height,
-                            &self.state_layout,
-                            self.own_subnet_type,
-                        );
+                let sub_manifest_index = ix - MANIFEST_CHUNK_ID_OFFSET as usize;
+                let log = &self.log;
+                crate::manifest::validate_sub_manifest(sub_manifest_index, payload, meta_manifest)
+                    .map_err(|err| {
+                        warn!(log, "Received invalid sub-manifest: {}", err);
+                        ChunkVerificationFailed
+                    })?;
 
-                        let artifact = Self::build_artifact(
-                            &self.state_layout,
-                            self.height,
-                            self.root_hash.clone(),
-                            &manifest,
-                        );
+                sub_manifests.insert(sub_manifest_index, payload.clone());
+                fetch_chunks.remove(&ix);
 
-                        self.state = DownloadState::Complete(Box::new(artifact.clone()));
-                        Ok(artifact)
-                    } else {
-                        self.state = DownloadState::Loading {
-                            manifest,
-                            fetch_chunks,
-                        };
-                        Err(ChunksMoreNeeded)
-                    }
-                } else {
+                if !fetch_chunks.is_empty() {
+                    return Err(ChunksMoreNeeded);
+                }
+
+                // The sub-manifests are ordered by index.
+                let encoded_manifest: Vec<u8> = sub_manifests.values().flatten().copied().collect();
+                let manifest = decode_manifest(&encoded_manifest).map_err(|err| {
                     warn!(
                         self.log,
-                        "Received non-manifest chunk {} on blank state {}", ix, self.height
+                        "Failed to decode the manifest of state {}: {}", self.height, err
                     );
-                    Err(ChunkVerificationFailed)
-                }
+                    ChunkVerificationFailed
+                })?;
+                self.on_manifest_received(manifest)
             }
             DownloadState::Loading {
                 ref manifest,
@@ -981,14 +1171,30 @@ impl Chunkable for IncompleteState {
                 let chunk_table_index = ix - 1;
 
                 let log = &self.log;
-                crate::manifest::validate_chunk(chunk_table_index, payload, manifest).map_err(
+                let decompressed;
+                let bytes = if manifest.version >= STATE_SYNC_V2 {
+                    let size_bytes = manifest.chunk_table[chunk_table_index].size_bytes;
+                    decompressed = decompress_chunk(payload, size_bytes).map_err(|err| {
+                        warn!(log, "Failed to decompress chunk {}: {}", ix, err);
+                        ChunkVerificationFailed
+                    })?;
+                    self.metrics
+                        .state_sync_size
+                        .with_label_values(&["fetch_compressed"])
+                        .inc_by(payload.len() as u64);
+                    &decompressed[..]
+                } else {
+                    &payload[..]
+                };
+
+                crate::manifest::validate_chunk(chunk_table_index, bytes, manifest).map_err(
                     |err| {
                         warn!(log, "Received invalid chunk: {}", err);
                         ChunkVerificationFailed
                     },
                 )?;
 
-                Self::apply_chunk(&self.log, &self.root, chunk_table_index, payload, manifest);
+                Self::apply_chunk(&self.log, &self.root, chunk_table_index, bytes, manifest);
 
                 fetch_chunks.remove(&ix);
 
@@ -1037,10 +1243,13 @@ impl Chunkable for IncompleteState {
             // Guestimate of manifest size
             return crate::manifest::DEFAULT_CHUNK_SIZE as usize;
         }
+        if is_sub_manifest_chunk(chunk_id) {
+            return MAX_SUB_MANIFEST_SIZE;
+        }
         match &self.state {
-            DownloadState::Blank | DownloadState::Complete(_) => {
-                crate::manifest::DEFAULT_CHUNK_SIZE as usize
-            }
+            DownloadState::Blank
+            | DownloadState::LoadingManifest { .. }
+            | DownloadState::Complete(_) => crate::manifest::DEFAULT_CHUNK_SIZE as usize,
             DownloadState::Loading { manifest, .. } => {
                 if ix > manifest.chunk_table.len() {
                     return 0;
@@ -63,6 +63,13 @@ impl StateSyncCache {
         self.entry.as_ref().map(Arc::clone)
     }
 
+    /// Removes the cached entry, if any, and returns it. The data on disk is
+    /// deleted once the returned entry and all other references to it are
+    /// dropped.
+    pub fn take(&mut self) -> Option<Arc<StateSyncCacheEntry>> {
+        self.entry.take()
+    }
+
     /// Pushes the state sync data to the cache without checking that
     /// the new state is newer that the stored one.
     ///
@@ -121,7 +128,7 @@ impl StateSyncCache {
         // same height (and path)
         if let Some(ref entry) = self.entry {
             match sync.state {
-                DownloadState::Blank => {
+                DownloadState::Blank | DownloadState::LoadingManifest { .. } => {
                     // Keep what we have
                 }
                 _ => {
@@ -144,10 +151,13 @@ impl StateSyncCache {
                     self.push_inner(sync, manifest, fetch_chunks);
                 }
             }
-            DownloadState::Complete(_) | DownloadState::Blank => {
+            DownloadState::Complete(_)
+            | DownloadState::Blank
+            | DownloadState::LoadingManifest { .. } => {
                 // Nothing to cache
                 // Sanity check that the folder is gone (if completed, should have been moved to
-                // a permanent checkpoint, if blank, should never have been created)
+                // a permanent checkpoint, if blank or still loading the manifest, should never
+                // have been created)
                 if sync.root.exists() {
                     warn!(
                         self.log,
@@ -59,6 +59,8 @@ fn fake_complete() -> DownloadState {
         root_hash: CryptoHashOfState::from(CryptoHash(vec![0; 32])),
         checkpoint_root: PathBuf::new(),
         manifest,
+        meta_manifest: None,
+        encoded_manifest: None,
         get_state_sync_chunk: None,
     });
     DownloadState::Complete(Box::new(artifact))
@@ -80,7 +82,7 @@ fn incomplete_state_for_tests(
         height,
         hash,
         env.state_layout.clone(),
-        None,
+        Vec::new(),
         env.metrics.clone(),
         SubnetType::Application,
         state_sync_refs,
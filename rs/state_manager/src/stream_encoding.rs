@@ -180,12 +180,13 @@ pub fn encode_tree(t: LabeledTree<Vec<u8>>) -> Vec<u8> {
 }
 
 /// Decodes a stream slice and the subnet it came from from a serialized
-/// canonical tree.
+/// canonical tree, as encoded with the given certification version.
 pub fn decode_stream_slice(
     tree_bytes: &[u8],
+    certification_version: u32,
 ) -> Result<(SubnetId, StreamSlice), DecodeStreamError> {
     let tree = decode_labeled_tree(tree_bytes)?;
-    decode_slice_from_tree(&tree)
+    decode_slice_from_tree(&tree, certification_version)
 }
 
 /// Decodes a labeled tree from a byte buffer.
@@ -218,9 +219,11 @@ struct EncodedStream<'a> {
     messages: BTreeMap<StreamIndex, &'a serde_bytes::Bytes>,
 }
 
-/// Recovers a stream slice from its canonical form.
+/// Recovers a stream slice from its canonical form, as encoded by a replica
+/// certifying states with `certification_version`.
 pub fn decode_slice_from_tree(
     t: &LabeledTree<Vec<u8>>,
+    certification_version: u32,
 ) -> Result<(SubnetId, StreamSlice), DecodeStreamError> {
     let streams = EncodedStreams::deserialize(tree_deserializer::LabeledTreeDeserializer::new(t))
         .map_err(|err| {
@@ -240,12 +243,14 @@ pub fn decode_slice_from_tree(
     let (subnet, encoded_stream) = streams.streams.into_iter().next().unwrap();
 
     let header: StreamHeader =
-        decode_stream_header(encoded_stream.header.as_ref()).map_err(|err| {
-            DecodeStreamError::SerializationError(format!(
-                "failed to deserialize stream header from CBOR: {}",
-                err
-            ))
-        })?;
+        decode_stream_header(encoded_stream.header.as_ref(), certification_version).map_err(
+            |err| {
+                DecodeStreamError::SerializationError(format!(
+                    "failed to deserialize stream header from CBOR: {}",
+                    err
+                ))
+            },
+        )?;
 
     let mut messages = encoded_stream
         .messages
@@ -255,7 +260,7 @@ pub fn decode_slice_from_tree(
 
     if let Some(ref mut queue) = messages {
         for (idx, bytes) in encoded_stream.messages.into_iter() {
-            let msg = decode_message(bytes.as_ref()).map_err(|err| {
+            let msg = decode_message(bytes.as_ref(), certification_version).map_err(|err| {
                 DecodeStreamError::SerializationError(format!(
                     "failed to deserialize message {} from subnet {}: {}",
                     idx, subnet, err
@@ -33,7 +33,7 @@ proptest! {
 
         let tree_encoding = encode_stream_slice(&state, subnet, stream_slice.header().begin, stream_slice.header().end, None).0;
         let bytes = encode_tree(tree_encoding.clone());
-        assert_eq!(decode_stream_slice(&bytes[..]), Ok((subnet, stream_slice)), "failed to decode tree {:?}", tree_encoding);
+        assert_eq!(decode_stream_slice(&bytes[..], state.metadata.certification_version), Ok((subnet, stream_slice)), "failed to decode tree {:?}", tree_encoding);
     }
 
     #[test]
@@ -48,7 +48,7 @@ proptest! {
 
         let tree_encoding = encode_stream_slice(&state, subnet, stream_slice.header().begin, stream_slice.header().end, Some(size_limit)).0;
         let bytes = encode_tree(tree_encoding.clone());
-        match decode_stream_slice(&bytes[..]) {
+        match decode_stream_slice(&bytes[..], state.metadata.certification_version) {
             Ok((actual_subnet, actual_slice)) => {
                 assert_eq!(subnet, actual_subnet);
                 match stream_slice.messages() {
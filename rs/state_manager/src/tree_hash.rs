@@ -66,14 +66,16 @@ mod tests {
     use ic_registry_subnet_type::SubnetType;
     use ic_replicated_state::{
         canister_state::execution_state::WasmBinary,
-        metadata_state::Stream,
+        metadata_state::{NodeMetrics, NodeTopology, Stream, SubnetTopology},
         page_map::{PageIndex, PAGE_SIZE},
         testing::ReplicatedStateTesting,
         ExecutionState, ExportedFunctions, Global, Memory, NumWasmPages, PageMap, ReplicatedState,
     };
     use ic_test_utilities::{
         state::new_canister_state,
-        types::ids::{canister_test_id, message_test_id, subnet_test_id, user_test_id},
+        types::ids::{
+            canister_test_id, message_test_id, node_test_id, subnet_test_id, user_test_id,
+        },
         types::messages::ResponseBuilder,
     };
     use ic_types::{
@@ -83,6 +85,7 @@ mod tests {
         Cycles, ExecutionRound,
     };
     use ic_wasm_types::BinaryEncodedWasm;
+    use maplit::btreemap;
     use std::collections::BTreeSet;
     use std::sync::Arc;
 
@@ -212,6 +215,31 @@ mod tests {
                 state.set_ingress_status(message_test_id(i), IngressStatus::Unknown);
             }
 
+            // The subnet subtree is only populated for versions that certify
+            // more than the subnet public key, so that the hashes pinned for
+            // older versions stay valid.
+            if certification_version > 2 {
+                state.metadata.network_topology.subnets = btreemap! {
+                    subnet_test_id(1) => SubnetTopology {
+                        public_key: vec![1, 2, 3, 4],
+                        nodes: btreemap! {
+                            node_test_id(2) => NodeTopology {
+                                ip_address: "2a00:fb01:400:42:5000:22ff:fe5e:e3c4".to_string(),
+                                http_port: 8080,
+                                public_key: Some(vec![5, 6, 7, 8]),
+                            },
+                        },
+                        subnet_type: SubnetType::Application,
+                    },
+                };
+                state.metadata.node_metrics = btreemap! {
+                    node_test_id(2) => NodeMetrics {
+                        blocks_proposed_total: 5,
+                        failed_proposals_by_rank: vec![1, 2],
+                    },
+                };
+            }
+
             state.metadata.certification_version = certification_version;
 
             state
@@ -253,5 +281,47 @@ mod tests {
             // expected_hash
             "B4F0381DFA7C7B3800E6F066FC9614D8D60637C5BF6B212CEA1CAB9B94CEF540",
         );
+
+        assert_partial_state_hash_matches(
+            // certification_version
+            3,
+            // expected_hash
+            "087F54C0E48E18D4CBA30ED9FFF75CE9AAFE9AC193762432BA7DAEBF4877C500",
+        );
+
+        assert_partial_state_hash_matches(
+            // certification_version
+            4,
+            // expected_hash
+            "087F54C0E48E18D4CBA30ED9FFF75CE9AAFE9AC193762432BA7DAEBF4877C500",
+        );
+
+        assert_partial_state_hash_matches(
+            // certification_version
+            5,
+            // expected_hash
+            "D0F2E3565868FF1CB4600723BA4031A02935C8ADFEF516C77BDF51F75A1A21E9",
+        );
+
+        assert_partial_state_hash_matches(
+            // certification_version
+            6,
+            // expected_hash
+            "9A840EBE59F5E357F5E1F7D4D400CF2C3BE16A5627404ED6DB10694D1BDD8B22",
+        );
+
+        assert_partial_state_hash_matches(
+            // certification_version
+            7,
+            // expected_hash
+            "197350196180D6888275A4885AA8CFC1C962747E7A3254556D5581483E6EA11D",
+        );
+
+        assert_partial_state_hash_matches(
+            // certification_version
+            8,
+            // expected_hash
+            "A56FFA42CE07F8A54014D0CFC96AE1455F8877683D6B782C43E2A1936927A454",
+        );
     }
 }
@@ -30,6 +30,7 @@ use ic_types::{
         ThresholdSignature,
     },
     crypto::Signed,
+    state_sync::is_sub_manifest_chunk,
     xnet::{CertifiedStreamSlice, StreamIndex, StreamSlice},
     CanisterId, CryptoHashOfState, Cycles, Height, RegistryVersion, SubnetId,
 };
@@ -258,8 +259,13 @@ pub fn modify_encoded_stream_helper<F: FnOnce(StreamSlice) -> Stream>(
     slice: CertifiedStreamSlice,
     f: F,
 ) -> (StateManagerImpl, CertifiedStreamSlice) {
+    let certification_version = state_manager
+        .get_latest_state()
+        .get_ref()
+        .metadata
+        .certification_version;
     let (_subnet, decoded_slice) =
-        stream_encoding::decode_stream_slice(&slice.payload[..]).unwrap();
+        stream_encoding::decode_stream_slice(&slice.payload[..], certification_version).unwrap();
 
     let modified_stream = f(decoded_slice);
 
@@ -339,34 +345,42 @@ pub fn pipe_state_sync(src: StateSyncMessage, mut dst: Box<dyn Chunkable>) -> St
         .expect("State sync not completed.")
 }
 
-/// Pipe the manifest (chunk 0) from src to dest and return the StateSyncMessage
-/// if the state sync completes
+/// Pipe the manifest (chunk 0 and, if chunk 0 is a meta-manifest, the
+/// sub-manifests) from src to dest and return the StateSyncMessage if the
+/// state sync completes
 pub fn pipe_manifest(src: &StateSyncMessage, dst: &mut dyn Chunkable) -> Option<StateSyncMessage> {
-    let ids: Vec<_> = dst.chunks_to_download().collect();
+    let mut ids: Vec<_> = dst.chunks_to_download().collect();
 
     // Only the manifest should be requested
     assert_eq!(ids, vec! {ChunkId::new(0)});
 
-    let id = ids[0];
-
-    let chunk = Box::new(src.clone())
-        .get_chunk(id)
-        .unwrap_or_else(|| panic!("Requested unknown chunk {}", id));
+    while !ids.is_empty() {
+        for id in ids {
+            let chunk = Box::new(src.clone())
+                .get_chunk(id)
+                .unwrap_or_else(|| panic!("Requested unknown chunk {}", id));
 
-    match dst.add_chunk(chunk) {
-        Ok(Artifact::StateSync(msg)) => {
-            assert!(
-                dst.is_complete(),
-                "add_chunk returned OK but the artifact is not complete"
-            );
-            Some(msg)
-        }
-        Ok(artifact) => {
-            panic!("Unexpected artifact type: {:?}", artifact);
+            match dst.add_chunk(chunk) {
+                Ok(Artifact::StateSync(msg)) => {
+                    assert!(
+                        dst.is_complete(),
+                        "add_chunk returned OK but the artifact is not complete"
+                    );
+                    return Some(msg);
+                }
+                Ok(artifact) => {
+                    panic!("Unexpected artifact type: {:?}", artifact);
+                }
+                Err(ChunksMoreNeeded) => (),
+                Err(ChunkVerificationFailed) => panic!("Encountered invalid chunk {}", id),
+            }
         }
-        Err(ChunksMoreNeeded) => None,
-        Err(ChunkVerificationFailed) => panic!("Encountered invalid chunk {}", id),
+        ids = dst
+            .chunks_to_download()
+            .filter(|id| is_sub_manifest_chunk(*id))
+            .collect();
     }
+    None
 }
 
 /// Pipe chunks from src to dst, but omit any chunks in omit
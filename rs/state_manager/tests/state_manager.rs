@@ -1,4 +1,4 @@
-use ic_config::state_manager::Config;
+use ic_config::state_manager::{Config, RetentionPolicy};
 use ic_crypto_tree_hash::{flatmap, Label, LabeledTree, MixedHashTree};
 use ic_interfaces::{
     artifact_manager::{ArtifactClient, ArtifactProcessor},
@@ -16,7 +16,7 @@ use ic_state_manager::StateManagerImpl;
 use ic_sys::PAGE_SIZE;
 use ic_test_utilities::{
     consensus::fake::FakeVerifier,
-    metrics::{fetch_int_counter, fetch_int_gauge},
+    metrics::{fetch_int_counter, fetch_int_counter_vec, fetch_int_gauge, Labels},
     mock_time,
     state::{arb_stream, arb_stream_slice, canister_ids},
     types::{
@@ -993,6 +993,111 @@ fn encode_stream_index_is_checked() {
     });
 }
 
+/// Creates checkpoints @1 and @2, then turns @2 into a diverged checkpoint and
+/// backs up @1, and restarts the state manager on the same state root with
+/// the given retention policy.
+fn restart_with_diverged_state_and_backup<F>(retention_policy: RetentionPolicy, test: F)
+where
+    F: FnOnce(&MetricsRegistry, StateManagerImpl),
+{
+    let tmp = Builder::new().prefix("test").tempdir().unwrap();
+    let config = Config::new(tmp.path().into());
+    let own_subnet = subnet_test_id(42);
+    let verifier: Arc<dyn Verifier> = Arc::new(FakeVerifier::new());
+
+    with_test_replica_logger(|log| {
+        let make_state_manager = |config: &Config, metrics_registry: &MetricsRegistry| {
+            StateManagerImpl::new(
+                Arc::clone(&verifier),
+                own_subnet,
+                SubnetType::Application,
+                log.clone(),
+                metrics_registry,
+                config,
+                ic_types::malicious_flags::MaliciousFlags::default(),
+            )
+        };
+
+        let state_manager = make_state_manager(&config, &MetricsRegistry::new());
+        for h in 1..=2 {
+            let (_height, state) = state_manager.take_tip();
+            state_manager.commit_and_certify(state, height(h), CertificationScope::Full);
+            wait_for_checkpoint(&state_manager, height(h));
+        }
+        let layout = state_manager.state_layout();
+        layout.backup_checkpoint(height(1)).unwrap();
+        layout.mark_checkpoint_diverged(height(2)).unwrap();
+        drop(state_manager);
+
+        let metrics_registry = MetricsRegistry::new();
+        let state_manager = make_state_manager(
+            &config.with_retention_policy(retention_policy),
+            &metrics_registry,
+        );
+        test(&metrics_registry, state_manager);
+    });
+}
+
+fn reclaims(metrics: &MetricsRegistry, data: &str, reason: &str) -> u64 {
+    let labels: Labels = [("data", data), ("reason", reason)]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    fetch_int_counter_vec(metrics, "state_manager_reclaims_total")[&labels]
+}
+
+#[test]
+fn keeps_diverged_states_and_backups_within_retention_policy() {
+    restart_with_diverged_state_and_backup(RetentionPolicy::default(), |metrics, state_manager| {
+        let layout = state_manager.state_layout();
+        assert_eq!(
+            layout.diverged_checkpoint_heights().unwrap(),
+            vec![height(2)]
+        );
+        assert_eq!(layout.backup_heights().unwrap(), vec![height(1)]);
+        assert_eq!(reclaims(metrics, "diverged_checkpoint", "retention"), 0);
+    });
+}
+
+#[test]
+fn removes_diverged_states_and_backups_beyond_retention_policy() {
+    restart_with_diverged_state_and_backup(
+        RetentionPolicy {
+            diverged_states_to_keep: 0,
+            ..RetentionPolicy::default()
+        },
+        |metrics, state_manager| {
+            let layout = state_manager.state_layout();
+            assert!(layout.diverged_checkpoint_heights().unwrap().is_empty());
+            assert!(layout.backup_heights().unwrap().is_empty());
+            assert_eq!(reclaims(metrics, "diverged_checkpoint", "retention"), 1);
+            assert_eq!(reclaims(metrics, "backup", "retention"), 1);
+            assert_eq!(reclaims(metrics, "backup", "disk_pressure"), 0);
+        },
+    );
+}
+
+#[test]
+fn removes_optional_data_under_disk_pressure() {
+    restart_with_diverged_state_and_backup(
+        RetentionPolicy {
+            // The disk is never free enough.
+            min_free_disk_space_percent: 100,
+            ..RetentionPolicy::default()
+        },
+        |metrics, state_manager| {
+            let layout = state_manager.state_layout();
+            assert!(layout.diverged_checkpoint_heights().unwrap().is_empty());
+            assert!(layout.backup_heights().unwrap().is_empty());
+            assert_eq!(reclaims(metrics, "diverged_checkpoint", "disk_pressure"), 1);
+            assert_eq!(reclaims(metrics, "backup", "disk_pressure"), 1);
+            assert_eq!(reclaims(metrics, "backup", "retention"), 0);
+            // Checkpoints are never deleted to reclaim space.
+            assert_eq!(layout.checkpoint_heights().unwrap(), vec![height(1)]);
+        },
+    );
+}
+
 #[test]
 fn delivers_state_adverts_once() {
     state_manager_test(|_metrics, state_manager| {
@@ -1034,6 +1139,87 @@ fn recomputes_metadata_on_restart_if_missing() {
     })
 }
 
+#[test]
+fn can_export_and_import_checkpoint_archive() {
+    use ic_state_layout::StateLayout;
+    use ic_state_manager::{
+        checkpoint_archive::{export_checkpoint, import_checkpoint, ArchiveError},
+        manifest::{
+            compute_manifest, validate_manifest, CURRENT_STATE_SYNC_VERSION, DEFAULT_CHUNK_SIZE,
+        },
+        ManifestMetrics,
+    };
+    use std::io::Read;
+
+    state_manager_test(|_metrics, state_manager| {
+        let (_height, mut state) = state_manager.take_tip();
+        insert_dummy_canister(&mut state, canister_test_id(100));
+        state_manager.commit_and_certify(state, height(1), CertificationScope::Full);
+        let hash = wait_for_checkpoint(&state_manager, height(1));
+
+        let tmp = Builder::new().prefix("test").tempdir().unwrap();
+        let archive = tmp.path().join("checkpoint.tar");
+        let checkpoint = state_manager.state_layout().checkpoint(height(1)).unwrap();
+        assert_eq!(
+            export_checkpoint(&no_op_logger(), &checkpoint, &archive).unwrap(),
+            hash
+        );
+
+        let import_layout = |name: &str| StateLayout::new(no_op_logger(), tmp.path().join(name));
+
+        let layout = import_layout("good");
+        assert_eq!(
+            import_checkpoint(&layout, &archive, Some(&hash)).unwrap(),
+            (height(1), hash.clone())
+        );
+        assert_eq!(layout.checkpoint_heights().unwrap(), vec![height(1)]);
+        let manifest = compute_manifest(
+            &ManifestMetrics::new(&MetricsRegistry::new()),
+            &no_op_logger(),
+            CURRENT_STATE_SYNC_VERSION,
+            layout.checkpoint(height(1)).unwrap().raw_path(),
+            DEFAULT_CHUNK_SIZE,
+            None,
+        )
+        .unwrap();
+        validate_manifest(&manifest, &hash).unwrap();
+
+        // A different expected state is rejected.
+        let layout = import_layout("wrong_hash");
+        let wrong_hash = CryptoHashOfState::from(CryptoHash(vec![0; 32]));
+        assert!(matches!(
+            import_checkpoint(&layout, &archive, Some(&wrong_hash)),
+            Err(ArchiveError::UnexpectedRootHash { .. })
+        ));
+        assert!(layout.checkpoint_heights().unwrap().is_empty());
+
+        // Tampering with the contents of a file is detected.
+        let tampered = tmp.path().join("tampered.tar");
+        {
+            let mut src = tar::Archive::new(std::fs::File::open(&archive).unwrap());
+            let mut dst = tar::Builder::new(std::fs::File::create(&tampered).unwrap());
+            for entry in src.entries().unwrap() {
+                let mut entry = entry.unwrap();
+                let mut header = entry.header().clone();
+                let path = entry.path().unwrap().into_owned();
+                let mut bytes = Vec::new();
+                entry.read_to_end(&mut bytes).unwrap();
+                if path.ends_with("system_metadata.pbuf") {
+                    bytes[0] ^= 0xff;
+                }
+                dst.append_data(&mut header, &path, &bytes[..]).unwrap();
+            }
+            dst.finish().unwrap();
+        }
+        let layout = import_layout("tampered");
+        assert!(matches!(
+            import_checkpoint(&layout, &tampered, None),
+            Err(ArchiveError::InvalidChunk { .. })
+        ));
+        assert!(layout.checkpoint_heights().unwrap().is_empty());
+    });
+}
+
 #[test]
 fn state_sync_message_contains_manifest() {
     state_manager_test(|_metrics, state_manager| {
@@ -1395,6 +1581,70 @@ fn can_state_sync_based_on_old_checkpoint() {
     });
 }
 
+#[test]
+fn can_state_sync_based_on_older_checkpoints() {
+    state_manager_test(|_metrics, src_state_manager| {
+        let (_height, mut state) = src_state_manager.take_tip();
+        insert_dummy_canister(&mut state, canister_test_id(100));
+        insert_dummy_canister(&mut state, canister_test_id(200));
+        src_state_manager.commit_and_certify(state, height(1), CertificationScope::Full);
+
+        let hash = wait_for_checkpoint(&src_state_manager, height(1));
+        let id = StateSyncArtifactId {
+            height: height(3),
+            hash,
+        };
+        let msg = src_state_manager
+            .get_validated_by_identifier(&StateSyncArtifactId {
+                height: height(1),
+                hash: id.hash.clone(),
+            })
+            .expect("failed to get state sync message");
+
+        state_manager_test(|_metrics, dst_state_manager| {
+            // The canisters are only present in the older of the local checkpoints.
+            let (_height, mut state) = dst_state_manager.take_tip();
+            insert_dummy_canister(&mut state, canister_test_id(100));
+            dst_state_manager.commit_and_certify(state, height(1), CertificationScope::Full);
+            wait_for_checkpoint(&dst_state_manager, height(1));
+
+            let (_height, mut state) = dst_state_manager.take_tip();
+            state.canister_states.remove(&canister_test_id(100));
+            dst_state_manager.commit_and_certify(state, height(2), CertificationScope::Full);
+            wait_for_checkpoint(&dst_state_manager, height(2));
+
+            let mut chunkable = dst_state_manager.create_chunkable_state(&id);
+            let dst_msg = match pipe_manifest(&msg, &mut *chunkable) {
+                Some(dst_msg) => dst_msg,
+                None => {
+                    // None of the canister chunks need to be fetched.
+                    for chunk_id in chunkable.chunks_to_download() {
+                        let chunk = &msg.manifest.chunk_table[chunk_id.get() as usize - 1];
+                        let path =
+                            &msg.manifest.file_table[chunk.file_index as usize].relative_path;
+                        assert!(
+                            !path.starts_with("canister_states"),
+                            "Unexpectedly fetching chunk {} of {}",
+                            chunk_id,
+                            path.display()
+                        );
+                    }
+                    pipe_state_sync(msg, chunkable)
+                }
+            };
+            dst_state_manager
+                .check_artifact_acceptance(dst_msg, &node_test_id(0))
+                .expect("failed to process state sync artifact");
+
+            let expected_state = src_state_manager.get_latest_state();
+            let recovered_state = dst_state_manager
+                .get_state_at(height(3))
+                .expect("Destination state manager didn't receive the state");
+            assert_eq!(recovered_state.take(), expected_state.take());
+        })
+    });
+}
+
 #[test]
 fn can_recover_from_corruption_on_state_sync() {
     use ic_state_layout::{CheckpointLayout, RwPolicy};
@@ -1605,7 +1855,7 @@ fn can_short_circuit_state_sync() {
 #[test]
 fn can_get_dirty_pages() {
     use ic_replicated_state::page_map::PageIndex;
-    use ic_state_manager::get_dirty_pages;
+    use ic_state_manager::{get_dirty_pages, PageMapType};
     use maplit::btreemap;
     use std::collections::BTreeMap;
 
@@ -1616,6 +1866,10 @@ fn can_get_dirty_pages() {
             (PageIndex::new(1), &[99u8; PAGE_SIZE]),
             (PageIndex::new(300), &[99u8; PAGE_SIZE]),
         ]);
+        execution_state
+            .stable_memory
+            .page_map
+            .update(&[(PageIndex::new(7), &[99u8; PAGE_SIZE])]);
     }
 
     fn drop_page_map(state: &mut ReplicatedState, canister_id: CanisterId) {
@@ -1643,9 +1897,12 @@ fn can_get_dirty_pages() {
         assert_eq!(
             get_dirty_pages(&state),
             btreemap! {
-                canister_test_id(80) => (height(1), vec![]),
-                canister_test_id(90) => (height(1), vec![PageIndex::new(1), PageIndex::new(300)]),
-                canister_test_id(100) => (height(1), vec![]),
+                (canister_test_id(80), PageMapType::WasmMemory) => (height(1), vec![]),
+                (canister_test_id(80), PageMapType::StableMemory) => (height(1), vec![]),
+                (canister_test_id(90), PageMapType::WasmMemory) => (height(1), vec![PageIndex::new(1), PageIndex::new(300)]),
+                (canister_test_id(90), PageMapType::StableMemory) => (height(1), vec![PageIndex::new(7)]),
+                (canister_test_id(100), PageMapType::WasmMemory) => (height(1), vec![]),
+                (canister_test_id(100), PageMapType::StableMemory) => (height(1), vec![]),
             }
         );
 
@@ -1659,8 +1916,11 @@ fn can_get_dirty_pages() {
         assert_eq!(
             get_dirty_pages(&state),
             btreemap! {
-                canister_test_id(80) => (height(2), vec![]),
-                canister_test_id(90) => (height(2), vec![]),
+                (canister_test_id(80), PageMapType::WasmMemory) => (height(2), vec![]),
+                (canister_test_id(80), PageMapType::StableMemory) => (height(2), vec![]),
+                (canister_test_id(90), PageMapType::WasmMemory) => (height(2), vec![]),
+                (canister_test_id(90), PageMapType::StableMemory) => (height(2), vec![]),
+                (canister_test_id(100), PageMapType::StableMemory) => (height(2), vec![]),
             }
         );
     })
@@ -1726,6 +1986,72 @@ fn can_reuse_chunk_hashes_when_computing_manifest() {
     });
 }
 
+#[test]
+fn checkpoints_store_memory_deltas_as_overlays() {
+    use ic_replicated_state::page_map::list_overlays;
+    use ic_state_manager::checkpoint::MAX_OVERLAYS;
+
+    state_manager_test(|_metrics, state_manager| {
+        let overlay_heights = |h: Height| -> Vec<Height> {
+            let checkpoint = state_manager.state_layout().checkpoint(h).unwrap();
+            let heap_file = checkpoint
+                .canister(&canister_test_id(1))
+                .unwrap()
+                .vmemory_0();
+            list_overlays(&heap_file)
+                .unwrap()
+                .into_iter()
+                .map(|(height, _)| height)
+                .collect()
+        };
+
+        let (_, mut state) = state_manager.take_tip();
+        insert_dummy_canister(&mut state, canister_test_id(1));
+        state_manager.commit_and_certify(state, height(1), CertificationScope::Full);
+        assert!(overlay_heights(height(1)).is_empty());
+
+        let last_height = MAX_OVERLAYS as u64 + 2;
+        for h in 2..=last_height {
+            let (_, mut state) = state_manager.take_tip();
+            let canister_state = state.canister_state_mut(&canister_test_id(1)).unwrap();
+            let execution_state = canister_state.execution_state.as_mut().unwrap();
+            execution_state
+                .wasm_memory
+                .page_map
+                .update(&[(PageIndex::new(h), &[h as u8; PAGE_SIZE])]);
+            state_manager.commit_and_certify(state, height(h), CertificationScope::Full);
+        }
+
+        // Every checkpoint adds an overlay until there are `MAX_OVERLAYS` of them.
+        assert_eq!(
+            overlay_heights(height(last_height - 1)),
+            (2..last_height).map(height).collect::<Vec<_>>()
+        );
+        // Then they get merged before the next overlay is written. The memory
+        // file of the canister is small, so the merged overlay is folded into it.
+        assert_eq!(
+            overlay_heights(height(last_height)),
+            vec![height(last_height)]
+        );
+
+        let state = state_manager.get_latest_state().take();
+        let page_map = &state
+            .canister_state(&canister_test_id(1))
+            .unwrap()
+            .execution_state
+            .as_ref()
+            .unwrap()
+            .wasm_memory
+            .page_map;
+        for h in 2..=last_height {
+            assert_eq!(page_map.get_page(PageIndex::new(h)), &[h as u8; PAGE_SIZE]);
+        }
+
+        let (_, tip) = state_manager.take_tip();
+        assert_eq!(tip, *state);
+    });
+}
+
 #[test]
 fn certified_read_can_certify_ingress_history_entry() {
     use LabeledTree::*;
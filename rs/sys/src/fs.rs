@@ -58,6 +58,25 @@ fn handle_last_os_error() -> FileCloneError {
     }
 }
 
+/// Space on the filesystem containing a path, as reported by statvfs(3).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiskSpace {
+    /// The size of the filesystem in bytes.
+    pub total_bytes: u64,
+    /// The number of bytes available to unprivileged processes.
+    pub available_bytes: u64,
+}
+
+/// Returns the size and the free space of the filesystem containing `path`.
+pub fn disk_space(path: &Path) -> std::io::Result<DiskSpace> {
+    let stat = nix::sys::statvfs::statvfs(path)?;
+    let fragment_size = stat.fragment_size() as u64;
+    Ok(DiskSpace {
+        total_bytes: stat.blocks() as u64 * fragment_size,
+        available_bytes: stat.blocks_available() as u64 * fragment_size,
+    })
+}
+
 #[cfg(target_os = "linux")]
 fn clone_file_impl(src: &Path, dst: &Path) -> Result<(), FileCloneError> {
     use std::fs::OpenOptions;
@@ -6,8 +6,9 @@ use ic_consensus_message::{make_genesis, ConsensusMessageHashable};
 use ic_interfaces::state_manager::StateManager;
 use ic_interfaces::{
     consensus_pool::{
-        ChangeAction, ChangeSet, ConsensusPool, ConsensusPoolCache, MutableConsensusPool,
-        PoolSection, UnvalidatedConsensusArtifact, ValidatedConsensusArtifact,
+        ChangeAction, ChangeSet, ConsensusPool, ConsensusPoolCache, HeightIndexedPool,
+        MutableConsensusPool, PoolSection, UnvalidatedConsensusArtifact,
+        ValidatedConsensusArtifact,
     },
     crypto::{MultiSigner, ThresholdSigner},
     dkg::DkgPool,
@@ -676,6 +677,12 @@ impl ConsensusPool for TestConsensusPool {
     fn as_cache(&self) -> &dyn ConsensusPoolCache {
         self.pool.as_cache()
     }
+
+    fn equivocation_evidence(
+        &self,
+    ) -> &dyn HeightIndexedPool<consensus::equivocation::EquivocationEvidence> {
+        self.pool.equivocation_evidence()
+    }
 }
 
 impl MutableConsensusPool for TestConsensusPool {
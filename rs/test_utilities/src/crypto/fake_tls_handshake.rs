@@ -5,6 +5,7 @@ use ic_crypto_tls_interfaces::{
 };
 use ic_types::{NodeId, RegistryVersion};
 use tokio::net::TcpStream;
+use tokio_rustls::rustls::{ClientConfig, ServerConfig};
 
 /// This implementation of TlsHandshake is so fake that it panics if
 /// you try to call any of the methods.
@@ -67,4 +68,20 @@ impl TlsHandshake for FakeTlsHandshake {
     ) -> Result<TlsStream, TlsClientHandshakeError> {
         unimplemented!()
     }
+
+    fn quic_server_config(
+        &self,
+        _allowed_clients: AllowedClients,
+        _registry_version: RegistryVersion,
+    ) -> Result<ServerConfig, TlsServerHandshakeError> {
+        unimplemented!()
+    }
+
+    fn quic_client_config(
+        &self,
+        _server: NodeId,
+        _registry_version: RegistryVersion,
+    ) -> Result<ClientConfig, TlsClientHandshakeError> {
+        unimplemented!()
+    }
 }
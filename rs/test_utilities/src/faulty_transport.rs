@@ -0,0 +1,546 @@
+//! A [`Transport`] wrapper that injects network faults: latency with jitter,
+//! message drops, bandwidth caps and partitions from peers.
+//!
+//! All random decisions are drawn from an RNG seeded from the
+//! [`FaultConfig`], so a test that sends the same messages in the same order
+//! sees the same messages dropped and delayed on every run. Wrapping the
+//! transport of every node (e.g. the [`ThreadPort`]s of a test hub) gives
+//! reproducible partitions and slow links on a single machine.
+//!
+//! [`ThreadPort`]: crate::thread_transport::ThreadPort
+
+use async_trait::async_trait;
+use ic_interfaces::transport::{AsyncTransportEventHandler, SendError, Transport};
+use ic_protobuf::registry::node::v1::NodeRecord;
+use ic_types::transport::{
+    FlowId, FlowTag, TransportClientType, TransportErrorCode, TransportPayload,
+    TransportStateChange,
+};
+use ic_types::{NodeId, RegistryVersion};
+use rand::Rng;
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The distribution of the delay added to each message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Latency {
+    /// Messages are passed on without delay.
+    None,
+    /// Every message is delayed by the same duration.
+    Fixed(Duration),
+    /// Messages are delayed by `base` plus a jitter drawn uniformly from
+    /// `[0, jitter)`.
+    Uniform { base: Duration, jitter: Duration },
+    /// Messages are delayed by `base` plus an exponentially distributed
+    /// jitter with mean `mean_jitter`, i.e. a few messages are delayed a lot.
+    Exponential {
+        base: Duration,
+        mean_jitter: Duration,
+    },
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Latency::None
+    }
+}
+
+impl Latency {
+    fn sample<R: Rng>(&self, rng: &mut R) -> Duration {
+        match self {
+            Latency::None => Duration::from_secs(0),
+            Latency::Fixed(delay) => *delay,
+            Latency::Uniform { base, jitter } => *base + jitter.mul_f64(rng.gen::<f64>()),
+            Latency::Exponential { base, mean_jitter } => {
+                // `1 - u` is in (0, 1], so the logarithm is finite.
+                let u: f64 = rng.gen();
+                *base + mean_jitter.mul_f64(-(1.0 - u).ln())
+            }
+        }
+    }
+}
+
+/// The faults injected on the link to a peer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkFaults {
+    /// The delay added to each message.
+    pub latency: Latency,
+    /// The probability, between 0 and 1, that a message is dropped.
+    pub drop_rate: f64,
+    /// The throughput of the link in bytes per second, if capped. Messages
+    /// sent faster than that queue up behind each other.
+    pub bandwidth_bytes_per_sec: Option<u64>,
+}
+
+/// The configuration of a [`FaultyTransport`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FaultConfig {
+    /// Seed of the RNG that decides which messages are dropped and how long
+    /// they are delayed.
+    pub seed: u64,
+    /// The faults on the links to peers without an entry in `links`.
+    pub default_link: LinkFaults,
+    /// The faults on the links to specific peers.
+    pub links: BTreeMap<NodeId, LinkFaults>,
+}
+
+/// Counts of the messages sent through a [`FaultyTransport`], and of the
+/// messages received from partitioned peers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FaultStats {
+    /// Messages passed on to the wrapped transport, possibly delayed.
+    pub forwarded: u64,
+    /// Messages dropped according to the drop rate of the link.
+    pub dropped: u64,
+    /// Messages sent to or received from a partitioned peer.
+    pub partitioned: u64,
+}
+
+/// The mutable state of the fault injection, shared by the transport and the
+/// event handlers it wraps.
+struct FaultState {
+    config: FaultConfig,
+    rng: ChaChaRng,
+    partitioned: BTreeSet<NodeId>,
+    /// Per peer, the time at which the link has sent all queued messages.
+    link_busy_until: BTreeMap<NodeId, Instant>,
+    stats: FaultStats,
+}
+
+impl FaultState {
+    fn new(config: FaultConfig) -> Self {
+        Self {
+            rng: ChaChaRng::seed_from_u64(config.seed),
+            config,
+            partitioned: BTreeSet::new(),
+            link_busy_until: BTreeMap::new(),
+            stats: FaultStats::default(),
+        }
+    }
+
+    /// Decides the fate of a message of `size` bytes sent to `peer` at `now`:
+    /// `None` if the message is lost, otherwise the delay after which it is
+    /// passed on.
+    fn schedule(&mut self, peer: &NodeId, size: usize, now: Instant) -> Option<Duration> {
+        if self.partitioned.contains(peer) {
+            self.stats.partitioned += 1;
+            return None;
+        }
+        let link = self
+            .config
+            .links
+            .get(peer)
+            .unwrap_or(&self.config.default_link);
+        if link.drop_rate > 0.0 && self.rng.gen_bool(link.drop_rate.min(1.0)) {
+            self.stats.dropped += 1;
+            return None;
+        }
+
+        let mut delay = link.latency.sample(&mut self.rng);
+        if let Some(bandwidth) = link.bandwidth_bytes_per_sec {
+            let start = match self.link_busy_until.get(peer) {
+                Some(busy_until) if *busy_until > now => *busy_until,
+                _ => now,
+            };
+            let sent = start + Duration::from_secs_f64(size as f64 / bandwidth as f64);
+            self.link_busy_until.insert(*peer, sent);
+            delay += sent - now;
+        }
+        self.stats.forwarded += 1;
+        Some(delay)
+    }
+
+    /// Returns true (and counts the message) if messages from `peer` must be
+    /// dropped.
+    fn drop_received(&mut self, peer: &NodeId) -> bool {
+        let partitioned = self.partitioned.contains(peer);
+        if partitioned {
+            self.stats.partitioned += 1;
+        }
+        partitioned
+    }
+}
+
+/// A [`Transport`] that passes messages on to the wrapped transport, after
+/// injecting the faults of its [`FaultConfig`].
+///
+/// Latency, drops and bandwidth caps apply to the messages sent by this node.
+/// Partitions apply in both directions: messages from partitioned peers are
+/// not delivered to the registered clients either.
+///
+/// Delayed messages are sent from a task spawned on the current tokio runtime,
+/// so `send()` must be called from within a runtime.
+pub struct FaultyTransport {
+    transport: Arc<dyn Transport>,
+    state: Arc<Mutex<FaultState>>,
+}
+
+impl FaultyTransport {
+    pub fn new(transport: Arc<dyn Transport>, config: FaultConfig) -> Self {
+        Self {
+            transport,
+            state: Arc::new(Mutex::new(FaultState::new(config))),
+        }
+    }
+
+    /// Cuts this node off from `peers`, until `heal()` is called for them.
+    pub fn partition(&self, peers: &[NodeId]) {
+        let mut state = self.state.lock().unwrap();
+        state.partitioned.extend(peers.iter().copied());
+    }
+
+    /// Reconnects this node to `peers`.
+    pub fn heal(&self, peers: &[NodeId]) {
+        let mut state = self.state.lock().unwrap();
+        for peer in peers {
+            state.partitioned.remove(peer);
+        }
+    }
+
+    /// Replaces the faults on the link to `peer`.
+    pub fn set_link_faults(&self, peer: NodeId, faults: LinkFaults) {
+        let mut state = self.state.lock().unwrap();
+        state.config.links.insert(peer, faults);
+    }
+
+    pub fn stats(&self) -> FaultStats {
+        self.state.lock().unwrap().stats
+    }
+}
+
+impl Transport for FaultyTransport {
+    fn register_client(
+        &self,
+        client_type: TransportClientType,
+        event_handler: Arc<dyn AsyncTransportEventHandler>,
+    ) -> Result<(), TransportErrorCode> {
+        self.transport.register_client(
+            client_type,
+            Arc::new(FaultyEventHandler {
+                event_handler,
+                state: self.state.clone(),
+            }),
+        )
+    }
+
+    fn start_connections(
+        &self,
+        client_type: TransportClientType,
+        peer: &NodeId,
+        record: &NodeRecord,
+        registry_version: RegistryVersion,
+    ) -> Result<(), TransportErrorCode> {
+        self.transport
+            .start_connections(client_type, peer, record, registry_version)
+    }
+
+    fn stop_connections(
+        &self,
+        client_type: TransportClientType,
+        peer: &NodeId,
+        registry_version: RegistryVersion,
+    ) -> Result<(), TransportErrorCode> {
+        self.transport
+            .stop_connections(client_type, peer, registry_version)
+    }
+
+    /// Lost messages are reported as sent, as they would be by a real network.
+    fn send(
+        &self,
+        client_type: TransportClientType,
+        peer: &NodeId,
+        flow: FlowTag,
+        message: TransportPayload,
+    ) -> Result<(), TransportErrorCode> {
+        let delay = self
+            .state
+            .lock()
+            .unwrap()
+            .schedule(peer, message.0.len(), Instant::now());
+        match delay {
+            None => Ok(()),
+            Some(delay) if delay == Duration::from_secs(0) => {
+                self.transport.send(client_type, peer, flow, message)
+            }
+            Some(delay) => {
+                let transport = self.transport.clone();
+                let peer = *peer;
+                tokio::task::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    // The message is lost if the wrapped transport is busy by now.
+                    let _ = transport.send(client_type, &peer, flow, message);
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn clear_send_queues(&self, client_type: TransportClientType, peer: &NodeId) {
+        self.transport.clear_send_queues(client_type, peer)
+    }
+
+    fn clear_send_queue(&self, client_type: TransportClientType, peer: &NodeId, flow: FlowTag) {
+        self.transport.clear_send_queue(client_type, peer, flow)
+    }
+}
+
+/// Drops the messages received from partitioned peers before they reach the
+/// client.
+struct FaultyEventHandler {
+    event_handler: Arc<dyn AsyncTransportEventHandler>,
+    state: Arc<Mutex<FaultState>>,
+}
+
+#[async_trait]
+impl AsyncTransportEventHandler for FaultyEventHandler {
+    async fn send_message(&self, flow: FlowId, message: TransportPayload) -> Result<(), SendError> {
+        if self.state.lock().unwrap().drop_received(&flow.peer_id) {
+            return Ok(());
+        }
+        self.event_handler.send_message(flow, message).await
+    }
+
+    async fn state_changed(&self, state_change: TransportStateChange) {
+        self.event_handler.state_changed(state_change).await
+    }
+
+    async fn error(&self, flow: FlowId, error: TransportErrorCode) {
+        self.event_handler.error(flow, error).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ids::{NODE_1, NODE_2};
+
+    /// Records the messages it is asked to send.
+    #[derive(Default)]
+    struct RecordingTransport {
+        sent: Mutex<Vec<(NodeId, TransportPayload)>>,
+        event_handler: Mutex<Option<Arc<dyn AsyncTransportEventHandler>>>,
+    }
+
+    impl Transport for RecordingTransport {
+        fn register_client(
+            &self,
+            _client_type: TransportClientType,
+            event_handler: Arc<dyn AsyncTransportEventHandler>,
+        ) -> Result<(), TransportErrorCode> {
+            *self.event_handler.lock().unwrap() = Some(event_handler);
+            Ok(())
+        }
+
+        fn start_connections(
+            &self,
+            _client_type: TransportClientType,
+            _peer: &NodeId,
+            _record: &NodeRecord,
+            _registry_version: RegistryVersion,
+        ) -> Result<(), TransportErrorCode> {
+            Ok(())
+        }
+
+        fn stop_connections(
+            &self,
+            _client_type: TransportClientType,
+            _peer: &NodeId,
+            _registry_version: RegistryVersion,
+        ) -> Result<(), TransportErrorCode> {
+            Ok(())
+        }
+
+        fn send(
+            &self,
+            _client_type: TransportClientType,
+            peer: &NodeId,
+            _flow: FlowTag,
+            message: TransportPayload,
+        ) -> Result<(), TransportErrorCode> {
+            self.sent.lock().unwrap().push((*peer, message));
+            Ok(())
+        }
+
+        fn clear_send_queues(&self, _client_type: TransportClientType, _peer: &NodeId) {}
+
+        fn clear_send_queue(
+            &self,
+            _client_type: TransportClientType,
+            _peer: &NodeId,
+            _flow: FlowTag,
+        ) {
+        }
+    }
+
+    /// Records the messages delivered to the client.
+    #[derive(Default)]
+    struct RecordingEventHandler {
+        received: Mutex<Vec<(NodeId, TransportPayload)>>,
+    }
+
+    #[async_trait]
+    impl AsyncTransportEventHandler for RecordingEventHandler {
+        async fn send_message(
+            &self,
+            flow: FlowId,
+            message: TransportPayload,
+        ) -> Result<(), SendError> {
+            self.received.lock().unwrap().push((flow.peer_id, message));
+            Ok(())
+        }
+
+        async fn state_changed(&self, _state_change: TransportStateChange) {}
+
+        async fn error(&self, _flow: FlowId, _error: TransportErrorCode) {}
+    }
+
+    fn payload(i: usize) -> TransportPayload {
+        TransportPayload(i.to_le_bytes().to_vec())
+    }
+
+    /// Sends 100 messages to `NODE_2` and returns the ones that got through.
+    fn send_all(config: FaultConfig) -> Vec<(NodeId, TransportPayload)> {
+        let recording = Arc::new(RecordingTransport::default());
+        let transport = FaultyTransport::new(recording.clone(), config);
+        for i in 0..100 {
+            transport
+                .send(
+                    TransportClientType::P2P,
+                    &NODE_2,
+                    FlowTag::from(0),
+                    payload(i),
+                )
+                .unwrap();
+        }
+        let sent = recording.sent.lock().unwrap().clone();
+        sent
+    }
+
+    #[test]
+    fn should_drop_the_same_messages_for_the_same_seed() {
+        let config = |seed| FaultConfig {
+            seed,
+            default_link: LinkFaults {
+                drop_rate: 0.5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let sent = send_all(config(7));
+        assert!(!sent.is_empty() && sent.len() < 100);
+        assert_eq!(sent, send_all(config(7)));
+        assert_ne!(sent, send_all(config(8)));
+    }
+
+    #[test]
+    fn should_partition_in_both_directions() {
+        let recording = Arc::new(RecordingTransport::default());
+        let transport = FaultyTransport::new(recording.clone(), FaultConfig::default());
+        let client = Arc::new(RecordingEventHandler::default());
+        transport
+            .register_client(TransportClientType::P2P, client.clone())
+            .unwrap();
+        let event_handler = recording.event_handler.lock().unwrap().clone().unwrap();
+        let flow = |peer_id| FlowId {
+            client_type: TransportClientType::P2P,
+            peer_id,
+            flow_tag: FlowTag::from(0),
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        transport.partition(&[NODE_2]);
+        for peer in &[NODE_1, NODE_2] {
+            transport
+                .send(TransportClientType::P2P, peer, FlowTag::from(0), payload(1))
+                .unwrap();
+            runtime
+                .block_on(event_handler.send_message(flow(*peer), payload(2)))
+                .unwrap();
+        }
+        assert_eq!(*recording.sent.lock().unwrap(), vec![(NODE_1, payload(1))]);
+        assert_eq!(*client.received.lock().unwrap(), vec![(NODE_1, payload(2))]);
+        assert_eq!(transport.stats().partitioned, 2);
+
+        transport.heal(&[NODE_2]);
+        transport
+            .send(
+                TransportClientType::P2P,
+                &NODE_2,
+                FlowTag::from(0),
+                payload(3),
+            )
+            .unwrap();
+        runtime
+            .block_on(event_handler.send_message(flow(NODE_2), payload(4)))
+            .unwrap();
+        assert_eq!(
+            recording.sent.lock().unwrap().last(),
+            Some(&(NODE_2, payload(3)))
+        );
+        assert_eq!(
+            client.received.lock().unwrap().last(),
+            Some(&(NODE_2, payload(4)))
+        );
+    }
+
+    #[test]
+    fn should_queue_messages_behind_the_bandwidth_cap() {
+        let mut links = BTreeMap::new();
+        links.insert(
+            NODE_2,
+            LinkFaults {
+                latency: Latency::Fixed(Duration::from_millis(100)),
+                bandwidth_bytes_per_sec: Some(1000),
+                ..Default::default()
+            },
+        );
+        let mut state = FaultState::new(FaultConfig {
+            links,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let delays: Vec<_> = (0..3)
+            .map(|_| state.schedule(&NODE_2, 500, now).unwrap())
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(600),
+                Duration::from_millis(1100),
+                Duration::from_millis(1600)
+            ]
+        );
+        // The link to other peers is not affected.
+        assert_eq!(
+            state.schedule(&NODE_1, 500, now),
+            Some(Duration::from_secs(0))
+        );
+        // Once the queue has drained, messages only see the latency again.
+        assert_eq!(
+            state.schedule(&NODE_2, 500, now + Duration::from_secs(10)),
+            Some(Duration::from_millis(600))
+        );
+    }
+
+    #[test]
+    fn should_sample_latency_within_the_jitter() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let base = Duration::from_millis(10);
+        let jitter = Duration::from_millis(5);
+        let latency = Latency::Uniform { base, jitter };
+        let samples: Vec<_> = (0..100).map(|_| latency.sample(&mut rng)).collect();
+        assert!(samples.iter().all(|d| *d >= base && *d < base + jitter));
+        assert!(samples.iter().any(|d| *d != samples[0]));
+
+        let latency = Latency::Exponential {
+            base,
+            mean_jitter: jitter,
+        };
+        assert!((0..100).all(|_| latency.sample(&mut rng) >= base));
+    }
+}
@@ -5,6 +5,7 @@ pub mod consensus;
 pub mod crypto;
 pub mod cycles_account_manager;
 pub mod empty_wasm;
+pub mod faulty_transport;
 pub mod history;
 pub mod ingress_selector;
 pub mod message_routing;
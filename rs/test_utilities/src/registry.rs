@@ -170,6 +170,9 @@ pub fn test_subnet_record() -> SubnetRecord {
         ssh_readonly_access: vec![],
         ssh_backup_access: vec![],
         ecdsa_config: None,
+        max_empty_block_delay_millis: 0,
+        min_block_delay_millis: 0,
+        ingress_compute_allocation_share_percent: 0,
     }
 }
 
@@ -224,6 +227,16 @@ impl SubnetRecordBuilder {
         self
     }
 
+    pub fn with_adaptive_block_rate(
+        mut self,
+        max_empty_block_delay_millis: u64,
+        min_block_delay_millis: u64,
+    ) -> Self {
+        self.record.max_empty_block_delay_millis = max_empty_block_delay_millis;
+        self.record.min_block_delay_millis = min_block_delay_millis;
+        self
+    }
+
     pub fn build(self) -> SubnetRecord {
         self.record
     }
@@ -1,6 +1,6 @@
 use crate::util::mock_time;
 use ic_types::{
-    batch::{Batch, BatchPayload},
+    batch::{Batch, BatchPayload, BlockmakerMetrics},
     Height, Randomness, RegistryVersion, Time,
 };
 
@@ -20,6 +20,7 @@ impl Default for BatchBuilder {
                 registry_version: RegistryVersion::from(1),
                 time: mock_time(),
                 consensus_responses: vec![],
+                blockmaker_metrics: None,
             },
         }
     }
@@ -60,6 +61,12 @@ impl BatchBuilder {
         self
     }
 
+    /// Set the blockmaker_metrics field to blockmaker_metrics.
+    pub fn blockmaker_metrics(mut self, blockmaker_metrics: BlockmakerMetrics) -> Self {
+        self.batch.blockmaker_metrics = Some(blockmaker_metrics);
+        self
+    }
+
     /// Return the built Batch.
     pub fn build(&self) -> Batch {
         self.batch.clone()
@@ -3,14 +3,16 @@
 //! The control plane handles tokio/TLS related details of connection
 //! management.  This component establishes/accepts connections
 //! to/from subnet peers. The component also manages re-establishment
-//! of severed connections.
+//! of severed connections. With the QUIC backend, connections are managed
+//! by the [`quic`](../quic/index.html) module instead, and this module only
+//! dispatches to it.
 //!
 //! The control plane module implements control plane functionality for
 //! [`TransportImpl`](../types/struct.TransportImpl.html).
 
 use crate::types::{
     ClientState, Connecting, ConnectionRole, ConnectionState, FlowState, PeerState, QueueSize,
-    ServerPort, ServerPortState, TransportImpl,
+    QuicConnectionSlot, ServerPort, ServerPortState, TransportImpl,
 };
 use crate::utils::{get_flow_ips, get_flow_label, SendQueueImpl};
 use futures::future::{AbortHandle, Abortable, Aborted};
@@ -19,7 +21,7 @@ use ic_interfaces::transport::AsyncTransportEventHandler;
 use ic_logger::{error, info, warn, ReplicaLogger};
 use ic_protobuf::registry::node::v1::NodeRecord;
 use ic_types::{
-    transport::{FlowId, FlowTag, TransportClientType, TransportErrorCode},
+    transport::{FlowId, FlowTag, TransportBackend, TransportClientType, TransportErrorCode},
     NodeId, RegistryVersion,
 };
 use std::collections::HashMap;
@@ -31,10 +33,10 @@ use tokio::net::{TcpListener, TcpSocket, TcpStream};
 use tokio::time::sleep;
 
 /// Time to wait before retrying an unsuccessful connection attempt
-const CONNECT_RETRY_SECONDS: u64 = 3;
+pub(crate) const CONNECT_RETRY_SECONDS: u64 = 3;
 
 /// Time to wait for the TLS handshake (for both client/server sides)
-const TLS_HANDSHAKE_TIMEOUT_SECONDS: u64 = 30;
+pub(crate) const TLS_HANDSHAKE_TIMEOUT_SECONDS: u64 = 30;
 
 /// Connection status values
 #[derive(Debug)]
@@ -64,6 +66,7 @@ impl TransportImpl {
             self.allowed_clients.write().unwrap().insert(*peer_id);
         }
         *self.registry_version.write().unwrap() = registry_version;
+        self.refresh_quic_server_config(client_state);
         info!(
             self.log,
             "ControlPlane::start_peer_connections(): client_type = {:?}, node_id = {:?} peer_id = {:?}",
@@ -87,6 +90,7 @@ impl TransportImpl {
         let client_state = client_map
             .get_mut(&client_type)
             .ok_or(TransportErrorCode::TransportClientNotFound)?;
+        self.refresh_quic_server_config(client_state);
         let peer_state = client_state
             .peer_map
             .get(peer_id)
@@ -134,6 +138,7 @@ impl TransportImpl {
 
         let mut peer_state = PeerState {
             flow_map: HashMap::new(),
+            quic_connection: Default::default(),
         };
 
         // TODO: P2P-514
@@ -173,6 +178,11 @@ impl TransportImpl {
             return Ok(());
         }
 
+        // With QUIC, all flows connect to the same endpoint of the peer.
+        let quic_peer_addr = match self.config.backend {
+            TransportBackend::TlsOverTcp => None,
+            TransportBackend::Quic => Self::quic_peer_addr(peer_record),
+        };
         for flow_endpoint in &peer_record.p2p_flow_endpoints {
             let endpoint = match &flow_endpoint.endpoint {
                 Some(x) => x,
@@ -202,16 +212,17 @@ impl TransportImpl {
             let peer_ip = IpAddr::from_str(endpoint.ip_addr.as_str())
                 .unwrap_or_else(|_| panic!("Invalid node IP: {}", endpoint.ip_addr));
             let flow_label = get_flow_label(endpoint.ip_addr.as_str(), peer_id);
-            let server_port = endpoint.port as u16;
-            let connecting_task = self.spawn_connect_task(
+            let peer_addr =
+                quic_peer_addr.unwrap_or_else(|| SocketAddr::new(peer_ip, endpoint.port as u16));
+            let connecting_task = self.spawn_flow_connect_task(
                 client_type,
-                flow_endpoint.flow_tag.into(),
+                flow_tag,
                 *peer_id,
-                peer_ip,
-                ServerPort::from(server_port),
+                peer_addr,
+                &peer_state.quic_connection,
             );
             let connecting_state = Connecting {
-                peer_addr: SocketAddr::new(peer_ip, server_port),
+                peer_addr,
                 connecting_task,
             };
             let flow_id = FlowId {
@@ -432,6 +443,34 @@ impl TransportImpl {
         abort_handle
     }
 
+    /// Spawns the task that connects a flow to the peer, over the configured
+    /// backend
+    fn spawn_flow_connect_task(
+        &self,
+        client_type: TransportClientType,
+        flow_tag: FlowTag,
+        peer_id: NodeId,
+        peer_addr: SocketAddr,
+        quic_connection: &QuicConnectionSlot,
+    ) -> AbortHandle {
+        match self.config.backend {
+            TransportBackend::TlsOverTcp => self.spawn_connect_task(
+                client_type,
+                flow_tag,
+                peer_id,
+                peer_addr.ip(),
+                ServerPort::from(peer_addr.port()),
+            ),
+            TransportBackend::Quic => self.spawn_quic_connect_task(
+                client_type,
+                flow_tag,
+                peer_id,
+                peer_addr,
+                quic_connection.clone(),
+            ),
+        }
+    }
+
     /// Handles the handshake completion during connection establishment (both
     /// server/client sides). Does the validation, sets up the connection state
     /// and spawns the read task for the connection.
@@ -525,12 +564,12 @@ impl TransportImpl {
             // reconnect if we have a listener
             if client_state.accept_ports.contains_key(&flow_id.flow_tag) {
                 let socket_addr = sa.peer_addr;
-                let connecting_task = self.spawn_connect_task(
+                let connecting_task = self.spawn_flow_connect_task(
                     flow_id.client_type,
                     flow_id.flow_tag,
                     flow_id.peer_id,
-                    socket_addr.ip(),
-                    ServerPort::from(socket_addr.port()),
+                    socket_addr,
+                    &peer_state.quic_connection,
                 );
                 let connecting_state = Connecting {
                     peer_addr: socket_addr,
@@ -913,27 +952,46 @@ impl TransportImpl {
             return Err(TransportErrorCode::TransportClientAlreadyRegistered);
         }
 
-        // Bind to the server ports.
-        let mut listeners = Vec::new();
-        for flow_config in &self.config.p2p_flows {
-            let server_addr = SocketAddr::new(self.node_ip, flow_config.server_port);
-            listeners.push((
-                flow_config.flow_tag,
-                flow_config.server_port,
-                self.init_listener(&server_addr)?,
-            ));
-        }
-
         let mut accept_ports = HashMap::new();
-        for (config_flow_tag, _, tcp_listener) in listeners {
-            let flow_tag = FlowTag::from(config_flow_tag);
-            let accept_task = self.spawn_accept_task(client_type, flow_tag, tcp_listener);
-            accept_ports.insert(flow_tag, ServerPortState { accept_task });
+        let mut quic_endpoint = None;
+        match self.config.backend {
+            TransportBackend::TlsOverTcp => {
+                // Bind to the server ports.
+                let mut listeners = Vec::new();
+                for flow_config in &self.config.p2p_flows {
+                    let server_addr = SocketAddr::new(self.node_ip, flow_config.server_port);
+                    listeners.push((
+                        flow_config.flow_tag,
+                        flow_config.server_port,
+                        self.init_listener(&server_addr)?,
+                    ));
+                }
+
+                for (config_flow_tag, _, tcp_listener) in listeners {
+                    let flow_tag = FlowTag::from(config_flow_tag);
+                    let accept_task = self.spawn_accept_task(client_type, flow_tag, tcp_listener);
+                    accept_ports.insert(flow_tag, ServerPortState { accept_task });
+                }
+            }
+            TransportBackend::Quic => {
+                // All flows are accepted by the same endpoint.
+                let (endpoint, accept_task) = self.init_quic_endpoint(client_type)?;
+                for flow_config in &self.config.p2p_flows {
+                    accept_ports.insert(
+                        FlowTag::from(flow_config.flow_tag),
+                        ServerPortState {
+                            accept_task: accept_task.clone(),
+                        },
+                    );
+                }
+                quic_endpoint = Some(endpoint);
+            }
         }
         client_map.insert(
             client_type,
             ClientState {
                 accept_ports,
+                quic_endpoint,
                 peer_map: HashMap::new(),
                 event_handler,
             },
@@ -963,8 +1021,8 @@ mod tests {
     use ic_types::transport::TransportErrorCode;
     use ic_types::{
         transport::{
-            FlowId, TransportClientType, TransportConfig, TransportFlowConfig, TransportPayload,
-            TransportStateChange,
+            FlowId, TransportBackend, TransportClientType, TransportConfig, TransportFlowConfig,
+            TransportPayload, TransportStateChange,
         },
         NodeId, RegistryVersion,
     };
@@ -1038,6 +1096,7 @@ mod tests {
             let mut client_config_1 = TransportConfig {
                 node_ip: "0.0.0.0".to_string(),
                 p2p_flows: Vec::new(),
+                backend: TransportBackend::TlsOverTcp,
             };
             let flow_internal_1 = TransportFlowConfig {
                 flow_tag: FLOW_TAG_1,
@@ -1058,6 +1117,7 @@ mod tests {
             let mut client_config_2 = TransportConfig {
                 node_ip: "0.0.0.0".to_string(),
                 p2p_flows: Vec::new(),
+                backend: TransportBackend::TlsOverTcp,
             };
             let flow_internal_2 = TransportFlowConfig {
                 flow_tag: FLOW_TAG_2,
@@ -19,10 +19,10 @@
 
 use crate::metrics::DataPlaneMetrics;
 use crate::types::{
-    Connected, ConnectionRole, ConnectionState, SendQueueReader, TransportHeader, TransportImpl,
-    TRANSPORT_FLAGS_IS_HEARTBEAT, TRANSPORT_FLAGS_SENDER_ERROR, TRANSPORT_HEADER_SIZE,
+    Connected, ConnectionRole, ConnectionState, FlowReader, FlowWriter, SendQueueReader,
+    TransportHeader, TransportImpl, TRANSPORT_FLAGS_IS_HEARTBEAT, TRANSPORT_FLAGS_SENDER_ERROR,
+    TRANSPORT_HEADER_SIZE,
 };
-use ic_crypto_tls_interfaces::{TlsReadHalf, TlsWriteHalf};
 use ic_interfaces::transport::AsyncTransportEventHandler;
 use ic_logger::warn;
 use ic_types::transport::{
@@ -127,7 +127,7 @@ impl TransportImpl {
         flow_id: FlowId,
         flow_label: String,
         mut send_queue_reader: Box<dyn SendQueueReader + Send + Sync>,
-        mut writer: Box<TlsWriteHalf>,
+        mut writer: FlowWriter,
         metrics: DataPlaneMetrics,
         state: Weak<TransportImpl>,
     ) {
@@ -209,7 +209,7 @@ impl TransportImpl {
         flow_id: FlowId,
         flow_label: String,
         event_handler: Arc<dyn AsyncTransportEventHandler>,
-        mut reader: Box<TlsReadHalf>,
+        mut reader: FlowReader,
         metrics: DataPlaneMetrics,
         state: Weak<TransportImpl>,
     ) {
@@ -289,7 +289,7 @@ impl TransportImpl {
     /// socket. The timeout is for each socket read (header, payload chunks)
     /// and not the full message.
     async fn read_one_message(
-        reader: &mut Box<TlsReadHalf>,
+        reader: &mut FlowReader,
         timeout: Duration,
     ) -> Result<(TransportHeader, Option<TransportPayload>), ReadError> {
         // Read the hdr
@@ -325,7 +325,7 @@ impl TransportImpl {
 
     /// Reads the requested bytes from the socket with a timeout
     async fn read_from_socket(
-        reader: &mut Box<TlsReadHalf>,
+        reader: &mut FlowReader,
         buf: &mut [u8],
         timeout: Duration,
     ) -> Result<(), ReadError> {
@@ -372,8 +372,8 @@ impl TransportImpl {
         flow_id: FlowId,
         role: ConnectionRole,
         peer_addr: SocketAddr,
-        reader: Box<TlsReadHalf>,
-        writer: Box<TlsWriteHalf>,
+        reader: FlowReader,
+        writer: FlowWriter,
     ) -> Result<Arc<dyn AsyncTransportEventHandler>, TransportErrorCode> {
         let mut client_map = self.client_map.write().unwrap();
         let client_state = match client_map.get_mut(&flow_id.client_type) {
@@ -472,8 +472,8 @@ impl TransportImpl {
         flow_id: FlowId,
         role: ConnectionRole,
         peer_addr: SocketAddr,
-        reader: Box<TlsReadHalf>,
-        writer: Box<TlsWriteHalf>,
+        reader: FlowReader,
+        writer: FlowWriter,
     ) -> Result<(), TransportErrorCode> {
         self.on_connect_setup(flow_id, role, peer_addr, reader, writer)?
             // Notify the client that peer flow is up.
@@ -31,10 +31,17 @@
 //! messages (artifact chunks), for ingress manager, consensus (incl DKG and
 //! certification) and state sync. Thus, Transport has to handle 3 x 3 flows per
 //! peer for Gossip.
+//!
+//! The flows with a peer run either over a TLS-over-TCP connection per flow,
+//! or as streams of a single QUIC connection per peer, as selected by the
+//! `backend` of the transport config.
 
 mod control_plane;
 mod data_plane;
 mod metrics;
+mod quic;
+#[cfg(test)]
+mod tests;
 pub mod transport;
 mod types;
 mod utils;
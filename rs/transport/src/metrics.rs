@@ -17,6 +17,11 @@ pub(crate) struct ControlPlaneMetrics {
     pub(crate) tcp_client_handshake_failed: IntCounterVec,
     pub(crate) tcp_client_handshake_success: IntCounterVec,
     pub(crate) retry_connection: IntCounterVec,
+    pub(crate) quic_accepts: IntCounter,
+    pub(crate) quic_accept_conn_err: IntCounter,
+    pub(crate) quic_connects: IntCounterVec,
+    pub(crate) quic_conn_to_server_err: IntCounterVec,
+    pub(crate) quic_flow_streams: IntCounterVec,
 }
 
 impl ControlPlaneMetrics {
@@ -82,6 +87,29 @@ impl ControlPlaneMetrics {
                 "Connection retries to reconnect to a peer from Transport",
                 &["peer_id", "flow_tag"],
             ),
+            quic_accepts: metrics_registry.int_counter(
+                "transport_quic_accepts",
+                "Total incoming QUIC connections in server mode",
+            ),
+            quic_accept_conn_err: metrics_registry.int_counter(
+                "transport_quic_accept_conn_error",
+                "Error authenticating incoming QUIC connections in server mode",
+            ),
+            quic_connects: metrics_registry.int_counter_vec(
+                "transport_quic_connects",
+                "Total outgoing QUIC connects in client mode",
+                &["peer_id"],
+            ),
+            quic_conn_to_server_err: metrics_registry.int_counter_vec(
+                "transport_quic_conn_to_server_error",
+                "Error connecting to peer QUIC server as client",
+                &["peer_id"],
+            ),
+            quic_flow_streams: metrics_registry.int_counter_vec(
+                "transport_quic_flow_streams",
+                "Flow streams set up over QUIC connections, in both modes",
+                &["flow_tag"],
+            ),
         }
     }
 }
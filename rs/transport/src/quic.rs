@@ -0,0 +1,617 @@
+//! QUIC control plane - Transport connection management over QUIC.
+//!
+//! With the QUIC backend, every transport client has a single QUIC endpoint,
+//! bound to the server port of the flow with the lowest flow tag. The client
+//! side of a peer pair (see `connection_role()`) opens one QUIC connection to
+//! the server side, and runs each flow on its own bidirectional stream of that
+//! connection. A stream starts with the flow tag (4 bytes, little endian),
+//! after which it carries the same messages as a TLS-over-TCP flow and is
+//! handed to the data plane in the same way.
+//!
+//! Streams are flow controlled independently, so a busy flow does not hold
+//! back the other flows with the same peer. Reconnecting a flow opens a new
+//! stream on the existing connection and does not repeat the TLS handshake.
+//! Both sides authenticate with their node TLS certificates, using the TLS
+//! configurations provided by the crypto component.
+//!
+//! The QUIC control plane module implements control plane functionality for
+//! [`TransportImpl`](../types/struct.TransportImpl.html).
+
+use crate::control_plane::{CONNECT_RETRY_SECONDS, TLS_HANDSHAKE_TIMEOUT_SECONDS};
+use crate::types::{ClientState, ConnectionRole, QuicConnectionSlot, TransportImpl};
+use futures::future::{AbortHandle, Abortable, Aborted};
+use futures::StreamExt;
+use ic_crypto_tls_interfaces::AllowedClients;
+use ic_logger::{info, warn};
+use ic_protobuf::registry::node::v1::NodeRecord;
+use ic_types::{
+    transport::{FlowId, FlowTag, TransportClientType, TransportErrorCode},
+    NodeId, PrincipalId,
+};
+use openssl::nid::Nid;
+use openssl::x509::X509;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+/// Server name used to connect to peers. Peers are authenticated by their node
+/// id and not by host name, so the value is irrelevant.
+const QUIC_SERVER_NAME: &str = "domain.is-irrelevant-as-hostname-verification-is.disabled";
+
+/// Size of the flow tag that starts every stream
+const FLOW_TAG_SIZE: usize = 4;
+
+/// Implementation for the QUIC transport control plane
+impl TransportImpl {
+    /// Binds the QUIC endpoint of a transport client, and starts the task that
+    /// accepts connections on it. The endpoint rejects connections until
+    /// `refresh_quic_server_config()` configures the allowed clients.
+    pub(crate) fn init_quic_endpoint(
+        &self,
+        client_type: TransportClientType,
+    ) -> Result<(quinn::Endpoint, AbortHandle), TransportErrorCode> {
+        let server_port = self
+            .config
+            .p2p_flows
+            .iter()
+            .min_by_key(|flow_config| flow_config.flow_tag)
+            .map(|flow_config| flow_config.server_port)
+            .ok_or(TransportErrorCode::TransportClientConfigNotFound)?;
+        let local_addr = SocketAddr::new(self.node_ip, server_port);
+
+        // The endpoint registers its socket with the runtime it is bound in.
+        let _guard = self.tokio_runtime.enter();
+        let (endpoint, incoming) = quinn::Endpoint::builder().bind(&local_addr).map_err(|e| {
+            warn!(
+                self.log,
+                "QuicControlPlane::init_quic_endpoint(): Failed to bind: local_addr = {:?} {:?}",
+                local_addr,
+                e
+            );
+            TransportErrorCode::ServerSocketBindFailed
+        })?;
+        let accept_task = self.spawn_quic_accept_task(client_type, incoming);
+        Ok((endpoint, accept_task))
+    }
+
+    /// Updates the TLS configuration of the client's QUIC endpoint (if any)
+    /// to the current allowed clients and registry version
+    pub(crate) fn refresh_quic_server_config(&self, client_state: &ClientState) {
+        let endpoint = match &client_state.quic_endpoint {
+            Some(endpoint) => endpoint,
+            None => return,
+        };
+        let allowed_clients = self.allowed_clients.read().unwrap().clone();
+        let server_config = match AllowedClients::new_with_nodes(allowed_clients) {
+            // No peer is allowed to connect to us.
+            Err(_) => None,
+            Ok(allowed_clients) => {
+                let registry_version = *self.registry_version.read().unwrap();
+                match self
+                    .crypto
+                    .quic_server_config(allowed_clients, registry_version)
+                {
+                    Ok(tls_config) => {
+                        let mut server_config = quinn::ServerConfig::default();
+                        server_config.crypto = Arc::new(tls_config);
+                        Some(server_config)
+                    }
+                    Err(e) => {
+                        warn!(
+                            self.log,
+                            "QuicControlPlane::refresh_quic_server_config(): node_id = {:?}, \
+                             registry_version = {:?}, error = {:?}",
+                            self.node_id,
+                            registry_version,
+                            e
+                        );
+                        return;
+                    }
+                }
+            }
+        };
+        endpoint.set_server_config(server_config);
+    }
+
+    /// Returns the address of the peer's QUIC endpoint, i.e. the endpoint of
+    /// the peer's flow with the lowest flow tag
+    pub(crate) fn quic_peer_addr(peer_record: &NodeRecord) -> Option<SocketAddr> {
+        peer_record
+            .p2p_flow_endpoints
+            .iter()
+            .filter_map(|flow_endpoint| {
+                flow_endpoint
+                    .endpoint
+                    .as_ref()
+                    .map(|endpoint| (flow_endpoint.flow_tag, endpoint))
+            })
+            .min_by_key(|(flow_tag, _)| *flow_tag)
+            .and_then(|(_, endpoint)| {
+                IpAddr::from_str(endpoint.ip_addr.as_str())
+                    .ok()
+                    .map(|peer_ip| SocketAddr::new(peer_ip, endpoint.port as u16))
+            })
+    }
+
+    /// Starts the async task to accept the incoming QUIC connections in server
+    /// mode
+    fn spawn_quic_accept_task(
+        &self,
+        client_type: TransportClientType,
+        mut incoming: quinn::Incoming,
+    ) -> AbortHandle {
+        let weak_self = self.weak_self.read().unwrap().clone();
+        let tokio_runtime = self.tokio_runtime.clone();
+        let metrics = self.control_plane_metrics.clone();
+        let accept_task = async move {
+            while let Some(connecting) = incoming.next().await {
+                // If the TransportImpl has been deleted, abort.
+                if weak_self.upgrade().is_none() {
+                    return;
+                }
+                metrics.quic_accepts.inc();
+                tokio_runtime.spawn(Self::serve_quic_connection(
+                    weak_self.clone(),
+                    client_type,
+                    connecting,
+                ));
+            }
+        };
+
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        let log_cl = self.log.clone();
+        self.tokio_runtime.spawn(async move {
+            if let Err(Aborted) = Abortable::new(accept_task, abort_registration).await {
+                warn!(log_cl, "QuicControlPlane: accept task aborted");
+            }
+        });
+        abort_handle
+    }
+
+    /// Authenticates an incoming connection, and hands the streams the peer
+    /// opens on it to the data plane until the connection is closed
+    async fn serve_quic_connection(
+        weak_self: Weak<TransportImpl>,
+        client_type: TransportClientType,
+        connecting: quinn::Connecting,
+    ) {
+        let (peer_id, connection, mut bi_streams) = match weak_self.upgrade() {
+            Some(arc_self) => match arc_self.accept_quic_connection(connecting).await {
+                Ok(accepted) => accepted,
+                Err(_) => {
+                    arc_self.control_plane_metrics.quic_accept_conn_err.inc();
+                    return;
+                }
+            },
+            None => return,
+        };
+
+        while let Some(stream) = bi_streams.next().await {
+            // If the TransportImpl has been deleted, abort.
+            let arc_self = match weak_self.upgrade() {
+                Some(arc_self) => arc_self,
+                _ => return,
+            };
+            let (send, recv) = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    info!(
+                        arc_self.log,
+                        "QuicControlPlane::serve_quic_connection(): connection closed: \
+                         peer = {:?}/{:?}, reason = {:?}",
+                        peer_id,
+                        connection.remote_address(),
+                        e
+                    );
+                    return;
+                }
+            };
+            if !arc_self.allowed_clients.read().unwrap().contains(&peer_id) {
+                connection.close(0u32.into(), b"peer removed");
+                return;
+            }
+            let peer_addr = connection.remote_address();
+            arc_self.tokio_runtime.clone().spawn(async move {
+                // Errors are reported in accept_quic_flow
+                let _ = arc_self
+                    .accept_quic_flow(client_type, peer_id, peer_addr, send, recv)
+                    .await;
+            });
+        }
+    }
+
+    /// Performs the server side of the QUIC handshake, and returns the
+    /// authenticated peer with the connection
+    async fn accept_quic_connection(
+        &self,
+        connecting: quinn::Connecting,
+    ) -> Result<(NodeId, quinn::Connection, quinn::IncomingBiStreams), TransportErrorCode> {
+        let new_connection = match timeout(
+            Duration::from_secs(TLS_HANDSHAKE_TIMEOUT_SECONDS),
+            connecting,
+        )
+        .await
+        {
+            Ok(Ok(new_connection)) => new_connection,
+            Ok(Err(e)) => {
+                warn!(
+                    every_n_seconds => 30,
+                    self.log,
+                    "QuicControlPlane::accept_quic_connection(): handshake failed: \
+                     node_id = {:?}, error = {:?}",
+                    self.node_id,
+                    e
+                );
+                return Err(TransportErrorCode::PeerTlsInfoNotFound);
+            }
+            Err(_) => {
+                warn!(
+                    every_n_seconds => 30,
+                    self.log,
+                    "QuicControlPlane::accept_quic_connection(): handshake timed out: \
+                     node_id = {:?}",
+                    self.node_id
+                );
+                return Err(TransportErrorCode::TimeoutExpired);
+            }
+        };
+        let connection = new_connection.connection;
+        let peer_addr = connection.remote_address();
+        let peer_id = Self::quic_peer_id(&connection).map_err(|e| {
+            warn!(
+                every_n_seconds => 30,
+                self.log,
+                "QuicControlPlane::accept_quic_connection(): no peer node id: \
+                 node_id = {:?}, peer_addr = {:?}",
+                self.node_id,
+                peer_addr
+            );
+            e
+        })?;
+        info!(
+            self.log,
+            "QuicControlPlane::accept_quic_connection(): node_id = {:?}, peer = {:?}/{:?}",
+            self.node_id,
+            peer_id,
+            peer_addr
+        );
+        Ok((peer_id, connection, new_connection.bi_streams))
+    }
+
+    /// Reads the flow tag of a stream opened by the peer, and passes the
+    /// stream to the data plane
+    async fn accept_quic_flow(
+        &self,
+        client_type: TransportClientType,
+        peer_id: NodeId,
+        peer_addr: SocketAddr,
+        send: quinn::SendStream,
+        mut recv: quinn::RecvStream,
+    ) -> Result<(), TransportErrorCode> {
+        let mut flow_tag = [0u8; FLOW_TAG_SIZE];
+        match timeout(
+            Duration::from_secs(TLS_HANDSHAKE_TIMEOUT_SECONDS),
+            recv.read_exact(&mut flow_tag),
+        )
+        .await
+        {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => {
+                warn!(
+                    every_n_seconds => 30,
+                    self.log,
+                    "QuicControlPlane::accept_quic_flow(): failed to read flow tag: \
+                     peer = {:?}/{:?}, error = {:?}",
+                    peer_id,
+                    peer_addr,
+                    e
+                );
+                return Err(TransportErrorCode::ConnectionReadFailed(e.to_string()));
+            }
+            Err(_) => return Err(TransportErrorCode::TimeoutExpired),
+        }
+        let flow_tag = FlowTag::from(u32::from_le_bytes(flow_tag));
+        self.control_plane_metrics
+            .quic_flow_streams
+            .with_label_values(&[&flow_tag.to_string()])
+            .inc();
+        self.on_quic_stream(
+            ConnectionRole::Server,
+            client_type,
+            peer_id,
+            flow_tag,
+            peer_addr,
+            send,
+            recv,
+        )
+        .await
+    }
+
+    /// Spawn a task that tries to set up a flow with a peer over QUIC (forever,
+    /// or until the flow is set up or the peer is removed)
+    pub(crate) fn spawn_quic_connect_task(
+        &self,
+        client_type: TransportClientType,
+        flow_tag: FlowTag,
+        peer_id: NodeId,
+        peer_addr: SocketAddr,
+        quic_connection: QuicConnectionSlot,
+    ) -> AbortHandle {
+        let weak_self = self.weak_self.read().unwrap().clone();
+        let connect_task = async move {
+            // Loop till the flow is set up
+            let mut retries: u32 = 0;
+            loop {
+                retries += 1;
+                // If the TransportImpl has been deleted, abort.
+                let arc_self = match weak_self.upgrade() {
+                    Some(arc_self) => arc_self,
+                    _ => return,
+                };
+                match arc_self
+                    .open_quic_flow(client_type, flow_tag, peer_id, peer_addr, &quic_connection)
+                    .await
+                {
+                    Ok(()) => {
+                        info!(
+                            arc_self.log,
+                            "QuicControlPlane::open_quic_flow(): flow set up: peer = {:?}/{:?}, \
+                             flow = {:?}, retries = {}",
+                            peer_id,
+                            peer_addr,
+                            flow_tag,
+                            retries,
+                        );
+                        return;
+                    }
+                    Err(e) => {
+                        info!(
+                            every_n_seconds => 300,
+                            arc_self.log,
+                            "QuicControlPlane::open_quic_flow(): failed: peer = {:?}/{:?}, \
+                             flow = {:?}, err = {:?}, retries = {}",
+                            peer_id,
+                            peer_addr,
+                            flow_tag,
+                            e,
+                            retries
+                        );
+                    }
+                }
+                sleep(Duration::from_secs(CONNECT_RETRY_SECONDS)).await;
+            }
+        };
+
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        let log_cl = self.log.clone();
+        self.tokio_runtime.spawn(async move {
+            if let Err(Aborted) = Abortable::new(connect_task, abort_registration).await {
+                warn!(log_cl, "QuicControlPlane: connect task aborted");
+            }
+        });
+        abort_handle
+    }
+
+    /// Opens the stream of a flow on the connection to the peer (connecting
+    /// first, if needed), and passes the stream to the data plane
+    async fn open_quic_flow(
+        &self,
+        client_type: TransportClientType,
+        flow_tag: FlowTag,
+        peer_id: NodeId,
+        peer_addr: SocketAddr,
+        quic_connection: &QuicConnectionSlot,
+    ) -> Result<(), TransportErrorCode> {
+        let connection = self
+            .quic_connect(client_type, peer_id, peer_addr, quic_connection)
+            .await?;
+        let (mut send, recv) = match connection.open_bi().await {
+            Ok(stream) => stream,
+            Err(e) => {
+                // The connection is lost: the next attempt reconnects, unless
+                // the connect task of another flow already did.
+                let mut slot = quic_connection.lock().await;
+                if slot.as_ref().map(|c| c.stable_id()) == Some(connection.stable_id()) {
+                    *slot = None;
+                }
+                return Err(TransportErrorCode::ConnectionWriteFailed(e.to_string()));
+            }
+        };
+        send.write_all(&flow_tag.get().to_le_bytes())
+            .await
+            .map_err(|e| TransportErrorCode::ConnectionWriteFailed(e.to_string()))?;
+        self.control_plane_metrics
+            .quic_flow_streams
+            .with_label_values(&[&flow_tag.to_string()])
+            .inc();
+        self.on_quic_stream(
+            ConnectionRole::Client,
+            client_type,
+            peer_id,
+            flow_tag,
+            connection.remote_address(),
+            send,
+            recv,
+        )
+        .await
+    }
+
+    /// Returns the connection to the peer, connecting to the peer if there is
+    /// no connection yet
+    async fn quic_connect(
+        &self,
+        client_type: TransportClientType,
+        peer_id: NodeId,
+        peer_addr: SocketAddr,
+        quic_connection: &QuicConnectionSlot,
+    ) -> Result<quinn::Connection, TransportErrorCode> {
+        let mut slot = quic_connection.lock().await;
+        if let Some(connection) = slot.as_ref() {
+            return Ok(connection.clone());
+        }
+
+        let endpoint = self
+            .client_map
+            .read()
+            .unwrap()
+            .get(&client_type)
+            .and_then(|client_state| client_state.quic_endpoint.clone())
+            .ok_or(TransportErrorCode::TransportClientNotFound)?;
+        let registry_version = *self.registry_version.read().unwrap();
+        let tls_config = self
+            .crypto
+            .quic_client_config(peer_id, registry_version)
+            .map_err(|e| {
+                warn!(
+                    every_n_seconds => 30,
+                    self.log,
+                    "QuicControlPlane::quic_connect(): no client config: peer = {:?}, error = {:?}",
+                    peer_id,
+                    e
+                );
+                TransportErrorCode::PeerTlsInfoNotFound
+            })?;
+        let mut client_config = quinn::ClientConfig::default();
+        client_config.crypto = Arc::new(tls_config);
+
+        self.control_plane_metrics
+            .quic_connects
+            .with_label_values(&[&peer_id.to_string()])
+            .inc();
+        let connect_err = || {
+            self.control_plane_metrics
+                .quic_conn_to_server_err
+                .with_label_values(&[&peer_id.to_string()])
+                .inc();
+        };
+        let connecting = endpoint
+            .connect_with(client_config, &peer_addr, QUIC_SERVER_NAME)
+            .map_err(|e| {
+                connect_err();
+                warn!(
+                    every_n_seconds => 30,
+                    self.log,
+                    "QuicControlPlane::quic_connect(): peer = {:?}/{:?}, error = {:?}",
+                    peer_id,
+                    peer_addr,
+                    e
+                );
+                TransportErrorCode::ConnectOsError
+            })?;
+        let new_connection = match timeout(
+            Duration::from_secs(TLS_HANDSHAKE_TIMEOUT_SECONDS),
+            connecting,
+        )
+        .await
+        {
+            Ok(Ok(new_connection)) => new_connection,
+            Ok(Err(e)) => {
+                connect_err();
+                return Err(TransportErrorCode::ConnectionReadFailed(e.to_string()));
+            }
+            Err(_) => {
+                connect_err();
+                return Err(TransportErrorCode::TimeoutExpired);
+            }
+        };
+        info!(
+            self.log,
+            "QuicControlPlane::quic_connect(): node_id = {:?}, peer = {:?}/{:?}",
+            self.node_id,
+            peer_id,
+            peer_addr
+        );
+        *slot = Some(new_connection.connection.clone());
+        Ok(new_connection.connection)
+    }
+
+    /// Passes the stream of a flow to the data plane
+    #[allow(clippy::too_many_arguments)]
+    async fn on_quic_stream(
+        &self,
+        role: ConnectionRole,
+        client_type: TransportClientType,
+        peer_id: NodeId,
+        flow_tag: FlowTag,
+        peer_addr: SocketAddr,
+        send: quinn::SendStream,
+        recv: quinn::RecvStream,
+    ) -> Result<(), TransportErrorCode> {
+        let flow_id = FlowId {
+            client_type,
+            peer_id,
+            flow_tag,
+        };
+        self.on_connect(flow_id, role, peer_addr, Box::new(recv), Box::new(send))
+            .await
+            .map_err(|e| {
+                warn!(
+                    every_n_seconds => 30,
+                    self.log,
+                    "QuicControlPlane::on_quic_stream(): failed to add flow: \
+                     node_id = {:?}, peer_addr = {:?}, flow = {:?}, error = {:?}",
+                    self.node_id,
+                    peer_addr,
+                    flow_id,
+                    e
+                );
+                e
+            })
+    }
+
+    /// Returns the node id in the common name of the certificate the peer
+    /// authenticated the connection with
+    fn quic_peer_id(connection: &quinn::Connection) -> Result<NodeId, TransportErrorCode> {
+        let certificate = connection
+            .authentication_data()
+            .peer_certificates
+            .and_then(|chain| chain.iter().next().cloned())
+            .ok_or(TransportErrorCode::PeerTlsInfoNotFound)?;
+        let certificate =
+            X509::from_der(&certificate.0).map_err(|_| TransportErrorCode::PeerTlsInfoNotFound)?;
+        let common_name = certificate
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .and_then(|entry| entry.data().as_utf8().ok())
+            .ok_or(TransportErrorCode::PeerTlsInfoNotFound)?;
+        PrincipalId::from_str(&common_name)
+            .map(NodeId::from)
+            .map_err(|_| TransportErrorCode::PeerTlsInfoNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_protobuf::registry::node::v1::{ConnectionEndpoint, FlowEndpoint};
+
+    fn flow_endpoint(flow_tag: u32, port: u32) -> FlowEndpoint {
+        FlowEndpoint {
+            flow_tag,
+            endpoint: Some(ConnectionEndpoint {
+                ip_addr: "10.0.0.1".to_string(),
+                port,
+                protocol: 0,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_quic_peer_addr_uses_lowest_flow_tag() {
+        let mut node_record: NodeRecord = Default::default();
+        node_record.p2p_flow_endpoints.push(flow_endpoint(3, 4103));
+        node_record.p2p_flow_endpoints.push(flow_endpoint(1, 4101));
+        node_record.p2p_flow_endpoints.push(FlowEndpoint {
+            flow_tag: 0,
+            endpoint: None,
+        });
+        assert_eq!(
+            TransportImpl::quic_peer_addr(&node_record),
+            Some("10.0.0.1:4101".parse().unwrap())
+        );
+        assert_eq!(TransportImpl::quic_peer_addr(&Default::default()), None);
+    }
+}
@@ -0,0 +1,275 @@
+//! In-process tests that run the transports of two nodes against each other,
+//! over each of the transport backends.
+
+use crate::transport::create_transport;
+use async_trait::async_trait;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use ic_crypto::utils::TempCryptoComponent;
+use ic_interfaces::transport::{AsyncTransportEventHandler, SendError, Transport};
+use ic_logger::ReplicaLogger;
+use ic_metrics::MetricsRegistry;
+use ic_protobuf::registry::node::v1::{
+    connection_endpoint::Protocol, ConnectionEndpoint, FlowEndpoint, NodeRecord,
+};
+use ic_registry_client::fake::FakeRegistryClient;
+use ic_registry_common::proto_registry_data_provider::ProtoRegistryDataProvider;
+use ic_registry_keys::make_crypto_tls_cert_key;
+use ic_test_utilities::metrics::{fetch_int_counter, fetch_int_counter_vec};
+use ic_test_utilities::types::ids::{NODE_1, NODE_2};
+use ic_test_utilities::with_test_replica_logger;
+use ic_types::{
+    transport::{
+        FlowId, FlowTag, TransportBackend, TransportClientType, TransportConfig,
+        TransportErrorCode, TransportFlowConfig, TransportFlowInfo, TransportPayload,
+        TransportStateChange,
+    },
+    NodeId, RegistryVersion,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+const REG_V1: RegistryVersion = RegistryVersion::new(1);
+const FLOW_TAGS: [u32; 2] = [1, 2];
+
+/// Time to wait for a flow to come up, or for a message to arrive
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Forwards the flows coming up and the received messages to the test
+struct TestEventHandler {
+    flows_up: Sender<TransportFlowInfo>,
+    messages: Sender<(FlowId, TransportPayload)>,
+}
+
+#[async_trait]
+impl AsyncTransportEventHandler for TestEventHandler {
+    async fn send_message(&self, flow: FlowId, message: TransportPayload) -> Result<(), SendError> {
+        self.messages.send((flow, message)).unwrap();
+        Ok(())
+    }
+
+    async fn state_changed(&self, state_change: TransportStateChange) {
+        if let TransportStateChange::PeerFlowUp(flow) = state_change {
+            self.flows_up.send(flow).unwrap();
+        }
+    }
+
+    async fn error(&self, _flow: FlowId, _error: TransportErrorCode) {}
+}
+
+/// A node under test: its transport and the events reported by it
+struct TestNode {
+    node_id: NodeId,
+    node_record: NodeRecord,
+    transport: Arc<dyn Transport>,
+    metrics_registry: MetricsRegistry,
+    flows_up: Receiver<TransportFlowInfo>,
+    messages: Receiver<(FlowId, TransportPayload)>,
+}
+
+/// Creates the transport of a node, with one flow per `FLOW_TAGS` entry
+/// listening on consecutive ports from `base_port` on
+fn test_node(
+    node_id: NodeId,
+    crypto: TempCryptoComponent,
+    backend: TransportBackend,
+    base_port: u16,
+    logger: &ReplicaLogger,
+) -> TestNode {
+    let config = TransportConfig {
+        node_ip: "127.0.0.1".to_string(),
+        p2p_flows: FLOW_TAGS
+            .iter()
+            .zip(base_port..)
+            .map(|(flow_tag, server_port)| TransportFlowConfig {
+                flow_tag: *flow_tag,
+                server_port,
+                queue_size: 10,
+            })
+            .collect(),
+        backend,
+    };
+    let mut node_record: NodeRecord = Default::default();
+    for flow_config in &config.p2p_flows {
+        node_record.p2p_flow_endpoints.push(FlowEndpoint {
+            flow_tag: flow_config.flow_tag,
+            endpoint: Some(ConnectionEndpoint {
+                ip_addr: config.node_ip.clone(),
+                port: flow_config.server_port as u32,
+                protocol: Protocol::P2p1Tls13 as i32,
+            }),
+        });
+    }
+
+    let metrics_registry = MetricsRegistry::new();
+    let transport = create_transport(
+        node_id,
+        config,
+        REG_V1,
+        metrics_registry.clone(),
+        Arc::new(crypto),
+        tokio::runtime::Handle::current(),
+        logger.clone(),
+    );
+    let (flows_up_sender, flows_up) = unbounded();
+    let (messages_sender, messages) = unbounded();
+    transport
+        .register_client(
+            TransportClientType::P2P,
+            Arc::new(TestEventHandler {
+                flows_up: flows_up_sender,
+                messages: messages_sender,
+            }),
+        )
+        .expect("register_client");
+    TestNode {
+        node_id,
+        node_record,
+        transport,
+        metrics_registry,
+        flows_up,
+        messages,
+    }
+}
+
+/// Creates the transports of `NODE_1` and `NODE_2`, and starts the connections
+/// between them. The nodes listen on the 4 ports from `base_port` on.
+fn connected_nodes(
+    backend: TransportBackend,
+    base_port: u16,
+    logger: &ReplicaLogger,
+) -> (TestNode, TestNode) {
+    let registry_and_data = empty_registry();
+    let crypto_1 = temp_crypto_component_with_tls_keys_in_registry(&registry_and_data, NODE_1);
+    let crypto_2 = temp_crypto_component_with_tls_keys_in_registry(&registry_and_data, NODE_2);
+    registry_and_data.registry.update_to_latest_version();
+
+    let node_1 = test_node(NODE_1, crypto_1, backend, base_port, logger);
+    let node_2 = test_node(NODE_2, crypto_2, backend, base_port + 2, logger);
+    for (node, peer) in &[(&node_1, &node_2), (&node_2, &node_1)] {
+        node.transport
+            .start_connections(
+                TransportClientType::P2P,
+                &peer.node_id,
+                &peer.node_record,
+                REG_V1,
+            )
+            .expect("start_connections");
+    }
+    (node_1, node_2)
+}
+
+/// Waits until all flows of the node are up
+fn wait_for_flows_up(node: &TestNode) {
+    let mut flow_tags: Vec<u32> = FLOW_TAGS
+        .iter()
+        .map(|_| {
+            node.flows_up
+                .recv_timeout(TIMEOUT)
+                .expect("flow did not come up")
+                .flow_tag
+                .get()
+        })
+        .collect();
+    flow_tags.sort_unstable();
+    assert_eq!(flow_tags, FLOW_TAGS.to_vec());
+}
+
+/// Sends a message on every flow from `sender` to `receiver`, and checks that
+/// each message arrives on the flow it was sent on
+fn exchange_messages(sender: &TestNode, receiver: &TestNode) {
+    for flow_tag in FLOW_TAGS.iter().map(|flow_tag| FlowTag::from(*flow_tag)) {
+        let payload = TransportPayload(format!("{} on {}", sender.node_id, flow_tag).into_bytes());
+        sender
+            .transport
+            .send(
+                TransportClientType::P2P,
+                &receiver.node_id,
+                flow_tag,
+                payload.clone(),
+            )
+            .expect("send");
+        let (flow_id, message) = receiver
+            .messages
+            .recv_timeout(TIMEOUT)
+            .expect("message not received");
+        assert_eq!(flow_id.peer_id, sender.node_id);
+        assert_eq!(flow_id.flow_tag, flow_tag);
+        assert_eq!(message, payload);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn should_exchange_messages_over_tls_over_tcp() {
+    with_test_replica_logger(|logger| {
+        let (node_1, node_2) = connected_nodes(TransportBackend::TlsOverTcp, 65011, &logger);
+        wait_for_flows_up(&node_1);
+        wait_for_flows_up(&node_2);
+        exchange_messages(&node_1, &node_2);
+        exchange_messages(&node_2, &node_1);
+    });
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn should_exchange_messages_over_quic() {
+    with_test_replica_logger(|logger| {
+        let (node_1, node_2) = connected_nodes(TransportBackend::Quic, 65021, &logger);
+        wait_for_flows_up(&node_1);
+        wait_for_flows_up(&node_2);
+        exchange_messages(&node_1, &node_2);
+        exchange_messages(&node_2, &node_1);
+    });
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn should_run_all_quic_flows_over_one_connection() {
+    with_test_replica_logger(|logger| {
+        let (node_1, node_2) = connected_nodes(TransportBackend::Quic, 65031, &logger);
+        wait_for_flows_up(&node_1);
+        wait_for_flows_up(&node_2);
+
+        // NODE_1 has the lower node id, so it connects to NODE_2.
+        let connects = fetch_int_counter_vec(&node_1.metrics_registry, "transport_quic_connects");
+        assert_eq!(connects.values().sum::<u64>(), 1);
+        assert_eq!(
+            fetch_int_counter(&node_2.metrics_registry, "transport_quic_accepts"),
+            Some(1)
+        );
+        let streams =
+            fetch_int_counter_vec(&node_2.metrics_registry, "transport_quic_flow_streams");
+        assert_eq!(streams.len(), FLOW_TAGS.len());
+        assert!(streams.values().all(|streams| *streams == 1));
+    });
+}
+
+struct RegistryAndDataProvider {
+    data_provider: Arc<ProtoRegistryDataProvider>,
+    registry: Arc<FakeRegistryClient>,
+}
+
+fn temp_crypto_component_with_tls_keys_in_registry(
+    registry_and_data: &RegistryAndDataProvider,
+    node_id: NodeId,
+) -> TempCryptoComponent {
+    let (temp_crypto, tls_pubkey_cert) = TempCryptoComponent::new_with_tls_key_generation(
+        Arc::clone(&registry_and_data.registry) as Arc<_>,
+        node_id,
+    );
+    registry_and_data
+        .data_provider
+        .add(
+            &make_crypto_tls_cert_key(node_id),
+            REG_V1,
+            Some(tls_pubkey_cert.to_proto()),
+        )
+        .expect("failed to add TLS cert to registry");
+    temp_crypto
+}
+
+fn empty_registry() -> RegistryAndDataProvider {
+    let data_provider = Arc::new(ProtoRegistryDataProvider::new());
+    let registry = Arc::new(FakeRegistryClient::new(Arc::clone(&data_provider) as Arc<_>));
+    RegistryAndDataProvider {
+        data_provider,
+        registry,
+    }
+}
@@ -44,8 +44,8 @@ use ic_transport::transport::create_transport;
 use ic_types::transport::TransportErrorCode;
 use ic_types::{
     transport::{
-        FlowId, FlowTag, TransportClientType, TransportConfig, TransportFlowConfig,
-        TransportFlowInfo, TransportPayload, TransportStateChange,
+        FlowId, FlowTag, TransportBackend, TransportClientType, TransportConfig,
+        TransportFlowConfig, TransportFlowInfo, TransportPayload, TransportStateChange,
     },
     NodeId, PrincipalId, RegistryVersion, SubnetId,
 };
@@ -495,6 +495,7 @@ fn generate_config_and_registry(node_id: &NodeId) -> ConfigAndRecords {
                         queue_size: 1024,
                     },
                 ],
+                backend: TransportBackend::TlsOverTcp,
             });
         }
 
@@ -53,8 +53,9 @@ use ic_protobuf::registry::node::v1::{
 use ic_transport::transport::create_transport;
 use ic_types::{
     transport::{
-        FlowId, FlowTag, TransportClientType, TransportConfig, TransportErrorCode,
-        TransportFlowConfig, TransportFlowInfo, TransportPayload, TransportStateChange,
+        FlowId, FlowTag, TransportBackend, TransportClientType, TransportConfig,
+        TransportErrorCode, TransportFlowConfig, TransportFlowInfo, TransportPayload,
+        TransportStateChange,
     },
     NodeId, RegistryVersion,
 };
@@ -640,6 +641,7 @@ fn generate_config_and_registry(
             server_port: FLOW_PORT as u16,
             queue_size: 8192,
         }],
+        backend: TransportBackend::TlsOverTcp,
     };
 
     let mut node_records = Vec::new();
@@ -18,6 +18,7 @@ use std::fmt::{self, Debug, Formatter};
 use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::sync::{Arc, RwLock, Weak};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::runtime::Handle;
 use tokio::time::Duration;
 
@@ -77,6 +78,18 @@ pub(crate) struct TransportHeader {
     pub(crate) payload_length: u32, // Serialized little endian.
 }
 
+/// The receiving end of a flow, as handed to the data plane. This is the read
+/// half of the TLS stream for TLS over TCP, and the receive side of the flow's
+/// stream for QUIC.
+pub(crate) type FlowReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// The sending end of a flow, as handed to the data plane
+pub(crate) type FlowWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// The QUIC connection to a peer, shared by the connect tasks of all the flows
+/// with the peer. `None` until the first flow connects.
+pub(crate) type QuicConnectionSlot = Arc<tokio::sync::Mutex<Option<quinn::Connection>>>;
+
 /// Transport implementation state struct. The control and data planes provide
 /// implementations for this struct.
 pub(crate) struct TransportImpl {
@@ -124,8 +137,11 @@ pub(crate) enum ConnectionRole {
 
 /// Per transport-client state
 pub(crate) struct ClientState {
-    /// Ports used to accept connections for this transport-client
+    /// Ports used to accept connections for this transport-client. With QUIC,
+    /// all flows share the accept task of the single endpoint.
     pub accept_ports: HashMap<FlowTag, ServerPortState>,
+    /// The QUIC endpoint of this transport-client, if the QUIC backend is used
+    pub quic_endpoint: Option<quinn::Endpoint>,
     /// Mapping of peers to their corresponding state
     pub peer_map: HashMap<NodeId, PeerState>,
     /// Event handler to report back to the transport client
@@ -148,6 +164,9 @@ impl Drop for ServerPortState {
 pub(crate) struct PeerState {
     /// State of the flows with the peer
     pub flow_map: HashMap<FlowTag, FlowState>,
+    /// The QUIC connection the flows are multiplexed on, if we are the client
+    /// and the QUIC backend is used
+    pub quic_connection: QuicConnectionSlot,
 }
 
 /// Per-flow state, specific to a transport-client and a peer.
@@ -26,6 +26,7 @@ use ic_protobuf::p2p::v1 as pb;
 use ic_protobuf::proxy::{try_from_option_field, ProxyDecodeError};
 use serde::{Deserialize, Serialize};
 use std::convert::{TryFrom, TryInto};
+use std::sync::Arc;
 use strum_macros::EnumIter;
 
 pub use crate::{
@@ -497,6 +498,13 @@ pub struct StateSyncMessage {
     pub checkpoint_root: std::path::PathBuf,
     /// The manifest containing the summary of the content.
     pub manifest: crate::state_sync::Manifest,
+    /// The meta-manifest of the manifest, for versions of the manifest that
+    /// are transferred as sub-manifests.
+    pub meta_manifest: Option<Arc<crate::state_sync::MetaManifest>>,
+    /// The encoded manifest, set together with `meta_manifest`. Sub-manifests
+    /// are sliced from it rather than encoding the manifest for every chunk.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub encoded_manifest: Option<Arc<Vec<u8>>>,
 
     #[serde(skip_serializing, skip_deserializing)]
     pub get_state_sync_chunk: Option<GetStateSyncChunk>,
@@ -4,7 +4,7 @@ use super::{
     artifact::IngressMessageId,
     messages::{MessageId, Response, SignedIngress, EXPECTED_MESSAGE_ID_LENGTH},
     xnet::CertifiedStreamSlice,
-    CountBytes, Height, Randomness, RegistryVersion, SubnetId, Time,
+    CountBytes, Height, NodeId, Randomness, RegistryVersion, SubnetId, Time,
 };
 use ic_protobuf::messaging::xnet::v1 as messaging_pb;
 use ic_protobuf::types::v1 as pb;
@@ -31,6 +31,24 @@ pub struct Batch {
     pub time: Time,
     /// Responses to subnet calls that reqire consensus' involvement.
     pub consensus_responses: Vec<Response>,
+    /// Which nodes made, or failed to make, the block of this batch. `None` if
+    /// the batch was not delivered by consensus, e.g. by tools and tests.
+    pub blockmaker_metrics: Option<BlockmakerMetrics>,
+}
+
+/// The block makers of the round in which a block was finalized.
+///
+/// Only the finalized chain is agreed upon by all replicas, so the proposals
+/// that some replicas saw but that did not get finalized are not accounted
+/// for: a block maker is credited when its block is finalized, and all block
+/// makers of a lower rank are known to have failed to get theirs finalized.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockmakerMetrics {
+    /// The node that made the finalized block.
+    pub blockmaker: NodeId,
+    /// The block makers with a rank lower than the one of the finalized block,
+    /// indexed by rank.
+    pub failed_blockmakers: Vec<NodeId>,
 }
 
 /// The context built by Consensus for deterministic processing. Captures all
@@ -132,8 +132,26 @@ chunkable_artifact_impl! {DkgMessage, |self|
 
 impl ChunkableArtifact for StateSyncMessage {
     fn get_chunk(self: Box<Self>, chunk_id: ChunkId) -> Option<ArtifactChunk> {
-        let buf = if chunk_id == crate::state_sync::MANIFEST_CHUNK {
-            crate::state_sync::encode_manifest(&self.manifest)
+        use crate::state_sync::{
+            encode_manifest, encode_meta_manifest, is_sub_manifest_chunk, MANIFEST_CHUNK,
+            MANIFEST_CHUNK_ID_OFFSET, MAX_SUB_MANIFEST_SIZE,
+        };
+
+        let buf = if chunk_id == MANIFEST_CHUNK {
+            match &self.meta_manifest {
+                Some(meta_manifest) => encode_meta_manifest(meta_manifest),
+                None => encode_manifest(&self.manifest),
+            }
+        } else if is_sub_manifest_chunk(chunk_id) {
+            let meta_manifest = self.meta_manifest.as_ref()?;
+            let encoded_manifest = self.encoded_manifest.as_ref()?;
+            let index = (chunk_id.get() - MANIFEST_CHUNK_ID_OFFSET) as usize;
+            if index >= meta_manifest.sub_manifest_hashes.len() {
+                return None;
+            }
+            let start = index * MAX_SUB_MANIFEST_SIZE;
+            let end = encoded_manifest.len().min(start + MAX_SUB_MANIFEST_SIZE);
+            encoded_manifest.get(start..end)?.to_vec()
         } else if let Some(chunk) = self
             .manifest
             .chunk_table
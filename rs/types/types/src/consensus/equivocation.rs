@@ -0,0 +1,65 @@
+//! Evidence of equivocation, i.e. of a node signing two conflicting consensus
+//! artifacts at the same height.
+//!
+//! Only block proposals are covered. Notarization shares can't be used as
+//! evidence: an honest notary signs every lowest ranked block it sees, so when
+//! a block maker equivocates, honest notaries sign several blocks of the same
+//! height and rank as well.
+use super::{BlockProposal, HasHeight, HasRank};
+use crate::{Height, NodeId};
+use serde::{Deserialize, Serialize};
+
+/// A pair of distinct artifacts of the same type that were signed by the same
+/// node at the same height. Both artifacts are validated before the evidence
+/// is created, so anyone who knows the signer's public key can check it.
+///
+/// The two artifacts of a pair are ordered by hash, so that the same two
+/// artifacts always make up the same evidence.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EquivocationEvidence {
+    /// Two different block proposals of the same block maker, for the same
+    /// height and rank.
+    BlockProposals(BlockProposal, BlockProposal),
+}
+
+impl EquivocationEvidence {
+    /// Return the evidence made up of the two given block proposals, or `None`
+    /// if they don't conflict, i.e. if they are the same proposal or differ in
+    /// signer, height or rank.
+    pub fn from_block_proposals(first: BlockProposal, second: BlockProposal) -> Option<Self> {
+        if first.signature.signer != second.signature.signer
+            || first.height() != second.height()
+            || first.rank() != second.rank()
+            || first.content.get_hash() == second.content.get_hash()
+        {
+            return None;
+        }
+        if first.content.get_hash().get_ref() < second.content.get_hash().get_ref() {
+            Some(EquivocationEvidence::BlockProposals(first, second))
+        } else {
+            Some(EquivocationEvidence::BlockProposals(second, first))
+        }
+    }
+
+    /// Return the node that equivocated.
+    pub fn signer(&self) -> NodeId {
+        match self {
+            EquivocationEvidence::BlockProposals(first, _) => first.signature.signer,
+        }
+    }
+
+    /// Return the type of the conflicting artifacts, for logs and metrics.
+    pub fn artifact_type(&self) -> &'static str {
+        match self {
+            EquivocationEvidence::BlockProposals(..) => "block_proposal",
+        }
+    }
+}
+
+impl HasHeight for EquivocationEvidence {
+    fn height(&self) -> Height {
+        match self {
+            EquivocationEvidence::BlockProposals(first, _) => first.height(),
+        }
+    }
+}
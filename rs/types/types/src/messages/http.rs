@@ -532,6 +532,68 @@ pub struct HttpQueryResponseReply {
     pub arg: Blob,
 }
 
+/// A query response together with the signatures of the nodes that executed
+/// the query. Clients verify each signature with the node's public key from
+/// the certified state tree (`/subnet/<subnet_id>/node/<node_id>/public_key`).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HttpSignedQueryResponse {
+    #[serde(flatten)]
+    pub response: HttpQueryResponse,
+    pub signatures: Vec<NodeSignature>,
+}
+
+/// A signature of a node on the `QueryResponseHash` of a query response.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NodeSignature {
+    /// The time of signing, in nanoseconds since UNIX epoch.
+    pub timestamp: u64,
+    pub signature: Blob,
+    /// The id of the node that created the signature.
+    pub identity: Blob,
+}
+
+/// The representation-independent hash of a query response, the id of the
+/// query it answers and the time at which the response was signed. This is
+/// what nodes sign to authenticate their query responses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryResponseHash([u8; 32]);
+
+impl QueryResponseHash {
+    pub fn new(response: &HttpQueryResponse, request_id: &MessageId, timestamp: Time) -> Self {
+        use RawHttpRequestVal::*;
+
+        let mut map = btreemap! {
+            "request_id" => Bytes(request_id.as_bytes().to_vec()),
+            "timestamp" => U64(timestamp.as_nanos_since_unix_epoch()),
+        };
+        match response {
+            HttpQueryResponse::Replied { reply } => {
+                map.insert("status", String("replied".to_string()));
+                map.insert("reply", Bytes(reply.arg.0.clone()));
+            }
+            HttpQueryResponse::Rejected {
+                reject_code,
+                reject_message,
+            } => {
+                map.insert("status", String("rejected".to_string()));
+                map.insert("reject_code", U64(*reject_code));
+                map.insert("reject_message", String(reject_message.clone()));
+            }
+        }
+        Self(hash_of_map(&map))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl SignedBytesWithoutDomainSeparator for QueryResponseHash {
+    fn as_signed_bytes_without_domain_separator(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+}
+
 /// The response to a `read_state` request.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct HttpReadStateResponse {
@@ -630,6 +692,71 @@ mod test {
         );
     }
 
+    #[test]
+    fn encoding_signed_query_response() {
+        assert_cbor_ser_equal(
+            &HttpSignedQueryResponse {
+                response: HttpQueryResponse::Replied {
+                    reply: HttpQueryResponseReply {
+                        arg: Blob(b"some_bytes".to_vec()),
+                    },
+                },
+                signatures: vec![NodeSignature {
+                    timestamp: 1,
+                    signature: Blob(b"sig".to_vec()),
+                    identity: Blob(b"node".to_vec()),
+                }],
+            },
+            Value::Map(btreemap! {
+                text("status") => text("replied"),
+                text("reply") => Value::Map(btreemap!{
+                    text("arg") => bytes(b"some_bytes")
+                }),
+                text("signatures") => Value::Array(vec![Value::Map(btreemap!{
+                    text("timestamp") => int(1),
+                    text("signature") => bytes(b"sig"),
+                    text("identity") => bytes(b"node"),
+                })]),
+            }),
+        );
+    }
+
+    #[test]
+    fn query_response_hash_covers_request_id_response_and_timestamp() {
+        let response = HttpQueryResponse::Replied {
+            reply: HttpQueryResponseReply {
+                arg: Blob(b"some_bytes".to_vec()),
+            },
+        };
+        let request_id = MessageId::from([1; 32]);
+        let timestamp = UNIX_EPOCH + std::time::Duration::from_secs(1);
+        let hash = QueryResponseHash::new(&response, &request_id, timestamp);
+
+        assert_eq!(
+            hash,
+            QueryResponseHash::new(&response, &request_id, timestamp)
+        );
+        assert_ne!(
+            hash,
+            QueryResponseHash::new(&response, &MessageId::from([2; 32]), timestamp)
+        );
+        assert_ne!(
+            hash,
+            QueryResponseHash::new(&response, &request_id, UNIX_EPOCH)
+        );
+        assert_ne!(
+            hash,
+            QueryResponseHash::new(
+                &HttpQueryResponse::Rejected {
+                    reject_code: 1,
+                    reject_message: "some_bytes".to_string(),
+                },
+                &request_id,
+                timestamp
+            )
+        );
+    }
+
     #[test]
     fn encoding_read_query_reject() {
         assert_cbor_ser_equal(
@@ -74,6 +74,7 @@ pub fn build_default_gossip_config() -> GossipConfig {
         registry_poll_period_ms: REGISTRY_POLL_PERIOD_MS,
         retransmission_request_ms: RETRANSMISSION_REQUEST_MS,
         advert_config: None,
+        push_config: None,
     }
 }
 
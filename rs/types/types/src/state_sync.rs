@@ -62,6 +62,18 @@
 //! ```text
 //! dsep(seq) = byte(len(seq)) · seq
 //! ``
+//!
+//! * Starting with version 3, the root hash is the hash of the meta-manifest
+//!   instead. The manifest is encoded as a `pb::Manifest` and the encoding is
+//!   split into sub-manifests of `MAX_SUB_MANIFEST_SIZE` bytes:
+//! ```text
+//!   sub_manifest_hash  := hash(dsep("ic-state-sub-manifest") · sub_manifest)
+//!   meta_manifest_hash := hash(dsep("ic-state-meta-manifest")
+//!                         · version as u32
+//!                         · len(sub_manifest_hashes) as u32
+//!                         · sub_manifest_hash*
+//!                         )
+//! ```
 pub mod proto;
 
 use crate::chunkable::ChunkId;
@@ -69,9 +81,23 @@ use ic_protobuf::state::sync::v1 as pb;
 use std::fmt;
 use std::ops::Range;
 
-/// Id of the manifest chunk in StateSync artifact.
+/// Id of the manifest chunk in StateSync artifact. For states with a
+/// meta-manifest, this chunk holds the meta-manifest.
 pub const MANIFEST_CHUNK: ChunkId = ChunkId::new(0);
 
+/// The chunk id of the first sub-manifest, the chunk id of sub-manifest `i`
+/// is `MANIFEST_CHUNK_ID_OFFSET + i`. Chunk ids of the chunk table must stay
+/// below this offset.
+pub const MANIFEST_CHUNK_ID_OFFSET: u32 = 1 << 30;
+
+/// The maximum size of a sub-manifest in bytes.
+pub const MAX_SUB_MANIFEST_SIZE: usize = 1 << 20; // 1 MiB.
+
+/// Returns true if `chunk_id` is the id of a sub-manifest.
+pub fn is_sub_manifest_chunk(chunk_id: ChunkId) -> bool {
+    chunk_id.get() >= MANIFEST_CHUNK_ID_OFFSET
+}
+
 /// An entry of the file table.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct FileInfo {
@@ -132,6 +158,20 @@ pub struct Manifest {
     pub chunk_table: Vec<ChunkInfo>,
 }
 
+/// A short description of a manifest that is too large to be transferred as
+/// a single chunk.
+///
+/// The encoded manifest is split into sub-manifests that are fetched as
+/// separate chunks, each verified against its hash in the meta-manifest.
+/// See note [Manifest Hash].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct MetaManifest {
+    /// The version of the manifest.
+    pub version: u32,
+    /// SHA-256 hashes of the sub-manifests.
+    pub sub_manifest_hashes: Vec<[u8; 32]>,
+}
+
 impl fmt::Display for Manifest {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fn write_header(f: &mut fmt::Formatter<'_>, spec: &[(&'static str, usize)]) -> fmt::Result {
@@ -234,3 +274,42 @@ pub fn decode_manifest(bytes: &[u8]) -> Result<Manifest, String> {
         .try_into()
         .map_err(|err| format!("failed to convert Manifest proto into an object: {}", err))
 }
+
+/// Serializes the meta-manifest into a byte array.
+pub fn encode_meta_manifest(meta_manifest: &MetaManifest) -> Vec<u8> {
+    use prost::Message;
+
+    let pb_meta_manifest = pb::MetaManifest::from(meta_manifest.clone());
+    let mut buf = vec![];
+    pb_meta_manifest
+        .encode(&mut buf)
+        .expect("failed to encode meta-manifest to protobuf");
+    buf
+}
+
+/// Deserializes the meta-manifest from a byte array.
+pub fn decode_meta_manifest(bytes: &[u8]) -> Result<MetaManifest, String> {
+    use prost::Message;
+    use std::convert::TryInto;
+
+    let pb_meta_manifest = pb::MetaManifest::decode(bytes)
+        .map_err(|err| format!("failed to decode MetaManifest proto {}", err))?;
+    pb_meta_manifest.try_into().map_err(|err| {
+        format!(
+            "failed to convert MetaManifest proto into an object: {}",
+            err
+        )
+    })
+}
+
+/// Returns the version of the manifest or meta-manifest encoded in the
+/// manifest chunk, without decoding the tables.
+pub fn decode_manifest_version(bytes: &[u8]) -> Result<u32, String> {
+    use prost::Message;
+
+    // The version is the first field of both `pb::Manifest` and
+    // `pb::MetaManifest`, and the tables of both are length-delimited fields.
+    pb::MetaManifest::decode(bytes)
+        .map(|meta_manifest| meta_manifest.version)
+        .map_err(|err| format!("failed to decode manifest version {}", err))
+}
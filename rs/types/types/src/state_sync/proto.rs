@@ -1,5 +1,5 @@
 //! Conversions from Rust to proto structs and back for `StateSync`.
-use crate::state_sync::{ChunkInfo, FileInfo, Manifest};
+use crate::state_sync::{ChunkInfo, FileInfo, Manifest, MetaManifest};
 use ic_protobuf::proxy::ProxyDecodeError;
 use ic_protobuf::state::sync::v1 as pb;
 use std::convert::{AsRef, TryFrom, TryInto};
@@ -88,6 +88,34 @@ impl TryFrom<pb::Manifest> for Manifest {
     }
 }
 
+impl From<MetaManifest> for pb::MetaManifest {
+    fn from(meta_manifest: MetaManifest) -> Self {
+        Self {
+            version: meta_manifest.version,
+            sub_manifest_hashes: meta_manifest
+                .sub_manifest_hashes
+                .into_iter()
+                .map(|hash| hash.to_vec())
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::MetaManifest> for MetaManifest {
+    type Error = ProxyDecodeError;
+
+    fn try_from(meta_manifest: pb::MetaManifest) -> Result<Self, ProxyDecodeError> {
+        Ok(Self {
+            version: meta_manifest.version,
+            sub_manifest_hashes: meta_manifest
+                .sub_manifest_hashes
+                .into_iter()
+                .map(try_decode_hash)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
 fn try_decode_hash(bytes: impl AsRef<[u8]>) -> Result<[u8; 32], ProxyDecodeError> {
     let slice = bytes.as_ref();
     let array: [u8; 32] = slice
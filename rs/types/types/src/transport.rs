@@ -55,6 +55,29 @@ pub struct TransportConfig {
 
     /// P2P specific config. In future, this will be made more generic.
     pub p2p_flows: Vec<TransportFlowConfig>,
+
+    /// The protocol the flows with peers are run over.
+    #[serde(default)]
+    pub backend: TransportBackend,
+}
+
+/// The protocol the flows with peers are run over.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportBackend {
+    /// A TLS-over-TCP connection per flow and peer, accepted on the server
+    /// port of the flow.
+    TlsOverTcp,
+
+    /// A stream per flow over a single QUIC connection per peer, accepted on
+    /// the server port of the flow with the lowest flow tag.
+    Quic,
+}
+
+impl Default for TransportBackend {
+    fn default() -> Self {
+        TransportBackend::TlsOverTcp
+    }
 }
 
 /// Per-flow config
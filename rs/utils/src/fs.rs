@@ -349,6 +349,41 @@ pub fn copy_file_sparse(from: &Path, to: &Path) -> io::Result<u64> {
     fs::copy(from, to)
 }
 
+/// Sets the modification time of `dst` to the modification time of `src`.
+#[cfg(target_family = "unix")]
+pub fn copy_mtime(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::{ffi::OsStrExt, fs::MetadataExt};
+
+    let metadata = src.metadata()?;
+    let times = [
+        // Leave the access time as is.
+        libc::timespec {
+            tv_sec: 0,
+            tv_nsec: libc::UTIME_OMIT,
+        },
+        libc::timespec {
+            tv_sec: metadata.mtime() as libc::time_t,
+            tv_nsec: metadata.mtime_nsec() as libc::c_long,
+        },
+    ];
+    let dst_path = CString::new(dst.as_os_str().as_bytes())?;
+    let ret = unsafe { libc::utimensat(libc::AT_FDCWD, dst_path.as_ptr(), times.as_ptr(), 0) };
+    if ret != 0 {
+        let err = Error::last_os_error();
+        return Err(Error::new(
+            err.kind(),
+            format!(
+                "failed to copy mtime {} -> {}: {}",
+                src.display(),
+                dst.display(),
+                err
+            ),
+        ));
+    }
+    Ok(())
+}
+
 /// Atomically write to `dst` file, using a random file in the parent directory
 /// of `dst` as the temporary file.
 ///